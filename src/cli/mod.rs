@@ -0,0 +1,861 @@
+//! # CLI
+//!
+//! Headless access to every plugin operation, e.g. on a Steam Deck or over SSH. The commands use the same cache
+//! and plugins directory as the GUI. The GUI is started when no command is given.
+mod output;
+
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use log::debug;
+use serde_json::json;
+use std::{
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use crate::core::backup::BackupManager;
+use crate::core::config::{
+    adopt_discovered_installation, get_active_profile, get_archives_dir, get_database_file_path,
+    get_lotro_documents_dir, get_plugin_data_dir, get_plugins_backup_dir, get_plugins_dir,
+    get_settings_file_path, get_storage_dir, get_tmp_dir, initialize_directories,
+    read_existing_settings_file, save_lotro_documents_dir, save_settings_changes,
+    set_profile_override, Profile, SettingsFile,
+};
+use crate::core::dependencies::{dependents, DependencyReport, DependencyResolver};
+use crate::core::discovery::discover_installations;
+use crate::core::installer::backup_manager;
+use crate::core::io::cache::DatabaseHandler;
+use crate::core::io::feed_snapshot::format_age;
+use crate::core::io::{ArchiveStore, Cache, FeedSnapshot, Synchronizer};
+use crate::core::lockfile::Lockfile;
+use crate::core::lotro_compendium::{refresh_feed, FeedDownloader, FeedSource};
+use crate::core::plugin_data;
+use crate::core::update::{UpdatePlan, UpdateSummary};
+use crate::core::version::{compare_versions, VersionOrdering};
+use crate::core::{Installer, Plugin};
+pub use output::{Output, PluginDetails, PluginStatus, PluginSummary};
+
+/// The command failed
+pub const EXIT_FAILURE: u8 = 1;
+/// The requested plugin doesn't exist in the cache
+pub const EXIT_NOT_FOUND: u8 = 3;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "lembas",
+    version,
+    about = "Plugin manager for Lord of the Rings Online. Starts the GUI if no command is given."
+)]
+pub struct Cli {
+    /// Print JSON instead of human-readable text
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Use this profile instead of the active one
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// List the installed plugins
+    List,
+    /// Search the plugin catalog by name or author
+    Search { query: String },
+    /// Show the details of a plugin
+    Info {
+        /// Name or id of the plugin
+        plugin: String,
+    },
+    /// Install a plugin from the catalog or from a local zip file or folder
+    Install {
+        /// Name or id of the plugin
+        #[arg(required_unless_present = "path")]
+        plugin: Option<String>,
+        /// Zip file or folder that contains the plugin
+        #[arg(long, conflicts_with = "plugin")]
+        path: Option<PathBuf>,
+    },
+    /// Update a plugin or every plugin with a newer version. Updating a pinned plugin removes its pin.
+    Update {
+        /// Name or id of the plugin
+        #[arg(required_unless_present = "all")]
+        plugin: Option<String>,
+        #[arg(long, conflicts_with = "plugin")]
+        all: bool,
+    },
+    /// Remove an installed plugin
+    Remove {
+        /// Name or id of the plugin
+        plugin: String,
+        /// Remove the plugin even if other installed plugins depend on it
+        #[arg(long)]
+        force: bool,
+    },
+    /// Reinstall a previously installed version and pin the plugin, so update --all skips it
+    Rollback {
+        /// Name or id of the plugin
+        plugin: String,
+        /// Version to restore. Defaults to the version that was installed before the current one.
+        version: Option<String>,
+    },
+    /// Keep a plugin on its installed version, update --all skips it
+    Pin {
+        /// Name or id of the plugin
+        plugin: String,
+    },
+    /// Let update --all update a pinned plugin again
+    Unpin {
+        /// Name or id of the plugin
+        plugin: String,
+    },
+    /// Ignore updates of a plugin until the feed offers the given version or a newer one
+    Ignore {
+        /// Name or id of the plugin
+        plugin: String,
+        /// First version that is offered as an update again. Offers every update again if it is omitted.
+        until: Option<String>,
+    },
+    /// Fetch the plugin catalog and scan the plugins directory
+    Refresh {
+        /// Ask the server even if the catalog was checked within the refresh interval
+        #[arg(long)]
+        force: bool,
+    },
+    /// Create a compressed backup of the plugins directory or manage the existing backups
+    Backup {
+        #[command(subcommand)]
+        action: Option<BackupCommand>,
+    },
+    /// List the characters with plugin settings
+    Characters,
+    /// Copy the plugin settings of one character to another
+    CopySettings {
+        /// Character as <account>/<server>/<character>
+        from: String,
+        /// Character as <account>/<server>/<character>
+        to: String,
+    },
+    /// List the profiles or manage them. Every profile has its own plugins directory, backups and cache.
+    Profile {
+        #[command(subcommand)]
+        action: Option<ProfileCommand>,
+    },
+    /// List the discovered LOTRO installations or choose the one whose plugins are managed
+    Installations {
+        /// Number of the installation in the list
+        #[arg(long)]
+        select: Option<usize>,
+    },
+    /// Write the installed plugins into a lockfile
+    Export {
+        /// Path of the lockfile
+        path: PathBuf,
+    },
+    /// Install, update and remove plugins until they match a lockfile
+    Import {
+        /// Path of the lockfile
+        path: PathBuf,
+        /// Only show the plan without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum BackupCommand {
+    /// List the backups, newest first
+    List,
+    /// Replace the plugins directory with a backup or restore a single plugin of it
+    Restore {
+        /// Name of the backup
+        backup: String,
+        /// Name of the plugin to restore. Restores every plugin if it is omitted.
+        #[arg(long)]
+        plugin: Option<String>,
+    },
+    /// Restore the plugin settings of a backup for every character or for a single one
+    RestoreData {
+        /// Name of the backup
+        backup: String,
+        /// Character as <account>/<server>/<character>. Restores every character if it is omitted.
+        #[arg(long)]
+        character: Option<String>,
+        /// Name of the data file, e.g. `TitanBar`. Restores every data file if it is omitted.
+        #[arg(long)]
+        file: Option<String>,
+    },
+    /// Delete a backup
+    Delete {
+        /// Name of the backup
+        backup: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ProfileCommand {
+    /// List the profiles
+    List,
+    /// Add a profile
+    Add {
+        name: String,
+        /// LOTRO documents directory of the profile. Defaults to the first discovered installation.
+        #[arg(long)]
+        documents_dir: Option<PathBuf>,
+    },
+    /// Remove a profile. Its cache is kept.
+    Remove { name: String },
+    /// Make a profile the active one
+    Use { name: String },
+}
+
+/// Returned if a plugin can't be found in the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginNotFound(pub String);
+
+impl fmt::Display for PluginNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Plugin {} doesn't exist", self.0)
+    }
+}
+
+impl Error for PluginNotFound {}
+
+/// Runs the command and prints its output. Returns the exit code of the process.
+pub fn run(command: Command, json: bool) -> ExitCode {
+    let result = match command {
+        Command::Profile { action } => profile(action.unwrap_or(ProfileCommand::List)),
+        Command::Installations { select } => installations(select),
+        command => Context::open().and_then(|context| context.execute(command)),
+    };
+
+    match result {
+        Ok(output) => {
+            output.print(json);
+            if output.success {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(EXIT_FAILURE)
+            }
+        }
+        Err(error) => {
+            if json {
+                println!("{}", json!({ "error": error.to_string() }));
+            } else {
+                eprintln!("Error: {}", error);
+            }
+            ExitCode::from(exit_code(error.as_ref()))
+        }
+    }
+}
+
+pub fn exit_code(error: &(dyn Error + 'static)) -> u8 {
+    if error.is::<PluginNotFound>() {
+        EXIT_NOT_FOUND
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+/// Resolves every path against the given profile for the rest of the process. Fails if the profile doesn't exist.
+pub fn use_profile(name: &str) -> Result<(), Box<dyn Error>> {
+    let settings = settings_or_default()?;
+    let profile = settings
+        .profile(name)
+        .ok_or_else(|| format!("Profile {} doesn't exist", name.trim()))?;
+    set_profile_override(Some(&profile.name));
+
+    Ok(())
+}
+
+/// Returns the settings without creating any directory, so profiles can be managed before the first start.
+fn settings_or_default() -> Result<SettingsFile, Box<dyn Error>> {
+    if get_settings_file_path().exists() {
+        Ok(read_existing_settings_file()?)
+    } else {
+        Ok(SettingsFile::default())
+    }
+}
+
+fn profile(action: ProfileCommand) -> Result<Output, Box<dyn Error>> {
+    let mut settings = settings_or_default()?;
+
+    let text = match action {
+        ProfileCommand::List => {
+            return Ok(Output::profiles(
+                &settings.profiles,
+                &get_active_profile().name,
+            ))
+        }
+        ProfileCommand::Add {
+            name,
+            documents_dir,
+        } => {
+            let mut profile = Profile::new(&name);
+            if let Some(documents_dir) = documents_dir {
+                profile = profile.with_lotro_documents_dir(&documents_dir);
+            }
+            settings.add_profile(profile)?;
+            format!("Added profile {}", name.trim())
+        }
+        ProfileCommand::Remove { name } => {
+            let profile = settings.remove_profile(&name)?;
+            format!("Removed profile {}", profile.name)
+        }
+        ProfileCommand::Use { name } => {
+            settings.set_active_profile(&name)?;
+            format!("Using profile {}", settings.active_profile)
+        }
+    };
+    fs::create_dir_all(get_storage_dir())?;
+    save_settings_changes(&settings)?;
+
+    Ok(Output::message(
+        &text,
+        json!({ "profiles": settings.profiles }),
+    ))
+}
+
+/// Lists the discovered installations or stores the selected one. The installations are numbered from 1.
+fn installations(select: Option<usize>) -> Result<Output, Box<dyn Error>> {
+    let installations = discover_installations();
+
+    let Some(number) = select else {
+        return Ok(Output::installations(
+            &installations,
+            &get_lotro_documents_dir(),
+        ));
+    };
+    let installation = number
+        .checked_sub(1)
+        .and_then(|index| installations.get(index))
+        .ok_or_else(|| format!("Installation {} doesn't exist", number))?;
+    save_lotro_documents_dir(&installation.documents_dir)?;
+
+    Ok(Output::message(
+        &format!("Managing the plugins of {}", installation),
+        json!({ "selected": installation }),
+    ))
+}
+
+/// Looks up a plugin by its id or its name. Names are compared case-insensitive if there is no exact match.
+pub fn find_plugin(cache: &Cache, query: &str) -> Result<Plugin, Box<dyn Error>> {
+    let query = query.trim();
+
+    if let Ok(plugin_id) = query.parse::<i32>() {
+        if let Some(plugin) = cache.get_plugin_by_id(plugin_id)? {
+            return Ok(plugin);
+        }
+    }
+    if let Some(plugin) = cache.get_plugin(query)? {
+        return Ok(plugin);
+    }
+
+    cache
+        .get_plugins()?
+        .into_values()
+        .find(|plugin| plugin.name.eq_ignore_ascii_case(query))
+        .ok_or_else(|| PluginNotFound(query.to_string()).into())
+}
+
+/// Returns the catalog plugins whose name or author contains the query, sorted by name.
+pub fn search_plugins(cache: &Cache, query: &str) -> Result<Vec<Plugin>, Box<dyn Error>> {
+    let query = query.trim().to_lowercase();
+
+    let mut plugins = cache
+        .get_plugins()?
+        .into_values()
+        .filter(|plugin| {
+            plugin.name.to_lowercase().contains(&query)
+                || plugin.author.to_lowercase().contains(&query)
+        })
+        .collect::<Vec<Plugin>>();
+    plugins.sort_by_key(|plugin| plugin.name.to_lowercase());
+
+    Ok(plugins)
+}
+
+struct Context {
+    cache: Cache,
+    plugins_dir: PathBuf,
+    plugin_data_dir: PathBuf,
+    tmp_dir: PathBuf,
+    archive_store: ArchiveStore,
+}
+
+impl Context {
+    fn open() -> Result<Self, Box<dyn Error>> {
+        let installations = adopt_discovered_installation()?;
+        if !installations.is_empty() {
+            debug!(
+                "Found {} installations, pick one with the installations command",
+                installations.len()
+            );
+        }
+        initialize_directories()?;
+
+        let cache = Cache::open(&get_database_file_path())?;
+        cache.create_cache_db()?;
+
+        Ok(Self {
+            cache,
+            plugins_dir: get_plugins_dir(),
+            plugin_data_dir: get_plugin_data_dir(),
+            tmp_dir: get_tmp_dir(),
+            archive_store: ArchiveStore::new(&get_archives_dir()),
+        })
+    }
+
+    fn installer(&self, plugin_id: i32, plugin_title: &str) -> Installer {
+        Installer::new(&self.tmp_dir, &self.plugins_dir, plugin_id, plugin_title)
+            .with_archive_store(self.archive_store.clone())
+    }
+
+    fn execute(&self, command: Command) -> Result<Output, Box<dyn Error>> {
+        match command {
+            Command::List => self.list(),
+            Command::Search { query } => Ok(Output::plugins(&search_plugins(&self.cache, &query)?)),
+            Command::Info { plugin } => self.info(&plugin),
+            Command::Install {
+                path: Some(path), ..
+            } => self.install_local(&path),
+            Command::Install { plugin, .. } => self.install(&plugin.unwrap_or_default()),
+            Command::Update { all: true, .. } => self.update_all(),
+            Command::Update { plugin, .. } => self.update(&plugin.unwrap_or_default()),
+            Command::Remove { plugin, force } => self.remove(&plugin, force),
+            Command::Rollback { plugin, version } => self.rollback(&plugin, version.as_deref()),
+            Command::Pin { plugin } => self.set_pinned(&plugin, true),
+            Command::Unpin { plugin } => self.set_pinned(&plugin, false),
+            Command::Ignore { plugin, until } => {
+                self.ignore_updates(&plugin, until.as_deref().unwrap_or_default())
+            }
+            Command::Refresh { force } => self.refresh(force),
+            Command::Backup { action: None } => self.backup(),
+            Command::Backup {
+                action: Some(BackupCommand::List),
+            } => Ok(Output::backups(&self.backup_manager()?.backups())),
+            Command::Backup {
+                action: Some(BackupCommand::Restore { backup, plugin }),
+            } => self.restore(&backup, plugin.as_deref()),
+            Command::Backup {
+                action:
+                    Some(BackupCommand::RestoreData {
+                        backup,
+                        character,
+                        file,
+                    }),
+            } => self.restore_data(&backup, character.as_deref(), file.as_deref()),
+            Command::Backup {
+                action: Some(BackupCommand::Delete { backup }),
+            } => self.delete_backup(&backup),
+            Command::Characters => Ok(Output::characters(&plugin_data::characters(
+                &self.plugin_data_dir,
+            ))),
+            Command::CopySettings { from, to } => self.copy_settings(&from, &to),
+            Command::Profile { action } => profile(action.unwrap_or(ProfileCommand::List)),
+            Command::Installations { select } => installations(select),
+            Command::Export { path } => self.export(&path),
+            Command::Import { path, dry_run } => self.import(&path, dry_run),
+        }
+    }
+
+    fn list(&self) -> Result<Output, Box<dyn Error>> {
+        let mut plugins = self
+            .cache
+            .get_installed_plugins()?
+            .into_values()
+            .collect::<Vec<Plugin>>();
+        plugins.sort_by_key(|plugin| plugin.name.to_lowercase());
+
+        Ok(Output::plugins(&plugins))
+    }
+
+    fn info(&self, query: &str) -> Result<Output, Box<dyn Error>> {
+        let plugin = find_plugin(&self.cache, query)?;
+        let installer = self.installer(plugin.id, &plugin.name);
+        let details = PluginDetails {
+            files: self.cache.get_plugin_files(plugin.id)?,
+            dependents: dependents(&self.cache, &self.plugins_dir, plugin.id)?,
+            rollback_versions: installer
+                .rollback_candidates(&self.cache, plugin.id)?
+                .into_iter()
+                .map(|entry| entry.version)
+                .collect(),
+            ..PluginDetails::from(&plugin)
+        };
+
+        Ok(Output::details(&details))
+    }
+
+    fn install(&self, query: &str) -> Result<Output, Box<dyn Error>> {
+        let plugin = find_plugin(&self.cache, query)?;
+        if plugin.is_local() {
+            return Err(format!(
+                "{} was installed from a local file, install it again with --path",
+                plugin.name
+            )
+            .into());
+        }
+        if plugin.download_url.is_empty() {
+            return Err(format!("{} has no catalog entry to install from", plugin.name).into());
+        }
+
+        let report = self.install_with_dependencies(&plugin)?;
+
+        Ok(Output::installed(&report))
+    }
+
+    fn install_local(&self, path: &Path) -> Result<Output, Box<dyn Error>> {
+        let mut installer = self.installer(0, "local_install");
+        let plugin = installer.install_local(&self.cache, path)?;
+
+        Ok(Output::installed(&DependencyReport {
+            installed: vec![plugin.name],
+            problems: Vec::new(),
+        }))
+    }
+
+    fn install_with_dependencies(
+        &self,
+        plugin: &Plugin,
+    ) -> Result<DependencyReport, Box<dyn Error>> {
+        let backup = backup_manager(&self.plugins_dir, &read_existing_settings_file()?);
+        let report = DependencyResolver::new(
+            &self.cache,
+            &self.tmp_dir,
+            &self.plugins_dir,
+            Installer::download,
+        )
+        .with_archive_store(self.archive_store.clone())
+        .with_backup(backup)
+        .install(plugin)?;
+
+        Ok(report)
+    }
+
+    fn update(&self, query: &str) -> Result<Output, Box<dyn Error>> {
+        let plugin = find_plugin(&self.cache, query)?;
+        if plugin.installed == 0 {
+            return Err(format!("{} isn't installed", plugin.name).into());
+        }
+        if plugin.is_local() || plugin.latest_version.is_empty() {
+            return Err(format!("{} has no catalog entry to update from", plugin.name).into());
+        }
+
+        match compare_versions(&plugin.latest_version, &plugin.current_version) {
+            VersionOrdering::Newer | VersionOrdering::Incomparable => {
+                self.install_with_dependencies(&plugin)?;
+                self.cache.set_pinned(plugin.id, false)?;
+                Ok(Output::updated(&[plugin.name], &[]))
+            }
+            VersionOrdering::Same | VersionOrdering::Older => Ok(Output::message(
+                &format!("{} is up to date", plugin.name),
+                json!({ "updated": [], "failed": [] }),
+            )),
+        }
+    }
+
+    /// Updates every installed plugin with a newer version after a single backup. Pinned plugins and ignored
+    /// updates are skipped, plugins with versions that can't be compared are left out, they can be updated one by
+    /// one.
+    fn update_all(&self) -> Result<Output, Box<dyn Error>> {
+        let plan = UpdatePlan::new(self.cache.get_installed_plugins()?.into_values());
+        let manager = backup_manager(&self.plugins_dir, &read_existing_settings_file()?);
+        let backup = plan.backup(manager.as_ref())?;
+
+        let mut summary = UpdateSummary::new(&plan, backup.as_ref());
+        for plugin in &plan.plugins {
+            let result = DependencyResolver::new(
+                &self.cache,
+                &self.tmp_dir,
+                &self.plugins_dir,
+                Installer::download,
+            )
+            .with_archive_store(self.archive_store.clone())
+            .install(plugin);
+
+            match result {
+                Ok(report) => summary.add_updated(plugin, &report),
+                Err(error) => {
+                    debug!("Update of {} failed: {}", plugin.name, error);
+                    summary.add_failed(plugin, &error.to_string());
+                }
+            }
+        }
+
+        Ok(Output::update_summary(&summary))
+    }
+
+    fn remove(&self, query: &str, force: bool) -> Result<Output, Box<dyn Error>> {
+        let plugin = find_plugin(&self.cache, query)?;
+        if plugin.installed == 0 {
+            return Err(format!("{} isn't installed", plugin.name).into());
+        }
+
+        let dependents = dependents(&self.cache, &self.plugins_dir, plugin.id)?;
+        if !dependents.is_empty() && !force {
+            return Err(format!(
+                "{} is needed by {}, use --force to remove it anyway",
+                plugin.name,
+                dependents.join(", ")
+            )
+            .into());
+        }
+
+        let mut installer = self.installer(plugin.id, &plugin.name);
+        installer.uninstall(&self.cache, plugin.id)?;
+        self.cache.mark_as_uninstalled(&plugin.name)?;
+
+        Ok(Output::message(
+            &format!("Removed {}", plugin.name),
+            json!({ "removed": plugin.name }),
+        ))
+    }
+
+    fn rollback(&self, query: &str, version: Option<&str>) -> Result<Output, Box<dyn Error>> {
+        let plugin = find_plugin(&self.cache, query)?;
+        if plugin.installed == 0 {
+            return Err(format!("{} isn't installed", plugin.name).into());
+        }
+
+        let mut installer = self.installer(plugin.id, &plugin.name);
+        let version = match version {
+            Some(version) => version.to_string(),
+            None => installer
+                .rollback_candidates(&self.cache, plugin.id)?
+                .into_iter()
+                .next()
+                .map(|entry| entry.version)
+                .ok_or_else(|| {
+                    format!("{} has no previous version to roll back to", plugin.name)
+                })?,
+        };
+        installer.roll_back_to(&self.cache, plugin.id, &version)?;
+
+        Ok(Output::message(
+            &format!(
+                "Rolled {} back from {} to {} and pinned it",
+                plugin.name, plugin.current_version, version
+            ),
+            json!({ "name": plugin.name, "from": plugin.current_version, "to": version }),
+        ))
+    }
+
+    fn set_pinned(&self, query: &str, pinned: bool) -> Result<Output, Box<dyn Error>> {
+        let plugin = find_plugin(&self.cache, query)?;
+        self.cache.set_pinned(plugin.id, pinned)?;
+
+        Ok(if pinned {
+            Output::message(
+                &format!("Pinned {} to {}", plugin.name, plugin.current_version),
+                json!({ "pinned": plugin.name }),
+            )
+        } else {
+            Output::message(
+                &format!("Unpinned {}", plugin.name),
+                json!({ "unpinned": plugin.name }),
+            )
+        })
+    }
+
+    fn ignore_updates(&self, query: &str, version: &str) -> Result<Output, Box<dyn Error>> {
+        let plugin = find_plugin(&self.cache, query)?;
+        let version = version.trim();
+        self.cache.set_ignore_updates_until(plugin.id, version)?;
+
+        let text = if version.is_empty() {
+            format!("Every update of {} is offered again", plugin.name)
+        } else {
+            format!("Ignoring updates of {} until {}", plugin.name, version)
+        };
+        Ok(Output::message(
+            &text,
+            json!({ "name": plugin.name, "ignore_updates_until": version }),
+        ))
+    }
+
+    fn refresh(&self, force: bool) -> Result<Output, Box<dyn Error>> {
+        let settings = read_existing_settings_file()?;
+        let snapshot = FeedSnapshot::new(&get_storage_dir());
+        let min_interval = if force {
+            Duration::ZERO
+        } else {
+            settings.feed_refresh_interval()
+        };
+        let refresh = tokio::runtime::Runtime::new()?.block_on(refresh_feed::<FeedDownloader>(
+            &self.cache,
+            &snapshot,
+            &settings.feed_url,
+            min_interval,
+        ))?;
+        let report = Synchronizer::synchronize(&self.cache, &self.plugins_dir)?;
+
+        let catalog = match refresh.source {
+            FeedSource::Downloaded => "Fetched",
+            FeedSource::NotModified => "The catalog didn't change, loaded",
+            FeedSource::Skipped => "The catalog was checked recently, loaded",
+            FeedSource::Offline(_) => "Loaded",
+        };
+        let mut text = format!(
+            "{} {} plugins from the catalog. Found {} new, {} known and {} removed local plugins",
+            catalog,
+            refresh.plugins,
+            report.added.len(),
+            report.matched.len(),
+            report.removed.len()
+        );
+        if let FeedSource::Offline(error) = &refresh.source {
+            text = format!(
+                "{}\nOffline: {}. Using the catalog from {}",
+                text,
+                error,
+                format_age(refresh.fetched_at, Utc::now().timestamp())
+            );
+        }
+
+        Ok(Output::message(
+            &text,
+            json!({
+                "catalog": refresh.plugins,
+                "offline": refresh.is_offline(),
+                "downloaded": refresh.source == FeedSource::Downloaded,
+                "fetched_at": refresh.fetched_at,
+                "added": report.added,
+                "matched": report.matched,
+                "removed": report.removed,
+            }),
+        ))
+    }
+
+    fn backup_manager(&self) -> Result<BackupManager, Box<dyn Error>> {
+        let (keep_last, max_age) = read_existing_settings_file()?.backup_retention();
+
+        Ok(
+            BackupManager::new(&get_plugins_backup_dir(), &self.plugins_dir)
+                .with_plugin_data_dir(&self.plugin_data_dir)
+                .with_retention(keep_last, max_age),
+        )
+    }
+
+    fn backup(&self) -> Result<Output, Box<dyn Error>> {
+        let manifest = self.backup_manager()?.create()?;
+
+        Ok(Output::message(
+            &format!(
+                "Created backup {} with {} plugins",
+                manifest.name,
+                manifest.plugins.len()
+            ),
+            json!(manifest),
+        ))
+    }
+
+    fn restore(&self, backup: &str, plugin: Option<&str>) -> Result<Output, Box<dyn Error>> {
+        let manager = self.backup_manager()?;
+        if let Some(plugin) = plugin {
+            manager.restore_plugin(&self.cache, backup, plugin)?;
+            return Ok(Output::message(
+                &format!("Restored {} from {}", plugin, backup),
+                json!({ "backup": backup, "restored": [plugin] }),
+            ));
+        }
+
+        manager.restore(&self.cache, backup)?;
+        Ok(Output::message(
+            &format!("Restored the plugins directory from {}", backup),
+            json!({ "backup": backup, "restored": "all" }),
+        ))
+    }
+
+    fn restore_data(
+        &self,
+        backup: &str,
+        character: Option<&str>,
+        file: Option<&str>,
+    ) -> Result<Output, Box<dyn Error>> {
+        let restored = self
+            .backup_manager()?
+            .restore_plugin_data(backup, character, file)?;
+
+        Ok(Output::message(
+            &format!(
+                "Restored {} plugin data files from {}",
+                restored.len(),
+                backup
+            ),
+            json!({ "backup": backup, "restored": restored }),
+        ))
+    }
+
+    fn copy_settings(&self, from: &str, to: &str) -> Result<Output, Box<dyn Error>> {
+        let from = plugin_data::find_character(&self.plugin_data_dir, from)?;
+        let to = plugin_data::find_character(&self.plugin_data_dir, to)?;
+        let copied = plugin_data::copy_character(&self.plugin_data_dir, &from, &to)?;
+
+        Ok(Output::message(
+            &format!(
+                "Copied {} plugin data files from {} to {}",
+                copied.len(),
+                from,
+                to
+            ),
+            json!({ "from": from.key(), "to": to.key(), "copied": copied }),
+        ))
+    }
+
+    fn delete_backup(&self, backup: &str) -> Result<Output, Box<dyn Error>> {
+        let manager = self.backup_manager()?;
+        // Looked up first for a readable error if the backup doesn't exist
+        manager.backup(backup)?;
+        manager.delete(backup)?;
+
+        Ok(Output::message(
+            &format!("Deleted backup {}", backup),
+            json!({ "deleted": backup }),
+        ))
+    }
+
+    fn export(&self, path: &Path) -> Result<Output, Box<dyn Error>> {
+        let lockfile = Lockfile::export(&self.cache)?;
+        lockfile.save(path)?;
+
+        Ok(Output::message(
+            &format!(
+                "Wrote {} plugins to {}",
+                lockfile.plugins.len(),
+                path.display()
+            ),
+            json!({ "path": path, "plugins": lockfile.plugins.len() }),
+        ))
+    }
+
+    fn import(&self, path: &Path, dry_run: bool) -> Result<Output, Box<dyn Error>> {
+        let plan = Lockfile::load(path)?.plan(&self.cache)?;
+        if dry_run {
+            return Ok(Output::plan(&plan, None));
+        }
+
+        if !plan.is_empty() {
+            if let Some(manager) =
+                backup_manager(&self.plugins_dir, &read_existing_settings_file()?)
+            {
+                manager.create()?;
+            }
+        }
+        let report = plan.apply(
+            &self.cache,
+            &self.tmp_dir,
+            &self.plugins_dir,
+            &self.archive_store,
+            Installer::download,
+        );
+
+        Ok(Output::plan(&plan, Some(&report)))
+    }
+}
@@ -0,0 +1,527 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::Path;
+
+use crate::core::backup::{format_size, format_timestamp, BackupManifest};
+use crate::core::config::Profile;
+use crate::core::dependencies::DependencyReport;
+use crate::core::discovery::Installation;
+use crate::core::lockfile::{LockPlan, LockReport};
+use crate::core::plugin_data::Character;
+use crate::core::update::UpdateSummary;
+use crate::core::version::{compare_versions, VersionOrdering};
+use crate::core::Plugin;
+
+/// A plugin as it is printed by the list and search commands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PluginSummary {
+    pub id: i32,
+    pub name: String,
+    pub author: String,
+    pub current_version: String,
+    pub latest_version: String,
+    pub installed: bool,
+    pub source: String,
+    pub status: PluginStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginStatus {
+    NotInstalled,
+    /// Rolled back to an older version, update --all skips it
+    Pinned,
+    UpdateAvailable,
+    /// A newer version exists, but its updates are ignored until a later version
+    UpdateIgnored,
+    Local,
+    /// Installed without a catalog entry
+    Unmanaged,
+    /// Disappeared from the feed
+    Delisted,
+    UpToDate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PluginDetails {
+    #[serde(flatten)]
+    pub summary: PluginSummary,
+    pub description: String,
+    pub category: String,
+    pub downloads: i32,
+    pub info_url: String,
+    /// Updates to older versions are ignored. Empty if every update is offered.
+    pub ignore_updates_until: String,
+    /// Installed files relative to the plugins directory
+    pub files: Vec<String>,
+    /// Installed plugins that depend on this plugin
+    pub dependents: Vec<String>,
+    /// Previously installed versions that can be restored with a rollback
+    pub rollback_versions: Vec<String>,
+}
+
+/// The result of a command in both output formats.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub text: String,
+    pub json: Value,
+    /// Set to false if a part of the command failed
+    pub success: bool,
+}
+
+impl From<&Plugin> for PluginSummary {
+    fn from(plugin: &Plugin) -> Self {
+        Self {
+            id: plugin.id,
+            name: plugin.name.clone(),
+            author: plugin.author.clone(),
+            current_version: plugin.current_version.clone(),
+            latest_version: plugin.latest_version.clone(),
+            installed: plugin.installed == 1,
+            source: plugin.source.clone(),
+            status: PluginStatus::from(plugin),
+        }
+    }
+}
+
+impl From<&Plugin> for PluginStatus {
+    fn from(plugin: &Plugin) -> Self {
+        if plugin.installed == 0 {
+            Self::NotInstalled
+        } else if plugin.pinned == 1 {
+            Self::Pinned
+        } else if plugin.offered_update() == VersionOrdering::Newer {
+            Self::UpdateAvailable
+        } else if !plugin.latest_version.is_empty()
+            && compare_versions(&plugin.latest_version, &plugin.current_version)
+                == VersionOrdering::Newer
+        {
+            Self::UpdateIgnored
+        } else if plugin.is_local() {
+            Self::Local
+        } else if plugin.latest_version.is_empty() {
+            Self::Unmanaged
+        } else if plugin.delisted == 1 {
+            Self::Delisted
+        } else {
+            Self::UpToDate
+        }
+    }
+}
+
+impl fmt::Display for PluginStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Self::NotInstalled => "Not installed",
+            Self::Pinned => "Pinned",
+            Self::UpdateAvailable => "Update available",
+            Self::UpdateIgnored => "Update ignored",
+            Self::Local => "Local",
+            Self::Unmanaged => "Unmanaged",
+            Self::Delisted => "Delisted",
+            Self::UpToDate => "Up to date",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl From<&Plugin> for PluginDetails {
+    fn from(plugin: &Plugin) -> Self {
+        Self {
+            summary: PluginSummary::from(plugin),
+            description: plugin.description.clone(),
+            category: plugin.category.clone(),
+            downloads: plugin.downloads,
+            info_url: plugin.info_url.clone(),
+            ignore_updates_until: plugin.ignore_updates_until.clone(),
+            files: Vec::new(),
+            dependents: Vec::new(),
+            rollback_versions: Vec::new(),
+        }
+    }
+}
+
+impl Output {
+    pub fn message(text: &str, json: Value) -> Self {
+        Self {
+            text: text.to_string(),
+            json,
+            success: true,
+        }
+    }
+
+    pub fn plugins(plugins: &[Plugin]) -> Self {
+        let summaries = plugins
+            .iter()
+            .map(PluginSummary::from)
+            .collect::<Vec<PluginSummary>>();
+
+        let text = if summaries.is_empty() {
+            "No plugins found".to_string()
+        } else {
+            let name_width = summaries
+                .iter()
+                .map(|summary| summary.name.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(4);
+
+            let mut lines = vec![format!(
+                "{:<name_width$}  {:<12}  {:<12}  Status",
+                "Name", "Installed", "Latest"
+            )];
+            for summary in &summaries {
+                lines.push(format!(
+                    "{:<name_width$}  {:<12}  {:<12}  {}",
+                    summary.name, summary.current_version, summary.latest_version, summary.status
+                ));
+            }
+            lines.join("\n")
+        };
+
+        Self::message(&text, json!(summaries))
+    }
+
+    pub fn details(details: &PluginDetails) -> Self {
+        let summary = &details.summary;
+        let mut lines = vec![
+            format!("{} ({})", summary.name, summary.id),
+            format!("Author:      {}", summary.author),
+            format!("Installed:   {}", summary.current_version),
+            format!("Latest:      {}", summary.latest_version),
+            format!("Status:      {}", summary.status),
+            format!("Category:    {}", details.category),
+            format!("Downloads:   {}", details.downloads),
+        ];
+        if !details.info_url.is_empty() {
+            lines.push(format!("Website:     {}", details.info_url));
+        }
+        if !details.ignore_updates_until.is_empty() {
+            lines.push(format!(
+                "Ignoring:    updates before {}",
+                details.ignore_updates_until
+            ));
+        }
+        if !details.dependents.is_empty() {
+            lines.push(format!("Needed by:   {}", details.dependents.join(", ")));
+        }
+        if !details.rollback_versions.is_empty() {
+            lines.push(format!(
+                "Roll back:   {}",
+                details.rollback_versions.join(", ")
+            ));
+        }
+        lines.push(format!("Files:       {}", details.files.len()));
+        if !details.description.is_empty() {
+            lines.push(String::new());
+            lines.push(details.description.trim().to_string());
+        }
+
+        Self::message(&lines.join("\n"), json!(details))
+    }
+
+    pub fn backups(backups: &[BackupManifest]) -> Self {
+        let text = if backups.is_empty() {
+            "No backups found".to_string()
+        } else {
+            let name_width = backups
+                .iter()
+                .map(|backup| backup.name.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(4);
+
+            let mut lines = vec![format!(
+                "{:<name_width$}  {:<16}  {:>10}  Plugins",
+                "Name", "Created", "Size"
+            )];
+            for backup in backups {
+                lines.push(format!(
+                    "{:<name_width$}  {:<16}  {:>10}  {}",
+                    backup.name,
+                    format_timestamp(backup.created_at),
+                    format_size(backup.size),
+                    backup.plugins.len()
+                ));
+            }
+            lines.join("\n")
+        };
+
+        Self::message(&text, json!(backups))
+    }
+
+    pub fn characters(characters: &[Character]) -> Self {
+        let text = if characters.is_empty() {
+            "No characters found".to_string()
+        } else {
+            characters
+                .iter()
+                .map(|character| {
+                    format!("{}  {} data files", character.key(), character.files.len())
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+
+        Self::message(&text, json!(characters))
+    }
+
+    /// Lists the profiles and marks the active one.
+    pub fn profiles(profiles: &[Profile], active: &str) -> Self {
+        let text = profiles
+            .iter()
+            .map(|profile| {
+                let marker = if profile.name == active { "*" } else { " " };
+                format!(
+                    "{} {}  {}",
+                    marker,
+                    profile.name,
+                    profile.documents_dir().display()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Self::message(&text, json!({ "profiles": profiles, "active": active }))
+    }
+
+    /// Lists the installations numbered from 1 and marks the managed one.
+    pub fn installations(installations: &[Installation], active: &Path) -> Self {
+        let text = if installations.is_empty() {
+            "No LOTRO installations found".to_string()
+        } else {
+            installations
+                .iter()
+                .enumerate()
+                .map(|(index, installation)| {
+                    let marker = if installation.documents_dir == active {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    format!("{} {}  {}", marker, index + 1, installation)
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+
+        Self::message(
+            &text,
+            json!({ "installations": installations, "active": active }),
+        )
+    }
+
+    pub fn installed(report: &DependencyReport) -> Self {
+        let problems = report
+            .problems
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+
+        let mut lines = report
+            .installed
+            .iter()
+            .map(|name| format!("Installed {}", name))
+            .collect::<Vec<String>>();
+        lines.extend(
+            problems
+                .iter()
+                .map(|problem| format!("Warning: {}", problem)),
+        );
+
+        Self::message(
+            &lines.join("\n"),
+            json!({ "installed": report.installed, "problems": problems }),
+        )
+    }
+
+    pub fn updated(updated: &[String], failed: &[(String, String)]) -> Self {
+        let mut lines = updated
+            .iter()
+            .map(|name| format!("Updated {}", name))
+            .collect::<Vec<String>>();
+        lines.extend(
+            failed
+                .iter()
+                .map(|(name, error)| format!("Update of {} failed: {}", name, error)),
+        );
+        if lines.is_empty() {
+            lines.push("Every plugin is up to date".to_string());
+        }
+
+        let failed_json = failed
+            .iter()
+            .map(|(name, error)| json!({ "name": name, "error": error }))
+            .collect::<Vec<Value>>();
+
+        Self {
+            success: failed.is_empty(),
+            ..Self::message(
+                &lines.join("\n"),
+                json!({ "updated": updated, "failed": failed_json }),
+            )
+        }
+    }
+
+    /// Lists the updated, failed and skipped plugins of update --all. Fails if a plugin couldn't be updated.
+    pub fn update_summary(summary: &UpdateSummary) -> Self {
+        let mut lines = Vec::new();
+        for updated in &summary.updated {
+            lines.push(format!(
+                "Updated {} from {} to {}",
+                updated.name, updated.from, updated.to
+            ));
+            lines.extend(
+                updated
+                    .dependencies
+                    .iter()
+                    .map(|dependency| format!("Installed {} as dependency", dependency)),
+            );
+            lines.extend(
+                updated
+                    .problems
+                    .iter()
+                    .map(|problem| format!("Warning: {}", problem)),
+            );
+        }
+        lines.extend(
+            summary
+                .failed
+                .iter()
+                .map(|failed| format!("Update of {} failed: {}", failed.name, failed.error)),
+        );
+        lines.extend(summary.skipped.iter().map(|skipped| {
+            format!(
+                "Skipped {} {}: {}",
+                skipped.name, skipped.version, skipped.reason
+            )
+        }));
+        if lines.is_empty() {
+            lines.push("Every plugin is up to date".to_string());
+        }
+        if let Some(backup) = &summary.backup {
+            lines.insert(0, format!("Created backup {}", backup));
+        }
+
+        Self {
+            success: summary.is_success(),
+            ..Self::message(&lines.join("\n"), json!(summary))
+        }
+    }
+
+    /// Shows the plan of a lockfile import together with the report if the plan was applied. Fails if a
+    /// plugin couldn't be changed or a locked version isn't available.
+    pub fn plan(plan: &LockPlan, report: Option<&LockReport>) -> Self {
+        let mut lines = Vec::new();
+        lines.extend(
+            plan.install
+                .iter()
+                .map(|plugin| format!("Install {} {}", plugin.name, plugin.latest_version)),
+        );
+        lines.extend(plan.update.iter().map(|plugin| {
+            format!(
+                "Update {} {} -> {}",
+                plugin.name, plugin.current_version, plugin.latest_version
+            )
+        }));
+        lines.extend(
+            plan.remove
+                .iter()
+                .map(|plugin| format!("Remove {}", plugin.name)),
+        );
+        if plan.is_empty() {
+            lines.push("Every plugin matches the lockfile".to_string());
+        }
+
+        let problems = plan
+            .problems
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+        lines.extend(
+            problems
+                .iter()
+                .map(|problem| format!("Warning: {}", problem)),
+        );
+
+        let mut success = problems.is_empty();
+        let applied = report.map(|report| {
+            success &= report.failed.is_empty();
+            if !plan.is_empty() {
+                lines.push(String::new());
+                lines.extend(
+                    report
+                        .installed
+                        .iter()
+                        .map(|name| format!("Installed {}", name)),
+                );
+                lines.extend(
+                    report
+                        .updated
+                        .iter()
+                        .map(|name| format!("Updated {}", name)),
+                );
+                lines.extend(
+                    report
+                        .removed
+                        .iter()
+                        .map(|name| format!("Removed {}", name)),
+                );
+                lines.extend(
+                    report
+                        .failed
+                        .iter()
+                        .map(|(name, error)| format!("{} failed: {}", name, error)),
+                );
+            }
+
+            json!({
+                "installed": report.installed,
+                "updated": report.updated,
+                "removed": report.removed,
+                "failed": report
+                    .failed
+                    .iter()
+                    .map(|(name, error)| json!({ "name": name, "error": error }))
+                    .collect::<Vec<Value>>(),
+            })
+        });
+
+        let plugins = |plugins: &[Plugin]| {
+            plugins
+                .iter()
+                .map(|plugin| {
+                    json!({
+                        "id": plugin.id,
+                        "name": plugin.name,
+                        "current_version": plugin.current_version,
+                        "latest_version": plugin.latest_version,
+                    })
+                })
+                .collect::<Vec<Value>>()
+        };
+
+        Self {
+            success,
+            ..Self::message(
+                &lines.join("\n"),
+                json!({
+                    "install": plugins(&plan.install),
+                    "update": plugins(&plan.update),
+                    "remove": plugins(&plan.remove),
+                    "problems": problems,
+                    "applied": applied,
+                }),
+            )
+        }
+    }
+
+    pub fn print(&self, json: bool) {
+        if json {
+            println!("{}", self.json);
+        } else {
+            println!("{}", self.text);
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! # Archive
+//!
+//! Safe extraction of plugin archives. Every entry of an archive is validated before anything is written to
+//! disk, because the archives are downloaded from a public site and can't be trusted.
+use log::debug;
+use std::{
+    error, fmt, fs,
+    io::{self, Cursor, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::error::{Error, PathContext};
+
+const FILE_TYPE_MASK: u32 = 0o170_000;
+const REGULAR_FILE: u32 = 0o100_000;
+const DIRECTORY: u32 = 0o040_000;
+const SYMLINK: u32 = 0o120_000;
+
+/// Limits that are applied to every archive before it gets extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveLimits {
+    /// Maximum uncompressed size of a single entry in bytes
+    pub max_entry_size: u64,
+    /// Maximum uncompressed size of all entries in bytes
+    pub max_total_size: u64,
+    /// Maximum ratio between the uncompressed and compressed size of an entry
+    pub max_compression_ratio: u64,
+    /// Entries below this uncompressed size are excluded from the compression ratio check because small
+    /// text files compress extremely well
+    pub compression_ratio_threshold: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entry_size: 256 * 1024 * 1024,
+            max_total_size: 1024 * 1024 * 1024,
+            max_compression_ratio: 100,
+            compression_ratio_threshold: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsafeEntryReason {
+    AbsolutePath,
+    ParentDirectory,
+    Symlink,
+    SpecialFile(u32),
+    TooLarge { size: u64, limit: u64 },
+    TotalSizeExceeded { limit: u64 },
+    SizeMismatch { declared: u64 },
+    CompressionRatio { ratio: u64, limit: u64 },
+}
+
+impl fmt::Display for UnsafeEntryReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AbsolutePath => write!(f, "absolute paths are not allowed"),
+            Self::ParentDirectory => write!(f, "paths must not contain `..`"),
+            Self::Symlink => write!(f, "symlinks are not allowed"),
+            Self::SpecialFile(mode) => {
+                write!(f, "special file with mode {:o} is not allowed", mode)
+            }
+            Self::TooLarge { size, limit } => {
+                write!(f, "{} bytes exceed the limit of {} bytes", size, limit)
+            }
+            Self::TotalSizeExceeded { limit } => {
+                write!(f, "the archive exceeds the total limit of {} bytes", limit)
+            }
+            Self::SizeMismatch { declared } => {
+                write!(
+                    f,
+                    "the entry is larger than the declared {} bytes",
+                    declared
+                )
+            }
+            Self::CompressionRatio { ratio, limit } => write!(
+                f,
+                "compression ratio of {} exceeds the limit of {}",
+                ratio, limit
+            ),
+        }
+    }
+}
+
+/// Returned if an entry of an archive was refused. Contains the name of the refused entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsafeArchiveEntry {
+    pub entry: String,
+    pub reason: UnsafeEntryReason,
+}
+
+impl fmt::Display for UnsafeArchiveEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Refused archive entry {}: {}", self.entry, self.reason)
+    }
+}
+
+impl error::Error for UnsafeArchiveEntry {}
+
+/// Checks every entry of the archive against the given limits without writing anything to disk.
+pub fn validate_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    limits: &ArchiveLimits,
+) -> Result<(), Error> {
+    let mut total_size: u64 = 0;
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+        let refuse = |reason| UnsafeArchiveEntry {
+            entry: name.clone(),
+            reason,
+        };
+
+        validate_entry_name(&name).map_err(refuse)?;
+
+        if let Some(mode) = entry.unix_mode() {
+            match mode & FILE_TYPE_MASK {
+                0 | REGULAR_FILE | DIRECTORY => {}
+                SYMLINK => return Err(refuse(UnsafeEntryReason::Symlink).into()),
+                _ => return Err(refuse(UnsafeEntryReason::SpecialFile(mode)).into()),
+            }
+        }
+
+        let size = entry.size();
+        if size > limits.max_entry_size {
+            return Err(refuse(UnsafeEntryReason::TooLarge {
+                size,
+                limit: limits.max_entry_size,
+            })
+            .into());
+        }
+
+        total_size = total_size.saturating_add(size);
+        if total_size > limits.max_total_size {
+            return Err(refuse(UnsafeEntryReason::TotalSizeExceeded {
+                limit: limits.max_total_size,
+            })
+            .into());
+        }
+
+        if size >= limits.compression_ratio_threshold {
+            let ratio = size / entry.compressed_size().max(1);
+            if ratio > limits.max_compression_ratio {
+                return Err(refuse(UnsafeEntryReason::CompressionRatio {
+                    ratio,
+                    limit: limits.max_compression_ratio,
+                })
+                .into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates the archive and extracts it into the target directory. The declared entry sizes are not trusted,
+/// every entry is read up to its declared size and the extraction stops as soon as an entry yields more or the
+/// written bytes exceed the total limit.
+pub fn extract_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    target_dir: &Path,
+    limits: &ArchiveLimits,
+) -> Result<(), Error> {
+    validate_archive(archive, limits)?;
+
+    let mut total_written: u64 = 0;
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+        let refuse = |reason| UnsafeArchiveEntry {
+            entry: name.clone(),
+            reason,
+        };
+        let path = target_dir.join(validate_entry_name(&name).map_err(refuse)?);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&path).at(&path)?;
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).at(parent)?;
+        }
+
+        let declared = entry.size();
+        let limit = declared.min(limits.max_entry_size);
+        let mut file = fs::File::create(&path).at(&path)?;
+        let written = io::copy(&mut entry.take(limit + 1), &mut file).at(&path)?;
+        if written > limit {
+            debug!("Entry {} is larger than declared in the archive", name);
+            let reason = if written > declared {
+                UnsafeEntryReason::SizeMismatch { declared }
+            } else {
+                UnsafeEntryReason::TooLarge {
+                    size: written,
+                    limit: limits.max_entry_size,
+                }
+            };
+            return Err(refuse(reason).into());
+        }
+
+        total_written = total_written.saturating_add(written);
+        if total_written > limits.max_total_size {
+            return Err(refuse(UnsafeEntryReason::TotalSizeExceeded {
+                limit: limits.max_total_size,
+            })
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Packs a local folder into an uncompressed archive, so that it can be installed like a downloaded plugin. The
+/// folder itself becomes the top level entry of the archive. Symlinks are skipped.
+pub fn archive_directory(path: &Path) -> Result<Vec<u8>, Error> {
+    let path = path.canonicalize().at(path)?;
+    let base = path.parent().unwrap_or(&path);
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for entry in WalkDir::new(&path).sort_by_file_name() {
+        let entry = entry?;
+        let name = entry
+            .path()
+            .strip_prefix(base)
+            .map_err(|_| {
+                Error::invalid_archive(format!(
+                    "{} is outside of the folder",
+                    entry.path().display()
+                ))
+            })?
+            .iter()
+            .map(|component| component.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/");
+
+        if entry.file_type().is_dir() {
+            writer.add_directory(name, options)?;
+        } else if entry.file_type().is_file() {
+            writer.start_file(name, options)?;
+            writer
+                .write_all(&fs::read(entry.path()).at(entry.path())?)
+                .at(entry.path())?;
+        } else {
+            debug!("Skipping {} because it isn't a regular file", name);
+        }
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Converts an entry name into a relative path. Absolute paths, drive letters and `..` components are refused.
+pub(crate) fn validate_entry_name(name: &str) -> Result<PathBuf, UnsafeEntryReason> {
+    if name.starts_with('/')
+        || name.starts_with('\\')
+        || name.contains('\0')
+        || name.chars().nth(1) == Some(':')
+    {
+        return Err(UnsafeEntryReason::AbsolutePath);
+    }
+
+    let mut path = PathBuf::new();
+    for component in name.split(['/', '\\']) {
+        match component {
+            ".." => return Err(UnsafeEntryReason::ParentDirectory),
+            "" | "." => {}
+            component => path.push(component),
+        }
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod validate_entry_name_tests {
+        use super::*;
+
+        #[test]
+        fn relative_path() {
+            let result = validate_entry_name("Munkey/Ah/Main.lua");
+            assert_eq!(result, Ok(PathBuf::from("Munkey/Ah/Main.lua")));
+        }
+
+        #[test]
+        fn absolute_path() {
+            assert_eq!(
+                validate_entry_name("/etc/passwd"),
+                Err(UnsafeEntryReason::AbsolutePath)
+            );
+            assert_eq!(
+                validate_entry_name("C:\\Windows\\win.ini"),
+                Err(UnsafeEntryReason::AbsolutePath)
+            );
+        }
+
+        #[test]
+        fn parent_directory() {
+            assert_eq!(
+                validate_entry_name("Munkey/../../Ah.plugin"),
+                Err(UnsafeEntryReason::ParentDirectory)
+            );
+            assert_eq!(
+                validate_entry_name("Munkey\\..\\Ah.plugin"),
+                Err(UnsafeEntryReason::ParentDirectory)
+            );
+        }
+    }
+}
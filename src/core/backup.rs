@@ -0,0 +1,565 @@
+//! # Backup
+//!
+//! Compressed backups of the plugins directory. Every backup is a zip file with a json manifest next to it, e.g.
+//! `backup_2022_05_01_101500.zip` and `backup_2022_05_01_101500.json`. The manifest lists the plugins of the
+//! backup together with their files, so a single plugin can be restored without touching the others. Old
+//! backups are removed by the retention policy whenever a new backup is created.
+//!
+//! The settings of the plugins are backed up into a second zip file, e.g. `backup_2022_05_01_101500_plugindata.zip`,
+//! and are restored on their own, either for a single character or for a single data file.
+use chrono::{TimeZone, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{PoisonError, RwLock},
+    time::Duration,
+};
+use walkdir::WalkDir;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::{
+    archive::{validate_entry_name, UnsafeArchiveEntry},
+    error::{Error, PathContext},
+    installer::{move_path, remove_manifest_files},
+    io::{cache::DatabaseHandler, Cache, Synchronizer},
+    plugin_data::{self, Character},
+    Plugin,
+};
+
+/// Backups and restores wait until the files of running installations are moved and the other way round, so a backup
+/// never contains a half installed plugin. Installations only touch the files of their own plugin, so they share the
+/// lock with each other.
+pub(crate) static PLUGINS_DIR_LOCK: RwLock<()> = RwLock::new(());
+
+/// The manifest of a backup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// Name of the backup, the zip file and the manifest are named after it
+    pub name: String,
+    /// Unix timestamp of the backup
+    pub created_at: i64,
+    /// Size of the zip file in bytes
+    pub size: u64,
+    pub plugins: Vec<BackedUpPlugin>,
+    /// Characters of the plugin data backup. Empty if the backup has no plugin data.
+    #[serde(default)]
+    pub characters: Vec<Character>,
+}
+
+/// A plugin that was found in the plugins directory while the backup was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackedUpPlugin {
+    /// Id of the plugin on lotrointerface. Zero if the plugin has no compendium file.
+    pub id: i32,
+    pub name: String,
+    pub version: String,
+    /// Files and folders of the plugin relative to the plugins directory
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupManager {
+    backup_dir: PathBuf,
+    plugins_dir: PathBuf,
+    plugin_data_dir: Option<PathBuf>,
+    keep_last: usize,
+    max_age: Duration,
+    fail_after: Option<usize>,
+}
+
+impl BackupManifest {
+    pub fn plugin(&self, name: &str) -> Option<&BackedUpPlugin> {
+        self.plugins.iter().find(|plugin| plugin.name == name)
+    }
+}
+
+impl BackupManager {
+    pub fn new(backup_dir: &Path, plugins_dir: &Path) -> Self {
+        Self {
+            backup_dir: backup_dir.to_path_buf(),
+            plugins_dir: plugins_dir.to_path_buf(),
+            plugin_data_dir: None,
+            keep_last: 0,
+            max_age: Duration::ZERO,
+            fail_after: None,
+        }
+    }
+
+    /// Sets the retention policy that is applied after every new backup. A limit of zero disables the limit.
+    pub fn with_retention(mut self, keep_last: usize, max_age: Duration) -> Self {
+        self.keep_last = keep_last;
+        self.max_age = max_age;
+        self
+    }
+
+    /// Backs up the plugin data directory together with the plugins directory.
+    pub fn with_plugin_data_dir(mut self, plugin_data_dir: &Path) -> Self {
+        self.plugin_data_dir = Some(plugin_data_dir.to_path_buf());
+        self
+    }
+
+    /// Lets a restore fail after the given number of entries were moved into the plugins directory. Used to verify
+    /// the rollback behaviour.
+    pub fn with_failure_after(mut self, entries: usize) -> Self {
+        self.fail_after = Some(entries);
+        self
+    }
+
+    /// Packs the plugins directory into a new compressed backup and applies the retention policy afterwards.
+    pub fn create(&self) -> Result<BackupManifest, Error> {
+        let _backing_up = PLUGINS_DIR_LOCK
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let plugins = Synchronizer::search_local(&self.plugins_dir)?
+            .into_iter()
+            .map(|local_plugin| BackedUpPlugin {
+                id: local_plugin.plugin.id,
+                name: local_plugin.plugin.name,
+                version: local_plugin.plugin.current_version,
+                files: local_plugin.files,
+            })
+            .collect::<Vec<BackedUpPlugin>>();
+
+        fs::create_dir_all(&self.backup_dir).at(&self.backup_dir)?;
+        let now = Utc::now();
+        let name = self.unused_name(&format!("backup_{}", now.format("%Y_%m_%d_%H%M%S")));
+        let archive_path = self.archive_path(&name);
+
+        let mut characters = Vec::new();
+        let mut size = 0;
+        if let Some(plugin_data_dir) = self.plugin_data_dir.as_ref().filter(|dir| dir.is_dir()) {
+            let data_archive_path = self.data_archive_path(&name);
+            write_archive(plugin_data_dir, &data_archive_path)?;
+            characters = plugin_data::characters(plugin_data_dir);
+            size += fs::metadata(&data_archive_path)
+                .at(&data_archive_path)?
+                .len();
+        }
+        write_archive(&self.plugins_dir, &archive_path)?;
+        size += fs::metadata(&archive_path).at(&archive_path)?.len();
+
+        let manifest = BackupManifest {
+            name,
+            created_at: now.timestamp(),
+            size,
+            plugins,
+            characters,
+        };
+        // The manifest is written last, an interrupted backup never shows up in the list
+        let manifest_path = archive_path.with_extension("json");
+        serde_json::to_string_pretty(&manifest)
+            .map_err(io::Error::from)
+            .and_then(|content| fs::write(&manifest_path, content))
+            .at(&manifest_path)?;
+
+        for removed in self.prune()? {
+            debug!("Removed the backup {}", removed.name);
+        }
+
+        Ok(manifest)
+    }
+
+    /// Returns every backup, newest first. Backups whose zip file is missing are skipped.
+    pub fn backups(&self) -> Vec<BackupManifest> {
+        let Ok(entries) = fs::read_dir(&self.backup_dir) else {
+            return Vec::new();
+        };
+
+        let mut backups = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "json")
+                    && path.with_extension("zip").is_file()
+            })
+            .filter_map(|path| {
+                fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|content| serde_json::from_str(&content).ok())
+            })
+            .collect::<Vec<BackupManifest>>();
+        // Backups of the same second only differ by the numbered suffix of their name
+        backups.sort_by(|a, b| {
+            (b.created_at, b.name.len(), &b.name).cmp(&(a.created_at, a.name.len(), &a.name))
+        });
+
+        backups
+    }
+
+    pub fn backup(&self, name: &str) -> Result<BackupManifest, Error> {
+        self.backups()
+            .into_iter()
+            .find(|backup| backup.name == name)
+            .ok_or_else(|| Error::backup(format!("Backup {} doesn't exist", name)))
+    }
+
+    /// Removes the backups that are older than the maximum age or that exceed the number of kept backups.
+    pub fn prune(&self) -> Result<Vec<BackupManifest>, Error> {
+        let oldest =
+            Utc::now().timestamp() - i64::try_from(self.max_age.as_secs()).unwrap_or(i64::MAX);
+        let mut removed = Vec::new();
+
+        for (index, backup) in self.backups().into_iter().enumerate() {
+            let expired = !self.max_age.is_zero() && backup.created_at < oldest;
+            let superfluous = self.keep_last > 0 && index >= self.keep_last;
+            if expired || superfluous {
+                self.delete(&backup.name)?;
+                removed.push(backup);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), Error> {
+        let archive_path = self.archive_path(name);
+        if archive_path.exists() {
+            fs::remove_file(&archive_path).at(&archive_path)?;
+        }
+        let data_archive_path = self.data_archive_path(name);
+        if data_archive_path.exists() {
+            fs::remove_file(&data_archive_path).at(&data_archive_path)?;
+        }
+        let manifest_path = archive_path.with_extension("json");
+        fs::remove_file(&manifest_path).at(&manifest_path)?;
+
+        Ok(())
+    }
+
+    /// Replaces the content of the plugins directory with the backup. The backup is extracted next to the plugins
+    /// directory before anything is touched, so a broken backup leaves the plugins directory untouched. The current
+    /// content is moved aside and moved back if the backup can't be moved in completely. The cache is synchronized
+    /// afterwards.
+    pub fn restore(&self, cache: &Cache, name: &str) -> Result<(), Error> {
+        let _restoring = PLUGINS_DIR_LOCK
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let manifest = self.backup(name)?;
+        let staging_dir = self.sibling_dir("restore")?;
+        let previous_dir = self.sibling_dir("previous")?;
+        for dir in [&staging_dir, &previous_dir] {
+            if dir.exists() {
+                fs::remove_dir_all(dir).at(dir)?;
+            }
+        }
+        fs::create_dir_all(&staging_dir).at(&staging_dir)?;
+        if let Err(error) = extract(&self.archive_path(name), &staging_dir, |_| true) {
+            fs::remove_dir_all(&staging_dir).ok();
+            return Err(error);
+        }
+
+        fs::create_dir_all(&self.plugins_dir).at(&self.plugins_dir)?;
+        fs::create_dir_all(&previous_dir).at(&previous_dir)?;
+        let mut restored = Vec::new();
+        let result = move_entries(&self.plugins_dir, &previous_dir, None, &mut Vec::new())
+            .and_then(|()| {
+                move_entries(
+                    &staging_dir,
+                    &self.plugins_dir,
+                    self.fail_after,
+                    &mut restored,
+                )
+            });
+        if let Err(error) = result {
+            fs::remove_dir_all(&staging_dir).ok();
+            return Err(match self.roll_back_restore(&previous_dir, &restored) {
+                Ok(()) => error,
+                Err(rollback_error) => Error::Rollback {
+                    error: Box::new(error),
+                    rollback_error: Box::new(rollback_error),
+                },
+            });
+        }
+        fs::remove_dir_all(&staging_dir).at(&staging_dir)?;
+        fs::remove_dir_all(&previous_dir).at(&previous_dir)?;
+
+        for plugin in &manifest.plugins {
+            commit_restored_plugin(cache, plugin)?;
+        }
+        Synchronizer::synchronize(cache, &self.plugins_dir)?;
+
+        Ok(())
+    }
+
+    /// Restores a single plugin of the backup. The current files of the plugin are replaced, every other plugin
+    /// stays untouched.
+    pub fn restore_plugin(
+        &self,
+        cache: &Cache,
+        name: &str,
+        plugin_name: &str,
+    ) -> Result<(), Error> {
+        let _restoring = PLUGINS_DIR_LOCK
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let manifest = self.backup(name)?;
+        let plugin = manifest.plugin(plugin_name).ok_or_else(|| {
+            Error::backup(format!("Backup {} doesn't contain {}", name, plugin_name))
+        })?;
+
+        if let Some(cached) = cached_plugin(cache, plugin)? {
+            let files = cache.get_plugin_files(cached.id)?;
+            remove_manifest_files(&self.plugins_dir, &files)?;
+        }
+
+        let files = plugin.files.iter().collect::<HashSet<&String>>();
+        extract(&self.archive_path(name), &self.plugins_dir, |entry| {
+            files.contains(&entry.to_string())
+        })?;
+
+        commit_restored_plugin(cache, plugin)?;
+        Synchronizer::synchronize(cache, &self.plugins_dir)?;
+
+        Ok(())
+    }
+
+    /// Restores the plugin data of the backup. Only the data files of the given character are restored if a
+    /// character key like `<account>/<server>/<character>` is given, only data files with the given name if a
+    /// file is given. Other data files stay untouched. Returns the restored files.
+    pub fn restore_plugin_data(
+        &self,
+        name: &str,
+        character: Option<&str>,
+        file: Option<&str>,
+    ) -> Result<Vec<String>, Error> {
+        let plugin_data_dir = self
+            .plugin_data_dir
+            .as_ref()
+            .ok_or_else(|| Error::config("The plugin data directory isn't known"))?;
+        let data_archive_path = self.data_archive_path(name);
+        if !data_archive_path.is_file() {
+            return Err(Error::backup(format!(
+                "Backup {} doesn't contain plugin data",
+                name
+            )));
+        }
+
+        let character = character.map(|key| key.trim().trim_matches('/').to_lowercase());
+        let file = file.map(|file| {
+            let file = file.trim().to_lowercase();
+            if file.ends_with(&format!(".{}", plugin_data::PLUGIN_DATA_EXTENSION)) {
+                file
+            } else {
+                format!("{}.{}", file, plugin_data::PLUGIN_DATA_EXTENSION)
+            }
+        });
+        let matches = |entry: &str| {
+            let entry = entry.to_lowercase();
+            let (parent, file_name) = entry.rsplit_once('/').unwrap_or(("", &entry));
+            !file_name.is_empty()
+                && character
+                    .as_ref()
+                    .is_none_or(|character| parent == character)
+                && file.as_ref().is_none_or(|file| file_name == file)
+        };
+
+        let archive = ZipArchive::new(fs::File::open(&data_archive_path).at(&data_archive_path)?)?;
+        let restored = archive
+            .file_names()
+            .filter(|entry| matches(entry))
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+        if restored.is_empty() {
+            return Err(Error::backup(format!(
+                "Backup {} has no matching plugin data",
+                name
+            )));
+        }
+        extract(&data_archive_path, plugin_data_dir, matches)?;
+
+        Ok(restored)
+    }
+
+    /// Removes the entries that were moved into the plugins directory during a failed restore and moves the
+    /// previous content back.
+    fn roll_back_restore(&self, previous_dir: &Path, restored: &[OsString]) -> Result<(), Error> {
+        for name in restored {
+            let path = self.plugins_dir.join(name);
+            if path.is_dir() {
+                fs::remove_dir_all(&path).at(&path)?;
+            } else if path.exists() {
+                fs::remove_file(&path).at(&path)?;
+            }
+        }
+        move_entries(previous_dir, &self.plugins_dir, None, &mut Vec::new())?;
+        fs::remove_dir_all(previous_dir).at(previous_dir)
+    }
+
+    /// A directory next to the plugins directory. It's on the same file system, so entries can be renamed into
+    /// the plugins directory.
+    fn sibling_dir(&self, suffix: &str) -> Result<PathBuf, Error> {
+        let name = self
+            .plugins_dir
+            .file_name()
+            .ok_or_else(|| Error::config("The plugins directory has no name"))?;
+        Ok(self
+            .plugins_dir
+            .with_file_name(format!(".{}_{}", name.to_string_lossy(), suffix)))
+    }
+
+    fn archive_path(&self, name: &str) -> PathBuf {
+        self.backup_dir.join(format!("{}.zip", name))
+    }
+
+    fn data_archive_path(&self, name: &str) -> PathBuf {
+        self.backup_dir.join(format!("{}_plugindata.zip", name))
+    }
+
+    /// Returns the name with a numbered suffix if a backup with the same name already exists.
+    fn unused_name(&self, name: &str) -> String {
+        let mut candidate = name.to_string();
+        let mut counter = 1;
+        while self.archive_path(&candidate).exists() {
+            candidate = format!("{}_{}", name, counter);
+            counter += 1;
+        }
+        candidate
+    }
+}
+
+/// Packs the content of a directory into a compressed archive. The archive is written under another name first,
+/// an interrupted write never looks like a complete archive.
+fn write_archive(source_dir: &Path, path: &Path) -> Result<(), Error> {
+    let partial_path = path.with_extension("part");
+    if let Err(error) = write_entries(source_dir, &partial_path) {
+        fs::remove_file(&partial_path).ok();
+        return Err(error);
+    }
+    fs::rename(&partial_path, path).at(path)?;
+
+    Ok(())
+}
+
+fn write_entries(source_dir: &Path, path: &Path) -> Result<(), Error> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut writer = ZipWriter::new(fs::File::create(path).at(path)?);
+
+    for entry in WalkDir::new(source_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let name = entry
+            .path()
+            .strip_prefix(source_dir)
+            .map_err(|_| {
+                Error::backup(format!(
+                    "{} is outside of {}",
+                    entry.path().display(),
+                    source_dir.display()
+                ))
+            })?
+            .iter()
+            .map(|component| component.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/");
+
+        if entry.file_type().is_dir() {
+            writer.add_directory(format!("{}/", name), options)?;
+        } else if entry.file_type().is_file() {
+            writer.start_file(name, options)?;
+            writer
+                .write_all(&fs::read(entry.path()).at(entry.path())?)
+                .at(path)?;
+        } else {
+            debug!("Skipping {} because it isn't a regular file", name);
+        }
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Extracts the entries of an archive that match the filter into the target directory.
+fn extract<F>(archive_path: &Path, target_dir: &Path, filter: F) -> Result<(), Error>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut archive = ZipArchive::new(fs::File::open(archive_path).at(archive_path)?)?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let entry_name = entry.name().to_string();
+        if !filter(&entry_name) {
+            continue;
+        }
+        let path = target_dir.join(validate_entry_name(&entry_name).map_err(|reason| {
+            UnsafeArchiveEntry {
+                entry: entry_name.clone(),
+                reason,
+            }
+        })?);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&path).at(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).at(parent)?;
+        }
+        io::copy(&mut entry, &mut fs::File::create(&path).at(&path)?).at(&path)?;
+    }
+
+    Ok(())
+}
+
+/// Formats a size in bytes as megabytes with one decimal place.
+pub fn format_size(bytes: u64) -> String {
+    let tenth_megabytes = bytes * 10 / (1024 * 1024);
+    format!("{}.{} MB", tenth_megabytes / 10, tenth_megabytes % 10)
+}
+
+/// Formats the timestamp of a backup for the backup list.
+pub fn format_timestamp(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn cached_plugin(cache: &Cache, plugin: &BackedUpPlugin) -> Result<Option<Plugin>, Error> {
+    let cached = match plugin.id {
+        id if id > 0 => cache.get_plugin_by_id(id)?,
+        _ => None,
+    };
+    match cached {
+        Some(cached) => Ok(Some(cached)),
+        None => cache.get_plugin(&plugin.name),
+    }
+}
+
+/// Marks a restored plugin with the version of the backup as installed. Plugins without a cache row are added by
+/// the synchronization afterwards.
+fn commit_restored_plugin(cache: &Cache, plugin: &BackedUpPlugin) -> Result<(), Error> {
+    if let Some(cached) = cached_plugin(cache, plugin)? {
+        cache.commit_installation(cached.id, &plugin.version, &plugin.files, false)?;
+    }
+    Ok(())
+}
+
+/// Moves every entry of a directory into another directory and records the names of the entries that were moved,
+/// including an entry that was only moved partially. Fails after the given number of entries if a limit is given,
+/// which is used to verify the rollback of a restore.
+fn move_entries(
+    from: &Path,
+    to: &Path,
+    fail_after: Option<usize>,
+    moved: &mut Vec<OsString>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(from).at(from)? {
+        if fail_after == Some(moved.len()) {
+            return Err(Error::backup(format!(
+                "Restore failed after {} entries",
+                moved.len()
+            )));
+        }
+        let entry = entry.at(from)?;
+        moved.push(entry.file_name());
+        move_path(&entry.path(), &to.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
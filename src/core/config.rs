@@ -1,60 +1,199 @@
 //! # Config
 //!
 //! Contains the config logic that is used to determine the storage paths for the `settings` and `plugins` directory.
+//! Every path of a game installation is resolved against the active [`Profile`]. The `plugins` directory is part of
+//! the LOTRO documents directory of the profile, which the user picked on the first start. The first discovered
+//! installation is used until then, see [`crate::core::discovery`].
+//! The `PluginData` directory next to the `plugins` directory contains the settings that plugins save per account,
+//! server and character.
 //! The `settings` directory contains:
 //! - settings.json -> Contains all settings that is specified by the user in the `settings` view.
-//! - plugins.sqlite3 -> Contains the cache plugins which are installed on the users computer.
+//! - plugins.sqlite3 -> Contains the cache plugins which are installed on the users computer. Every profile except
+//!   the default one has its own `plugins_<profile>.sqlite3`.
 //! - tmp/ -> Temporary directory that contains data which is created while installing new plugins.
+//! - feed.xml, feed.json -> The last downloaded plugin feed, used while lotrointerface can't be reached.
+//!
+//! The plugins and backup directory of a profile and the temporary directory can be moved in the settings.
+//! - archives/ -> Downloaded plugin archives that are reused by reinstalls and rollbacks.
+use crate::core::discovery::{discover_installations, Installation, LOTRO_DOCUMENTS_DIR};
+use crate::core::error::{Error, PathContext};
+use crate::core::io::directories::DirectoryKind;
+use crate::core::plugin_data::plugin_data_dir;
 use dirs::{data_dir, home_dir};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::{
-    fs::{self, write, File},
-    io::Read,
-};
+use std::fs::{self, write};
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+use std::time::Duration;
+
+/// Name of the profile that is created on the first start. It uses the cache database of older versions.
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Upper limit of the parallel installations. More parallel downloads only get throttled by lotrointerface.
+pub const MAX_PARALLEL_INSTALLS: usize = 8;
+
+/// Profile that is used instead of the active profile of the settings, e.g. from the `--profile` flag of the CLI,
+/// together with the resolved active profile.
+static PROFILE_OVERRIDE: RwLock<ProfileSelection> = RwLock::new(ProfileSelection {
+    name: None,
+    active: None,
+});
+
+struct ProfileSelection {
+    name: Option<String>,
+    /// The active profile as it was read from the settings. Cleared when the settings are saved or the profile is
+    /// switched.
+    active: Option<Profile>,
+}
+
+/// Uses the given profile for every path until it is reset with `None`. The profile isn't stored in the settings.
+pub fn set_profile_override(name: Option<&str>) {
+    let mut selection = PROFILE_OVERRIDE
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    selection.name = name.map(ToString::to_string);
+    selection.active = None;
+}
+
+/// Returns the profile that every path is resolved against. The settings are only read again after they were saved
+/// or the profile was switched.
+pub fn get_active_profile() -> Profile {
+    if let Some(profile) = &PROFILE_OVERRIDE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .active
+    {
+        return profile.clone();
+    }
+
+    let mut selection = PROFILE_OVERRIDE
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    let settings = load_settings();
+    let name = selection
+        .name
+        .clone()
+        .unwrap_or_else(|| settings.active_profile.clone());
+    let profile = settings
+        .profile(&name)
+        .cloned()
+        .unwrap_or_else(|| Profile::new(&name));
+
+    selection.active = Some(profile.clone());
+    profile
+}
+
+/// Reads the active profile from the settings again on the next access.
+fn forget_active_profile() {
+    PROFILE_OVERRIDE
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .active = None;
+}
+
+/// Returns the LOTRO documents directory of the active profile.
+pub fn get_lotro_documents_dir() -> PathBuf {
+    get_active_profile().documents_dir()
+}
+
+/// Returns the LOTRO documents directory that the user picked for the active profile. The settings file may not
+/// exist yet on the first start.
+pub fn configured_lotro_documents_dir() -> Option<PathBuf> {
+    let profile = get_active_profile();
+
+    (!profile.lotro_documents_dir.is_empty()).then(|| PathBuf::from(profile.lotro_documents_dir))
+}
+
+/// Stores the only discovered installation for the active profile if it has no LOTRO documents directory yet, so
+/// the plugins directory doesn't change once another installation shows up. Returns the discovered installations
+/// if the user has to pick one of them.
+pub fn adopt_discovered_installation() -> Result<Vec<Installation>, Error> {
+    if configured_lotro_documents_dir().is_some() {
+        return Ok(Vec::new());
+    }
+
+    let installations = discover_installations();
+    match installations.as_slice() {
+        [installation] => {
+            save_lotro_documents_dir(&installation.documents_dir)?;
+            Ok(Vec::new())
+        }
+        [] => Ok(Vec::new()),
+        _ => Ok(installations),
+    }
+}
+
+/// Stores the LOTRO documents directory that is managed by the active profile from now on.
+pub fn save_lotro_documents_dir(documents_dir: &Path) -> Result<(), Error> {
+    let storage_dir = get_storage_dir();
+    fs::create_dir_all(&storage_dir).at(&storage_dir)?;
 
-#[cfg(target_os = "windows")]
-pub fn get_home_dir() -> PathBuf {
-    home_dir().expect("Couldn't find your home directory")
+    let name = get_active_profile().name;
+    let mut settings = load_settings();
+    let documents_dir = documents_dir.to_string_lossy().to_string();
+    match settings
+        .profiles
+        .iter_mut()
+        .find(|profile| profile.name == name)
+    {
+        Some(profile) => profile.lotro_documents_dir = documents_dir,
+        None => settings
+            .profiles
+            .push(Profile::new(&name).with_lotro_documents_dir(Path::new(&documents_dir))),
+    }
+    save_settings_changes(&settings)
 }
 
-#[cfg(target_os = "linux")]
-pub fn get_lotro_home_dir() -> PathBuf {
-    let home = home_dir().expect("Couldn't find your home directory");
-    home.join(".steam/steam/steamapps/compatdata/212500/pfx/dosdevices/")
-        .join(r"c:/users/steamuser")
+/// Reads the settings file or returns the default settings if it doesn't exist yet.
+fn load_settings() -> SettingsFile {
+    fs::read_to_string(get_settings_file_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
 }
 
-pub fn initialize_directories() {
+/// Creates the storage, temporary and plugins directories. The plugins directory of a profile without a configured
+/// LOTRO documents directory is only a guess and isn't created.
+pub fn initialize_directories() -> Result<(), Error> {
     let storage_dir = get_storage_dir();
+    let profile = get_active_profile();
 
-    fs::create_dir_all(&get_plugins_dir()).expect("Couldn't create the plugins folder");
-    fs::create_dir_all(&storage_dir).expect("Couldn't create the storage folder");
-    fs::create_dir_all(&storage_dir.join("tmp")).expect("Couldn't create the storage folder");
+    let mut dirs = vec![storage_dir.clone(), get_tmp_dir()];
+    if profile.has_plugins_dir() {
+        dirs.push(profile.plugins_dir());
+    }
+    for dir in dirs {
+        fs::create_dir_all(&dir).at(&dir)?;
+    }
 
     let settings_file_path = &storage_dir.join("settings.json");
 
     if !settings_file_path.exists() {
-        create_existing_settings_file(settings_file_path);
+        create_existing_settings_file(settings_file_path)?;
     }
+
+    Ok(())
 }
 
 pub fn get_plugins_dir() -> PathBuf {
-    get_lotro_home_dir()
-        .join("Documents")
-        .join("The Lord of the Rings Online")
-        .join("Plugins")
+    get_active_profile().plugins_dir()
 }
 
 pub fn get_plugins_backup_dir() -> PathBuf {
-    get_lotro_home_dir()
-        .join("Documents")
-        .join("The Lord of the Rings Online")
-        .join("Plugins_Backup")
+    get_active_profile().backup_dir()
 }
 
+pub fn get_plugin_data_dir() -> PathBuf {
+    get_active_profile().plugin_data_dir()
+}
+
+/// Returns the `lembas` directory in the data directory of the user. The home directory is used on systems without
+/// a data directory.
 pub fn get_storage_dir() -> PathBuf {
-    data_dir().unwrap().join("lembas")
+    data_dir()
+        .or_else(home_dir)
+        .unwrap_or_default()
+        .join("lembas")
 }
 
 pub fn get_settings_file_path() -> PathBuf {
@@ -66,46 +205,218 @@ pub fn get_settings_file_path() -> PathBuf {
 pub fn get_database_file_path() -> PathBuf {
     let storage_dir = get_storage_dir();
 
-    storage_dir.join("plugins.sqlite3")
+    storage_dir.join(get_active_profile().database_file_name())
 }
 
 pub fn get_tmp_dir() -> PathBuf {
+    load_settings().tmp_dir()
+}
+
+pub fn get_archives_dir() -> PathBuf {
     let storage_dir = get_storage_dir();
 
-    storage_dir.join("tmp")
+    storage_dir.join("archives")
 }
 
-pub fn save_settings_changes(settings: &SettingsFile) {
-    let settings_file_path = get_settings_file_path();
+pub fn save_settings_changes(settings: &SettingsFile) -> Result<(), Error> {
+    write_settings_file(&get_settings_file_path(), settings)
+}
 
-    write(
-        &settings_file_path,
-        serde_json::to_string(&settings).unwrap(),
-    )
-    .unwrap();
+fn create_existing_settings_file(settings_path: &Path) -> Result<(), Error> {
+    write_settings_file(settings_path, &SettingsFile::default())
 }
 
-fn create_existing_settings_file(settings_path: &PathBuf) {
-    let initial_settings = SettingsFile::default();
-    File::create(&settings_path).unwrap();
-    write(
-        &settings_path,
-        serde_json::to_string(&initial_settings).unwrap(),
-    )
-    .unwrap();
+fn write_settings_file(settings_path: &Path, settings: &SettingsFile) -> Result<(), Error> {
+    let result = serde_json::to_string(settings)
+        .map_err(std::io::Error::from)
+        .and_then(|data| write(settings_path, data))
+        .at(settings_path);
+    forget_active_profile();
+    result
 }
 
-pub fn read_existing_settings_file() -> SettingsFile {
-    let mut file = File::open(get_settings_file_path()).unwrap();
-    let mut data = String::new();
-    file.read_to_string(&mut data).unwrap();
-    serde_json::from_str(&data).unwrap()
+pub fn read_existing_settings_file() -> Result<SettingsFile, Error> {
+    let settings_path = get_settings_file_path();
+    let data = fs::read_to_string(&settings_path).at(&settings_path)?;
+
+    serde_json::from_str(&data).map_err(|error| {
+        Error::config(format!(
+            "The settings file {} is invalid: {}",
+            settings_path.display(),
+            error
+        ))
+    })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SettingsFile {
     pub backup_enabled: bool,
     pub feed_url: String,
+    /// The feed is checked at most once in this many minutes, refreshes in between use the stored feed. Zero checks
+    /// the feed on every refresh.
+    pub feed_refresh_interval_minutes: u64,
+    /// The GUI checks for plugin updates every this many hours while it's open. Zero disables the check.
+    pub update_check_interval_hours: u64,
+    /// Maximum size of the archive store in megabytes. Zero keeps every archive.
+    pub archive_cache_limit_mb: u64,
+    /// Archives that weren't used for this many days are removed. Zero keeps every archive.
+    pub archive_cache_max_age_days: u64,
+    /// Number of backups that are kept. Zero keeps every backup.
+    pub backup_keep_last: u64,
+    /// Backups that are older than this many days are removed. Zero keeps every backup.
+    pub backup_max_age_days: u64,
+    /// Number of plugins that are installed at the same time, e.g. by Update all
+    pub parallel_installs: u64,
+    pub profiles: Vec<Profile>,
+    /// Name of the profile that is used when no profile is given
+    pub active_profile: String,
+    /// Temporary directory that is used instead of `tmp/` in the storage directory
+    pub tmp_dir_override: String,
+}
+
+impl SettingsFile {
+    /// Returns the maximum size in bytes and the maximum age of the archive store.
+    pub fn archive_cache_limits(&self) -> (u64, Duration) {
+        (
+            self.archive_cache_limit_mb.saturating_mul(1024 * 1024),
+            Duration::from_secs(self.archive_cache_max_age_days.saturating_mul(24 * 60 * 60)),
+        )
+    }
+
+    /// Looks up a profile by its name. Names are compared case-insensitive.
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        let name = name.trim();
+
+        self.profiles
+            .iter()
+            .find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn profile_mut(&mut self, name: &str) -> Option<&mut Profile> {
+        let name = name.trim();
+
+        self.profiles
+            .iter_mut()
+            .find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn tmp_dir(&self) -> PathBuf {
+        if self.tmp_dir_override.is_empty() {
+            get_storage_dir().join("tmp")
+        } else {
+            PathBuf::from(&self.tmp_dir_override)
+        }
+    }
+
+    /// Returns the directory of the given kind for a profile.
+    pub fn directory(&self, profile_name: &str, kind: DirectoryKind) -> PathBuf {
+        let profile = self
+            .profile(profile_name)
+            .cloned()
+            .unwrap_or_else(|| Profile::new(profile_name));
+
+        match kind {
+            DirectoryKind::LotroDocuments => profile.documents_dir(),
+            DirectoryKind::Plugins => profile.plugins_dir(),
+            DirectoryKind::Backups => profile.backup_dir(),
+            DirectoryKind::Temp => self.tmp_dir(),
+        }
+    }
+
+    /// Overrides the directory of the given kind for a profile. An empty path restores the default directory. The
+    /// temporary directory is shared by every profile.
+    pub fn set_directory(&mut self, profile_name: &str, kind: DirectoryKind, path: &str) {
+        let field = match kind {
+            DirectoryKind::LotroDocuments => {
+                &mut self.profile_entry(profile_name).lotro_documents_dir
+            }
+            DirectoryKind::Plugins => &mut self.profile_entry(profile_name).plugins_dir_override,
+            DirectoryKind::Backups => &mut self.profile_entry(profile_name).backup_dir_override,
+            DirectoryKind::Temp => &mut self.tmp_dir_override,
+        };
+        *field = path.trim().to_string();
+    }
+
+    /// Returns the profile with the given name and adds it if it doesn't exist.
+    fn profile_entry(&mut self, name: &str) -> &mut Profile {
+        if self.profile(name).is_none() {
+            self.profiles.push(Profile::new(name));
+        }
+
+        self.profile_mut(name).expect("The profile was added")
+    }
+
+    /// Adds a profile. Names must be unique and must not map to the cache database of another profile.
+    pub fn add_profile(&mut self, mut profile: Profile) -> Result<(), Error> {
+        profile.name = profile.name.trim().to_string();
+        if profile.name.is_empty() {
+            return Err(Error::config("The profile needs a name"));
+        }
+        if self.profiles.iter().any(|existing| {
+            existing.name.eq_ignore_ascii_case(&profile.name)
+                || existing.database_file_name() == profile.database_file_name()
+        }) {
+            return Err(Error::config(format!(
+                "Profile {} already exists",
+                profile.name
+            )));
+        }
+
+        self.profiles.push(profile);
+        Ok(())
+    }
+
+    /// Removes a profile. Its cache database is kept, so adding the profile again restores it.
+    pub fn remove_profile(&mut self, name: &str) -> Result<Profile, Error> {
+        let profile = self
+            .profile(name)
+            .cloned()
+            .ok_or_else(|| Error::config(format!("Profile {} doesn't exist", name.trim())))?;
+        if profile.name == self.active_profile {
+            return Err(Error::config(format!("Profile {} is active", profile.name)));
+        }
+
+        self.profiles
+            .retain(|existing| existing.name != profile.name);
+        Ok(profile)
+    }
+
+    /// Makes an existing profile the active one.
+    pub fn set_active_profile(&mut self, name: &str) -> Result<(), Error> {
+        let profile = self
+            .profile(name)
+            .ok_or_else(|| Error::config(format!("Profile {} doesn't exist", name.trim())))?;
+
+        self.active_profile = profile.name.clone();
+        Ok(())
+    }
+
+    /// Returns the number of kept backups and the maximum age of a backup.
+    pub fn backup_retention(&self) -> (usize, Duration) {
+        (
+            usize::try_from(self.backup_keep_last).unwrap_or(usize::MAX),
+            Duration::from_secs(self.backup_max_age_days.saturating_mul(24 * 60 * 60)),
+        )
+    }
+
+    /// Returns the minimum time between two checks of the feed.
+    pub fn feed_refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.feed_refresh_interval_minutes.saturating_mul(60))
+    }
+
+    /// Returns the interval of the background update check, `None` if the check is disabled.
+    pub fn update_check_interval(&self) -> Option<Duration> {
+        (self.update_check_interval_hours > 0)
+            .then(|| Duration::from_secs(self.update_check_interval_hours.saturating_mul(60 * 60)))
+    }
+
+    /// Returns the number of parallel installations, at least one and at most `MAX_PARALLEL_INSTALLS`.
+    pub fn install_concurrency(&self) -> usize {
+        usize::try_from(self.parallel_installs)
+            .unwrap_or(MAX_PARALLEL_INSTALLS)
+            .clamp(1, MAX_PARALLEL_INSTALLS)
+    }
 }
 
 impl Default for SettingsFile {
@@ -113,6 +424,113 @@ impl Default for SettingsFile {
         Self {
             backup_enabled: true,
             feed_url: String::from("http://api.lotrointerface.com/fav/plugincompendium.xml"),
+            feed_refresh_interval_minutes: 15,
+            update_check_interval_hours: 0,
+            archive_cache_limit_mb: 512,
+            archive_cache_max_age_days: 0,
+            backup_keep_last: 10,
+            backup_max_age_days: 0,
+            parallel_installs: 3,
+            profiles: vec![Profile::new(DEFAULT_PROFILE)],
+            active_profile: DEFAULT_PROFILE.to_string(),
+            tmp_dir_override: String::new(),
         }
     }
 }
+
+/// A game installation with its own plugins, backups and cache database, e.g. a Legendary server client or the
+/// Wine prefix of an alt account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// LOTRO documents directory that contains the plugins. Empty uses the first discovered installation.
+    pub lotro_documents_dir: String,
+    /// Plugins directory that is used instead of `Plugins` in the documents directory
+    pub plugins_dir_override: String,
+    /// Backup directory that is used instead of `Plugins_Backup` in the documents directory
+    pub backup_dir_override: String,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.trim().to_string(),
+            lotro_documents_dir: String::new(),
+            plugins_dir_override: String::new(),
+            backup_dir_override: String::new(),
+        }
+    }
+
+    pub fn with_lotro_documents_dir(mut self, documents_dir: &Path) -> Self {
+        self.lotro_documents_dir = documents_dir.to_string_lossy().to_string();
+        self
+    }
+
+    /// Returns the configured LOTRO documents directory, the first discovered installation or
+    /// `~/Documents/The Lord of the Rings Online` if no installation was found.
+    pub fn documents_dir(&self) -> PathBuf {
+        if !self.lotro_documents_dir.is_empty() {
+            return PathBuf::from(&self.lotro_documents_dir);
+        }
+
+        discover_installations().into_iter().next().map_or_else(
+            || {
+                home_dir()
+                    .unwrap_or_default()
+                    .join("Documents")
+                    .join(LOTRO_DOCUMENTS_DIR)
+            },
+            |installation| installation.documents_dir,
+        )
+    }
+
+    /// Whether the plugins directory was configured instead of guessed from the discovered installations.
+    pub fn has_plugins_dir(&self) -> bool {
+        !self.lotro_documents_dir.is_empty() || !self.plugins_dir_override.is_empty()
+    }
+
+    pub fn plugins_dir(&self) -> PathBuf {
+        if self.plugins_dir_override.is_empty() {
+            self.documents_dir().join("Plugins")
+        } else {
+            PathBuf::from(&self.plugins_dir_override)
+        }
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        if self.backup_dir_override.is_empty() {
+            self.documents_dir().join("Plugins_Backup")
+        } else {
+            PathBuf::from(&self.backup_dir_override)
+        }
+    }
+
+    /// Returns the `PluginData` directory of the documents directory. It stays there if the plugins directory is
+    /// moved, because the game writes it.
+    pub fn plugin_data_dir(&self) -> PathBuf {
+        plugin_data_dir(&self.documents_dir().join("Plugins"))
+    }
+
+    /// Returns the file name of the cache database in the storage directory. Characters that aren't allowed in
+    /// file names are replaced.
+    pub fn database_file_name(&self) -> String {
+        if self.name.eq_ignore_ascii_case(DEFAULT_PROFILE) {
+            return "plugins.sqlite3".to_string();
+        }
+
+        let name = self
+            .name
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        format!("plugins_{}.sqlite3", name)
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new(DEFAULT_PROFILE)
+    }
+}
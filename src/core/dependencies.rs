@@ -0,0 +1,273 @@
+//! # Dependencies
+//!
+//! Plugins declare the ids of the plugins they depend on in their `.plugincompendium` file. Missing dependencies
+//! are looked up in the catalog cache and installed before the plugin itself.
+use bytes::Bytes;
+use log::debug;
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::File,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc},
+};
+use zip::ZipArchive;
+
+use super::{
+    backup::BackupManager,
+    error::{Error, PathContext},
+    installer::ProgressListener,
+    io::{cache::DatabaseHandler, compendium_parser::read_dependencies, ArchiveStore, Cache},
+    Installer, Plugin,
+};
+
+/// Compendium files are small xml files. Everything above this size is not parsed.
+const MAX_COMPENDIUM_SIZE: u64 = 1024 * 1024;
+
+/// A dependency that couldn't be resolved. Problems don't stop the installation, they are reported instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyProblem {
+    /// The dependency id doesn't exist in the catalog cache
+    Unknown { plugin: String, dependency_id: i32 },
+    /// The plugins depend on each other. Contains the names of the plugins that form the cycle.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for DependencyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown {
+                plugin,
+                dependency_id,
+            } => write!(
+                f,
+                "{} depends on plugin {} which isn't in the catalog",
+                plugin, dependency_id
+            ),
+            Self::Cycle(plugins) => write!(f, "Dependency cycle: {}", plugins.join(" -> ")),
+        }
+    }
+}
+
+/// The result of an installation with dependencies.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DependencyReport {
+    /// Names of every installed plugin in the order of the installation. The requested plugin is the last one.
+    pub installed: Vec<String>,
+    pub problems: Vec<DependencyProblem>,
+}
+
+/// Installs a plugin together with its missing dependencies.
+///
+/// The fetch function downloads the archive of a plugin with the given installer, e.g.
+/// `|installer, plugin| installer.download(plugin)`.
+pub struct DependencyResolver<'a, F>
+where
+    F: FnMut(&mut Installer, &Plugin) -> Result<Bytes, Error>,
+{
+    cache: &'a Cache,
+    tmp_dir: PathBuf,
+    plugins_dir: PathBuf,
+    fetch: F,
+    progress: Option<ProgressListener>,
+    cancelled: Arc<AtomicBool>,
+    backup: Option<BackupManager>,
+    archive_store: Option<ArchiveStore>,
+    visiting: Vec<Plugin>,
+    visited: HashSet<i32>,
+    report: DependencyReport,
+}
+
+impl<'a, F> DependencyResolver<'a, F>
+where
+    F: FnMut(&mut Installer, &Plugin) -> Result<Bytes, Error>,
+{
+    pub fn new(cache: &'a Cache, tmp_dir: &Path, plugins_dir: &Path, fetch: F) -> Self {
+        Self {
+            cache,
+            tmp_dir: tmp_dir.to_path_buf(),
+            plugins_dir: plugins_dir.to_path_buf(),
+            fetch,
+            progress: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            backup: None,
+            archive_store: None,
+            visiting: Vec::new(),
+            visited: HashSet::new(),
+            report: DependencyReport::default(),
+        }
+    }
+
+    /// Reports the progress of every installation, dependencies included, to the listener.
+    pub fn with_progress(mut self, listener: ProgressListener) -> Self {
+        self.progress = Some(listener);
+        self
+    }
+
+    /// Cancels the installation of the plugin and of the dependencies that aren't installed yet as soon as the
+    /// flag is set.
+    pub fn with_cancel_flag(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = cancelled;
+        self
+    }
+
+    /// Backs up the plugins directory with the given manager before the first download. The plugin and its
+    /// dependencies are covered by this single backup, the installers never back up on their own. Without a
+    /// manager nothing is backed up, e.g. because a single backup was created before several plugins are updated.
+    pub fn with_backup(mut self, manager: Option<BackupManager>) -> Self {
+        self.backup = manager;
+        self
+    }
+
+    /// Stores the archives of every installation, dependencies included, in the given store.
+    pub fn with_archive_store(mut self, store: ArchiveStore) -> Self {
+        self.archive_store = Some(store);
+        self
+    }
+
+    /// Installs the plugin after every missing dependency is installed. Fails if the plugin or one of its
+    /// dependencies can't be installed. Unknown dependencies and cycles are part of the report.
+    pub fn install(mut self, plugin: &Plugin) -> Result<DependencyReport, Error> {
+        if let Some(manager) = &self.backup {
+            manager.create()?;
+        }
+        self.install_plugin(plugin)?;
+
+        for problem in &self.report.problems {
+            debug!("{}", problem);
+        }
+
+        Ok(self.report)
+    }
+
+    fn install_plugin(&mut self, plugin: &Plugin) -> Result<(), Error> {
+        if let Some(position) = self
+            .visiting
+            .iter()
+            .position(|visiting| visiting.id == plugin.id)
+        {
+            let mut cycle = self.visiting[position..]
+                .iter()
+                .map(|visiting| visiting.name.clone())
+                .collect::<Vec<String>>();
+            cycle.push(plugin.name.clone());
+            self.report.problems.push(DependencyProblem::Cycle(cycle));
+            return Ok(());
+        }
+        if self.visited.contains(&plugin.id) {
+            return Ok(());
+        }
+
+        self.visiting.push(plugin.clone());
+        let mut installer =
+            Installer::new(&self.tmp_dir, &self.plugins_dir, plugin.id, &plugin.name)
+                .with_cancel_flag(self.cancelled.clone())
+                .with_backup(false);
+        if let Some(listener) = &self.progress {
+            installer = installer.with_progress(listener.clone());
+        }
+        if let Some(store) = &self.archive_store {
+            installer = installer.with_archive_store(store.clone());
+        }
+        let bytes = (self.fetch)(&mut installer, plugin)?;
+
+        for dependency_id in archive_dependencies(&bytes)? {
+            match self.cache.get_plugin_by_id(dependency_id)? {
+                Some(dependency) if dependency.installed == 1 => {}
+                Some(dependency) => {
+                    debug!(
+                        "Installing {} as dependency of {}",
+                        dependency.name, plugin.name
+                    );
+                    self.install_plugin(&dependency)?;
+                }
+                None => self.report.problems.push(DependencyProblem::Unknown {
+                    plugin: plugin.name.clone(),
+                    dependency_id,
+                }),
+            }
+        }
+
+        installer.install_archive(self.cache, plugin.id, &plugin.latest_version, &bytes)?;
+
+        self.visiting.pop();
+        self.visited.insert(plugin.id);
+        self.report.installed.push(plugin.name.clone());
+
+        Ok(())
+    }
+}
+
+/// Returns the dependency ids of every compendium file inside of the archive.
+pub fn archive_dependencies(bytes: &[u8]) -> Result<Vec<i32>, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    let mut dependencies = Vec::new();
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        if !is_compendium(entry.name()) {
+            continue;
+        }
+
+        let name = entry.name().to_string();
+        match read_dependencies(entry.take(MAX_COMPENDIUM_SIZE), Path::new(&name)) {
+            Ok(ids) => push_unique(&mut dependencies, ids),
+            Err(error) => debug!("Can't read the dependencies: {}", error),
+        }
+    }
+
+    Ok(dependencies)
+}
+
+/// Returns the dependency ids of the installed plugin. The compendium files are taken from its file manifest.
+pub fn installed_dependencies(
+    cache: &Cache,
+    plugins_dir: &Path,
+    plugin_id: i32,
+) -> Result<Vec<i32>, Error> {
+    let mut dependencies = Vec::new();
+
+    for file in cache.get_plugin_files(plugin_id)? {
+        let path = plugins_dir.join(&file);
+        if !is_compendium(&file) || !path.is_file() {
+            continue;
+        }
+
+        match read_dependencies(File::open(&path).at(&path)?, &path) {
+            Ok(ids) => push_unique(&mut dependencies, ids),
+            Err(error) => debug!("Can't read the dependencies: {}", error),
+        }
+    }
+
+    Ok(dependencies)
+}
+
+/// Returns the names of the installed plugins that depend on the given plugin.
+pub fn dependents(cache: &Cache, plugins_dir: &Path, plugin_id: i32) -> Result<Vec<String>, Error> {
+    let mut dependents = Vec::new();
+
+    for plugin in cache.get_installed_plugins()?.values() {
+        if plugin.id == plugin_id {
+            continue;
+        }
+        if installed_dependencies(cache, plugins_dir, plugin.id)?.contains(&plugin_id) {
+            dependents.push(plugin.name.clone());
+        }
+    }
+    dependents.sort();
+
+    Ok(dependents)
+}
+
+fn is_compendium(name: &str) -> bool {
+    name.to_lowercase().ends_with(".plugincompendium")
+}
+
+fn push_unique(dependencies: &mut Vec<i32>, ids: Vec<i32>) {
+    for id in ids {
+        if !dependencies.contains(&id) {
+            dependencies.push(id);
+        }
+    }
+}
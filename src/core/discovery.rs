@@ -0,0 +1,295 @@
+//! # Discovery
+//!
+//! Finds the `The Lord of the Rings Online` documents directory of every LOTRO installation on the computer. The
+//! directory contains the `Plugins` and `PluginData` directories. Native clients on Windows and macOS use the
+//! documents directory of the user, every other installation has its own Wine prefix:
+//! - Steam -> `<library>/steamapps/compatdata/212500/pfx` for every Steam library
+//! - Lutris -> the `prefix` of every game configuration and the directories in `~/Games`
+//! - Bottles -> every bottle of the native and the Flatpak installation
+//! - `CrossOver` -> every bottle
+//! - Wine -> `$WINEPREFIX` and `~/.wine`
+use dirs::{document_dir, home_dir};
+use serde::Serialize;
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Name of the directory that LOTRO creates in the documents directory.
+pub const LOTRO_DOCUMENTS_DIR: &str = "The Lord of the Rings Online";
+/// Steam app id of LOTRO.
+pub const LOTRO_STEAM_APP_ID: &str = "212500";
+
+/// Steam installations relative to the home directory. The Flatpak uses two different layouts.
+const STEAM_ROOTS: [&str; 4] = [
+    ".steam/steam",
+    ".local/share/Steam",
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+    ".var/app/com.valvesoftware.Steam/data/Steam",
+];
+const LUTRIS_CONFIG_DIRS: [&str; 2] = [".config/lutris/games", ".local/share/lutris/games"];
+const BOTTLES_DIRS: [&str; 2] = [
+    ".local/share/bottles/bottles",
+    ".var/app/com.usebottles.bottles/data/bottles/bottles",
+];
+const CROSSOVER_DIRS: [&str; 2] = ["Library/Application Support/CrossOver/Bottles", ".cxoffice"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InstallationSource {
+    Native,
+    Steam,
+    Lutris,
+    Bottles,
+    CrossOver,
+    Wine,
+}
+
+impl fmt::Display for InstallationSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Native => write!(f, "Native"),
+            Self::Steam => write!(f, "Steam"),
+            Self::Lutris => write!(f, "Lutris"),
+            Self::Bottles => write!(f, "Bottles"),
+            Self::CrossOver => write!(f, "CrossOver"),
+            Self::Wine => write!(f, "Wine"),
+        }
+    }
+}
+
+/// A LOTRO documents directory together with the kind of installation it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Installation {
+    pub source: InstallationSource,
+    pub documents_dir: PathBuf,
+}
+
+impl fmt::Display for Installation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.documents_dir.display(), self.source)
+    }
+}
+
+/// Probes the known installation layouts below a home directory.
+#[derive(Debug, Clone)]
+pub struct Discovery {
+    home_dir: PathBuf,
+    documents_dir: PathBuf,
+    wine_prefix: Option<PathBuf>,
+}
+
+impl Discovery {
+    pub fn new(home_dir: &Path) -> Self {
+        Self {
+            home_dir: home_dir.to_path_buf(),
+            documents_dir: home_dir.join("Documents"),
+            wine_prefix: None,
+        }
+    }
+
+    /// Sets the documents directory of the user if it isn't `~/Documents`, e.g. if Windows redirects it.
+    pub fn with_documents_dir(mut self, documents_dir: &Path) -> Self {
+        self.documents_dir = documents_dir.to_path_buf();
+        self
+    }
+
+    /// Adds a Wine prefix that isn't in one of the default locations, e.g. `$WINEPREFIX`.
+    pub fn with_wine_prefix(mut self, wine_prefix: &Path) -> Self {
+        self.wine_prefix = Some(wine_prefix.to_path_buf());
+        self
+    }
+
+    /// Returns every LOTRO documents directory that exists. Native installations come first, followed by the Wine
+    /// prefixes in the order of the module documentation. Directories that are found twice are only returned once.
+    pub fn installations(&self) -> Vec<Installation> {
+        let mut candidates = vec![
+            (
+                InstallationSource::Native,
+                self.documents_dir.join(LOTRO_DOCUMENTS_DIR),
+            ),
+            (
+                InstallationSource::Native,
+                self.home_dir
+                    .join("OneDrive/Documents")
+                    .join(LOTRO_DOCUMENTS_DIR),
+            ),
+        ];
+        for prefix in self.steam_prefixes() {
+            candidates.extend(prefix_candidates(InstallationSource::Steam, &prefix));
+        }
+        for prefix in self.lutris_prefixes() {
+            candidates.extend(prefix_candidates(InstallationSource::Lutris, &prefix));
+        }
+        for bottles_dir in BOTTLES_DIRS {
+            for prefix in subdirectories(&self.home_dir.join(bottles_dir)) {
+                candidates.extend(prefix_candidates(InstallationSource::Bottles, &prefix));
+            }
+        }
+        for crossover_dir in CROSSOVER_DIRS {
+            for prefix in subdirectories(&self.home_dir.join(crossover_dir)) {
+                candidates.extend(prefix_candidates(InstallationSource::CrossOver, &prefix));
+            }
+        }
+        for prefix in self
+            .wine_prefix
+            .iter()
+            .chain([&self.home_dir.join(".wine")])
+        {
+            candidates.extend(prefix_candidates(InstallationSource::Wine, prefix));
+        }
+
+        let mut installations: Vec<Installation> = Vec::new();
+        let mut seen = Vec::new();
+        for (source, documents_dir) in candidates {
+            if !documents_dir.is_dir() {
+                continue;
+            }
+            let canonical =
+                fs::canonicalize(&documents_dir).unwrap_or_else(|_| documents_dir.clone());
+            if seen.contains(&canonical) {
+                continue;
+            }
+            seen.push(canonical);
+            installations.push(Installation {
+                source,
+                documents_dir,
+            });
+        }
+
+        installations
+    }
+
+    /// Returns the LOTRO prefix of every Steam library. Libraries outside of the Steam directory are listed in
+    /// `steamapps/libraryfolders.vdf`.
+    fn steam_prefixes(&self) -> Vec<PathBuf> {
+        let mut libraries = Vec::new();
+        for root in STEAM_ROOTS {
+            let root = self.home_dir.join(root);
+            let library_folders = root.join("steamapps/libraryfolders.vdf");
+            libraries.push(root);
+            if let Ok(content) = fs::read_to_string(library_folders) {
+                libraries.extend(vdf_values(&content, "path").into_iter().map(PathBuf::from));
+            }
+        }
+
+        libraries
+            .into_iter()
+            .map(|library| {
+                library
+                    .join("steamapps/compatdata")
+                    .join(LOTRO_STEAM_APP_ID)
+                    .join("pfx")
+            })
+            .collect()
+    }
+
+    /// Returns the prefixes of the Lutris game configurations and the directories of the default game directory.
+    fn lutris_prefixes(&self) -> Vec<PathBuf> {
+        let mut prefixes = Vec::new();
+        for config_dir in LUTRIS_CONFIG_DIRS {
+            let Ok(entries) = fs::read_dir(self.home_dir.join(config_dir)) else {
+                continue;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                if let Ok(content) = fs::read_to_string(entry.path()) {
+                    prefixes.extend(
+                        yaml_values(&content, "prefix")
+                            .into_iter()
+                            .map(PathBuf::from),
+                    );
+                }
+            }
+        }
+        prefixes.extend(subdirectories(&self.home_dir.join("Games")));
+
+        prefixes
+    }
+}
+
+/// Installations that were found by the first search of this session.
+static DISCOVERED_INSTALLATIONS: OnceLock<Vec<Installation>> = OnceLock::new();
+
+/// Returns the installations found in the home directory of the current user. The home directory is searched once
+/// per session.
+pub fn discover_installations() -> Vec<Installation> {
+    DISCOVERED_INSTALLATIONS
+        .get_or_init(search_installations)
+        .clone()
+}
+
+fn search_installations() -> Vec<Installation> {
+    let Some(home_dir) = home_dir() else {
+        return Vec::new();
+    };
+
+    let mut discovery = Discovery::new(&home_dir);
+    if let Some(documents_dir) = document_dir() {
+        discovery = discovery.with_documents_dir(&documents_dir);
+    }
+    if let Some(wine_prefix) = env::var_os("WINEPREFIX") {
+        discovery = discovery.with_wine_prefix(Path::new(&wine_prefix));
+    }
+
+    discovery.installations()
+}
+
+/// Returns the LOTRO documents directory of every user of a Wine prefix. Older Wine versions name the documents
+/// directory `My Documents`.
+fn prefix_candidates(
+    source: InstallationSource,
+    prefix: &Path,
+) -> Vec<(InstallationSource, PathBuf)> {
+    subdirectories(&prefix.join("drive_c/users"))
+        .into_iter()
+        .filter(|user_dir| !user_dir.ends_with("Public"))
+        .flat_map(|user_dir| {
+            ["Documents", "My Documents"]
+                .map(|documents| (source, user_dir.join(documents).join(LOTRO_DOCUMENTS_DIR)))
+        })
+        .collect()
+}
+
+fn subdirectories(path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    let mut directories = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<PathBuf>>();
+    directories.sort();
+
+    directories
+}
+
+/// Returns the values of a key in a Steam `.vdf` file, e.g. `"path"  "/mnt/games/SteamLibrary"`.
+fn vdf_values(content: &str, key: &str) -> Vec<String> {
+    let key = format!("\"{}\"", key);
+
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(&key))
+        .map(|value| value.trim().trim_matches('"').replace("\\\\", "\\"))
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Returns the values of a key in a YAML file without parsing its structure, e.g. `  prefix: /home/user/Games/lotro`.
+fn yaml_values(content: &str, key: &str) -> Vec<String> {
+    let key = format!("{}:", key);
+
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(&key))
+        .map(|value| {
+            value
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string()
+        })
+        .filter(|value| !value.is_empty())
+        .collect()
+}
@@ -0,0 +1,236 @@
+//! # Error
+//!
+//! The error type of the core. Every public function of the core returns [`Error`], so the CLI and the GUI can
+//! show a readable message instead of panicking. Errors of the filesystem carry the path they occurred at, use
+//! [`PathContext::at`] to attach it to an [`io::Error`].
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use super::{archive::UnsafeArchiveEntry, installer::CorruptDownload};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The feed or an archive couldn't be downloaded from the given URL
+    Network {
+        url: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The feed isn't valid XML or misses required fields
+    FeedParse(serde_xml_rs::Error),
+    Archive(ArchiveError),
+    /// A `.plugin` or `.plugincompendium` file of a plugin can't be parsed
+    Descriptor {
+        path: PathBuf,
+        message: String,
+    },
+    Filesystem {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Database(DatabaseError),
+    /// The settings, a profile or a lockfile are invalid
+    Config(String),
+    /// The plugin can't be installed, updated or removed in its current state, e.g. because it isn't installed
+    Plugin(String),
+    /// The backup doesn't exist or doesn't contain what should be restored
+    Backup(String),
+    /// The installation was cancelled by the user, nothing was changed
+    Cancelled,
+    /// The installation failed and the previous files of the plugin couldn't be restored
+    Rollback {
+        error: Box<Error>,
+        rollback_error: Box<Error>,
+    },
+}
+
+impl Error {
+    pub fn network(url: &str, source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self::Network {
+            url: url.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn filesystem(path: &Path, source: io::Error) -> Self {
+        Self::Filesystem {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn descriptor(path: &Path, message: &impl fmt::Display) -> Self {
+        Self::Descriptor {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        Self::Config(message.into())
+    }
+
+    pub fn plugin(message: impl Into<String>) -> Self {
+        Self::Plugin(message.into())
+    }
+
+    pub fn backup(message: impl Into<String>) -> Self {
+        Self::Backup(message.into())
+    }
+
+    pub fn invalid_archive(message: impl Into<String>) -> Self {
+        Self::Archive(ArchiveError::Invalid(message.into()))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network { url, source } => write!(f, "Can't download {}: {}", url, source),
+            Self::FeedParse(error) => write!(f, "The plugin feed can't be read: {}", error),
+            Self::Archive(error) => write!(f, "{}", error),
+            Self::Descriptor { path, message } => {
+                write!(f, "{} can't be parsed: {}", path.display(), message)
+            }
+            Self::Filesystem { path, source } => write!(f, "{}: {}", path.display(), source),
+            Self::Database(error) => write!(f, "Database error: {}", error),
+            Self::Config(message) | Self::Plugin(message) | Self::Backup(message) => {
+                write!(f, "{}", message)
+            }
+            Self::Cancelled => write!(f, "Installation cancelled"),
+            Self::Rollback {
+                error,
+                rollback_error,
+            } => write!(f, "{} (rollback failed: {})", error, rollback_error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Network { source, .. } => Some(source.as_ref()),
+            Self::FeedParse(error) => Some(error),
+            Self::Archive(error) => Some(error),
+            Self::Filesystem { source, .. } => Some(source),
+            Self::Rollback { error, .. } => Some(error.as_ref()),
+            Self::Database(error) => Some(error),
+            Self::Descriptor { .. }
+            | Self::Config(_)
+            | Self::Plugin(_)
+            | Self::Backup(_)
+            | Self::Cancelled => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Zip(zip::result::ZipError),
+    UnsafeEntry(UnsafeArchiveEntry),
+    /// The downloaded archive doesn't match the size or hash of the feed
+    Corrupt(CorruptDownload),
+    /// The archive doesn't contain an installable plugin
+    Invalid(String),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zip(error) => write!(f, "Invalid archive: {}", error),
+            Self::UnsafeEntry(entry) => write!(f, "{}", entry),
+            Self::Corrupt(corrupt) => write!(f, "{}", corrupt),
+            Self::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Zip(error) => Some(error),
+            Self::UnsafeEntry(entry) => Some(entry),
+            Self::Corrupt(corrupt) => Some(corrupt),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Sqlite(rusqlite::Error),
+    /// No connection of the pool is available
+    Pool(r2d2::Error),
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite(error) => write!(f, "{}", error),
+            Self::Pool(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Sqlite(error) => Some(error),
+            Self::Pool(error) => Some(error),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(error: zip::result::ZipError) -> Self {
+        Self::Archive(ArchiveError::Zip(error))
+    }
+}
+
+impl From<UnsafeArchiveEntry> for Error {
+    fn from(entry: UnsafeArchiveEntry) -> Self {
+        Self::Archive(ArchiveError::UnsafeEntry(entry))
+    }
+}
+
+impl From<CorruptDownload> for Error {
+    fn from(corrupt: CorruptDownload) -> Self {
+        Self::Archive(ArchiveError::Corrupt(corrupt))
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::Database(DatabaseError::Sqlite(error))
+    }
+}
+
+impl From<r2d2::Error> for Error {
+    fn from(error: r2d2::Error) -> Self {
+        Self::Database(DatabaseError::Pool(error))
+    }
+}
+
+impl From<walkdir::Error> for Error {
+    fn from(error: walkdir::Error) -> Self {
+        let path = error.path().map(Path::to_path_buf).unwrap_or_default();
+        let source = match error.into_io_error() {
+            Some(source) => source,
+            None => io::Error::other("filesystem loop"),
+        };
+
+        Self::Filesystem { path, source }
+    }
+}
+
+/// Attaches the path to the error of a filesystem operation, e.g. `fs::read(&path).at(&path)?`.
+pub trait PathContext<T> {
+    fn at(self, path: &Path) -> Result<T, Error>;
+}
+
+impl<T> PathContext<T> for io::Result<T> {
+    fn at(self, path: &Path) -> Result<T, Error> {
+        self.map_err(|source| Error::filesystem(path, source))
+    }
+}
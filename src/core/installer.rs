@@ -1,31 +1,104 @@
 use bytes::Bytes;
-use chrono::offset::Utc;
-use chrono::DateTime;
-use fs_extra::{
-    self,
-    dir::{copy, CopyOptions},
-};
 use log::debug;
-use std::time::SystemTime;
+use std::path::Path;
 use std::{
-    error::Error,
+    cmp::Reverse,
+    collections::{BTreeSet, HashSet},
+    error,
     fs::{create_dir_all, File, OpenOptions},
-    path::PathBuf,
+    io::{self, Cursor},
+    path::{Component, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, PoisonError,
+    },
 };
-use std::{fs, io::prelude::*};
-use std::{fs::metadata, path::Path};
+use std::{fmt, fs, io::prelude::*};
 
-use crate::gui::views::plugins::{Event, PluginRow};
+use walkdir::WalkDir;
 
 use super::{
-    config::{get_plugins_backup_dir, read_existing_settings_file},
-    io::{cache::DatabaseHandler, Cache},
+    archive::{archive_directory, extract_archive, validate_archive, ArchiveLimits},
+    backup::{BackupManager, PLUGINS_DIR_LOCK},
+    config::{get_archives_dir, get_plugins_backup_dir, read_existing_settings_file, SettingsFile},
+    error::{Error, PathContext},
+    io::{
+        cache::{DatabaseHandler, HistoryEntry},
+        ArchiveStore, Cache, Synchronizer,
+    },
+    layout::ArchiveLayout,
+    plugin::LOCAL_SOURCE,
+    plugin_data::plugin_data_dir,
+    Plugin,
 };
 
+/// Archives are downloaded and reported to the progress listener in chunks of this size.
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The steps of an installation. Every step after the download is rolled back if a later step fails. Tests let the
+/// installation fail after a step.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InstallStep {
+    Download,
+    Extract,
+    MoveAside,
+    Swap,
+    Commit,
+}
+
+/// Progress of an installation that is reported to the progress listener of the installer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallProgress {
+    /// Bytes of the archive that were received so far. The total is unknown if the server doesn't send it.
+    Downloading {
+        received: u64,
+        total: Option<u64>,
+    },
+    Extracting,
+    Moving,
+}
+
+/// Receives the name of the plugin and the progress of its installation. It's called from the thread that runs
+/// the installation.
+pub type ProgressListener = Arc<dyn Fn(&str, &InstallProgress) + Send + Sync>;
+
+/// Returned if a downloaded archive doesn't match the size or MD5 hash announced by the feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptDownload {
+    pub expected_size: i32,
+    pub actual_size: usize,
+    pub expected_hash: String,
+    pub actual_hash: String,
+}
+
+impl fmt::Display for CorruptDownload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Corrupt download: expected {} bytes with MD5 {}, received {} bytes with MD5 {}",
+            self.expected_size, self.expected_hash, self.actual_size, self.actual_hash
+        )
+    }
+}
+
+impl error::Error for CorruptDownload {}
+
 pub struct Installer {
     pub plugins_dir: PathBuf,
     pub tmp_file_path: PathBuf,
     pub files: Vec<String>,
+    pub verified: bool,
+    plugin_title: String,
+    archive_limits: ArchiveLimits,
+    archive_store: ArchiveStore,
+    moved_aside: Vec<String>,
+    swap_started: bool,
+    #[cfg(test)]
+    fail_at: Option<InstallStep>,
+    progress: Option<ProgressListener>,
+    cancelled: Arc<AtomicBool>,
+    backup: bool,
 }
 
 impl Installer {
@@ -38,198 +111,827 @@ impl Installer {
                 plugin_title.replace(' ', "_")
             )),
             files: Vec::new(),
+            verified: false,
+            plugin_title: plugin_title.to_string(),
+            archive_limits: ArchiveLimits::default(),
+            archive_store: ArchiveStore::new(&get_archives_dir()),
+            moved_aside: Vec::new(),
+            swap_started: false,
+            #[cfg(test)]
+            fail_at: None,
+            progress: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            backup: true,
         }
     }
 
-    pub fn run_installation(
+    pub fn with_archive_limits(mut self, limits: ArchiveLimits) -> Self {
+        self.archive_limits = limits;
+        self
+    }
+
+    /// Sets the store of the downloaded and installed archives. Without a store the archives are kept in the archives
+    /// directory of the storage directory.
+    pub fn with_archive_store(mut self, store: ArchiveStore) -> Self {
+        self.archive_store = store;
+        self
+    }
+
+    pub fn with_progress(mut self, listener: ProgressListener) -> Self {
+        self.progress = Some(listener);
+        self
+    }
+
+    /// The installation stops with `Error::Cancelled` as soon as the flag is set. Files that were already moved
+    /// aside are restored, an installation whose files were swapped in is finished.
+    pub fn with_cancel_flag(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = cancelled;
+        self
+    }
+
+    /// Disables the backup before the download if a single backup was created before several plugins are installed.
+    pub fn with_backup(mut self, enabled: bool) -> Self {
+        self.backup = enabled;
+        self
+    }
+
+    /// Downloads the latest version of the plugin and installs it with `install_archive`.
+    pub fn run_installation(&mut self, cache: &Cache, plugin: &Plugin) -> Result<(), Error> {
+        let bytes = self.download(plugin)?;
+
+        self.install_archive(cache, plugin.id, &plugin.latest_version, &bytes)
+    }
+
+    /// Installs or updates a plugin as a single transaction.
+    ///
+    /// The archive is extracted into the tmp directory, the files of the installed version are moved aside,
+    /// the new files are swapped in and the cache is updated last. If any of these steps fails the previous
+    /// files are restored and the cache stays untouched.
+    pub fn install_archive(
         &mut self,
         cache: &Cache,
-        plugin: &PluginRow,
-    ) -> (Event, String, String) {
-        if let Ok(bytes) = self.download(&plugin.download_url) {
-            if let Ok(root_folder_name) = self.install(&bytes) {
-                if self.delete().is_ok() {
-                    if cache.delete_plugin(&plugin.title).is_ok() {
-                        self.move_files(&root_folder_name);
-                        if cache
-                            .mark_as_installed(plugin.id, &plugin.latest_version)
-                            .is_ok()
-                        {
-                            self.delete_cache_folder();
-                            (
-                                Event::Synchronize,
-                                "Updated".to_string(),
-                                plugin.latest_version.clone(),
-                            )
-                        } else {
-                            (Event::Nothing, "Update failed".to_string(), String::new())
-                        }
-                    } else {
-                        (Event::Nothing, "Update failed".to_string(), String::new())
-                    }
-                } else {
-                    (Event::Nothing, "Update failed".to_string(), String::new())
+        plugin_id: i32,
+        version: &str,
+        bytes: &Bytes,
+    ) -> Result<(), Error> {
+        self.moved_aside.clear();
+        self.swap_started = false;
+        let _moving = PLUGINS_DIR_LOCK
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let result = match self.apply_archive(cache, plugin_id, version, bytes) {
+            Ok(()) => {
+                if let Err(error) = remove_empty_directories(&self.plugins_dir, &self.moved_aside) {
+                    debug!("Error while cleaning up the old plugin folders: {}", error);
+                }
+                if let Err(error) = self.record_history(cache, plugin_id, version, bytes) {
+                    debug!(
+                        "Can't record the version history of {}: {}",
+                        plugin_id, error
+                    );
                 }
-            } else {
-                (Event::Nothing, "Update failed".to_string(), String::new())
+                Ok(())
             }
+            Err(error) => {
+                debug!("Installation of plugin {} failed: {}", plugin_id, error);
+                if let Err(rollback_error) = self.rollback() {
+                    return Err(Error::Rollback {
+                        error: Box::new(error),
+                        rollback_error: Box::new(rollback_error),
+                    });
+                }
+                Err(error)
+            }
+        };
+
+        if self.tmp_file_path.exists() {
+            if let Err(error) = self.delete_cache_folder() {
+                debug!("Error while deleting the tmp folder: {}", error);
+            }
+        }
+
+        result
+    }
+
+    /// Reinstalls a version from the version history and pins the plugin, so Update all skips it. The archive
+    /// of the version has to be in the archive store.
+    pub fn roll_back_to(
+        &mut self,
+        cache: &Cache,
+        plugin_id: i32,
+        version: &str,
+    ) -> Result<(), Error> {
+        let entry = self
+            .rollback_candidates(cache, plugin_id)?
+            .into_iter()
+            .find(|entry| entry.version == version)
+            .ok_or_else(|| {
+                Error::plugin(format!(
+                    "Version {} of {} can't be restored, it was never installed or its archive is gone",
+                    version, self.plugin_title
+                ))
+            })?;
+        let bytes = self
+            .archive_store
+            .get(plugin_id, &entry.version, &entry.hash)
+            .ok_or_else(|| {
+                Error::plugin(format!(
+                    "The archive of {} {} is gone",
+                    self.plugin_title, version
+                ))
+            })?;
+
+        self.install_archive(cache, plugin_id, &entry.version, &bytes)?;
+        cache.set_pinned(plugin_id, true)?;
+
+        Ok(())
+    }
+
+    /// Returns the versions of the history that can be restored, most recently installed first. The installed
+    /// version and versions whose archive was pruned are skipped.
+    pub fn rollback_candidates(
+        &self,
+        cache: &Cache,
+        plugin_id: i32,
+    ) -> Result<Vec<HistoryEntry>, Error> {
+        let current_version = cache
+            .get_plugin_by_id(plugin_id)?
+            .map(|plugin| plugin.current_version)
+            .unwrap_or_default();
+        let stored_hashes = self
+            .archive_store
+            .plugin_archives(plugin_id)
+            .into_iter()
+            .map(|archive| archive.hash)
+            .collect::<HashSet<String>>();
+
+        let mut versions = HashSet::new();
+        Ok(cache
+            .get_plugin_history(plugin_id)?
+            .into_iter()
+            .filter(|entry| entry.version != current_version && stored_hashes.contains(&entry.hash))
+            .filter(|entry| versions.insert(entry.version.clone()))
+            .collect())
+    }
+
+    /// Installs a plugin from a local zip file or folder, e.g. a beta build that isn't on lotrointerface.
+    ///
+    /// The plugin goes through the same pipeline as a downloaded archive. Name, author and version are taken
+    /// from the first descriptor inside of the archive. The cache record is marked as local, so the
+    /// synchronization with the feed never overwrites it. Installing the same plugin again updates it.
+    pub fn install_local(&mut self, cache: &Cache, path: &Path) -> Result<Plugin, Error> {
+        let bytes = if path.is_dir() {
+            Bytes::from(archive_directory(path)?)
+        } else if path.is_file() {
+            Bytes::from(fs::read(path).at(path)?)
+        } else {
+            return Err(Error::plugin(format!(
+                "{} is neither a zip file nor a folder",
+                path.display()
+            )));
+        };
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes.clone()))?;
+        validate_archive(&mut archive, &self.archive_limits)?;
+        let layout = ArchiveLayout::analyse(&mut archive)?;
+
+        let information = layout.plugins.first().cloned().unwrap_or_default();
+        let name = if information.name.is_empty() {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .ok_or_else(|| {
+                    Error::plugin(format!("Can't find a plugin name for {}", path.display()))
+                })?
+        } else {
+            information.name
+        };
+        let version = if information.version.is_empty() {
+            LOCAL_SOURCE.to_string()
         } else {
-            (Event::Nothing, "Update failed".to_string(), String::new())
+            information.version
+        };
+
+        let existing_plugin = cache.get_plugin(&name)?;
+        let plugin_id = match &existing_plugin {
+            Some(plugin) if plugin.is_local() => plugin.id,
+            Some(_) => {
+                return Err(Error::plugin(format!(
+                    "{} is already managed through the plugin catalog",
+                    name
+                )))
+            }
+            None => cache.next_local_plugin_id()?,
+        };
+
+        let mut plugin = Plugin::new(&name)
+            .with_id(plugin_id)
+            .with_author(&information.author)
+            .with_description(&information.description)
+            .with_current_version(&version)
+            .with_source(LOCAL_SOURCE)
+            .build();
+        plugin.latest_version.clone_from(&version);
+
+        if existing_plugin.is_none() {
+            cache.insert_plugin(&plugin, 0)?;
+        }
+
+        if let Err(error) = self.install_archive(cache, plugin_id, &version, &bytes) {
+            if existing_plugin.is_none() {
+                cache.delete_plugin(&name)?;
+            }
+            return Err(error);
         }
+
+        plugin.installed = 1;
+        cache.insert_plugin(&plugin, plugin.installed)?;
+
+        Ok(plugin)
+    }
+
+    fn apply_archive(
+        &mut self,
+        cache: &Cache,
+        plugin_id: i32,
+        version: &str,
+        bytes: &Bytes,
+    ) -> Result<(), Error> {
+        self.check_cancelled()?;
+        let previous_files = cache.get_plugin_files(plugin_id)?;
+
+        let layout = self.install(bytes)?;
+        #[cfg(test)]
+        self.check_failure(InstallStep::Extract)?;
+
+        self.report_progress(&InstallProgress::Moving);
+
+        // Files of the new archive that already exist are moved aside as well. This covers plugins that were
+        // installed before file manifests were recorded.
+        let mut replaced_files = previous_files;
+        replaced_files.extend(self.files.iter().cloned());
+        self.move_aside(&replaced_files)?;
+        #[cfg(test)]
+        self.check_failure(InstallStep::MoveAside)?;
+        self.check_cancelled()?;
+
+        self.swap_started = true;
+        self.move_files(&layout)?;
+        #[cfg(test)]
+        self.check_failure(InstallStep::Swap)?;
+
+        #[cfg(test)]
+        self.check_failure(InstallStep::Commit)?;
+        cache.commit_installation(plugin_id, version, &self.files, self.verified)?;
+
+        Ok(())
     }
 
-    pub fn download(&self, download_url: &str) -> Result<Bytes, Box<dyn Error>> {
-        let settings = read_existing_settings_file();
+    /// Adds the installed archive to the archive store and the version history.
+    fn record_history(
+        &self,
+        cache: &Cache,
+        plugin_id: i32,
+        version: &str,
+        bytes: &Bytes,
+    ) -> Result<(), Error> {
+        let archive = self
+            .archive_store
+            .put(plugin_id, &self.plugin_title, version, bytes)?;
+        cache.add_history_entry(plugin_id, version, &archive.hash)
+    }
+
+    /// Lets the installation fail right after the given step. Used to verify the rollback behaviour.
+    #[cfg(test)]
+    fn with_failure_at(mut self, step: InstallStep) -> Self {
+        self.fail_at = Some(step);
+        self
+    }
 
-        if settings.backup_enabled {
-            self.backup_plugin_folder();
+    #[cfg(test)]
+    fn check_failure(&self, step: InstallStep) -> Result<(), Error> {
+        if self.fail_at == Some(step) {
+            return Err(Error::plugin(format!(
+                "Simulated failure at step {:?}",
+                step
+            )));
         }
-        let bytes = reqwest::blocking::get(download_url)?.bytes()?;
 
-        Ok(bytes)
+        Ok(())
     }
 
-    pub fn install(&mut self, bytes: &Bytes) -> Result<String, Box<dyn Error>> {
-        if self.tmp_file_path.exists() {
-            fs::remove_dir_all(&self.tmp_file_path).unwrap();
+    fn check_cancelled(&self) -> Result<(), Error> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
         }
 
-        fs::create_dir(&self.tmp_file_path)?;
+        Ok(())
+    }
 
-        let cache_path = &self.tmp_file_path.join("plugin.zip");
+    fn report_progress(&self, progress: &InstallProgress) {
+        if let Some(listener) = &self.progress {
+            listener(&self.plugin_title, progress);
+        }
+    }
 
-        match File::create(&cache_path) {
-            Err(why) => panic!("couldn't create {}", why),
-            Ok(mut file) => {
-                file.write_all(bytes)?;
-                let file = OpenOptions::new()
-                    .write(true)
-                    .read(true)
-                    .open(&cache_path)
-                    .unwrap();
-                let mut zip_archive = zip::ZipArchive::new(file)?;
-                zip::ZipArchive::extract(&mut zip_archive, &self.tmp_file_path)?;
-                let root_folder_name = zip_archive
-                    .by_index(0)?
-                    .name()
-                    .to_string()
-                    .replace(' ', "_")
-                    .split('/')
-                    .next()
-                    .unwrap()
-                    .to_string();
-
-                self.files = zip_archive
-                    .file_names()
-                    .map(std::string::ToString::to_string)
-                    .collect::<Vec<String>>();
-
-                Ok(root_folder_name)
-            }
-        }
-    }
-
-    pub fn delete(&self) -> Result<(), Box<dyn Error>> {
-        let root_name = self.files[0].split('/').next().unwrap();
-        let plugin_name = &self.files[0]
-            .split('/')
-            .into_iter()
-            .map(std::string::ToString::to_string)
-            .collect::<Vec<String>>()[1];
+    fn rollback_dir(&self) -> PathBuf {
+        self.tmp_file_path.join(".rollback")
+    }
+
+    /// Moves the given files from the plugins directory into the rollback folder.
+    fn move_aside(&mut self, files: &[String]) -> Result<(), Error> {
+        let rollback_dir = self.rollback_dir();
+
+        for file in files {
+            if file.ends_with('/') || !is_relative_path(file) {
+                continue;
+            }
+
+            let source = self.plugins_dir.join(file);
+            if !source.is_file() {
+                continue;
+            }
+
+            let target = rollback_dir.join(file);
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent).at(parent)?;
+            }
+            move_file(&source, &target)?;
+            self.moved_aside.push(file.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Removes the swapped in files and moves the previous files back to their original location.
+    fn rollback(&mut self) -> Result<(), Error> {
+        if self.swap_started {
+            self.delete()?;
+        }
+
+        let rollback_dir = self.rollback_dir();
+        for file in &self.moved_aside {
+            let target = self.plugins_dir.join(file);
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent).at(parent)?;
+            }
+            move_file(&rollback_dir.join(file), &target)?;
+        }
+        self.moved_aside.clear();
+        self.swap_started = false;
 
-        fs::remove_dir_all(&self.plugins_dir.join(&root_name).join(&plugin_name))?;
+        Ok(())
+    }
+
+    /// Downloads the archive of the plugin and verifies it against the size and hash from the feed. A corrupt
+    /// download is retried once. Archives of the archive store are reused instead of downloading them again,
+    /// new downloads are added to the store.
+    pub fn download(&mut self, plugin: &Plugin) -> Result<Bytes, Error> {
+        #[cfg(test)]
+        self.check_failure(InstallStep::Download)?;
+        self.check_cancelled()?;
 
-        let plugin_path = self
-            .plugins_dir
-            .join(root_name)
-            .join(&format!("{}.plugin", plugin_name));
+        let settings = read_existing_settings_file()?;
+
+        if self.backup {
+            if let Some(manager) = backup_manager(&self.plugins_dir, &settings) {
+                manager.create()?;
+            }
+        }
 
-        if plugin_path.exists() {
-            fs::remove_file(
-                self.plugins_dir
-                    .join(root_name)
-                    .join(&format!("{}.plugin", plugin_name)),
-            )?;
+        if let Some(bytes) = self
+            .archive_store
+            .get(plugin.id, &plugin.latest_version, &plugin.hash)
+        {
+            if let Ok(verified) = verify_archive(&bytes, plugin.size, &plugin.hash) {
+                debug!("Using the stored archive of {}", plugin.name);
+                self.verified = verified;
+                return Ok(bytes);
+            }
         }
 
-        let plugin_name = format!("{}.plugincompendium", plugin_name);
-        if self.plugins_dir.join(root_name).join(&plugin_name).exists() {
-            fs::remove_file(self.plugins_dir.join(root_name).join(plugin_name))?;
+        let bytes = self.fetch_archive(&plugin.download_url)?;
+        let (bytes, verified) = match verify_archive(&bytes, plugin.size, &plugin.hash) {
+            Ok(verified) => (bytes, verified),
+            Err(error) => {
+                debug!("{}. Retrying the download of {}", error, plugin.name);
+                let bytes = self.fetch_archive(&plugin.download_url)?;
+                let verified = verify_archive(&bytes, plugin.size, &plugin.hash)?;
+                (bytes, verified)
+            }
+        };
+        self.verified = verified;
+
+        self.store_archive(plugin, &bytes, &settings);
+
+        Ok(bytes)
+    }
+
+    /// Downloads the archive in chunks, so the progress can be reported and the download can be cancelled.
+    fn fetch_archive(&self, download_url: &str) -> Result<Bytes, Error> {
+        let mut response = reqwest::blocking::get(download_url)
+            .and_then(reqwest::blocking::Response::error_for_status)
+            .map_err(|error| Error::network(download_url, error))?;
+        let total = response.content_length();
+
+        let mut bytes = Vec::new();
+        let mut chunk = vec![0; DOWNLOAD_CHUNK_SIZE];
+        loop {
+            self.check_cancelled()?;
+            let read = response
+                .read(&mut chunk)
+                .map_err(|error| Error::network(download_url, error))?;
+            if read == 0 {
+                break;
+            }
+            bytes.extend_from_slice(&chunk[..read]);
+            self.report_progress(&InstallProgress::Downloading {
+                received: bytes.len() as u64,
+                total,
+            });
         }
 
-        if fs::read_dir(&self.plugins_dir.join(root_name))?
-            .next()
-            .is_none()
+        Ok(Bytes::from(bytes))
+    }
+
+    /// Adds a downloaded archive to the archive store and prunes the store. A failing store doesn't fail the
+    /// download.
+    fn store_archive(&self, plugin: &Plugin, bytes: &Bytes, settings: &SettingsFile) {
+        if let Err(error) =
+            self.archive_store
+                .put(plugin.id, &plugin.name, &plugin.latest_version, bytes)
         {
-            fs::remove_dir_all(&self.plugins_dir.join(root_name))?;
+            debug!("Can't store the archive of {}: {}", plugin.name, error);
+            return;
+        }
+
+        let (max_size, max_age) = settings.archive_cache_limits();
+        match self.archive_store.prune(max_size, max_age) {
+            Ok(removed) => {
+                for archive in removed {
+                    debug!(
+                        "Pruned the stored archive of {} {}",
+                        archive.name, archive.version
+                    );
+                }
+            }
+            Err(error) => debug!("Can't prune the archive store: {}", error),
+        }
+    }
+
+    /// Extracts the archive into the tmp directory and analyses its layout. The file manifest contains the
+    /// paths of every entry that gets installed, relative to the plugins directory.
+    pub fn install(&mut self, bytes: &Bytes) -> Result<ArchiveLayout, Error> {
+        self.report_progress(&InstallProgress::Extracting);
+
+        if self.tmp_file_path.exists() {
+            fs::remove_dir_all(&self.tmp_file_path).at(&self.tmp_file_path)?;
+        }
+
+        fs::create_dir_all(&self.tmp_file_path).at(&self.tmp_file_path)?;
+
+        let cache_path = &self.tmp_file_path.join("plugin.zip");
+
+        let mut file = File::create(cache_path).at(cache_path)?;
+        file.write_all(bytes).at(cache_path)?;
+        let file = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .open(cache_path)
+            .at(cache_path)?;
+        let mut zip_archive = zip::ZipArchive::new(file)?;
+        extract_archive(&mut zip_archive, &self.tmp_file_path, &self.archive_limits)?;
+
+        let layout = ArchiveLayout::analyse(&mut zip_archive)?;
+        for skipped in &layout.skipped {
+            debug!("Skipping {} because it doesn't belong to a plugin", skipped);
         }
+        self.files = layout.targets();
+
+        Ok(layout)
+    }
+
+    /// Removes every path of the current file manifest from the plugins directory. Directories that are
+    /// empty afterwards, including the author folder, are removed as well.
+    pub fn delete(&self) -> Result<(), Error> {
+        remove_manifest_files(&self.plugins_dir, &self.files)
+    }
+
+    /// Uninstalls a plugin by using the file manifest that was recorded in the cache during the installation.
+    /// Plugins that were installed before manifests were recorded are looked up in the plugins directory instead.
+    pub fn uninstall(&mut self, cache: &Cache, plugin_id: i32) -> Result<(), Error> {
+        let _moving = PLUGINS_DIR_LOCK
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.files = cache.get_plugin_files(plugin_id)?;
+        if self.files.is_empty() {
+            self.files = self.scan_files(cache, plugin_id)?;
+        }
+        if self.files.is_empty() {
+            return Err(Error::plugin(format!(
+                "No file manifest recorded for plugin {}",
+                plugin_id
+            )));
+        }
+
+        self.delete()?;
+        cache.delete_plugin_files(plugin_id)?;
 
         Ok(())
     }
 
-    pub fn move_files(&self, folder_name: &str) {
-        let tmp_folder = fs::read_dir(&self.tmp_file_path.join(&folder_name))
-            .map_err(|_| {
+    /// Returns the files of the plugin that the descriptor scan of the plugins directory finds. The plugin is
+    /// matched by its id and by its name.
+    fn scan_files(&self, cache: &Cache, plugin_id: i32) -> Result<Vec<String>, Error> {
+        let Some(plugin) = cache.get_plugin_by_id(plugin_id)? else {
+            return Ok(Vec::new());
+        };
+        debug!(
+            "No file manifest recorded for plugin {}, scanning the plugins directory",
+            plugin.name
+        );
+
+        let local_plugins = Synchronizer::search_local(&self.plugins_dir)?;
+        let local_plugin = local_plugins
+            .iter()
+            .find(|local_plugin| local_plugin.plugin.id == plugin_id)
+            .or_else(|| {
+                local_plugins
+                    .iter()
+                    .find(|local_plugin| local_plugin.plugin.name == plugin.name)
+            });
+
+        Ok(local_plugin
+            .map(|local_plugin| local_plugin.files.clone())
+            .unwrap_or_default())
+    }
+
+    /// Moves the extracted entries of every plugin root from the tmp directory into the plugins directory.
+    pub fn move_files(&self, layout: &ArchiveLayout) -> Result<(), Error> {
+        let archive_path = self.tmp_file_path.join("plugin.zip");
+        fs::remove_file(&archive_path).at(&archive_path)?;
+
+        for entry in &layout.entries {
+            let target = self.plugins_dir.join(&entry.target);
+
+            if entry.target.ends_with('/') {
+                create_dir_all(&target).at(&target)?;
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent).at(parent)?;
+            }
+            move_file(&self.tmp_file_path.join(&entry.source), &target).inspect_err(|_| {
                 debug!(
-                    "Error while reading tmp folder with the name {}",
-                    &self.tmp_file_path.join(&folder_name).to_str().unwrap()
+                    "Error while moving {} into the plugins directory",
+                    &self.tmp_file_path.join(&entry.source).display()
                 );
-            })
-            .unwrap();
-        fs::remove_file(&self.tmp_file_path.join("plugin.zip")).unwrap();
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_cache_folder(&self) -> Result<(), Error> {
+        fs::remove_dir_all(&self.tmp_file_path).at(&self.tmp_file_path)
+    }
+}
 
-        if !&self.plugins_dir.join(&folder_name).exists() {
-            fs::create_dir_all(&self.plugins_dir.join(&folder_name)).unwrap();
+/// Returns the manager that backs up the plugins and their settings with the retention policy of the settings.
+/// Returns `None` if backups are disabled.
+pub fn backup_manager(plugins_dir: &Path, settings: &SettingsFile) -> Option<BackupManager> {
+    if !settings.backup_enabled {
+        return None;
+    }
+    let (keep_last, max_age) = settings.backup_retention();
+
+    Some(
+        BackupManager::new(&get_plugins_backup_dir(), plugins_dir)
+            .with_plugin_data_dir(&plugin_data_dir(plugins_dir))
+            .with_retention(keep_last, max_age),
+    )
+}
+
+/// Checks the archive against the size and MD5 hash from the feed. Returns `false` if the feed provides
+/// neither of them and the archive can't be verified.
+pub fn verify_archive(
+    bytes: &[u8],
+    expected_size: i32,
+    expected_hash: &str,
+) -> Result<bool, CorruptDownload> {
+    let expected_size_known = expected_size > 0;
+    let expected_hash = expected_hash.trim().to_lowercase();
+    let actual_hash = format!("{:x}", md5::compute(bytes));
+
+    let size_matches = !expected_size_known || usize::try_from(expected_size) == Ok(bytes.len());
+    let hash_matches = expected_hash.is_empty() || expected_hash == actual_hash;
+
+    if size_matches && hash_matches {
+        Ok(expected_size_known || !expected_hash.is_empty())
+    } else {
+        Err(CorruptDownload {
+            expected_size,
+            actual_size: bytes.len(),
+            expected_hash,
+            actual_hash,
+        })
+    }
+}
+
+/// Returns true if the manifest entry is a plain relative path that can't escape the plugins directory.
+fn is_relative_path(file: &str) -> bool {
+    Path::new(file)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Removes every path of a file manifest from the plugins directory. Directories that are empty afterwards,
+/// including the author folder, are removed as well.
+pub(crate) fn remove_manifest_files(plugins_dir: &Path, files: &[String]) -> Result<(), Error> {
+    for file in files {
+        if !is_relative_path(file) {
+            debug!("Skipping invalid manifest entry {}", file);
+            continue;
         }
 
-        for file in tmp_folder {
-            let file_str = file.unwrap().file_name().into_string().unwrap();
+        let path = plugins_dir.join(file);
+        if !file.ends_with('/') && path.is_file() {
+            fs::remove_file(&path).at(&path)?;
+        }
+    }
+
+    remove_empty_directories(plugins_dir, files)
+}
 
-            let md = metadata(&self.tmp_file_path.join(&folder_name).join(&file_str)).unwrap();
+/// Removes the directories of the given manifest entries that are empty. The deepest directories are checked first
+/// so that their parents are empty by the time they are checked.
+fn remove_empty_directories(plugins_dir: &Path, files: &[String]) -> Result<(), Error> {
+    let mut directories = BTreeSet::new();
 
-            if md.is_dir() {
-                let mut options = fs_extra::dir::CopyOptions::new();
-                options.overwrite = true;
-                options.copy_inside = true;
-                fs_extra::dir::move_dir(
-                    &self.tmp_file_path.join(&folder_name).join(&file_str),
-                    &self.plugins_dir.join(folder_name),
-                    &options,
-                )
-                .unwrap();
-            } else {
-                let mut options = fs_extra::file::CopyOptions::new();
-                options.overwrite = true;
-                fs_extra::file::move_file(
-                    &self.tmp_file_path.join(&folder_name).join(&file_str),
-                    &self.plugins_dir.join(&folder_name).join(&file_str),
-                    &options,
-                )
-                .unwrap();
+    for file in files.iter().filter(|file| is_relative_path(file)) {
+        let relative_path = Path::new(file);
+        if file.ends_with('/') {
+            directories.insert(plugins_dir.join(relative_path));
+        }
+
+        for parent in relative_path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() {
+                break;
             }
+            directories.insert(plugins_dir.join(parent));
+        }
+    }
+
+    let mut directories = directories.into_iter().collect::<Vec<PathBuf>>();
+    directories.sort_by_key(|directory| Reverse(directory.components().count()));
+
+    for directory in directories {
+        if directory.is_dir() && fs::read_dir(&directory).at(&directory)?.next().is_none() {
+            fs::remove_dir(&directory).at(&directory)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves a single file. Falls back to copy and delete if both paths are on different file systems.
+fn move_file(source: &Path, target: &Path) -> Result<(), Error> {
+    if fs::rename(source, target).is_err() {
+        fs::copy(source, target).at(target)?;
+        fs::remove_file(source).at(source)?;
+    }
+
+    Ok(())
+}
+
+/// Moves a file or a whole directory. Directories are copied and deleted afterwards if both paths are on different
+/// file systems.
+pub(crate) fn move_path(source: &Path, target: &Path) -> Result<(), Error> {
+    if !source.is_dir() {
+        return move_file(source, target);
+    }
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(source) {
+        let entry = entry.map_err(io::Error::from).at(source)?;
+        let Ok(relative_path) = entry.path().strip_prefix(source) else {
+            continue;
+        };
+        let path = target.join(relative_path);
+        if entry.file_type().is_dir() {
+            create_dir_all(&path).at(&path)?;
+        } else {
+            fs::copy(entry.path(), &path).at(&path)?;
         }
     }
+    fs::remove_dir_all(source).at(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use r2d2::Pool;
+    use r2d2_sqlite::SqliteConnectionManager;
+    use std::{env, io::Cursor};
+    use uuid::Uuid;
+    use zip::{write::FileOptions, ZipWriter};
+
+    /// Creates a plugins and a tmp directory and a cache with an installed version 1.0 of plugin 1.
+    fn setup() -> (PathBuf, PathBuf, PathBuf, Cache) {
+        let uuid = Uuid::new_v4().to_string();
+        let test_dir = env::temp_dir().join(format!("lembas_test_{}", &uuid[..7]));
+        let (tmp_dir, plugins_dir) = (test_dir.join("tmp"), test_dir.join("plugins"));
+        fs::create_dir_all(&tmp_dir).unwrap();
+        fs::create_dir_all(&plugins_dir).unwrap();
+
+        let manager = SqliteConnectionManager::file(test_dir.join("db.sqlite3"));
+        let cache = Cache::new(Pool::new(manager).unwrap());
+        cache.create_cache_db().unwrap();
+        let plugin = Plugin::new("Hello World").with_id(1).build();
+        cache.insert_plugin(&plugin, 0).unwrap();
+        installer(&tmp_dir, &plugins_dir)
+            .install_archive(&cache, 1, "1.0", &archive("v1", "Old.lua"))
+            .unwrap();
+
+        (test_dir, tmp_dir, plugins_dir, cache)
+    }
+
+    fn installer(tmp_dir: &Path, plugins_dir: &Path) -> Installer {
+        Installer::new(tmp_dir, plugins_dir, 1, "Hello World")
+            .with_archive_store(ArchiveStore::new(&tmp_dir.join("archives")))
+    }
+
+    fn archive(version: &str, extra_file: &str) -> Bytes {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in [
+            ("Munkey/Ah/Main.lua", version),
+            (&format!("Munkey/Ah/{}", extra_file), version),
+            ("Munkey/Ah.plugin", "<Plugin></Plugin>"),
+        ] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
 
-    pub fn delete_cache_folder(&self) {
-        fs::remove_dir_all(&self.tmp_file_path).unwrap();
+        Bytes::from(writer.finish().unwrap().into_inner())
     }
 
-    fn backup_plugin_folder(&self) {
-        let backup_path = get_plugins_backup_dir();
+    fn file_tree(path: &Path) -> Vec<(String, String)> {
+        let mut files = WalkDir::new(path)
+            .into_iter()
+            .map(Result::unwrap)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                (
+                    entry.path().to_string_lossy().to_string(),
+                    fs::read_to_string(entry.path()).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
 
-        if !backup_path.exists() {
-            create_dir_all(&backup_path).unwrap();
+    #[test]
+    fn failed_update_rolls_back_every_step() {
+        for step in [
+            InstallStep::Extract,
+            InstallStep::MoveAside,
+            InstallStep::Swap,
+            InstallStep::Commit,
+        ] {
+            let (test_dir, tmp_dir, plugins_dir, cache) = setup();
+            let files_before = file_tree(&plugins_dir);
+            let manifest_before = cache.get_plugin_files(1).unwrap();
+
+            let mut installer = installer(&tmp_dir, &plugins_dir).with_failure_at(step);
+            let result = installer.install_archive(&cache, 1, "2.0", &archive("v2", "New.lua"));
+
+            assert!(result.is_err(), "{:?} didn't fail", step);
+            assert_eq!(file_tree(&plugins_dir), files_before, "{:?}", step);
+            assert_eq!(cache.get_plugin_files(1).unwrap(), manifest_before);
+            let plugin = cache.get_plugin("Hello World").unwrap().unwrap();
+            assert_eq!(plugin.current_version, "1.0");
+            assert!(!installer.tmp_file_path.exists());
+
+            fs::remove_dir_all(test_dir).unwrap();
         }
+    }
 
-        let options = CopyOptions::new();
+    #[test]
+    fn failed_download_keeps_installed_version() {
+        let (test_dir, tmp_dir, plugins_dir, cache) = setup();
+        let files_before = file_tree(&plugins_dir);
 
-        let system_time = SystemTime::now();
-        let datetime: DateTime<Utc> = system_time.into();
-        let datetime = datetime.format("%Y_%m_%d_%H%M%S").to_string();
+        let plugin = cache.get_plugin("Hello World").unwrap().unwrap();
+        let mut installer =
+            installer(&tmp_dir, &plugins_dir).with_failure_at(InstallStep::Download);
 
-        let tmp_backup_path = &backup_path.join(format!("{}_backup", datetime));
-        create_dir_all(&tmp_backup_path).unwrap();
+        assert!(installer.run_installation(&cache, &plugin).is_err());
+        assert_eq!(file_tree(&plugins_dir), files_before);
 
-        copy(&self.plugins_dir, &tmp_backup_path, &options).unwrap();
+        fs::remove_dir_all(test_dir).unwrap();
     }
 }
@@ -0,0 +1,257 @@
+//! # Archive store
+//!
+//! Keeps every downloaded plugin archive so reinstalls and rollbacks work without a download. Archives are
+//! stored per plugin id and named after the MD5 hash of their content, e.g. `archives/692/<hash>.zip`. A small
+//! json file next to every archive records the version and when the archive was used last.
+use bytes::Bytes;
+use chrono::Utc;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::core::error::{Error, PathContext};
+use crate::core::version::{compare_versions, VersionOrdering};
+
+/// An archive of the store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredArchive {
+    pub plugin_id: i32,
+    pub name: String,
+    pub version: String,
+    /// MD5 hash of the archive
+    pub hash: String,
+    /// Size of the archive in bytes
+    pub size: u64,
+    /// Unix timestamp of the download
+    pub stored_at: i64,
+    /// Unix timestamp of the last reuse
+    pub used_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveStore {
+    root: PathBuf,
+}
+
+impl ArchiveStore {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// Stores the archive of a plugin version. An archive with the same content is stored only once.
+    pub fn put(
+        &self,
+        plugin_id: i32,
+        name: &str,
+        version: &str,
+        bytes: &[u8],
+    ) -> Result<StoredArchive, Error> {
+        let hash = format!("{:x}", md5::compute(bytes));
+        let now = Utc::now().timestamp();
+        let archive = StoredArchive {
+            plugin_id,
+            name: name.to_string(),
+            version: version.to_string(),
+            hash,
+            size: bytes.len() as u64,
+            stored_at: now,
+            used_at: now,
+        };
+
+        let archive_path = self.archive_path(&archive);
+        let plugin_dir = self.plugin_dir(plugin_id);
+        fs::create_dir_all(&plugin_dir).at(&plugin_dir)?;
+        if !archive_path.exists() {
+            // Written under another name first, an interrupted write never looks like a complete archive
+            let partial_path = archive_path.with_extension("part");
+            fs::write(&partial_path, bytes).at(&partial_path)?;
+            fs::rename(&partial_path, &archive_path).at(&archive_path)?;
+        }
+        self.write_metadata(&archive)?;
+
+        Ok(archive)
+    }
+
+    /// Returns the stored archive of a plugin version. The hash is used if the feed provides one, otherwise the
+    /// most recently used archive of the version is returned. Archives whose content doesn't match their hash
+    /// are removed.
+    pub fn get(&self, plugin_id: i32, version: &str, hash: &str) -> Option<Bytes> {
+        let hash = hash.trim().to_lowercase();
+        let archive = self
+            .plugin_archives(plugin_id)
+            .into_iter()
+            .filter(|archive| {
+                if hash.is_empty() {
+                    compare_versions(&archive.version, version) == VersionOrdering::Same
+                } else {
+                    archive.hash == hash
+                }
+            })
+            .max_by_key(|archive| archive.used_at)?;
+
+        let bytes = match fs::read(self.archive_path(&archive)) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                debug!(
+                    "Can't read the stored archive of {}: {}",
+                    archive.name, error
+                );
+                return None;
+            }
+        };
+        if format!("{:x}", md5::compute(&bytes)) != archive.hash {
+            debug!("Removing the corrupt stored archive of {}", archive.name);
+            self.remove(&archive).ok();
+            return None;
+        }
+
+        let used = StoredArchive {
+            used_at: Utc::now().timestamp(),
+            ..archive
+        };
+        if let Err(error) = self.write_metadata(&used) {
+            debug!(
+                "Can't update the stored archive of {}: {}",
+                used.name, error
+            );
+        }
+
+        Some(Bytes::from(bytes))
+    }
+
+    /// Returns the stored archives of a plugin, newest download first.
+    pub fn plugin_archives(&self, plugin_id: i32) -> Vec<StoredArchive> {
+        let mut archives = read_metadata_dir(&self.plugin_dir(plugin_id));
+        archives.sort_by_key(|archive| std::cmp::Reverse(archive.stored_at));
+        archives
+    }
+
+    /// Returns every stored archive.
+    pub fn archives(&self) -> Vec<StoredArchive> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .flat_map(|entry| read_metadata_dir(&entry.path()))
+            .collect()
+    }
+
+    /// Returns the size of every stored archive in bytes.
+    pub fn size(&self) -> u64 {
+        self.archives().iter().map(|archive| archive.size).sum()
+    }
+
+    /// Removes archives that weren't used for longer than the maximum age. Afterwards the least recently used
+    /// archives are removed until the store fits into the maximum size. The most recent archive of every plugin
+    /// is kept regardless of the size, so the installed version can still be reinstalled. A limit of zero disables
+    /// the limit.
+    pub fn prune(&self, max_size: u64, max_age: Duration) -> Result<Vec<StoredArchive>, Error> {
+        let mut archives = self.archives();
+        archives.sort_by_key(|archive| archive.used_at);
+
+        let mut latest: HashMap<i32, &StoredArchive> = HashMap::new();
+        for archive in &archives {
+            let entry = latest.entry(archive.plugin_id).or_insert(archive);
+            if (archive.stored_at, archive.used_at) > (entry.stored_at, entry.used_at) {
+                *entry = archive;
+            }
+        }
+        let latest = latest
+            .into_iter()
+            .map(|(plugin_id, archive)| (plugin_id, archive.hash.clone()))
+            .collect::<HashMap<i32, String>>();
+
+        let oldest = Utc::now().timestamp() - i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX);
+        let mut size = archives.iter().map(|archive| archive.size).sum::<u64>();
+        let mut removed = Vec::new();
+
+        for archive in archives {
+            let expired = !max_age.is_zero() && archive.used_at < oldest;
+            let too_large = max_size > 0
+                && size > max_size
+                && latest.get(&archive.plugin_id) != Some(&archive.hash);
+            if !expired && !too_large {
+                continue;
+            }
+
+            self.remove(&archive)?;
+            size -= archive.size;
+            removed.push(archive);
+        }
+
+        Ok(removed)
+    }
+
+    /// Removes every stored archive.
+    pub fn clear(&self) -> Result<(), Error> {
+        if self.root.exists() {
+            fs::remove_dir_all(&self.root).at(&self.root)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, archive: &StoredArchive) -> Result<(), Error> {
+        let archive_path = self.archive_path(archive);
+        if archive_path.exists() {
+            fs::remove_file(&archive_path).at(&archive_path)?;
+        }
+        let metadata_path = archive_path.with_extension("json");
+        fs::remove_file(&metadata_path).at(&metadata_path)?;
+
+        let plugin_dir = self.plugin_dir(archive.plugin_id);
+        if fs::read_dir(&plugin_dir).at(&plugin_dir)?.next().is_none() {
+            fs::remove_dir(&plugin_dir).at(&plugin_dir)?;
+        }
+
+        Ok(())
+    }
+
+    fn plugin_dir(&self, plugin_id: i32) -> PathBuf {
+        self.root.join(plugin_id.to_string())
+    }
+
+    fn archive_path(&self, archive: &StoredArchive) -> PathBuf {
+        self.plugin_dir(archive.plugin_id)
+            .join(format!("{}.zip", archive.hash))
+    }
+
+    fn write_metadata(&self, archive: &StoredArchive) -> Result<(), Error> {
+        let metadata_path = self.archive_path(archive).with_extension("json");
+        serde_json::to_string(archive)
+            .map_err(io::Error::from)
+            .and_then(|content| fs::write(&metadata_path, content))
+            .at(&metadata_path)
+    }
+}
+
+/// Reads the metadata files of a plugin directory. Metadata without an archive is skipped.
+fn read_metadata_dir(plugin_dir: &Path) -> Vec<StoredArchive> {
+    let Ok(entries) = fs::read_dir(plugin_dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+                && path.with_extension("zip").is_file()
+        })
+        .filter_map(|path| {
+            fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+        })
+        .collect()
+}
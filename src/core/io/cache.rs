@@ -1,25 +1,94 @@
+use chrono::Utc;
 use log::debug;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, Statement};
-use std::{collections::HashMap, error::Error, sync::Arc};
-
-use crate::core::Plugin;
+use rusqlite::{params, Connection, Row, Statement, Transaction};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::core::{error::Error, plugin::FEED_SOURCE, Plugin};
+
+const PLUGIN_COLUMNS: &str = "name, author, current_version, plugin_id, description, download_url, info_url, category, latest_version, downloads, archive_name, updated_at, hash, installed, size, verified, source, delisted, pinned, ignore_updates_until";
+
+/// How long a connection waits for a lock held by another connection, the GUI and the CLI can share the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Columns that were added to the `plugins` table after its initial release. They are added to existing
+/// databases when the cache is created.
+const ADDED_PLUGIN_COLUMNS: [(&str, &str); 6] = [
+    ("size", "INT DEFAULT 0"),
+    ("verified", "INT DEFAULT 0"),
+    ("source", "TEXT DEFAULT 'feed'"),
+    ("delisted", "INT DEFAULT 0"),
+    ("pinned", "INT DEFAULT 0"),
+    ("ignore_updates_until", "TEXT DEFAULT ''"),
+];
+
+/// A version of a plugin that was installed at some point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub plugin_id: i32,
+    pub version: String,
+    /// MD5 hash of the installed archive. The archive is looked up in the archive store by this hash.
+    pub hash: String,
+    /// Unix timestamp of the last installation of this version
+    pub installed_at: i64,
+}
 
 pub trait DatabaseHandler {
-    fn insert_plugin(&self, plugin: &Plugin, installed: i32) -> Result<(), Box<dyn Error>>;
+    fn insert_plugin(&self, plugin: &Plugin, installed: i32) -> Result<(), Error>;
+
+    fn sync_plugins(&self, plugins: &[Plugin]) -> Result<(), Error>;
+
+    fn mark_as_installed(&self, plugin_id: i32, version: &str) -> Result<(), Error>;
+
+    fn delete_plugin(&self, name: &str) -> Result<(), Error>;
+
+    /// Marks a removed plugin as not installed. Plugins of the feed stay in the catalog, so they can be installed
+    /// again, every other plugin is deleted.
+    fn mark_as_uninstalled(&self, name: &str) -> Result<(), Error>;
+
+    fn get_installed_plugins(&self) -> Result<HashMap<String, Plugin>, Error>;
+
+    fn get_plugins(&self) -> Result<HashMap<String, Plugin>, Error>;
+
+    fn get_plugin(&self, name: &str) -> Result<Option<Plugin>, Error>;
+
+    fn get_plugin_by_id(&self, plugin_id: i32) -> Result<Option<Plugin>, Error>;
 
-    fn sync_plugins(&self, plugins: &[Plugin]) -> Result<(), Box<dyn Error>>;
+    fn save_plugin_files(&self, plugin_id: i32, files: &[String]) -> Result<(), Error>;
 
-    fn mark_as_installed(&self, plugin_id: i32, version: &str) -> Result<(), Box<dyn Error>>;
+    fn get_plugin_files(&self, plugin_id: i32) -> Result<Vec<String>, Error>;
 
-    fn delete_plugin(&self, name: &str) -> Result<(), Box<dyn Error>>;
+    fn delete_plugin_files(&self, plugin_id: i32) -> Result<(), Error>;
 
-    fn get_installed_plugins(&self) -> HashMap<String, Plugin>;
+    fn commit_installation(
+        &self,
+        plugin_id: i32,
+        version: &str,
+        files: &[String],
+        verified: bool,
+    ) -> Result<(), Error>;
+
+    fn next_local_plugin_id(&self) -> Result<i32, Error>;
+
+    fn add_history_entry(&self, plugin_id: i32, version: &str, hash: &str) -> Result<(), Error>;
+
+    fn get_plugin_history(&self, plugin_id: i32) -> Result<Vec<HistoryEntry>, Error>;
 
-    fn get_plugins(&self) -> HashMap<String, Plugin>;
+    fn set_pinned(&self, plugin_id: i32, pinned: bool) -> Result<(), Error>;
 
-    fn get_plugin(&self, name: &str) -> Result<Option<Plugin>, Box<dyn Error>>;
+    /// Ignores updates to versions older than the given version. An empty version offers every update again.
+    fn set_ignore_updates_until(&self, plugin_id: i32, version: &str) -> Result<(), Error>;
+
+    /// Returns the download timestamp of the feed whose plugins are in the cache.
+    fn get_feed_fetched_at(&self) -> Result<Option<i64>, Error>;
+
+    fn set_feed_fetched_at(&self, fetched_at: i64) -> Result<(), Error>;
 }
 
 #[derive(Debug, Clone)]
@@ -34,11 +103,16 @@ impl Cache {
         }
     }
 
-    pub fn create_cache_db(&self) -> Result<(), rusqlite::Error> {
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
+    /// Opens the database at `path`, its connections wait for locks instead of failing with `SQLITE_BUSY`.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let manager = SqliteConnectionManager::file(path)
+            .with_init(|connection| connection.busy_timeout(BUSY_TIMEOUT));
+
+        Ok(Self::new(Pool::new(manager)?))
+    }
+
+    pub fn create_cache_db(&self) -> Result<(), Error> {
+        let connection = self.pool.get()?;
 
         connection.execute(
             "
@@ -57,18 +131,101 @@ impl Cache {
                     archive_name TEXT,
                     updated_at INT,
                     hash TEXT,
-                    installed INT DEFAULT 0
+                    installed INT DEFAULT 0,
+                    size INT DEFAULT 0,
+                    verified INT DEFAULT 0,
+                    source TEXT DEFAULT 'feed',
+                    delisted INT DEFAULT 0,
+                    pinned INT DEFAULT 0,
+                    ignore_updates_until TEXT DEFAULT ''
                 );
         ",
             [],
         )?;
 
+        connection.execute(
+            "
+                CREATE TABLE IF NOT EXISTS plugin_files (
+                    plugin_id INTEGER NOT NULL,
+                    path TEXT NOT NULL,
+                    UNIQUE (plugin_id, path)
+                );
+        ",
+            [],
+        )?;
+
+        connection.execute(
+            "
+                CREATE TABLE IF NOT EXISTS plugin_history (
+                    plugin_id INTEGER NOT NULL,
+                    version TEXT NOT NULL,
+                    hash TEXT NOT NULL,
+                    installed_at INT NOT NULL,
+                    UNIQUE (plugin_id, version, hash)
+                );
+        ",
+            [],
+        )?;
+
+        connection.execute(
+            "
+                CREATE TABLE IF NOT EXISTS feed_sync (
+                    id INTEGER PRIMARY KEY CHECK (id = 0),
+                    fetched_at INT NOT NULL
+                );
+        ",
+            [],
+        )?;
+
+        Cache::add_missing_columns(&connection)?;
+
+        Ok(())
+    }
+
+    fn add_missing_columns(connection: &Connection) -> Result<(), rusqlite::Error> {
+        let mut stmt = connection.prepare("PRAGMA table_info(plugins);")?;
+        let existing_columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<HashSet<String>, rusqlite::Error>>()?;
+
+        for (column, definition) in ADDED_PLUGIN_COLUMNS {
+            if !existing_columns.contains(column) {
+                connection.execute(
+                    &format!("ALTER TABLE plugins ADD COLUMN {} {};", column, definition),
+                    [],
+                )?;
+            }
+        }
+
         Ok(())
     }
 
-    fn execute_stmt(stmt: &mut Statement, params: &str) -> Vec<Plugin> {
-        let mut all_plugins = Vec::new();
+    fn map_plugin(row: &Row) -> Result<Plugin, rusqlite::Error> {
+        Ok(Plugin {
+            name: row.get(0)?,
+            author: row.get(1)?,
+            current_version: row.get(2)?,
+            id: row.get(3)?,
+            description: row.get(4)?,
+            download_url: row.get(5)?,
+            info_url: row.get(6)?,
+            category: row.get(7)?,
+            latest_version: row.get(8)?,
+            downloads: row.get(9)?,
+            archive_name: row.get(10)?,
+            updated: row.get(11)?,
+            hash: row.get(12)?,
+            installed: row.get(13)?,
+            size: row.get(14)?,
+            verified: row.get(15)?,
+            source: row.get(16)?,
+            delisted: row.get(17)?,
+            pinned: row.get(18)?,
+            ignore_updates_until: row.get(19)?,
+        })
+    }
 
+    fn execute_stmt(stmt: &mut Statement, params: &str) -> Result<Vec<Plugin>, rusqlite::Error> {
         let empty_params = params![];
         let has_params = params![params];
         let mut query_params = empty_params;
@@ -77,50 +234,111 @@ impl Cache {
             query_params = has_params;
         }
 
-        let plugin_iter = stmt
-            .query_map(query_params, |row| {
-                Ok(Plugin {
-                    name: row.get(0).unwrap(),
-                    author: row.get(1).unwrap(),
-                    current_version: row.get(2).unwrap(),
-                    id: row.get(3).unwrap(),
-                    description: row.get(4).unwrap(),
-                    download_url: row.get(5).unwrap(),
-                    info_url: row.get(6).unwrap(),
-                    category: row.get(7).unwrap(),
-                    latest_version: row.get(8).unwrap(),
-                    downloads: row.get(9).unwrap(),
-                    archive_name: row.get(10).unwrap(),
-                    updated: row.get(11).unwrap(),
-                    hash: row.get(12).unwrap(),
-                    installed: row.get(13).unwrap(),
-                })
-            })
-            .unwrap();
+        stmt.query_map(query_params, Cache::map_plugin)?
+            .collect::<Result<Vec<Plugin>, rusqlite::Error>>()
+    }
 
-        for plugin in plugin_iter {
-            all_plugins.push(plugin.unwrap());
+    fn replace_plugin_files(
+        transaction: &Transaction,
+        plugin_id: i32,
+        files: &[String],
+    ) -> Result<(), rusqlite::Error> {
+        transaction.execute(
+            "DELETE FROM plugin_files WHERE plugin_id=?1;",
+            params![plugin_id],
+        )?;
+        for file in files {
+            transaction.execute(
+                "INSERT OR IGNORE INTO plugin_files (plugin_id, path) VALUES (?1, ?2);",
+                params![plugin_id, file],
+            )?;
         }
 
-        all_plugins
+        Ok(())
     }
 
-    fn update_plugin_version(
-        &self,
-        plugin_id: i32,
-        latest_version: &str,
-    ) -> Result<(), rusqlite::Error> {
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
+    /// Replaces an unmanaged plugin with its catalog entry. The installed version and the file manifest are kept.
+    fn adopt_plugin(&self, plugin: &Plugin, unmanaged: &Plugin) -> Result<(), Error> {
+        debug!("Plugin {} is now managed by the catalog", plugin.name);
+        let files = self.get_plugin_files(unmanaged.id)?;
 
-        connection.execute(
+        if unmanaged.name != plugin.name {
+            self.delete_plugin(&unmanaged.name)?;
+        }
+        self.insert_plugin(
+            &plugin
+                .clone()
+                .with_current_version(&unmanaged.current_version),
+            1,
+        )?;
+        if unmanaged.id != plugin.id {
+            self.delete_plugin_files(unmanaged.id)?;
+            self.save_plugin_files(plugin.id, &files)?;
+        }
+
+        Ok(())
+    }
+
+    /// Refreshes the catalog information of an existing feed entry.
+    fn update_feed_plugin(
+        transaction: &Transaction,
+        plugin: &Plugin,
+    ) -> Result<(), rusqlite::Error> {
+        transaction.execute(
             "UPDATE plugins
-            SET latest_version = ?2
+            SET name = ?2, author = ?3, description = ?4, download_url = ?5, info_url = ?6, category = ?7,
+                latest_version = ?8, downloads = ?9, archive_name = ?10, updated_at = ?11, hash = ?12, size = ?13,
+                delisted = 0
             WHERE
-                plugin_id=?1",
-            params![plugin_id, latest_version],
+                plugin_id = ?1 AND source = ?14",
+            params![
+                plugin.id,
+                plugin.name,
+                plugin.author,
+                plugin.description,
+                plugin.download_url,
+                plugin.info_url,
+                plugin.category,
+                plugin.latest_version,
+                plugin.downloads,
+                plugin.archive_name,
+                plugin.updated,
+                plugin.hash,
+                plugin.size,
+                FEED_SOURCE
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn insert_feed_plugin(
+        transaction: &Transaction,
+        plugin: &Plugin,
+    ) -> Result<(), rusqlite::Error> {
+        transaction.execute(
+            &format!(
+                "INSERT INTO plugins ({})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, 0, ?14, 0, ?15, 0, 0, '');",
+                PLUGIN_COLUMNS
+            ),
+            params![
+                plugin.name,
+                plugin.author,
+                plugin.current_version,
+                plugin.id,
+                plugin.description,
+                plugin.download_url,
+                plugin.info_url,
+                plugin.category,
+                plugin.latest_version,
+                plugin.downloads,
+                plugin.archive_name,
+                plugin.updated,
+                plugin.hash,
+                plugin.size,
+                FEED_SOURCE
+            ],
         )?;
 
         Ok(())
@@ -128,50 +346,93 @@ impl Cache {
 }
 
 impl DatabaseHandler for Cache {
-    fn insert_plugin(&self, plugin: &Plugin, installed: i32) -> Result<(), Box<dyn Error>> {
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
+    fn insert_plugin(&self, plugin: &Plugin, installed: i32) -> Result<(), Error> {
+        let connection = self.pool.get()?;
 
         connection.execute(
-            "INSERT INTO plugins (name, author, current_version, plugin_id, description, download_url, info_url, category, latest_version, downloads, archive_name, updated_at, hash, installed)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+            "INSERT INTO plugins (name, author, current_version, plugin_id, description, download_url, info_url, category, latest_version, downloads, archive_name, updated_at, hash, installed, size, verified, source, delisted)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
             ON CONFLICT (name)
-            DO UPDATE SET name=?1, author=?2, current_version=?3, plugin_id=?4, description=?5, download_url=?6, info_url=?7, category=?8, latest_version=?9, downloads=?10, archive_name=?11, updated_at=?12, hash=?13, installed=?14;",
-        params![plugin.name, plugin.author, plugin.current_version, plugin.id, plugin.description, plugin.download_url, plugin.info_url, plugin.category, plugin.latest_version, plugin.downloads, plugin.archive_name, plugin.updated, plugin.hash, installed])?;
+            DO UPDATE SET name=?1, author=?2, current_version=?3, plugin_id=?4, description=?5, download_url=?6, info_url=?7, category=?8, latest_version=?9, downloads=?10, archive_name=?11, updated_at=?12, hash=?13, installed=?14, size=?15, verified=?16, source=?17, delisted=?18;",
+        params![plugin.name, plugin.author, plugin.current_version, plugin.id, plugin.description, plugin.download_url, plugin.info_url, plugin.category, plugin.latest_version, plugin.downloads, plugin.archive_name, plugin.updated, plugin.hash, installed, plugin.size, plugin.verified, plugin.source, plugin.delisted])?;
 
         Ok(())
     }
 
-    fn sync_plugins(&self, plugins: &[Plugin]) -> Result<(), Box<dyn Error>> {
-        let database_plugins = self.get_plugins();
+    /// Reconciles the catalog with the entries of the feed. Feed entries are matched by their lotrointerface id,
+    /// so renamed plugins keep their installation state. Entries that are missing in the feed are marked as
+    /// delisted. The installed version and the install flag are never changed.
+    fn sync_plugins(&self, plugins: &[Plugin]) -> Result<(), Error> {
+        let database_plugins = self.get_plugins()?;
+        let mut feed_ids = database_plugins
+            .values()
+            .filter(|plugin| plugin.source == FEED_SOURCE)
+            .map(|plugin| plugin.id)
+            .collect::<HashSet<i32>>();
+        let unmanaged_plugins = database_plugins
+            .values()
+            .filter(|plugin| plugin.is_unmanaged())
+            .map(|plugin| (plugin.id, plugin))
+            .collect::<HashMap<i32, &Plugin>>();
+        let mut names = database_plugins
+            .values()
+            .map(|plugin| (plugin.name.clone(), plugin.id))
+            .collect::<HashMap<String, i32>>();
+
+        let mut adopted_plugins = Vec::new();
+        let mut connection = self.pool.get()?;
+        let transaction = connection.transaction()?;
+
+        // Every entry that is part of the feed gets listed again below
+        transaction.execute(
+            "UPDATE plugins SET delisted = 1 WHERE source = ?1;",
+            params![FEED_SOURCE],
+        )?;
 
-        if database_plugins.is_empty() {
-            for plugin in plugins {
-                self.insert_plugin(plugin, 0).unwrap();
+        for plugin in plugins {
+            // Entries that can't be applied because of a name collision are still part of the feed
+            transaction.execute(
+                "UPDATE plugins SET delisted = 0 WHERE source = ?1 AND (plugin_id = ?2 OR name = ?3);",
+                params![FEED_SOURCE, plugin.id, plugin.name],
+            )?;
+
+            let unmanaged = database_plugins
+                .get(&plugin.name)
+                .or_else(|| unmanaged_plugins.get(&plugin.id).copied())
+                .filter(|database_plugin| database_plugin.is_unmanaged());
+            if let (Some(unmanaged), false) = (unmanaged, feed_ids.contains(&plugin.id)) {
+                adopted_plugins.push((plugin, unmanaged));
+                continue;
             }
-        } else {
-            for plugin in plugins {
-                if database_plugins.contains_key(&plugin.name) {
-                    let database_plugin = database_plugins.get(&plugin.name).unwrap();
-                    if plugin.latest_version != database_plugin.latest_version {
-                        self.update_plugin_version(plugin.id, &database_plugin.latest_version).map_err(|_|{
-                            debug!("Error while updating the latest version of plugin {} to version {}", plugin.name, plugin.latest_version);
-                        }).unwrap();
-                    }
-                }
+
+            if names.get(&plugin.name).is_some_and(|id| *id != plugin.id) {
+                debug!(
+                    "Skipping plugin {} because its name is used by another plugin",
+                    plugin.name
+                );
+                continue;
+            }
+
+            if feed_ids.contains(&plugin.id) {
+                Cache::update_feed_plugin(&transaction, plugin)?;
+            } else {
+                debug!("Adding new plugin {} to the catalog", plugin.name);
+                Cache::insert_feed_plugin(&transaction, plugin)?;
+                feed_ids.insert(plugin.id);
             }
+            names.insert(plugin.name.clone(), plugin.id);
+        }
+        transaction.commit()?;
+
+        for (plugin, unmanaged) in adopted_plugins {
+            self.adopt_plugin(plugin, unmanaged)?;
         }
 
         Ok(())
     }
 
-    fn mark_as_installed(&self, plugin_id: i32, version: &str) -> Result<(), Box<dyn Error>> {
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
+    fn mark_as_installed(&self, plugin_id: i32, version: &str) -> Result<(), Error> {
+        let connection = self.pool.get()?;
         connection.execute(
             "UPDATE plugins
             SET installed = 1, current_version = ?2
@@ -183,11 +444,8 @@ impl DatabaseHandler for Cache {
         Ok(())
     }
 
-    fn delete_plugin(&self, name: &str) -> Result<(), Box<dyn Error>> {
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
+    fn delete_plugin(&self, name: &str) -> Result<(), Error> {
+        let connection = self.pool.get()?;
         connection.execute(
             "DELETE FROM plugins WHERE name=?1;",
             params![name.to_string()],
@@ -196,72 +454,222 @@ impl DatabaseHandler for Cache {
         Ok(())
     }
 
-    fn get_installed_plugins(&self) -> HashMap<String, Plugin> {
+    fn mark_as_uninstalled(&self, name: &str) -> Result<(), Error> {
+        let mut connection = self.pool.get()?;
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "UPDATE plugins
+            SET installed = 0, current_version = '', verified = 0, pinned = 0, ignore_updates_until = ''
+            WHERE
+                name=?1 AND source=?2 AND delisted = 0",
+            params![name, FEED_SOURCE],
+        )?;
+        transaction.execute(
+            "DELETE FROM plugins WHERE name=?1 AND installed = 1;",
+            params![name],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn get_installed_plugins(&self) -> Result<HashMap<String, Plugin>, Error> {
         let mut plugins = HashMap::new();
 
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
-        let mut stmt = connection
-            .prepare("SELECT name, author, current_version, plugin_id, description, download_url, info_url, category, latest_version, downloads, archive_name, updated_at, hash, installed
-            FROM plugins
-            WHERE installed=1
-            ORDER BY name;")
-            .unwrap();
+        let connection = self.pool.get()?;
+        let mut stmt = connection.prepare(&format!(
+            "SELECT {} FROM plugins WHERE installed=1 ORDER BY name;",
+            PLUGIN_COLUMNS
+        ))?;
 
-        for element in Cache::execute_stmt(&mut stmt, "") {
+        for element in Cache::execute_stmt(&mut stmt, "")? {
             plugins.insert(element.name.clone(), element);
         }
 
-        plugins
+        Ok(plugins)
     }
 
-    fn get_plugins(&self) -> HashMap<String, Plugin> {
+    fn get_plugins(&self) -> Result<HashMap<String, Plugin>, Error> {
         let mut plugins = HashMap::new();
 
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
-        let mut stmt = connection
-            .prepare("SELECT name, author, current_version, plugin_id, description, download_url, info_url, category, latest_version, downloads, archive_name, updated_at, hash, installed FROM plugins ORDER BY name;")
-            .unwrap();
+        let connection = self.pool.get()?;
+        let mut stmt = connection.prepare(&format!(
+            "SELECT {} FROM plugins ORDER BY name;",
+            PLUGIN_COLUMNS
+        ))?;
 
-        for element in Cache::execute_stmt(&mut stmt, "") {
+        for element in Cache::execute_stmt(&mut stmt, "")? {
             plugins.insert(element.name.clone(), element);
         }
 
-        plugins
+        Ok(plugins)
     }
 
-    fn get_plugin(&self, name: &str) -> Result<Option<Plugin>, Box<dyn Error>> {
-        let connection = self
-            .pool
-            .get()
-            .expect("Error while creating a pooled connection");
-        let mut stmt = connection
-            .prepare("SELECT name, author, current_version, plugin_id, description, download_url, info_url, category, latest_version, downloads, archive_name, updated_at, hash, installed FROM plugins WHERE name=?1;")
-            .unwrap();
-        let mut plugin_iter = stmt.query_map([name.to_string()], |row| {
-            Ok(Plugin {
-                name: row.get(0).unwrap(),
-                author: row.get(1).unwrap(),
-                current_version: row.get(2).unwrap(),
-                id: row.get(3).unwrap(),
-                description: row.get(4).unwrap(),
-                download_url: row.get(5).unwrap(),
-                info_url: row.get(6).unwrap(),
-                category: row.get(7).unwrap(),
-                latest_version: row.get(8).unwrap(),
-                downloads: row.get(9).unwrap(),
-                archive_name: row.get(10).unwrap(),
-                updated: row.get(11).unwrap(),
-                hash: row.get(12).unwrap(),
-                installed: row.get(13).unwrap(),
-            })
-        })?;
+    fn get_plugin(&self, name: &str) -> Result<Option<Plugin>, Error> {
+        let connection = self.pool.get()?;
+        let mut stmt = connection.prepare(&format!(
+            "SELECT {} FROM plugins WHERE name=?1;",
+            PLUGIN_COLUMNS
+        ))?;
+        let mut plugin_iter = stmt.query_map([name.to_string()], Cache::map_plugin)?;
+
+        Ok(plugin_iter.next().transpose()?)
+    }
+
+    fn get_plugin_by_id(&self, plugin_id: i32) -> Result<Option<Plugin>, Error> {
+        let connection = self.pool.get()?;
+        let mut stmt = connection.prepare(&format!(
+            "SELECT {} FROM plugins WHERE plugin_id=?1;",
+            PLUGIN_COLUMNS
+        ))?;
+        let mut plugin_iter = stmt.query_map(params![plugin_id], Cache::map_plugin)?;
 
         Ok(plugin_iter.next().transpose()?)
     }
+
+    fn save_plugin_files(&self, plugin_id: i32, files: &[String]) -> Result<(), Error> {
+        let mut connection = self.pool.get()?;
+        let transaction = connection.transaction()?;
+        Cache::replace_plugin_files(&transaction, plugin_id, files)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn get_plugin_files(&self, plugin_id: i32) -> Result<Vec<String>, Error> {
+        let connection = self.pool.get()?;
+        let mut stmt = connection
+            .prepare("SELECT path FROM plugin_files WHERE plugin_id=?1 ORDER BY path;")?;
+        let files = stmt
+            .query_map(params![plugin_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+
+        Ok(files)
+    }
+
+    fn delete_plugin_files(&self, plugin_id: i32) -> Result<(), Error> {
+        let connection = self.pool.get()?;
+        connection.execute(
+            "DELETE FROM plugin_files WHERE plugin_id=?1;",
+            params![plugin_id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Marks the plugin as installed and replaces its file manifest within a single database transaction.
+    fn commit_installation(
+        &self,
+        plugin_id: i32,
+        version: &str,
+        files: &[String],
+        verified: bool,
+    ) -> Result<(), Error> {
+        let mut connection = self.pool.get()?;
+        let transaction = connection.transaction()?;
+
+        let updated_rows = transaction.execute(
+            "UPDATE plugins
+            SET installed = 1, current_version = ?2, verified = ?3
+            WHERE
+                plugin_id=?1",
+            params![plugin_id, version, i32::from(verified)],
+        )?;
+        if updated_rows == 0 {
+            return Err(Error::plugin(format!(
+                "Plugin {} doesn't exist in the cache",
+                plugin_id
+            )));
+        }
+        Cache::replace_plugin_files(&transaction, plugin_id, files)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Returns the id for a new locally installed plugin. Local plugins use negative ids so that they never
+    /// collide with the ids of lotrointerface.
+    fn next_local_plugin_id(&self) -> Result<i32, Error> {
+        let connection = self.pool.get()?;
+        let lowest_id: Option<i32> = connection.query_row(
+            "SELECT MIN(plugin_id) FROM plugins WHERE plugin_id < 0;",
+            [],
+            |row| row.get(0),
+        )?;
+
+        Ok(lowest_id.unwrap_or(0) - 1)
+    }
+
+    /// Records an installed version. Installing a version again updates its timestamp.
+    fn add_history_entry(&self, plugin_id: i32, version: &str, hash: &str) -> Result<(), Error> {
+        let connection = self.pool.get()?;
+        connection.execute(
+            "INSERT OR REPLACE INTO plugin_history (plugin_id, version, hash, installed_at)
+            VALUES (?1, ?2, ?3, ?4);",
+            params![plugin_id, version, hash, Utc::now().timestamp()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the installed versions of a plugin, most recently installed first.
+    fn get_plugin_history(&self, plugin_id: i32) -> Result<Vec<HistoryEntry>, Error> {
+        let connection = self.pool.get()?;
+        let mut stmt = connection.prepare(
+            "SELECT plugin_id, version, hash, installed_at FROM plugin_history
+            WHERE plugin_id=?1 ORDER BY installed_at DESC, rowid DESC;",
+        )?;
+        let history = stmt
+            .query_map(params![plugin_id], |row| {
+                Ok(HistoryEntry {
+                    plugin_id: row.get(0)?,
+                    version: row.get(1)?,
+                    hash: row.get(2)?,
+                    installed_at: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<HistoryEntry>, rusqlite::Error>>()?;
+
+        Ok(history)
+    }
+
+    fn set_pinned(&self, plugin_id: i32, pinned: bool) -> Result<(), Error> {
+        let connection = self.pool.get()?;
+        connection.execute(
+            "UPDATE plugins SET pinned = ?2 WHERE plugin_id=?1;",
+            params![plugin_id, i32::from(pinned)],
+        )?;
+
+        Ok(())
+    }
+
+    fn set_ignore_updates_until(&self, plugin_id: i32, version: &str) -> Result<(), Error> {
+        let connection = self.pool.get()?;
+        connection.execute(
+            "UPDATE plugins SET ignore_updates_until = ?2 WHERE plugin_id=?1;",
+            params![plugin_id, version.trim()],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_feed_fetched_at(&self) -> Result<Option<i64>, Error> {
+        let connection = self.pool.get()?;
+        let fetched_at =
+            connection.query_row("SELECT MAX(fetched_at) FROM feed_sync;", [], |row| {
+                row.get(0)
+            })?;
+
+        Ok(fetched_at)
+    }
+
+    fn set_feed_fetched_at(&self, fetched_at: i64) -> Result<(), Error> {
+        let connection = self.pool.get()?;
+        connection.execute(
+            "INSERT OR REPLACE INTO feed_sync (id, fetched_at) VALUES (0, ?1);",
+            params![fetched_at],
+        )?;
+
+        Ok(())
+    }
 }
@@ -1,12 +1,13 @@
 //! This module is used to parse `.plugincompendium files`. These compendium files are xml files that contain
 //! information about the specific plugin that gets installed. Not every information is in the compendium file.
 //! Often there is a second file which has the `.plugin` extension which contains the rest.
-use super::plugin_parser::parse_plugin_file;
-use crate::core::lotro_compendium::Plugin;
+use crate::core::error::{Error, PathContext};
+use crate::core::Plugin;
 use serde::Deserialize;
 use serde_xml_rs::from_reader;
 use std::{
     fs::File,
+    io::Read,
     path::{Path, PathBuf},
 };
 
@@ -16,47 +17,53 @@ enum Separator {
     Backslash,
 }
 
-pub fn parse_compendium_file(path: &Path) -> Plugin {
-    let file = File::open(&path).unwrap();
+pub fn parse_compendium_file(path: &Path) -> Result<Plugin, Error> {
+    let file = File::open(path).at(path)?;
 
-    let content: PluginCompendiumContent = from_reader(file).unwrap();
-    let compendium_content = content._purge_descriptors(path);
+    let content: PluginCompendiumContent =
+        from_reader(file).map_err(|error| Error::descriptor(path, &error))?;
+    let compendium_content = content.purge_descriptors(path);
 
-    if compendium_content.description.is_some() {
-        Plugin::new(&compendium_content.name)
-            .with_id(compendium_content.id)
-            .with_description(&compendium_content.description.unwrap())
-            .with_author(&compendium_content.author)
-            .with_current_version(&compendium_content.version)
-            .build()
+    let plugin = Plugin::new(&compendium_content.name)
+        .with_id(compendium_content.id)
+        .with_author(&compendium_content.author)
+        .with_current_version(&compendium_content.version);
+
+    if let Some(description) = &compendium_content.description {
+        Ok(plugin.with_description(description).build())
     } else if compendium_content.plugin_file_location.is_empty() {
-        Plugin::new(&compendium_content.name)
-            .with_id(compendium_content.id)
-            .with_author(&compendium_content.author)
-            .with_current_version(&compendium_content.version)
-            .build()
+        Ok(plugin.build())
     } else {
         let plugin_path = build_plugin_path(path, &compendium_content.plugin_file_location);
 
         if plugin_path.exists() {
-            let plugin_content = parse_plugin_file(&plugin_path);
-
-            Plugin::new(&compendium_content.name)
-                .with_id(compendium_content.id)
-                .with_description(&plugin_content.description.unwrap())
-                .with_author(&compendium_content.author)
-                .with_current_version(&compendium_content.version)
-                .build()
+            let plugin_content = parse_plugin_file(&plugin_path)?;
+
+            Ok(plugin.with_description(&plugin_content.description).build())
         } else {
-            Plugin::new(&compendium_content.name)
-                .with_id(compendium_content.id)
-                .with_author(&compendium_content.author)
-                .with_current_version(&compendium_content.version)
-                .build()
+            Ok(plugin.build())
         }
     }
 }
 
+/// Returns the ids of the plugins that the compendium file at the given path declares as dependencies. The path
+/// is only used for errors, the file can also be read from an archive.
+pub fn read_dependencies<R: Read>(reader: R, path: &Path) -> Result<Vec<i32>, Error> {
+    let content: PluginCompendiumContent =
+        from_reader(reader).map_err(|error| Error::descriptor(path, &error))?;
+
+    content
+        .dependencies
+        .dependency
+        .iter()
+        .map(|dependency| {
+            dependency.trim().parse::<i32>().map_err(|_| {
+                Error::descriptor(path, &format!("Invalid dependency id {}", dependency))
+            })
+        })
+        .collect()
+}
+
 /// Returns an enum which indicates the separator of the given descriptor based on their
 /// String and Separator char amounts in the descriptor
 fn calculate_separator(descriptor: &str) -> Separator {
@@ -71,7 +78,7 @@ fn calculate_separator(descriptor: &str) -> Separator {
 
 /// Returns the compendium file name which is used to extract the correct descriptors from the compendium file.
 fn build_plugin_file_name(path: &Path) -> String {
-    let file_name = path.file_stem().unwrap().to_str().unwrap();
+    let file_name = path.file_stem().unwrap_or_default().to_string_lossy();
 
     format!("{}.plugin", file_name)
 }
@@ -103,30 +110,35 @@ fn build_plugin_path(plugin_folder_path: &Path, file_path: &str) -> PathBuf {
     }
 }
 
-use serde::Deserialize;
-use serde_xml_rs::from_reader;
-use std::{fs::File, path::Path};
-
 // When .plugin file, check all folders if there is a .compendium file with the same name. If not,
 // return the .plugin content with an unmaintained name
-pub fn parse_plugin_file(path: &Path) -> PluginDataClass {
-    let file = File::open(path).unwrap();
-
-    let content: Plugin = from_reader(file).unwrap();
-
-    PluginDataClass::new(
-        &content.information.name,
-        &content.information.author,
-        &content.information.version,
-    )
-    .with_description(&content.information.description)
-    .build()
+pub fn parse_plugin_file(path: &Path) -> Result<Plugin, Error> {
+    let file = File::open(path).at(path)?;
+
+    let content: PluginFile = from_reader(file).map_err(|error| Error::descriptor(path, &error))?;
+
+    Ok(Plugin::new(&content.information.name)
+        .with_author(&content.information.author)
+        .with_current_version(&content.information.version)
+        .with_description(&content.information.description)
+        .build())
+}
+
+/// Returns the `Package` of a `.plugin` file, e.g. `HabnaPlugins.TitanBar.Main`. The first two segments are the
+/// author folder and the plugin folder inside of the plugins directory.
+pub fn parse_plugin_package(path: &Path) -> Result<String, Error> {
+    let file = File::open(path).at(path)?;
+    let content: PluginFile = from_reader(file).map_err(|error| Error::descriptor(path, &error))?;
+
+    Ok(content.package.trim().to_string())
 }
 
 #[derive(Deserialize, Debug, PartialEq, Hash, Eq)]
 #[serde(rename_all = "PascalCase")]
-struct Plugin {
+struct PluginFile {
     pub information: Information,
+    #[serde(default)]
+    pub package: String,
 }
 
 #[derive(Deserialize, Debug, PartialEq, Hash, Eq, Clone)]
@@ -147,7 +159,9 @@ struct PluginCompendiumContent {
     version: String,
     author: String,
     description: Option<String>,
+    #[serde(default)]
     info_url: String,
+    #[serde(default)]
     download_url: String,
     #[serde(default)]
     descriptors: Descriptors,
@@ -169,16 +183,8 @@ struct PluginCompendium {
 }
 
 impl PluginCompendiumContent {
-    pub fn _purge_descriptors(&self, path: &Path) -> PluginCompendium {
+    pub fn purge_descriptors(&self, path: &Path) -> PluginCompendium {
         let file_name = build_plugin_file_name(path);
-        let other_descriptors: Vec<String> = self
-            .descriptors
-            .descriptor
-            .clone()
-            .into_iter()
-            .filter(|element| !element.contains(&file_name))
-            .collect();
-
         let purged_descriptors: Vec<String> = self
             .descriptors
             .descriptor
@@ -235,7 +241,7 @@ mod tests {
     fn extract_plugin_descriptor() {
         let file = File::open(&"tests/samples/xml_files/TitanBar.plugincompendium").unwrap();
         let content: PluginCompendiumContent = from_reader(file).unwrap();
-        let content = content._purge_descriptors(Path::new(
+        let content = content.purge_descriptors(Path::new(
             "tests/samples/xml_files/TitanBar.plugincompendium",
         ));
 
@@ -287,9 +293,41 @@ mod tests {
 
     #[test]
     fn plugin_parsing() {
-        let plugin = parse_plugin_file(Path::new("tests/samples/xml_files/PreciseCoords.plugin"));
+        let plugin =
+            parse_plugin_file(Path::new("tests/samples/xml_files/PreciseCoords.plugin")).unwrap();
         assert_eq!(plugin.name, "Precise Coords");
     }
+
+    mod dependencies_tests {
+        use super::*;
+
+        #[test]
+        fn with_dependencies() {
+            let file = File::open("tests/samples/xml_files/Compendium.plugincompendium").unwrap();
+            assert_eq!(
+                read_dependencies(file, Path::new("Compendium.plugincompendium")).unwrap(),
+                vec![640]
+            );
+        }
+
+        #[test]
+        fn without_dependencies() {
+            let file = File::open("tests/samples/xml_files/Waypoint.plugincompendium").unwrap();
+            assert!(
+                read_dependencies(file, Path::new("Waypoint.plugincompendium"))
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+
+        #[test]
+        fn invalid_dependency() {
+            let content = "<PluginConfig><Id>1</Id><Name>A</Name><Version>1</Version><Author>B</Author><Dependencies><dependency>Waypoint</dependency></Dependencies></PluginConfig>";
+            assert!(
+                read_dependencies(content.as_bytes(), Path::new("A.plugincompendium")).is_err()
+            );
+        }
+    }
 }
 
 //     mod calculate_separator_tests {
@@ -0,0 +1,169 @@
+//! # Directories
+//!
+//! Checks the directories that the user picks in the settings and moves the content of a directory if its path
+//! changes.
+use crate::core::discovery::LOTRO_DOCUMENTS_DIR;
+use crate::core::error::{Error, PathContext};
+use fs_extra::dir::CopyOptions;
+use std::{
+    fmt,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Files and directories of which at least one exists in a LOTRO documents directory that the game has used.
+const LOTRO_DOCUMENTS_ENTRIES: [&str; 4] = [
+    "Plugins",
+    "PluginData",
+    "UserPreferences.ini",
+    "ScreenShots",
+];
+const WRITE_CHECK_FILE: &str = ".lembas_write_check";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectoryKind {
+    LotroDocuments,
+    Plugins,
+    Backups,
+    Temp,
+}
+
+impl fmt::Display for DirectoryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LotroDocuments => write!(f, "LOTRO documents directory"),
+            Self::Plugins => write!(f, "Plugins directory"),
+            Self::Backups => write!(f, "Backup directory"),
+            Self::Temp => write!(f, "Temporary directory"),
+        }
+    }
+}
+
+impl DirectoryKind {
+    /// Returns true if the directory only holds files of lembas, so its content can be moved along when the directory
+    /// changes. The LOTRO documents directory belongs to the game and is never moved.
+    pub fn is_owned(self) -> bool {
+        !matches!(self, Self::LotroDocuments)
+    }
+}
+
+/// Returns true if the directory is named like the LOTRO documents directory or contains files of the game.
+pub fn looks_like_lotro_documents_dir(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.eq_ignore_ascii_case(LOTRO_DOCUMENTS_DIR))
+        || LOTRO_DOCUMENTS_ENTRIES
+            .iter()
+            .any(|entry| path.join(entry).exists())
+}
+
+/// Checks that the directory can be used for the given kind. Directories that don't exist yet are created later, so
+/// one of their parents has to exist. Returns warnings for directories that can be used but look wrong, e.g. a
+/// plugins directory outside of a LOTRO documents directory. Nothing is written, use `check_writable` before the
+/// directory is used.
+pub fn check_directory(kind: DirectoryKind, path: &Path) -> Result<Vec<String>, Error> {
+    if !path.is_absolute() {
+        return Err(Error::config(format!(
+            "{} isn't an absolute path",
+            path.display()
+        )));
+    }
+
+    let mut warnings = Vec::new();
+    if path.exists() && !path.is_dir() {
+        return Err(Error::config(format!(
+            "{} isn't a directory",
+            path.display()
+        )));
+    } else if !path.exists() {
+        if !path.ancestors().any(Path::is_dir) {
+            return Err(Error::config(format!(
+                "{} can't be created",
+                path.display()
+            )));
+        }
+        warnings.push(format!("{} is created", path.display()));
+    }
+
+    match kind {
+        DirectoryKind::LotroDocuments if !looks_like_lotro_documents_dir(path) => {
+            warnings.push(format!(
+                "{} doesn't look like a LOTRO documents directory",
+                path.display()
+            ));
+        }
+        DirectoryKind::Plugins if !path.parent().is_some_and(looks_like_lotro_documents_dir) => {
+            warnings.push(
+                "LOTRO only loads plugins from the Plugins directory of its documents directory"
+                    .to_string(),
+            );
+        }
+        _ => {}
+    }
+
+    Ok(warnings)
+}
+
+/// Fails if no file can be created in the directory.
+pub fn check_writable(path: &Path) -> Result<(), Error> {
+    let check_file = path.join(WRITE_CHECK_FILE);
+
+    File::create(&check_file)
+        .and_then(|_| fs::remove_file(&check_file))
+        .at(path)
+}
+
+/// Returns the subdirectories of a directory sorted by their name.
+pub fn subdirectories(path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    let mut directories = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<PathBuf>>();
+    directories.sort_by_key(|path| path.to_string_lossy().to_lowercase());
+
+    directories
+}
+
+/// Moves every entry of a directory into another directory, which is created if it doesn't exist. Nothing is moved
+/// if an entry already exists in the target. Returns the names of the moved entries.
+pub fn move_contents(from: &Path, to: &Path) -> Result<Vec<String>, Error> {
+    if from == to || !from.is_dir() {
+        return Ok(Vec::new());
+    }
+    if to.starts_with(from) {
+        return Err(Error::config(format!(
+            "Can't move {} into its own subdirectory {}",
+            from.display(),
+            to.display()
+        )));
+    }
+
+    let entries = fs::read_dir(from)
+        .at(from)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()
+        .at(from)?;
+    let names = entries
+        .iter()
+        .filter_map(|entry| entry.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    if let Some(name) = names.iter().find(|name| to.join(name).exists()) {
+        return Err(Error::config(format!(
+            "{} already exists in {}",
+            name,
+            to.display()
+        )));
+    }
+
+    fs::create_dir_all(to).at(to)?;
+    fs_extra::move_items(&entries, to, &CopyOptions::new())
+        .map_err(|error| Error::filesystem(to, io::Error::other(error.to_string())))?;
+
+    Ok(names)
+}
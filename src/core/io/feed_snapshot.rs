@@ -0,0 +1,161 @@
+//! # Feed snapshot
+//!
+//! Keeps the last feed that was downloaded and parsed successfully, so the catalog can be shown without a
+//! connection to lotrointerface. The feed is stored as it was downloaded in `feed.xml`, `feed.json` records where
+//! and when it was fetched together with the validators that make the next download conditional.
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::core::error::{Error, PathContext};
+use crate::core::lotro_compendium::{FeedUrlParser, FeedValidators};
+use crate::core::Plugin;
+
+/// Describes the stored feed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMetadata {
+    pub url: String,
+    /// Unix timestamp of the download
+    pub fetched_at: i64,
+    /// Amount of plugins in the feed
+    pub plugins: usize,
+    /// `ETag` header of the download
+    #[serde(default)]
+    pub etag: String,
+    /// `Last-Modified` header of the download
+    #[serde(default)]
+    pub last_modified: String,
+    /// Unix timestamp of the last time the feed was downloaded or the server confirmed that it didn't change
+    #[serde(default)]
+    pub checked_at: i64,
+}
+
+impl SnapshotMetadata {
+    pub fn validators(&self) -> FeedValidators {
+        FeedValidators {
+            etag: self.etag.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedSnapshot {
+    root: PathBuf,
+}
+
+impl FeedSnapshot {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// Replaces the stored feed. Only feeds that were parsed successfully should be saved.
+    pub fn save(
+        &self,
+        url: &str,
+        content: &str,
+        plugins: usize,
+        validators: &FeedValidators,
+    ) -> Result<SnapshotMetadata, Error> {
+        let now = Utc::now().timestamp();
+        let metadata = SnapshotMetadata {
+            url: url.to_string(),
+            fetched_at: now,
+            plugins,
+            etag: validators.etag.clone(),
+            last_modified: validators.last_modified.clone(),
+            checked_at: now,
+        };
+
+        fs::create_dir_all(&self.root).at(&self.root)?;
+        write_atomic(&self.feed_path(), content)?;
+        self.write_metadata(&metadata)?;
+
+        Ok(metadata)
+    }
+
+    /// Records that the server confirmed that the stored feed is still up to date.
+    pub fn mark_checked(&self) -> Result<Option<SnapshotMetadata>, Error> {
+        let Some(metadata) = self.metadata() else {
+            return Ok(None);
+        };
+        let metadata = SnapshotMetadata {
+            checked_at: Utc::now().timestamp(),
+            ..metadata
+        };
+        self.write_metadata(&metadata)?;
+
+        Ok(Some(metadata))
+    }
+
+    /// Returns the metadata of the stored feed, `None` if no feed was stored yet.
+    pub fn metadata(&self) -> Option<SnapshotMetadata> {
+        if !self.feed_path().is_file() {
+            return None;
+        }
+
+        fs::read_to_string(self.metadata_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    /// Parses the stored feed. Returns `None` if no feed was stored yet.
+    pub fn load(&self) -> Result<Option<(SnapshotMetadata, Vec<Plugin>)>, Error> {
+        let Some(metadata) = self.metadata() else {
+            return Ok(None);
+        };
+
+        let feed_path = self.feed_path();
+        let content = fs::read_to_string(&feed_path).at(&feed_path)?;
+        let plugins = FeedUrlParser::parse_response_xml(&content)?;
+
+        Ok(Some((metadata, plugins)))
+    }
+
+    fn write_metadata(&self, metadata: &SnapshotMetadata) -> Result<(), Error> {
+        let metadata_path = self.metadata_path();
+        let content = serde_json::to_string(metadata)
+            .map_err(io::Error::from)
+            .at(&metadata_path)?;
+        write_atomic(&metadata_path, &content)
+    }
+
+    fn feed_path(&self) -> PathBuf {
+        self.root.join("feed.xml")
+    }
+
+    fn metadata_path(&self) -> PathBuf {
+        self.root.join("feed.json")
+    }
+}
+
+/// Written under another name first, an interrupted write never replaces the previous snapshot.
+fn write_atomic(path: &Path, content: &str) -> Result<(), Error> {
+    let mut partial_path = path.as_os_str().to_owned();
+    partial_path.push(".part");
+    let partial_path = PathBuf::from(partial_path);
+    fs::write(&partial_path, content).at(&partial_path)?;
+    fs::rename(&partial_path, path).at(path)
+}
+
+/// Describes the age of a snapshot for humans, e.g. `5 minutes ago`.
+pub fn format_age(fetched_at: i64, now: i64) -> String {
+    let seconds = (now - fetched_at).max(0);
+    let (amount, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86_399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86_400, "day"),
+    };
+
+    if amount == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", amount, unit)
+    }
+}
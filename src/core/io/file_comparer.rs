@@ -28,7 +28,10 @@ pub fn compare_files(compendium_files: &[PathBuf], plugin_files: &[PathBuf]) ->
 }
 
 fn extract_file_stem(file_name: &Path) -> &str {
-    file_name.file_stem().unwrap().to_str().unwrap()
+    file_name
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
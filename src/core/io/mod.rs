@@ -1,7 +1,13 @@
+pub mod archive_store;
 pub mod cache;
+pub mod compendium_parser;
+pub mod directories;
+pub mod feed_snapshot;
 pub mod file_comparer;
 pub mod plugin_collector;
 pub mod synchronizer;
 
+pub use archive_store::ArchiveStore;
 pub use cache::Cache;
-//pub use synchronizer::Synchronizer;
+pub use feed_snapshot::FeedSnapshot;
+pub use synchronizer::Synchronizer;
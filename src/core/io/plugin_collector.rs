@@ -1,10 +1,9 @@
 use globset::{Glob, GlobMatcher};
-use std::{
-    error::Error,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+use crate::core::error::Error;
+
 pub struct PluginCollector {
     compendium_glob: GlobMatcher,
     plugin_glob: GlobMatcher,
@@ -23,33 +22,31 @@ impl PluginCollector {
     pub fn collect_compendium_files(
         &self,
         plugins_directory: &Path,
-    ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-        let content: Vec<PathBuf> = WalkDir::new(&plugins_directory)
-            .into_iter()
-            .filter(|element| {
-                self.compendium_glob
-                    .is_match(element.as_ref().unwrap().path())
-            })
-            .map(|element| element.unwrap().into_path())
-            .collect();
-
-        Ok(content)
+    ) -> Result<Vec<PathBuf>, Error> {
+        collect_matching_files(plugins_directory, &self.compendium_glob)
     }
 
-    pub fn collect_plugin_files(
-        &self,
-        plugins_directory: &Path,
-    ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-        let content: Vec<PathBuf> = WalkDir::new(&plugins_directory)
-            .into_iter()
-            .filter(|element| self.plugin_glob.is_match(element.as_ref().unwrap().path()))
-            .map(|element| element.unwrap().into_path())
-            .collect();
-
-        Ok(content)
+    pub fn collect_plugin_files(&self, plugins_directory: &Path) -> Result<Vec<PathBuf>, Error> {
+        collect_matching_files(plugins_directory, &self.plugin_glob)
     }
 }
 
+fn collect_matching_files(
+    plugins_directory: &Path,
+    glob: &GlobMatcher,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut content = Vec::new();
+
+    for element in WalkDir::new(plugins_directory) {
+        let element = element?;
+        if glob.is_match(element.path()) {
+            content.push(element.into_path());
+        }
+    }
+
+    Ok(content)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
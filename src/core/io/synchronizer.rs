@@ -1,363 +1,305 @@
-// use super::api_connector;
-// use super::file_comparer::compare_files;
-// use super::plugin_collector::{collect_all_compendium_files, collect_all_plugin_files};
-// use crate::core::parsers::compendium_parser::parse_compendium_file;
-// use crate::core::parsers::plugin_parser::parse_plugin_file;
-// use crate::core::{is_not_existing_in_blacklist, PluginCollection, PluginDataClass};
-// use log::{debug, error};
-// use std::{collections::HashMap, error::Error, path::Path};
-
-// const BLACKLIST: [&str; 3] = ["Demo", "AutoLoader", "Examples"];
-
-// pub fn is_not_existing_in_blacklist(plugin_name: &str) -> bool {
-//     for element in BLACKLIST {
-//         if plugin_name.contains(element) {
-//             return false;
-//         }
-//     }
-//     true
-// }
-
-// #[derive(Default, Debug, Clone)]
-// pub struct Synchronizer;
-
-// impl Synchronizer {
-//     pub async fn synchronize_application(
-//         plugins_dir: &Path,
-//         db_path: &Path,
-//         feed_url: &str,
-//     ) -> Result<(), Box<dyn Error>> {
-//         let local_plugins = Synchronizer::search_local(plugins_dir).unwrap();
-
-//         match api_connector::fetch_plugins().await {
-//             Ok(remote_plugins) => {
-//                 Synchronizer::sync_cache(&local_plugins, &remote_plugins, db_path);
-//             }
-//             Err(_) => {
-//                 error!(
-//                     "{}",
-//                     format!(
-//                         "Couldn't fetch plugins with the given feed url: {}",
-//                         feed_url
-//                     )
-//                 );
-//             }
-//         };
-
-//         Ok(())
-//     }
-
-//     fn sync_cache(
-//         local_plugins: &PluginCollection,
-//         remote_plugins: &PluginCollection,
-//         db_path: &Path,
-//     ) {
-//         let db_plugins = get_plugins(db_path);
-//         // Managed plugins
-//         Synchronizer::update_local_plugins(remote_plugins, local_plugins, db_path);
-
-//         // Unmanaged plugins
-//         Synchronizer::check_existing_plugins(remote_plugins, local_plugins, db_path).unwrap();
-
-//         Synchronizer::delete_not_existing_local_plugins(local_plugins, &db_plugins, db_path);
-//     }
-
-//     fn update_local_plugins(
-//         remote_plugins: &HashMap<String, PluginDataClass>,
-//         local_plugins: &HashMap<String, PluginDataClass>,
-//         db_path: &Path,
-//     ) {
-//         for (title, remote_plugin) in remote_plugins {
-//             if local_plugins.contains_key(title) && is_not_existing_in_blacklist(title) {
-//                 match insert_plugin(remote_plugin, db_path) {
-//                     Ok(_) => {
-//                         debug!("Local plugin {} updated", remote_plugin.name);
-//                     }
-//                     Err(_) => {
-//                         debug!("Error while updating local plugin {}", remote_plugin.name);
-//                     }
-//                 }
-//             }
-//         }
-//     }
-
-//     /// Checks if local plugins exist in the database. If they exist and their versions are different,
-//     /// the versions are updated. If they don't exist the missing plugin is inserted.
-//     fn check_existing_plugins(
-//         remote_plugins: &HashMap<String, PluginDataClass>,
-//         local_plugins: &HashMap<String, PluginDataClass>,
-//         db_path: &Path,
-//     ) -> Result<(), Box<dyn Error>> {
-//         for (title, local_plugin) in local_plugins {
-//             if !remote_plugins.contains_key(title) && is_not_existing_in_blacklist(title) {
-//                 insert_plugin(local_plugin, db_path)?;
-//             } else if let Some(db_plugin) = get_plugin(&local_plugin.name, db_path)? {
-//                 insert_plugin(&db_plugin, db_path)?;
-//             }
-//         }
-
-//         Ok(())
-//     }
-
-//     /// Checks if the database contains plugins that are not existing anymore in the local plugins folder
-//     /// If a plugin doesn't exist in the plugin folder but in the database, the database entry gets deleted.
-//     fn delete_not_existing_local_plugins(
-//         local_plugins: &HashMap<String, PluginDataClass>,
-//         db_plugins: &HashMap<String, PluginDataClass>,
-//         db_path: &Path,
-//     ) {
-//         for keys in db_plugins.keys() {
-//             if !local_plugins.contains_key(keys) {
-//                 delete_plugin(keys, db_path).unwrap();
-//             }
-//         }
-//     }
-
-//     pub fn search_local(plugins_dir: &Path) -> Result<PluginCollection, Box<dyn Error>> {
-//         let mut local_plugins = HashMap::new();
-
-//         let compendium_files = collect_all_compendium_files(Path::new(&plugins_dir))?;
-//         let mut plugin_files = collect_all_plugin_files(Path::new(&plugins_dir))?;
-
-//         for compendium_file in &compendium_files {
-//             let tmp_plugin_name = Synchronizer::build_plugin_file_path(compendium_file);
-
-//             if let Some(position) = plugin_files
-//                 .iter()
-//                 .position(|element| element.to_str().unwrap().contains(&tmp_plugin_name))
-//             {
-//                 let compendium_content = parse_compendium_file(compendium_file);
-//                 local_plugins.insert(compendium_content.name.clone(), compendium_content);
-
-//                 plugin_files.remove(position);
-//             }
-//         }
-
-//         plugin_files = compare_files(&compendium_files, &plugin_files);
-//         for plugin_file_path in plugin_files {
-//             let plugin = parse_plugin_file(&plugin_file_path);
-//             local_plugins.insert(plugin.name.clone(), plugin);
-//         }
-
-//         Ok(local_plugins)
-//     }
-
-//     fn build_plugin_file_path(path: &Path) -> String {
-//         let mut path = path.to_path_buf();
-//         path.set_extension("plugin");
-//         path.file_name().unwrap().to_str().unwrap().to_string()
-//     }
-// }
-
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use crate::core::PluginDataClass;
-//     use fs_extra::dir::{copy, CopyOptions};
-//     use std::{
-//         collections::HashMap,
-//         env,
-//         fs::{create_dir_all, read_dir, remove_dir_all},
-//         path::{Path, PathBuf},
-//     };
-//     use uuid::Uuid;
-
-//     fn setup() -> PathBuf {
-//         // Create plugins directory and move items from the samples to this directory to test functionality
-//         let uuid = Uuid::new_v4().to_string();
-//         let plugins_dir = env::temp_dir().join(format!("lembas_test_{}", &uuid[..7]));
-//         let samples_path = Path::new("tests/samples/plugin_folders");
-//         let samples_content = read_dir(samples_path).unwrap();
-
-//         create_dir_all(&plugins_dir).unwrap();
-//         let options = CopyOptions::new();
-
-//         for element in samples_content {
-//             copy(element.unwrap().path(), &plugins_dir, &options)
-//                 .expect("Error while running setup method");
-//         }
-
-//         plugins_dir
-//     }
-
-//     fn teardown(test_dir: &Path) {
-//         remove_dir_all(test_dir).expect("Error while running teardown method");
-//     }
-
-//     #[test]
-//     fn search_local_plugins() {
-//         let test_dir = setup();
-
-//         let local_plugins = Synchronizer::search_local(&test_dir).unwrap();
-
-//         assert_eq!(local_plugins.len(), 7);
-
-//         teardown(&test_dir);
-//     }
-
-//     #[test]
-//     fn cache_synching() {
-//         let (test_dir, db_path) = setup_db();
-//         let mut local_plugins = HashMap::new();
-//         let mut remote_plugins = HashMap::new();
-
-//         let plugin = PluginDataClass::new("GreenCar", "Marius", "1.0");
-//         local_plugins.insert(plugin.name.clone(), plugin);
-//         let plugin = PluginDataClass::new("BlueElephant", "Marius", "1.1");
-//         local_plugins.insert(plugin.name.clone(), plugin);
-
-//         let plugin = PluginDataClass::new("BlueElephant", "Marius", "1.2")
-//             .with_remote_information("", "1.2", 0, "");
-
-//         let remote_plugin_name = plugin.name.clone();
-//         remote_plugins.insert(remote_plugin_name.clone(), plugin);
-
-//         Synchronizer::sync_cache(&local_plugins, &remote_plugins, &db_path);
-
-//         get_plugins(&db_path);
-
-//         assert!(remote_plugins.contains_key(&remote_plugin_name));
-//         assert_eq!(
-//             remote_plugins.get(&remote_plugin_name).unwrap().version,
-//             "1.2"
-//         );
-
-//         teardown_db(&test_dir);
-//     }
-
-//     #[test]
-//     fn update_local_plugin() {
-//         let (test_dir, db_path) = setup_db();
-
-//         let mut remote_plugins = HashMap::new();
-//         let data_class_one = PluginDataClass::new("Hello World", "Marius", "0.1.0")
-//             .with_remote_information("", "0.1.0", 0, "")
-//             .build();
-//         let data_class_two = PluginDataClass::new("PetStable", "Marius", "1.1")
-//             .with_remote_information("", "1.1", 0, "")
-//             .build();
-//         remote_plugins.insert(data_class_one.name.clone(), data_class_one.clone());
-//         remote_plugins.insert(data_class_two.name.clone(), data_class_two);
-
-//         let mut local_plugins = HashMap::new();
-//         let local_data_class_two = PluginDataClass::new("PetStable", "Marius", "1.0").build();
-
-//         local_plugins.insert(data_class_one.name.clone(), data_class_one);
-//         local_plugins.insert(
-//             local_data_class_two.name.clone(),
-//             local_data_class_two.clone(),
-//         );
-
-//         Synchronizer::update_local_plugins(&remote_plugins, &local_plugins, &db_path);
-
-//         let plugin = get_plugins(&db_path)
-//             .get(&local_data_class_two.name)
-//             .unwrap()
-//             .clone();
-
-//         assert_eq!(plugin.name, "PetStable");
-//         assert_eq!(plugin.latest_version.unwrap(), "1.1");
-
-//         teardown_db(&test_dir);
-//     }
-
-//     #[test]
-//     fn insert_not_existing_local_plugin() {
-//         let (test_dir, db_path) = setup_db();
-
-//         let mut remote_plugins = HashMap::new();
-//         let data_class_one = PluginDataClass::new("Hello World", "Marius", "0.1.0")
-//             .with_remote_information("", "0.1.0", 0, "")
-//             .build();
-//         let data_class_two = PluginDataClass::new("PetStable", "Marius", "1.1")
-//             .with_remote_information("", "1.1", 0, "")
-//             .build();
-//         remote_plugins.insert(data_class_one.name.clone(), data_class_one.clone());
-//         remote_plugins.insert(data_class_two.name.clone(), data_class_two);
-
-//         let mut local_plugins = HashMap::new();
-//         let local_data_class_two = PluginDataClass::new("PetStable", "Marius", "1.0").build();
-
-//         local_plugins.insert(data_class_one.name.clone(), data_class_one);
-//         local_plugins.insert(
-//             local_data_class_two.name.clone(),
-//             local_data_class_two.clone(),
-//         );
-
-//         Synchronizer::check_existing_plugins(&remote_plugins, &local_plugins, &db_path).unwrap();
-
-//         let plugin = get_plugins(&db_path)
-//             .get(&local_data_class_two.name)
-//             .unwrap()
-//             .clone();
-
-//         assert_eq!(plugin.name, "PetStable");
-//         assert_eq!(plugin.latest_version.unwrap(), "1.1");
-
-//         teardown_db(&test_dir);
-//     }
-
-//     #[test]
-//     fn delete_not_existing_plugin_from_db() {
-//         let (test_dir, db_path) = setup_db();
-
-//         let mut local_plugins = HashMap::new();
-//         let data_class_one = PluginDataClass::new("Hello World", "Marius", "0.1.0")
-//             .with_remote_information("", "0.1.0", 0, "")
-//             .build();
-//         let local_data_class_two = PluginDataClass::new("PetStable", "Marius", "1.0").build();
-//         let local_data_class_two_hash = local_data_class_two.name;
-//         local_plugins.insert(data_class_one.name.clone(), data_class_one);
-
-//         let db_plugins = get_plugins(&db_path);
-
-//         Synchronizer::delete_not_existing_local_plugins(&local_plugins, &db_plugins, &db_path);
-
-//         let plugins = get_plugins(&db_path);
-
-//         assert!(!plugins.contains_key(&local_data_class_two_hash));
-//         teardown_db(&test_dir);
-//     }
-
-// #[test]
-// fn not_existing_in_blacklist_positive() {
-//     let result = is_not_existing_in_blacklist("TitanBar");
-//     assert!(result);
-// }
-
-// #[test]
-// fn not_existing_in_blacklist_negative() {
-//     let result = is_not_existing_in_blacklist("DragBarDemoTwo");
-//     assert!(!result);
-// }
-
-//     type TemporaryPaths = (PathBuf, PathBuf);
-
-//     fn setup_db() -> TemporaryPaths {
-//         let uuid = Uuid::new_v4().to_string();
-//         let test_dir = env::temp_dir().join(format!("lembas_test_{}", &uuid[..7]));
-//         let db_path = test_dir.join("db.sqlite3");
-
-//         create_dir_all(&test_dir).unwrap();
-//         create_cache_db(&db_path).unwrap();
-
-//         let data_class = PluginDataClass::new("Hello World", "Marius", "0.1.0")
-//             .with_id(1)
-//             .with_description("Lorem ipsum")
-//             .build();
-//         insert_plugin(&data_class, &db_path).expect("Error while running test setup");
-
-//         let data_class = PluginDataClass::new("PetStable", "Marius", "1.0")
-//             .with_id(2)
-//             .with_description("Lorem ipsum")
-//             .with_remote_information("", "1.1", 0, "")
-//             .build();
-//         insert_plugin(&data_class, &db_path).expect("Error while running test setup");
-
-//         (test_dir, db_path)
-//     }
-
-//     fn teardown_db(test_dir: &Path) {
-//         remove_dir_all(test_dir).expect("Error while running test teardown");
-//     }
-// }
+//! # Synchronizer
+//!
+//! Brings the cache in line with the plugins directory. Plugins that were installed without lembas are
+//! discovered through their `.plugincompendium` and `.plugin` files and matched to the catalog. Cache rows of
+//! plugins whose files were removed by hand are deleted.
+use super::cache::DatabaseHandler;
+use super::compendium_parser::{parse_compendium_file, parse_plugin_file, parse_plugin_package};
+use super::file_comparer::compare_files;
+use super::plugin_collector::PluginCollector;
+use super::Cache;
+use crate::core::error::Error;
+use crate::core::plugin::UNMANAGED_SOURCE;
+use crate::core::Plugin;
+use log::debug;
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+const BLACKLIST: [&str; 3] = ["Demo", "AutoLoader", "Examples"];
+
+pub fn is_not_existing_in_blacklist(plugin_name: &str) -> bool {
+    for element in BLACKLIST {
+        if plugin_name.contains(element) {
+            return false;
+        }
+    }
+    true
+}
+
+/// A plugin that was found in the plugins directory.
+#[derive(Debug, Clone)]
+pub struct LocalPlugin {
+    pub plugin: Plugin,
+    /// Files and folders of the plugin relative to the plugins directory. Used as file manifest.
+    pub files: Vec<String>,
+}
+
+/// Names of the plugins that were changed during a synchronization.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SynchronizationReport {
+    /// Plugins without a catalog entry that were added as unmanaged plugins
+    pub added: Vec<String>,
+    /// Plugins that were matched to an existing cache row
+    pub matched: Vec<String>,
+    /// Cache rows that were deleted because the files of the plugin are gone
+    pub removed: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Synchronizer;
+
+impl Synchronizer {
+    /// Scans the plugins directory and updates the cache. Plugins are matched to the cache by their id and by
+    /// their name.
+    pub fn synchronize(cache: &Cache, plugins_dir: &Path) -> Result<SynchronizationReport, Error> {
+        let mut report = SynchronizationReport::default();
+        let mut found_ids = HashSet::new();
+
+        for local_plugin in Synchronizer::search_local(plugins_dir)? {
+            let plugin = &local_plugin.plugin;
+            let cached_plugin = match plugin.id {
+                id if id > 0 => cache.get_plugin_by_id(id)?,
+                _ => None,
+            };
+            let cached_plugin = match cached_plugin {
+                Some(cached_plugin) => Some(cached_plugin),
+                None => cache.get_plugin(&plugin.name)?,
+            };
+
+            let Some(cached_plugin) = cached_plugin else {
+                let plugin_id = if plugin.id > 0 {
+                    plugin.id
+                } else {
+                    cache.next_local_plugin_id()?
+                };
+                debug!("Found unmanaged plugin {}", plugin.name);
+
+                let unmanaged = plugin
+                    .clone()
+                    .with_id(plugin_id)
+                    .with_source(UNMANAGED_SOURCE);
+                cache.insert_plugin(&unmanaged, 1)?;
+                cache.save_plugin_files(plugin_id, &local_plugin.files)?;
+
+                found_ids.insert(plugin_id);
+                report.added.push(unmanaged.name);
+                continue;
+            };
+
+            found_ids.insert(cached_plugin.id);
+            if cached_plugin.is_local() {
+                continue;
+            }
+            if cached_plugin.installed == 0 {
+                debug!("Found catalog plugin {} on disk", cached_plugin.name);
+                cache.mark_as_installed(cached_plugin.id, &plugin.current_version)?;
+            }
+            if cache.get_plugin_files(cached_plugin.id)?.is_empty() {
+                cache.save_plugin_files(cached_plugin.id, &local_plugin.files)?;
+            }
+            report.matched.push(cached_plugin.name);
+        }
+
+        for plugin in cache.get_installed_plugins()?.values() {
+            if found_ids.contains(&plugin.id) {
+                continue;
+            }
+
+            let files = cache.get_plugin_files(plugin.id)?;
+            let exists = files
+                .iter()
+                .filter(|file| !file.ends_with('/'))
+                .any(|file| plugins_dir.join(file).exists());
+            if exists {
+                continue;
+            }
+
+            debug!("Removing plugin {} because its files are gone", plugin.name);
+            cache.delete_plugin(&plugin.name)?;
+            cache.delete_plugin_files(plugin.id)?;
+            report.removed.push(plugin.name.clone());
+        }
+
+        Ok(report)
+    }
+
+    /// Returns every plugin of the plugins directory. A `.plugincompendium` file together with the `.plugin`
+    /// files that start with its name form a single plugin. The remaining `.plugin` files are plugins on their
+    /// own. Plugins that can't be parsed are skipped.
+    pub fn search_local(plugins_dir: &Path) -> Result<Vec<LocalPlugin>, Error> {
+        if !plugins_dir.is_dir() {
+            return Err(Error::filesystem(
+                plugins_dir,
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the plugins directory doesn't exist",
+                ),
+            ));
+        }
+
+        let collector = PluginCollector::default();
+        let compendium_files = collector.collect_compendium_files(plugins_dir)?;
+        let plugin_files = collector.collect_plugin_files(plugins_dir)?;
+        let mut local_plugins = Vec::new();
+
+        for compendium_file in &compendium_files {
+            let descriptors =
+                Synchronizer::compendium_descriptors(plugins_dir, compendium_file, &plugin_files);
+            if descriptors.is_empty() {
+                debug!(
+                    "Skipping {} because its plugin files are missing",
+                    compendium_file.display()
+                );
+                continue;
+            }
+
+            match parse_compendium_file(compendium_file) {
+                Ok(plugin) => {
+                    let mut files = Synchronizer::collect_files(plugins_dir, &descriptors);
+                    files.extend(relative_name(plugins_dir, compendium_file));
+                    local_plugins.push(LocalPlugin {
+                        plugin,
+                        files: sorted(files),
+                    });
+                }
+                Err(error) => debug!("Can't parse {}: {}", compendium_file.display(), error),
+            }
+        }
+
+        for plugin_file in compare_files(&compendium_files, &plugin_files) {
+            match parse_plugin_file(&plugin_file) {
+                Ok(plugin) => {
+                    let files = Synchronizer::collect_files(plugins_dir, &[plugin_file]);
+                    local_plugins.push(LocalPlugin {
+                        plugin,
+                        files: sorted(files),
+                    });
+                }
+                Err(error) => debug!("Can't parse {}: {}", plugin_file.display(), error),
+            }
+        }
+
+        local_plugins
+            .retain(|local_plugin| is_not_existing_in_blacklist(&local_plugin.plugin.name));
+
+        Ok(local_plugins)
+    }
+
+    /// Returns the `.plugin` files of the author folder that start with the name of the compendium file.
+    fn compendium_descriptors(
+        plugins_dir: &Path,
+        compendium_file: &Path,
+        plugin_files: &[PathBuf],
+    ) -> Vec<PathBuf> {
+        let stem = file_stem(compendium_file);
+        let author = author_folder(plugins_dir, compendium_file);
+
+        plugin_files
+            .iter()
+            .filter(|plugin_file| {
+                file_stem(plugin_file).starts_with(&stem)
+                    && author_folder(plugins_dir, plugin_file) == author
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the descriptors together with the content of their plugin folders. The plugin folder is taken
+    /// from the `Package` of the descriptor.
+    fn collect_files(plugins_dir: &Path, descriptors: &[PathBuf]) -> Vec<String> {
+        let mut files = Vec::new();
+
+        for descriptor in descriptors {
+            files.extend(relative_name(plugins_dir, descriptor));
+
+            let package = match parse_plugin_package(descriptor) {
+                Ok(package) => package,
+                Err(error) => {
+                    debug!(
+                        "Can't read the package of {}: {}",
+                        descriptor.display(),
+                        error
+                    );
+                    continue;
+                }
+            };
+            let segments = package
+                .split(['.', '\\', '/'])
+                .filter(|segment| !segment.is_empty())
+                .take(2)
+                .collect::<Vec<&str>>();
+            let [author, folder] = segments[..] else {
+                continue;
+            };
+
+            let plugin_folder = plugins_dir.join(author).join(folder);
+            if !plugin_folder.is_dir() {
+                continue;
+            }
+            for entry in WalkDir::new(&plugin_folder)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(Result::ok)
+            {
+                if let Some(mut name) = relative_name(plugins_dir, entry.path()) {
+                    if entry.file_type().is_dir() {
+                        name.push('/');
+                    }
+                    files.push(name);
+                }
+            }
+        }
+
+        files
+    }
+}
+
+/// Returns the path relative to the plugins directory with `/` as separator.
+fn relative_name(plugins_dir: &Path, path: &Path) -> Option<String> {
+    let relative_path = path.strip_prefix(plugins_dir).ok()?;
+
+    Some(
+        relative_path
+            .iter()
+            .map(|component| component.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/"),
+    )
+}
+
+fn author_folder(plugins_dir: &Path, path: &Path) -> Option<String> {
+    relative_name(plugins_dir, path).and_then(|name| name.split('/').next().map(str::to_string))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn sorted(mut files: Vec<String>) -> Vec<String> {
+    files.sort();
+    files.dedup();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_existing_in_blacklist_positive() {
+        let result = is_not_existing_in_blacklist("TitanBar");
+        assert!(result);
+    }
+
+    #[test]
+    fn not_existing_in_blacklist_negative() {
+        let result = is_not_existing_in_blacklist("DragBarDemoTwo");
+        assert!(!result);
+    }
+}
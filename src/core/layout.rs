@@ -0,0 +1,324 @@
+//! # Layout
+//!
+//! Finds the plugin folders inside of an archive. Uploads on lotrointerface don't share a common layout. Some
+//! archives contain several author folders, ship a readme next to the author folder or wrap everything in a
+//! `Plugins` folder. The `.plugin` descriptors inside of the archive are used to find the folders that belong
+//! into the plugins directory.
+use log::debug;
+use serde::Deserialize;
+use serde_xml_rs::from_str;
+use std::io::{Read, Seek};
+use zip::{result::ZipError, ZipArchive};
+
+use super::archive::{validate_entry_name, UnsafeArchiveEntry};
+use super::error::Error;
+
+/// A single entry of the archive and the path it gets installed to, relative to the plugins directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutEntry {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArchiveLayout {
+    /// Names of the folders that get created in the plugins directory
+    pub roots: Vec<String>,
+    /// Entries of the archive that belong to one of the roots
+    pub entries: Vec<LayoutEntry>,
+    /// Files outside of every root, e.g. readme files next to the author folder
+    pub skipped: Vec<String>,
+    /// Information of every descriptor in the order of the archive
+    pub plugins: Vec<PluginInformation>,
+}
+
+/// The `Information` section of a `.plugin` descriptor.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct PluginInformation {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "Author", default)]
+    pub author: String,
+    #[serde(rename = "Version", default)]
+    pub version: String,
+    #[serde(rename = "Description", default)]
+    pub description: String,
+}
+
+/// A folder of the archive that gets installed as an author folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Root {
+    /// The descriptor is inside of the author folder. Everything below `prefix` is installed into `name`.
+    Folder { prefix: String, name: String },
+    /// The descriptor is not inside of an author folder. The descriptor and its plugin folder are installed
+    /// into `name`, which is taken from the `Package` of the descriptor.
+    Loose {
+        prefix: String,
+        descriptor: String,
+        folder: Option<String>,
+        name: String,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PluginDescriptor {
+    #[serde(rename = "Information", default)]
+    information: PluginInformation,
+    #[serde(rename = "Package", default)]
+    package: String,
+}
+
+/// Descriptors are small xml files. Everything above this size is not parsed.
+const MAX_DESCRIPTOR_SIZE: u64 = 1024 * 1024;
+
+impl ArchiveLayout {
+    /// Analyses the entries of the archive. Returns an error if the archive doesn't contain anything that can
+    /// be installed.
+    pub fn analyse<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Self, Error> {
+        let entries = entry_names(archive)?;
+        let names = entries
+            .iter()
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<String>>();
+
+        let mut roots = Vec::new();
+        let mut plugins = Vec::new();
+        for (index, name) in entries.iter().filter(|(_, name)| is_descriptor(name)) {
+            let descriptor = read_descriptor(archive, *index, name)?;
+            if !descriptor.information.name.is_empty() {
+                plugins.push(descriptor.information.clone());
+            }
+
+            let (parent, file_name) = name.rsplit_once('/').unwrap_or(("", name));
+            let is_wrapper = parent
+                .rsplit('/')
+                .next()
+                .is_some_and(|folder| folder.eq_ignore_ascii_case("plugins"));
+
+            let root = if parent.is_empty() || is_wrapper {
+                let mut segments = descriptor
+                    .package
+                    .split(['.', '\\', '/'])
+                    .filter(|segment| !segment.is_empty());
+                let Some(author) = segments.next().map(str::to_string) else {
+                    debug!("Skipping descriptor {} without a package", name);
+                    continue;
+                };
+                let prefix = prefix_of(parent);
+
+                Root::Loose {
+                    descriptor: name.clone(),
+                    folder: segments
+                        .next()
+                        .map(|folder| format!("{}{}/", prefix, folder)),
+                    prefix,
+                    name: author,
+                }
+            } else {
+                Root::Folder {
+                    prefix: prefix_of(parent),
+                    name: parent.rsplit('/').next().unwrap_or(parent).to_string(),
+                }
+            };
+
+            debug!("Found plugin root {:?} through {}", root, file_name);
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+
+        if roots.is_empty() {
+            debug!("No plugin descriptors found, installing every top level folder");
+            roots = top_level_folders(&names);
+        }
+
+        // Folders inside of another root belong to the outer plugin
+        let folder_prefixes = roots
+            .iter()
+            .filter_map(|root| match root {
+                Root::Folder { prefix, .. } => Some(prefix.clone()),
+                Root::Loose { .. } => None,
+            })
+            .collect::<Vec<String>>();
+        roots.retain(|root| match root {
+            Root::Folder { prefix, .. } => !folder_prefixes
+                .iter()
+                .any(|other| other != prefix && prefix.starts_with(other.as_str())),
+            Root::Loose { .. } => true,
+        });
+
+        let mut layout = Self {
+            plugins,
+            ..Self::default()
+        };
+        for name in &names {
+            match roots.iter().find_map(|root| root.target(name)) {
+                Some(target) => layout.entries.push(LayoutEntry {
+                    source: name.clone(),
+                    target,
+                }),
+                None if name.ends_with('/') => {}
+                None => layout.skipped.push(name.clone()),
+            }
+        }
+
+        for root in &roots {
+            let name = root.name().to_string();
+            if !layout.roots.contains(&name) {
+                layout.roots.push(name);
+            }
+        }
+
+        if layout.entries.is_empty() {
+            return Err(Error::invalid_archive(
+                "The archive doesn't contain a plugin",
+            ));
+        }
+
+        Ok(layout)
+    }
+
+    /// Paths of the installed entries relative to the plugins directory. Used as file manifest.
+    pub fn targets(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| entry.target.clone())
+            .collect()
+    }
+}
+
+impl Root {
+    fn name(&self) -> &str {
+        match self {
+            Self::Folder { name, .. } | Self::Loose { name, .. } => name,
+        }
+    }
+
+    /// Returns the install path of the entry if the entry belongs to this root.
+    fn target(&self, entry: &str) -> Option<String> {
+        match self {
+            Self::Folder { prefix, name } => entry
+                .strip_prefix(prefix.as_str())
+                .map(|rest| format!("{}/{}", name, rest)),
+            Self::Loose {
+                prefix,
+                descriptor,
+                folder,
+                name,
+            } => {
+                let belongs = entry == descriptor
+                    || folder
+                        .as_ref()
+                        .is_some_and(|folder| entry.starts_with(folder.as_str()));
+
+                if belongs {
+                    entry
+                        .strip_prefix(prefix.as_str())
+                        .map(|rest| format!("{}/{}", name, rest))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Returns the index and the normalized name of every entry in the order of the archive. Directories keep
+/// their trailing `/`.
+fn entry_names<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Vec<(usize, String)>, Error> {
+    let mut names = Vec::new();
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        let path = validate_entry_name(entry.name()).map_err(|reason| UnsafeArchiveEntry {
+            entry: entry.name().to_string(),
+            reason,
+        })?;
+
+        let mut name = path
+            .iter()
+            .map(|component| component.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/");
+        if name.is_empty() {
+            continue;
+        }
+        if entry.is_dir() {
+            name.push('/');
+        }
+
+        names.push((index, name));
+    }
+
+    Ok(names)
+}
+
+fn is_descriptor(name: &str) -> bool {
+    name.to_lowercase().ends_with(".plugin")
+}
+
+fn prefix_of(parent: &str) -> String {
+    if parent.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", parent)
+    }
+}
+
+/// Reads a `.plugin` descriptor. Descriptors that can't be parsed are treated as empty.
+fn read_descriptor<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    name: &str,
+) -> Result<PluginDescriptor, Error> {
+    let mut content = Vec::new();
+    archive
+        .by_index(index)?
+        .take(MAX_DESCRIPTOR_SIZE)
+        .read_to_end(&mut content)
+        .map_err(ZipError::Io)?;
+
+    match from_str::<PluginDescriptor>(&String::from_utf8_lossy(&content)) {
+        Ok(mut descriptor) => {
+            descriptor.package = descriptor.package.trim().to_string();
+            Ok(descriptor)
+        }
+        Err(error) => {
+            debug!("Can't parse the descriptor {}: {}", name, error);
+            Ok(PluginDescriptor::default())
+        }
+    }
+}
+
+/// Used for archives without descriptors. Every top level folder becomes a root, a `Plugins` wrapper is
+/// skipped.
+fn top_level_folders(names: &[String]) -> Vec<Root> {
+    let wrapped = names.iter().all(|name| {
+        name.split('/')
+            .next()
+            .is_some_and(|folder| folder.eq_ignore_ascii_case("plugins"))
+            && name.contains('/')
+    });
+
+    let mut roots = Vec::new();
+    for name in names {
+        let relative = if wrapped {
+            name.split_once('/').map_or("", |(_, rest)| rest)
+        } else {
+            name.as_str()
+        };
+
+        if let Some((folder, _)) = relative.split_once('/') {
+            let prefix = format!("{}{}/", &name[..name.len() - relative.len()], folder);
+            let root = Root::Folder {
+                prefix,
+                name: folder.to_string(),
+            };
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+    }
+
+    roots
+}
@@ -0,0 +1,268 @@
+//! # Lockfile
+//!
+//! A lockfile records the installed plugins of one player, e.g. the plugin set of a raid group. Importing a
+//! lockfile computes a plan that installs, updates and removes plugins until the installation matches the
+//! lockfile. Only versions that are offered by the feed can be installed, the feed has no older versions.
+use bytes::Bytes;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, fs, io, path::Path};
+
+use super::{
+    error::{Error, PathContext},
+    io::{cache::DatabaseHandler, ArchiveStore, Cache},
+    version::{compare_versions, VersionOrdering},
+    Installer, Plugin,
+};
+
+/// Version of the lockfile format. Lockfiles with a newer format are rejected.
+pub const LOCKFILE_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub format: u32,
+    pub plugins: Vec<LockedPlugin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPlugin {
+    /// Id of the plugin on lotrointerface
+    pub id: i32,
+    pub name: String,
+    pub version: String,
+    /// MD5 hash of the archive. Empty if the installed version is not the latest version of the feed.
+    #[serde(default)]
+    pub hash: String,
+}
+
+/// A locked plugin that can't be installed. Problems don't stop the import, they are reported instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockProblem {
+    /// The plugin isn't in the feed or was delisted
+    NotInFeed(LockedPlugin),
+    /// The feed offers another version or another archive than the locked one
+    VersionUnavailable {
+        plugin: LockedPlugin,
+        offered: String,
+    },
+}
+
+/// The changes that are needed to match a lockfile.
+#[derive(Debug, Default, Clone)]
+pub struct LockPlan {
+    pub install: Vec<Plugin>,
+    pub update: Vec<Plugin>,
+    /// Installed plugins that aren't part of the lockfile. Local plugins are never removed.
+    pub remove: Vec<Plugin>,
+    pub problems: Vec<LockProblem>,
+}
+
+/// Names of the plugins that were changed while a plan was applied.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LockReport {
+    pub installed: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// Names and errors of the plugins that couldn't be changed
+    pub failed: Vec<(String, String)>,
+}
+
+impl fmt::Display for LockProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotInFeed(plugin) => write!(
+                f,
+                "{} {} isn't offered by the feed anymore",
+                plugin.name, plugin.version
+            ),
+            Self::VersionUnavailable { plugin, offered } => write!(
+                f,
+                "{} {} isn't offered by the feed anymore, the feed has version {}",
+                plugin.name, plugin.version, offered
+            ),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Creates a lockfile of the installed plugins. Plugins that were installed from a local file are skipped
+    /// because they can't be installed on another machine.
+    pub fn export(cache: &Cache) -> Result<Self, Error> {
+        let mut plugins = cache
+            .get_installed_plugins()?
+            .into_values()
+            .filter(|plugin| !plugin.is_local())
+            .map(|plugin| LockedPlugin {
+                hash: if compare_versions(&plugin.latest_version, &plugin.current_version)
+                    == VersionOrdering::Same
+                {
+                    plugin.hash.clone()
+                } else {
+                    String::new()
+                },
+                id: plugin.id,
+                name: plugin.name,
+                version: plugin.current_version,
+            })
+            .collect::<Vec<LockedPlugin>>();
+        plugins.sort_by_key(|plugin| plugin.name.to_lowercase());
+
+        Ok(Self {
+            format: LOCKFILE_FORMAT,
+            plugins,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let lockfile: Self =
+            serde_json::from_str(&fs::read_to_string(path).at(path)?).map_err(|error| {
+                Error::config(format!(
+                    "The lockfile {} is invalid: {}",
+                    path.display(),
+                    error
+                ))
+            })?;
+        if lockfile.format > LOCKFILE_FORMAT {
+            return Err(Error::config(format!(
+                "Lockfile format {} isn't supported, update lembas to import it",
+                lockfile.format
+            )));
+        }
+
+        Ok(lockfile)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        serde_json::to_string_pretty(self)
+            .map_err(io::Error::from)
+            .and_then(|data| fs::write(path, data))
+            .at(path)
+    }
+
+    /// Compares the lockfile with the cache. Plugins are matched by their id and by their name.
+    pub fn plan(&self, cache: &Cache) -> Result<LockPlan, Error> {
+        let mut plan = LockPlan::default();
+        let mut locked_ids = HashSet::new();
+
+        for locked in &self.plugins {
+            let cached = match locked.id {
+                id if id > 0 => cache.get_plugin_by_id(id)?,
+                _ => None,
+            };
+            let cached = match cached {
+                Some(cached) => Some(cached),
+                None => cache.get_plugin(&locked.name)?,
+            };
+
+            let Some(cached) = cached else {
+                plan.problems.push(LockProblem::NotInFeed(locked.clone()));
+                continue;
+            };
+            locked_ids.insert(cached.id);
+
+            if cached.installed == 1
+                && compare_versions(&locked.version, &cached.current_version)
+                    == VersionOrdering::Same
+            {
+                continue;
+            }
+            if cached.is_local() || cached.download_url.is_empty() || cached.delisted == 1 {
+                plan.problems.push(LockProblem::NotInFeed(locked.clone()));
+                continue;
+            }
+
+            let other_archive =
+                !locked.hash.is_empty() && !cached.hash.is_empty() && locked.hash != cached.hash;
+            if compare_versions(&cached.latest_version, &locked.version) != VersionOrdering::Same
+                || other_archive
+            {
+                plan.problems.push(LockProblem::VersionUnavailable {
+                    plugin: locked.clone(),
+                    offered: cached.latest_version.clone(),
+                });
+                continue;
+            }
+
+            if cached.installed == 1 {
+                plan.update.push(cached);
+            } else {
+                plan.install.push(cached);
+            }
+        }
+
+        let mut extras = cache
+            .get_installed_plugins()?
+            .into_values()
+            .filter(|plugin| !plugin.is_local() && !locked_ids.contains(&plugin.id))
+            .collect::<Vec<Plugin>>();
+        extras.sort_by_key(|plugin| plugin.name.to_lowercase());
+        plan.remove = extras;
+
+        Ok(plan)
+    }
+}
+
+impl LockPlan {
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.update.is_empty() && self.remove.is_empty()
+    }
+
+    /// Removes the extra plugins first and installs the missing and outdated plugins afterwards. A plugin that
+    /// fails doesn't stop the other plugins. The installers don't back up the plugins directory, it's backed up
+    /// once before the plan is applied.
+    ///
+    /// The fetch function downloads the archive of a plugin with the given installer, e.g.
+    /// `Installer::download`.
+    pub fn apply<F>(
+        &self,
+        cache: &Cache,
+        tmp_dir: &Path,
+        plugins_dir: &Path,
+        archive_store: &ArchiveStore,
+        mut fetch: F,
+    ) -> LockReport
+    where
+        F: FnMut(&mut Installer, &Plugin) -> Result<Bytes, Error>,
+    {
+        let mut report = LockReport::default();
+
+        for plugin in &self.remove {
+            let mut installer = Installer::new(tmp_dir, plugins_dir, plugin.id, &plugin.name)
+                .with_archive_store(archive_store.clone());
+            let result = installer
+                .uninstall(cache, plugin.id)
+                .and_then(|()| cache.mark_as_uninstalled(&plugin.name));
+            match result {
+                Ok(()) => report.removed.push(plugin.name.clone()),
+                Err(error) => {
+                    debug!("Removal of {} failed: {}", plugin.name, error);
+                    report.failed.push((plugin.name.clone(), error.to_string()));
+                }
+            }
+        }
+
+        let changes = self
+            .install
+            .iter()
+            .map(|plugin| (plugin, false))
+            .chain(self.update.iter().map(|plugin| (plugin, true)));
+        for (plugin, update) in changes {
+            let mut installer = Installer::new(tmp_dir, plugins_dir, plugin.id, &plugin.name)
+                .with_archive_store(archive_store.clone())
+                .with_backup(false);
+            let result = fetch(&mut installer, plugin).and_then(|bytes| {
+                installer.install_archive(cache, plugin.id, &plugin.latest_version, &bytes)
+            });
+            match result {
+                Ok(()) if update => report.updated.push(plugin.name.clone()),
+                Ok(()) => report.installed.push(plugin.name.clone()),
+                Err(error) => {
+                    debug!("Installation of {} failed: {}", plugin.name, error);
+                    report.failed.push((plugin.name.clone(), error.to_string()));
+                }
+            }
+        }
+
+        report
+    }
+}
@@ -1,30 +1,288 @@
 use async_trait::async_trait;
+use chrono::Utc;
 use log::debug;
+use reqwest::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use std::time::Duration;
+
+use super::error::Error;
+use super::io::{cache::DatabaseHandler, feed_snapshot::SnapshotMetadata, Cache, FeedSnapshot};
+
+/// The `ETag` and `Last-Modified` headers of a downloaded feed. Empty values aren't sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeedValidators {
+    pub etag: String,
+    pub last_modified: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedResponse {
+    Modified {
+        content: String,
+        validators: FeedValidators,
+    },
+    /// The feed didn't change since the download with the sent validators
+    NotModified,
+}
 
 #[async_trait]
-pub trait Downloader {
-    async fn fetch_feed_content(url: String) -> Result<String, String>;
+pub trait Downloader: Send {
+    async fn fetch_feed_content(url: String) -> Result<String, Error>;
+
+    /// Downloads the feed unless it didn't change since the download with the given validators. Downloaders
+    /// without conditional requests always download the feed.
+    async fn fetch_feed_if_modified(
+        url: String,
+        _validators: FeedValidators,
+    ) -> Result<FeedResponse, Error> {
+        let content = Self::fetch_feed_content(url).await?;
+
+        Ok(FeedResponse::Modified {
+            content,
+            validators: FeedValidators::default(),
+        })
+    }
 }
 
 pub struct FeedDownloader;
 
 #[async_trait]
 impl Downloader for FeedDownloader {
-    async fn fetch_feed_content(url: String) -> Result<String, String> {
-        match reqwest::get(url).await {
-            Ok(response) => match response.text().await {
-                Ok(content) => Ok(content),
-                Err(err) => {
-                    debug!("{}", err);
-                    Err(String::new())
-                }
-            },
-            Err(err) => {
+    async fn fetch_feed_content(url: String) -> Result<String, Error> {
+        let response = reqwest::get(&url)
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        let content = match response {
+            Ok(response) => response.text().await,
+            Err(err) => Err(err),
+        };
+
+        content.map_err(|err| {
+            debug!("{}", err);
+            Error::network(&url, err)
+        })
+    }
+
+    async fn fetch_feed_if_modified(
+        url: String,
+        validators: FeedValidators,
+    ) -> Result<FeedResponse, Error> {
+        let mut request = reqwest::Client::new().get(&url);
+        if !validators.etag.is_empty() {
+            request = request.header(IF_NONE_MATCH, &validators.etag);
+        }
+        if !validators.last_modified.is_empty() {
+            request = request.header(IF_MODIFIED_SINCE, &validators.last_modified);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| {
                 debug!("{}", err);
-                Err(String::new())
+                Error::network(&url, err)
+            })?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(FeedResponse::NotModified);
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let validators = FeedValidators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        let content = response
+            .text()
+            .await
+            .map_err(|err| Error::network(&url, err))?;
+
+        Ok(FeedResponse::Modified {
+            content,
+            validators,
+        })
+    }
+}
+
+/// Where the plugins of a refresh come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedSource {
+    /// The feed was downloaded and saved as the new snapshot
+    Downloaded,
+    /// The server confirmed that the snapshot is up to date
+    NotModified,
+    /// The feed was checked within the refresh interval, the snapshot was used without asking the server
+    Skipped,
+    /// The feed couldn't be downloaded or parsed, the snapshot was used instead. Contains the error.
+    Offline(String),
+}
+
+/// The result of a feed refresh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedRefresh {
+    pub source: FeedSource,
+    /// Unix timestamp of the download of the used feed
+    pub fetched_at: i64,
+    /// Amount of plugins in the feed
+    pub plugins: usize,
+}
+
+impl FeedRefresh {
+    fn from_snapshot(source: FeedSource, metadata: &SnapshotMetadata) -> Self {
+        Self {
+            source,
+            fetched_at: metadata.fetched_at,
+            plugins: metadata.plugins,
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        matches!(self.source, FeedSource::Offline(_))
+    }
+}
+
+/// Downloads the feed and updates the catalog cache with its plugins. Every feed that can be parsed is saved as
+/// the snapshot. The download is conditional on the validators of the snapshot, and skipped entirely if the feed
+/// was checked within the minimum interval. A zero interval always asks the server. If the feed can't be
+/// downloaded or parsed, the cache is updated from the snapshot instead. Fails only if no snapshot exists.
+pub async fn refresh_feed<D: Downloader>(
+    cache: &Cache,
+    snapshot: &FeedSnapshot,
+    feed_url: &str,
+    min_interval: Duration,
+) -> Result<FeedRefresh, Error> {
+    // A snapshot of another feed URL is only good enough while offline
+    let previous = snapshot
+        .metadata()
+        .filter(|metadata| metadata.url == feed_url);
+
+    if let Some(metadata) = &previous {
+        let interval = i64::try_from(min_interval.as_secs()).unwrap_or(i64::MAX);
+        if Utc::now().timestamp() - metadata.checked_at < interval {
+            match sync_snapshot(cache, snapshot) {
+                Ok(Some(metadata)) => {
+                    return Ok(FeedRefresh::from_snapshot(FeedSource::Skipped, &metadata))
+                }
+                Ok(None) => {}
+                Err(error) => debug!("Can't use the feed snapshot: {}", error),
+            }
+        }
+    }
+
+    let validators = previous
+        .as_ref()
+        .map(SnapshotMetadata::validators)
+        .unwrap_or_default();
+    let response = match D::fetch_feed_if_modified(feed_url.to_string(), validators).await {
+        Ok(FeedResponse::NotModified) => match sync_snapshot(cache, snapshot) {
+            Ok(Some(metadata)) => {
+                // Without the new timestamp the next refresh only asks the server again
+                let metadata = match snapshot.mark_checked() {
+                    Ok(Some(checked)) => checked,
+                    Ok(None) => metadata,
+                    Err(error) => {
+                        debug!("Can't update the feed snapshot: {}", error);
+                        metadata
+                    }
+                };
+                return Ok(FeedRefresh::from_snapshot(
+                    FeedSource::NotModified,
+                    &metadata,
+                ));
+            }
+            // The whole feed is downloaded again if the snapshot is gone or broken
+            Ok(None) => {
+                D::fetch_feed_if_modified(feed_url.to_string(), FeedValidators::default()).await
+            }
+            Err(error) => {
+                debug!("Can't use the feed snapshot: {}", error);
+                D::fetch_feed_if_modified(feed_url.to_string(), FeedValidators::default()).await
             }
+        },
+        response => response,
+    };
+
+    let downloaded = response.and_then(|response| match response {
+        FeedResponse::Modified {
+            content,
+            validators,
+        } => FeedUrlParser::parse_response_xml(&content)
+            .map(|plugins| (content, validators, plugins)),
+        FeedResponse::NotModified => Err(Error::network(
+            feed_url,
+            "The server didn't send the feed of an unconditional request",
+        )),
+    });
+
+    match downloaded {
+        Ok((content, validators, plugins)) => {
+            cache.sync_plugins(&plugins)?;
+            let fetched_at = match snapshot.save(feed_url, &content, plugins.len(), &validators) {
+                Ok(metadata) => {
+                    cache.set_feed_fetched_at(metadata.fetched_at)?;
+                    metadata.fetched_at
+                }
+                Err(error) => {
+                    debug!("Can't save the feed snapshot: {}", error);
+                    Utc::now().timestamp()
+                }
+            };
+
+            Ok(FeedRefresh {
+                source: FeedSource::Downloaded,
+                fetched_at,
+                plugins: plugins.len(),
+            })
         }
+        Err(error) => match sync_snapshot(cache, snapshot) {
+            Ok(Some(metadata)) => {
+                debug!(
+                    "Using the feed snapshot of {}: {}",
+                    metadata.fetched_at, error
+                );
+                Ok(FeedRefresh::from_snapshot(
+                    FeedSource::Offline(error.to_string()),
+                    &metadata,
+                ))
+            }
+            Ok(None) => Err(error),
+            Err(snapshot_error) => {
+                debug!("Can't read the feed snapshot: {}", snapshot_error);
+                Err(error)
+            }
+        },
+    }
+}
+
+/// Updates the cache from the snapshot. The snapshot is only parsed if the cache contains the plugins of another
+/// feed. Returns `None` if no snapshot exists.
+fn sync_snapshot(
+    cache: &Cache,
+    snapshot: &FeedSnapshot,
+) -> Result<Option<SnapshotMetadata>, Error> {
+    let Some(metadata) = snapshot.metadata() else {
+        return Ok(None);
+    };
+    if cache.get_feed_fetched_at()? == Some(metadata.fetched_at) {
+        return Ok(Some(metadata));
     }
+
+    let Some((metadata, plugins)) = snapshot.load()? else {
+        return Ok(None);
+    };
+    cache.sync_plugins(&plugins)?;
+    cache.set_feed_fetched_at(metadata.fetched_at)?;
+
+    Ok(Some(metadata))
 }
 
 use serde::Deserialize;
@@ -36,9 +294,10 @@ use super::Plugin;
 pub struct FeedUrlParser;
 
 impl FeedUrlParser {
-    pub fn parse_response_xml(content: &str) -> Vec<Plugin> {
-        let favorites: Favorites = from_str(&content.replace('&', "&amp;")).unwrap();
-        convert_ui_to_plugin(&favorites.Ui)
+    pub fn parse_response_xml(content: &str) -> Result<Vec<Plugin>, Error> {
+        let favorites: Favorites =
+            from_str(&content.replace('&', "&amp;")).map_err(Error::FeedParse)?;
+        Ok(convert_ui_to_plugin(&favorites.Ui))
     }
 }
 
@@ -58,6 +317,7 @@ fn convert_ui_to_plugin(ui_collection: &[Ui]) -> Vec<Plugin> {
                 element.UIUpdated,
                 &element.UIMD5,
             )
+            .with_size(element.UISize)
             .build();
 
         plugins.push(plugin);
@@ -85,8 +345,9 @@ struct Ui {
     pub UIDescription: String,
     pub UIFile: String,
     #[serde(default)]
-    UIMD5: String,
-    UISize: i32,
+    pub UIMD5: String,
+    #[serde(default)]
+    pub UISize: i32,
     pub UIFileURL: String,
 }
 
@@ -100,8 +361,15 @@ mod tests {
         let xml_content: String =
             fs::read_to_string("tests/samples/xml_files/feed_url.xml").unwrap();
 
-        let feed_url = FeedUrlParser::parse_response_xml(&xml_content);
+        let feed_url = FeedUrlParser::parse_response_xml(&xml_content).unwrap();
 
         assert_eq!(feed_url.len(), 2);
     }
+
+    #[test]
+    fn parse_invalid_response() {
+        let result = FeedUrlParser::parse_response_xml("<html>Service unavailable</html>");
+
+        assert!(matches!(result, Err(Error::FeedParse(_))));
+    }
 }
@@ -1,9 +1,20 @@
+pub mod archive;
+pub mod backup;
 pub mod config;
+pub mod dependencies;
+pub mod discovery;
+pub mod error;
 pub mod installer;
 pub mod io;
+pub mod layout;
+pub mod lockfile;
 pub mod lotro_compendium;
 pub mod plugin;
+pub mod plugin_data;
+pub mod update;
+pub mod version;
 
 //pub use lotro_compendium::{Downloader, FeedDownloader};
-pub use installer::Installer;
+pub use error::Error;
+pub use installer::{CorruptDownload, InstallProgress, Installer};
 pub use plugin::Plugin;
@@ -1,3 +1,12 @@
+use crate::core::version::{offered_update, VersionOrdering};
+
+/// Plugins that were fetched from the feed of lotrointerface.
+pub const FEED_SOURCE: &str = "feed";
+/// Plugins that were installed from a local archive or folder. The synchronization never overwrites them.
+pub const LOCAL_SOURCE: &str = "local";
+/// Plugins that were found in the plugins directory without a matching catalog entry.
+pub const UNMANAGED_SOURCE: &str = "unmanaged";
+
 #[derive(Default, Debug, Clone)]
 pub struct Plugin {
     pub name: String,
@@ -14,6 +23,15 @@ pub struct Plugin {
     pub hash: String,
     pub download_url: String,
     pub info_url: String,
+    pub size: i32,
+    pub verified: i32,
+    pub source: String,
+    /// Set if the plugin disappeared from the feed
+    pub delisted: i32,
+    /// Set if the plugin was rolled back to an older version. Update all skips pinned plugins.
+    pub pinned: i32,
+    /// Updates to versions older than this version are ignored. Empty if every update is offered.
+    pub ignore_updates_until: String,
 }
 
 impl Plugin {
@@ -21,6 +39,7 @@ impl Plugin {
         Self {
             name: name.to_string(),
             installed: 0,
+            source: FEED_SOURCE.to_string(),
             ..Default::default()
         }
     }
@@ -64,12 +83,40 @@ impl Plugin {
         self
     }
 
+    pub fn with_size(mut self, size: i32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = source.to_string();
+        self
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.source == LOCAL_SOURCE
+    }
+
+    pub fn is_unmanaged(&self) -> bool {
+        self.source == UNMANAGED_SOURCE
+    }
+
+    /// Returns `Newer` if an update of the plugin should be offered, pinned and ignored updates are `Same`.
+    pub fn offered_update(&self) -> VersionOrdering {
+        offered_update(
+            &self.latest_version,
+            &self.current_version,
+            self.pinned == 1,
+            &self.ignore_updates_until,
+        )
+    }
+
     pub fn build(mut self) -> Self {
-        if self.id != 0 {
+        if self.id > 0 && !self.is_local() {
             let base_url = "http://www.lotrointerface.com/downloads/";
             self.info_url = format!("{}info{}", base_url, self.id);
             self.download_url = format!("{}download{}", base_url, self.id);
         }
         self
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,141 @@
+//! # Plugin data
+//!
+//! Plugins save their settings into the `PluginData` directory next to the plugins directory. The files are
+//! stored per scope, e.g. `PluginData/<account>/<server>/<character>/TitanBar.plugindata` for a character and
+//! `PluginData/<account>/AllServers/TitanBar.plugindata` for an account. A plugin names its data files itself,
+//! most plugins use their own name.
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use super::error::{Error, PathContext};
+
+/// Extension of the files that are written by `Turbine.PluginData.Save`.
+pub const PLUGIN_DATA_EXTENSION: &str = "plugindata";
+
+/// Returns the plugin data directory that belongs to the plugins directory.
+pub fn plugin_data_dir(plugins_dir: &Path) -> PathBuf {
+    plugins_dir.with_file_name("PluginData")
+}
+
+/// A character folder of the plugin data directory together with its data files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Character {
+    pub account: String,
+    pub server: String,
+    pub name: String,
+    /// Names of the data files of the character, e.g. `TitanBar.plugindata`
+    pub files: Vec<String>,
+}
+
+impl Character {
+    /// Returns the character as `<account>/<server>/<character>`, which is also its path relative to the plugin
+    /// data directory.
+    pub fn key(&self) -> String {
+        format!("{}/{}/{}", self.account, self.server, self.name)
+    }
+}
+
+impl fmt::Display for Character {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}, {})", self.name, self.server, self.account)
+    }
+}
+
+/// Returns every character of the plugin data directory sorted by their key. Characters without data files are
+/// included, so settings can be copied to a character that never ran a plugin.
+pub fn characters(plugin_data_dir: &Path) -> Vec<Character> {
+    let mut characters = Vec::new();
+
+    for account in subdirectories(plugin_data_dir) {
+        for server in subdirectories(&plugin_data_dir.join(&account)) {
+            for name in subdirectories(&plugin_data_dir.join(&account).join(&server)) {
+                let files = data_files(&plugin_data_dir.join(&account).join(&server).join(&name));
+                characters.push(Character {
+                    account: account.clone(),
+                    server: server.clone(),
+                    name,
+                    files,
+                });
+            }
+        }
+    }
+    characters.sort_by_key(Character::key);
+
+    characters
+}
+
+/// Looks up a character by its key. Names are compared case-insensitive.
+pub fn find_character(plugin_data_dir: &Path, key: &str) -> Result<Character, Error> {
+    let key = key.trim().trim_matches('/');
+
+    characters(plugin_data_dir)
+        .into_iter()
+        .find(|character| character.key().eq_ignore_ascii_case(key))
+        .ok_or_else(|| Error::config(format!("Character {} doesn't exist", key)))
+}
+
+/// Copies the data files of one character to another character. Existing files of the target are overwritten,
+/// files that only the target has are kept. Returns the names of the copied files.
+pub fn copy_character(
+    plugin_data_dir: &Path,
+    from: &Character,
+    to: &Character,
+) -> Result<Vec<String>, Error> {
+    if from.key() == to.key() {
+        return Err(Error::config(
+            "Can't copy the settings of a character to itself",
+        ));
+    }
+
+    let source_dir = plugin_data_dir.join(from.key());
+    let target_dir = plugin_data_dir.join(to.key());
+    fs::create_dir_all(&target_dir).at(&target_dir)?;
+
+    for file in &from.files {
+        debug!("Copying {} from {} to {}", file, from.key(), to.key());
+        let target = target_dir.join(file);
+        fs::copy(source_dir.join(file), &target).at(&target)?;
+    }
+
+    Ok(from.files.clone())
+}
+
+fn subdirectories(path: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+fn data_files(path: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    let mut files = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == PLUGIN_DATA_EXTENSION)
+        })
+        .filter_map(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .collect::<Vec<String>>();
+    files.sort();
+
+    files
+}
@@ -0,0 +1,164 @@
+//! # Update
+//!
+//! Updates several installed plugins at once. The plan lists the plugins with a newer version and the ones that
+//! are skipped, a single backup is created before the first plugin is updated and the summary collects the result
+//! of every update.
+use std::fmt;
+
+use serde::Serialize;
+
+use super::{
+    backup::{BackupManager, BackupManifest},
+    dependencies::{DependencyProblem, DependencyReport},
+    error::Error,
+    version::{compare_versions, VersionOrdering},
+    Plugin,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    Pinned,
+    /// Updates are ignored until the given version
+    Ignored(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pinned => write!(f, "pinned"),
+            Self::Ignored(version) => write!(f, "updates ignored until {}", version),
+        }
+    }
+}
+
+/// A plugin with a newer version that isn't updated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedUpdate {
+    pub name: String,
+    pub version: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UpdatePlan {
+    /// Plugins with a newer version, sorted by their name
+    pub plugins: Vec<Plugin>,
+    pub skipped: Vec<SkippedUpdate>,
+}
+
+impl UpdatePlan {
+    /// Plans the update of the given installed plugins. Local plugins and versions that can't be compared are
+    /// left out, they can be updated one by one.
+    pub fn new(installed_plugins: impl IntoIterator<Item = Plugin>) -> Self {
+        let mut plan = Self::default();
+
+        for plugin in installed_plugins {
+            if plugin.is_local()
+                || compare_versions(&plugin.latest_version, &plugin.current_version)
+                    != VersionOrdering::Newer
+            {
+                continue;
+            }
+
+            if plugin.offered_update() == VersionOrdering::Newer {
+                plan.plugins.push(plugin);
+            } else {
+                let reason = if plugin.pinned == 1 {
+                    SkipReason::Pinned
+                } else {
+                    SkipReason::Ignored(plugin.ignore_updates_until.clone())
+                };
+                plan.skipped.push(SkippedUpdate {
+                    name: plugin.name,
+                    version: plugin.latest_version,
+                    reason,
+                });
+            }
+        }
+        plan.plugins
+            .sort_by_key(|plugin| plugin.name.to_lowercase());
+        plan.skipped
+            .sort_by_key(|skipped| skipped.name.to_lowercase());
+
+        plan
+    }
+
+    /// Creates the single backup of the plugins directory with the given manager before the planned plugins are
+    /// updated. Nothing is backed up without a manager, e.g. because backups are disabled, or if nothing gets updated.
+    pub fn backup(&self, manager: Option<&BackupManager>) -> Result<Option<BackupManifest>, Error> {
+        match manager {
+            Some(manager) if !self.plugins.is_empty() => manager.create().map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UpdatedPlugin {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    /// Names of the dependencies that were installed together with the plugin
+    pub dependencies: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailedUpdate {
+    pub name: String,
+    pub error: String,
+}
+
+/// Result of an update of several plugins.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct UpdateSummary {
+    /// Name of the backup that was created before the update
+    pub backup: Option<String>,
+    pub updated: Vec<UpdatedPlugin>,
+    pub failed: Vec<FailedUpdate>,
+    pub skipped: Vec<SkippedUpdate>,
+}
+
+impl UpdateSummary {
+    pub fn new(plan: &UpdatePlan, backup: Option<&BackupManifest>) -> Self {
+        Self {
+            backup: backup.map(|manifest| manifest.name.clone()),
+            skipped: plan.skipped.clone(),
+            ..Self::default()
+        }
+    }
+
+    pub fn add_updated(&mut self, plugin: &Plugin, report: &DependencyReport) {
+        self.updated.push(UpdatedPlugin {
+            name: plugin.name.clone(),
+            from: plugin.current_version.clone(),
+            to: plugin.latest_version.clone(),
+            dependencies: report
+                .installed
+                .iter()
+                .filter(|name| **name != plugin.name)
+                .cloned()
+                .collect(),
+            problems: report
+                .problems
+                .iter()
+                .map(DependencyProblem::to_string)
+                .collect(),
+        });
+        self.updated
+            .sort_by_key(|updated| updated.name.to_lowercase());
+    }
+
+    pub fn add_failed(&mut self, plugin: &Plugin, error: &str) {
+        self.failed.push(FailedUpdate {
+            name: plugin.name.clone(),
+            error: error.to_string(),
+        });
+        self.failed.sort_by_key(|failed| failed.name.to_lowercase());
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
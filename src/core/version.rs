@@ -0,0 +1,340 @@
+//! # Version
+//!
+//! Plugin versions are free text on lotrointerface. Authors use `v1.24.45`, `8.3.5`, `1.0.1b`, dates like
+//! `2021-03-04` or no version at all, e.g. `by Habna`. Versions are parsed into one of these shapes before they
+//! are compared, versions of different shapes can't be compared.
+use std::cmp::Ordering;
+
+/// Prefixes that are removed in front of the first number.
+const PREFIXES: [&str; 5] = ["version", "ver", "rev", "v", "r"];
+/// Suffixes that mark a version before its release, e.g. `2.0 beta` is older than `2.0`.
+const PRE_RELEASES: [&str; 5] = ["alpha", "beta", "pre", "rc", "test"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Version {
+    /// Dotted numbers with an optional suffix, e.g. `v1.24.45` or `1.0.1b`
+    Numeric { numbers: Vec<u64>, suffix: String },
+    /// A release date, e.g. `2021-03-04`
+    Date { year: u32, month: u32, day: u32 },
+    /// Everything that can't be parsed. Contains the normalized text.
+    Unknown(String),
+}
+
+/// The result of a comparison between two versions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOrdering {
+    Newer,
+    Older,
+    #[default]
+    Same,
+    Incomparable,
+}
+
+impl Version {
+    pub fn parse(version: &str) -> Self {
+        let normalized = version.trim().to_lowercase();
+
+        let Some(start) = normalized.find(|c: char| c.is_ascii_digit()) else {
+            return Self::Unknown(normalized);
+        };
+        let prefix = normalized[..start].trim_end_matches(['.', ' ', ':']);
+        if !prefix.is_empty() && !PREFIXES.contains(&prefix) {
+            return Self::Unknown(normalized);
+        }
+        let text = &normalized[start..];
+
+        if let Some(date) = parse_date(text) {
+            return date;
+        }
+
+        let mut numbers = Vec::new();
+        let mut rest = text;
+        loop {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            match rest[..end].parse::<u64>() {
+                Ok(number) => numbers.push(number),
+                Err(_) => break,
+            }
+            rest = &rest[end..];
+
+            // Only a dot that is followed by another number continues the version
+            match rest.strip_prefix('.') {
+                Some(next) if next.starts_with(|c: char| c.is_ascii_digit()) => rest = next,
+                _ => break,
+            }
+        }
+
+        if numbers.is_empty() {
+            return Self::Unknown(normalized);
+        }
+
+        Self::Numeric {
+            numbers,
+            suffix: rest
+                .trim_start_matches(['.', '-', '_', ' '])
+                .trim()
+                .to_string(),
+        }
+    }
+
+    /// Returns how this version relates to the other version, e.g. `Newer` if this version is newer.
+    pub fn compare(&self, other: &Version) -> VersionOrdering {
+        let ordering = match (self, other) {
+            (
+                Self::Numeric { numbers, suffix },
+                Self::Numeric {
+                    numbers: other_numbers,
+                    suffix: other_suffix,
+                },
+            ) => compare_numbers(numbers, other_numbers)
+                .then_with(|| compare_suffixes(suffix, other_suffix)),
+            (
+                Self::Date { year, month, day },
+                Self::Date {
+                    year: other_year,
+                    month: other_month,
+                    day: other_day,
+                },
+            ) => (year, month, day).cmp(&(other_year, other_month, other_day)),
+            (Self::Unknown(text), Self::Unknown(other_text)) if text == other_text => {
+                Ordering::Equal
+            }
+            _ => return VersionOrdering::Incomparable,
+        };
+
+        match ordering {
+            Ordering::Greater => VersionOrdering::Newer,
+            Ordering::Less => VersionOrdering::Older,
+            Ordering::Equal => VersionOrdering::Same,
+        }
+    }
+}
+
+/// Compares the latest version of a plugin with its installed version. Returns `Newer` if an update is
+/// available.
+pub fn compare_versions(latest_version: &str, current_version: &str) -> VersionOrdering {
+    Version::parse(latest_version).compare(&Version::parse(current_version))
+}
+
+/// Returns how the latest version relates to the installed version if an update should be offered, otherwise
+/// `Same`. Pinned plugins are never offered an update. Updates to versions that are older than the ignored
+/// version are skipped, so a plugin can stay on a known-good version until a fixed release comes out.
+pub fn offered_update(
+    latest_version: &str,
+    current_version: &str,
+    pinned: bool,
+    ignore_until: &str,
+) -> VersionOrdering {
+    if latest_version.is_empty() || pinned {
+        return VersionOrdering::Same;
+    }
+    if !ignore_until.trim().is_empty()
+        && compare_versions(latest_version, ignore_until) == VersionOrdering::Older
+    {
+        return VersionOrdering::Same;
+    }
+
+    compare_versions(latest_version, current_version)
+}
+
+/// Parses dates in the `yyyy-mm-dd`, `yyyy.mm.dd` and `yyyy/mm/dd` formats.
+fn parse_date(text: &str) -> Option<Version> {
+    let parts = text.split(['-', '.', '/']).collect::<Vec<&str>>();
+    let [year, month, day] = parts[..] else {
+        return None;
+    };
+    if year.len() != 4 || month.len() > 2 || day.len() > 2 {
+        return None;
+    }
+
+    let (year, month, day) = (
+        year.parse::<u32>().ok()?,
+        month.parse::<u32>().ok()?,
+        day.parse::<u32>().ok()?,
+    );
+    if !(1990..=2100).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(Version::Date { year, month, day })
+}
+
+/// Compares dotted numbers. Missing numbers count as zero, so `1.2` and `1.2.0` are the same version.
+fn compare_numbers(numbers: &[u64], other_numbers: &[u64]) -> Ordering {
+    let length = numbers.len().max(other_numbers.len());
+
+    (0..length)
+        .map(|index| {
+            let number = numbers.get(index).unwrap_or(&0);
+            let other_number = other_numbers.get(index).unwrap_or(&0);
+            number.cmp(other_number)
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Pre-release suffixes are older than no suffix. A single letter like the `b` of `1.0.1b` marks a revision and is
+/// newer than no suffix. Other suffixes like `(U30)` or `fixed` are notes that don't change the version.
+fn compare_suffixes(suffix: &str, other_suffix: &str) -> Ordering {
+    suffix_rank(suffix).cmp(&suffix_rank(other_suffix))
+}
+
+/// Returns the rank of a suffix and the part of it that orders suffixes of the same rank.
+fn suffix_rank(suffix: &str) -> (u8, &str) {
+    if PRE_RELEASES
+        .iter()
+        .any(|pre_release| suffix.starts_with(pre_release))
+    {
+        (0, suffix)
+    } else if suffix.len() == 1 && suffix.chars().all(|c| c.is_ascii_alphabetic()) {
+        (2, suffix)
+    } else {
+        (1, "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod parse_tests {
+        use super::*;
+
+        #[test]
+        fn prefixed_version() {
+            assert_eq!(
+                Version::parse("v1.24.45"),
+                Version::Numeric {
+                    numbers: vec![1, 24, 45],
+                    suffix: String::new()
+                }
+            );
+            assert_eq!(Version::parse("Version 2.1"), Version::parse("2.1"));
+        }
+
+        #[test]
+        fn suffix_letter() {
+            assert_eq!(
+                Version::parse("1.0.1b"),
+                Version::Numeric {
+                    numbers: vec![1, 0, 1],
+                    suffix: "b".to_string()
+                }
+            );
+        }
+
+        #[test]
+        fn date() {
+            assert_eq!(
+                Version::parse("2021-03-04"),
+                Version::Date {
+                    year: 2021,
+                    month: 3,
+                    day: 4
+                }
+            );
+        }
+
+        #[test]
+        fn junk() {
+            assert_eq!(
+                Version::parse("by Habna"),
+                Version::Unknown("by habna".to_string())
+            );
+            assert_eq!(
+                Version::parse("Beta 2"),
+                Version::Unknown("beta 2".to_string())
+            );
+        }
+    }
+
+    mod compare_tests {
+        use super::*;
+
+        #[test]
+        fn newer() {
+            assert_eq!(
+                compare_versions("v1.24.45", "v1.24.9"),
+                VersionOrdering::Newer
+            );
+            assert_eq!(compare_versions("8.3.5", "8.3"), VersionOrdering::Newer);
+            assert_eq!(compare_versions("1.0.1b", "1.0.1a"), VersionOrdering::Newer);
+            assert_eq!(compare_versions("1.0.1b", "1.0.1"), VersionOrdering::Newer);
+            assert_eq!(
+                compare_versions("1.2b", "1.2 (U30)"),
+                VersionOrdering::Newer
+            );
+            assert_eq!(compare_versions("2.0", "2.0 beta"), VersionOrdering::Newer);
+            assert_eq!(
+                compare_versions("2021-03-04", "2020-12-24"),
+                VersionOrdering::Newer
+            );
+        }
+
+        #[test]
+        fn older() {
+            assert_eq!(compare_versions("1.9", "1.10"), VersionOrdering::Older);
+            assert_eq!(compare_versions("2.0 rc1", "2.0"), VersionOrdering::Older);
+            assert_eq!(
+                compare_versions("2.0 rc1", "2.0 rc2"),
+                VersionOrdering::Older
+            );
+            assert_eq!(
+                compare_versions("1.2-fixed", "1.2b"),
+                VersionOrdering::Older
+            );
+            assert_eq!(compare_versions("1.2 (U30)", "1.3"), VersionOrdering::Older);
+        }
+
+        #[test]
+        fn same() {
+            assert_eq!(compare_versions("v1.2", "1.2.0"), VersionOrdering::Same);
+            assert_eq!(
+                compare_versions("By Habna", "by habna"),
+                VersionOrdering::Same
+            );
+            assert_eq!(compare_versions("1.2 (U30)", "1.2"), VersionOrdering::Same);
+            assert_eq!(compare_versions("1.2-fixed", "1.2"), VersionOrdering::Same);
+            assert_eq!(
+                compare_versions("1.2-fixed", "1.2 (U30)"),
+                VersionOrdering::Same
+            );
+        }
+
+        #[test]
+        fn offered_update_respects_pins_and_ignored_versions() {
+            assert_eq!(
+                offered_update("1.2", "1.0", false, ""),
+                VersionOrdering::Newer
+            );
+            assert_eq!(
+                offered_update("1.2", "1.0", true, ""),
+                VersionOrdering::Same
+            );
+            assert_eq!(
+                offered_update("1.2", "1.0", false, "1.3"),
+                VersionOrdering::Same
+            );
+            assert_eq!(
+                offered_update("1.3", "1.0", false, "1.3"),
+                VersionOrdering::Newer
+            );
+            assert_eq!(offered_update("", "1.0", false, ""), VersionOrdering::Same);
+        }
+
+        #[test]
+        fn incomparable() {
+            assert_eq!(
+                compare_versions("1.2", "by Habna"),
+                VersionOrdering::Incomparable
+            );
+            assert_eq!(
+                compare_versions("2021-03-04", "1.2"),
+                VersionOrdering::Incomparable
+            );
+            assert_eq!(compare_versions("", "1.0"), VersionOrdering::Incomparable);
+        }
+    }
+}
@@ -1,2 +1,3 @@
+pub mod queue;
 pub mod style;
 pub mod views;
@@ -0,0 +1,359 @@
+//! # Queue
+//!
+//! Runs installations in the background, so the window keeps responding while archives are downloaded. The jobs
+//! start in the order they were queued, at most as many at once as the settings allow, and report their progress
+//! to the view that queued them.
+use std::{
+    collections::HashMap,
+    convert::identity,
+    fmt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use iced::pure::{button, column, progress_bar, row, text, Element};
+use iced::{Alignment, Command, Length};
+use log::debug;
+use tokio::sync::{mpsc, Mutex, Semaphore};
+use tokio::task;
+
+use crate::core::backup::BackupManager;
+use crate::core::config::{
+    get_archives_dir, get_plugins_dir, get_tmp_dir, read_existing_settings_file,
+};
+use crate::core::dependencies::DependencyReport;
+use crate::core::error::ArchiveError;
+use crate::core::installer::{backup_manager, ProgressListener};
+use crate::core::io::ArchiveStore;
+use crate::core::{Error, InstallProgress, Installer};
+use crate::gui::style;
+
+/// Installs, rolls back or removes a plugin with the given progress listener and cancel flag. Runs on a blocking
+/// thread.
+pub type Job =
+    Box<dyn FnOnce(ProgressListener, Arc<AtomicBool>) -> Result<DependencyReport, Error> + Send>;
+
+/// Directories of the profile that a job was queued for. They're resolved when the job is queued, so a job never
+/// works on the directories of another profile than the cache it writes to.
+#[derive(Debug, Clone)]
+pub struct JobDirs {
+    pub tmp_dir: PathBuf,
+    pub plugins_dir: PathBuf,
+    pub archive_store: ArchiveStore,
+    /// Backs up the plugins directory before an installation, `None` if backups are disabled
+    pub backup: Option<BackupManager>,
+}
+
+impl JobDirs {
+    /// Resolves the directories of the active profile.
+    pub fn current() -> Result<Self, Error> {
+        let plugins_dir = get_plugins_dir();
+        let backup = backup_manager(&plugins_dir, &read_existing_settings_file()?);
+
+        Ok(Self {
+            tmp_dir: get_tmp_dir(),
+            plugins_dir,
+            archive_store: ArchiveStore::new(&get_archives_dir()),
+            backup,
+        })
+    }
+
+    /// Creates an installer that keeps its archives in the archive store of the storage directory.
+    pub fn installer(&self, plugin_id: i32, plugin_title: &str) -> Installer {
+        Installer::new(&self.tmp_dir, &self.plugins_dir, plugin_id, plugin_title)
+            .with_archive_store(self.archive_store.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum QueueEvent {
+    Progress {
+        plugin_id: i32,
+        plugin: String,
+        progress: InstallProgress,
+    },
+    Finished {
+        plugin_id: i32,
+        result: Result<DependencyReport, JobFailure>,
+    },
+    /// The queue was dropped, its progress isn't listened to anymore
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobFailure {
+    Cancelled,
+    CorruptDownload,
+    /// Contains the readable message of the error
+    Failed(String),
+}
+
+impl JobFailure {
+    pub fn from_error(error: &Error) -> Self {
+        match error {
+            Error::Cancelled => Self::Cancelled,
+            Error::Archive(ArchiveError::Corrupt(_)) => Self::CorruptDownload,
+            error => Self::Failed(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for JobFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::CorruptDownload => write!(f, "the downloaded archive is corrupt"),
+            Self::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Progress of a queued job as it's shown below the plugin row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobStatus {
+    pub step: String,
+    /// Downloaded share of the archive in percent
+    pub percent: f32,
+}
+
+#[derive(Debug, Clone)]
+struct QueuedJob {
+    cancelled: Arc<AtomicBool>,
+    status: JobStatus,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstallQueue {
+    jobs: HashMap<i32, QueuedJob>,
+    /// Shared by every queue, so the views together never exceed the concurrency limit
+    slots: Arc<Semaphore>,
+    sender: mpsc::UnboundedSender<QueueEvent>,
+    receiver: Arc<Mutex<mpsc::UnboundedReceiver<QueueEvent>>>,
+    listening: bool,
+}
+
+impl InstallQueue {
+    /// Creates a queue that runs up to `concurrency` jobs at the same time.
+    pub fn new(concurrency: usize) -> Self {
+        Self::with_slots(Arc::new(Semaphore::new(concurrency.max(1))))
+    }
+
+    fn with_slots(slots: Arc<Semaphore>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        Self {
+            jobs: HashMap::new(),
+            slots,
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            listening: false,
+        }
+    }
+
+    /// Returns an empty queue for another view whose jobs wait for the jobs of this queue.
+    pub fn sibling(&self) -> Self {
+        Self::with_slots(self.slots.clone())
+    }
+
+    /// Whether a job is queued or running.
+    pub fn is_busy(&self) -> bool {
+        !self.jobs.is_empty()
+    }
+
+    pub fn status(&self, plugin_id: i32) -> Option<&JobStatus> {
+        self.jobs.get(&plugin_id).map(|job| &job.status)
+    }
+
+    /// Queues the installation of a plugin. A plugin that is already queued isn't queued again.
+    pub fn push(&mut self, plugin_id: i32, job: Job) -> Command<QueueEvent> {
+        if self.jobs.contains_key(&plugin_id) {
+            return Command::none();
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.jobs.insert(
+            plugin_id,
+            QueuedJob {
+                cancelled: cancelled.clone(),
+                status: JobStatus {
+                    step: "Queued".to_string(),
+                    percent: 0.0,
+                },
+            },
+        );
+
+        let run = Command::perform(
+            run(
+                self.slots.clone(),
+                self.sender.clone(),
+                plugin_id,
+                cancelled,
+                job,
+            ),
+            identity,
+        );
+        if self.listening {
+            run
+        } else {
+            self.listening = true;
+            Command::batch([run, self.listen()])
+        }
+    }
+
+    /// Cancels a queued or running job. The job finishes with `JobFailure::Cancelled`.
+    pub fn cancel(&mut self, plugin_id: i32) {
+        if let Some(job) = self.jobs.get_mut(&plugin_id) {
+            job.cancelled.store(true, Ordering::Relaxed);
+            job.status.step = "Cancelling".to_string();
+        }
+    }
+
+    /// Applies an event to the status of the jobs. Keeps listening to the progress as long as the queue exists.
+    pub fn update(&mut self, event: &QueueEvent) -> Command<QueueEvent> {
+        match event {
+            QueueEvent::Progress {
+                plugin_id,
+                plugin,
+                progress,
+            } => {
+                // Progress can arrive after the job already finished
+                if let Some(job) = self.jobs.get_mut(plugin_id) {
+                    if !job.cancelled.load(Ordering::Relaxed) {
+                        job.status = job_status(plugin, progress);
+                    }
+                }
+                self.listen()
+            }
+            QueueEvent::Finished { plugin_id, .. } => {
+                self.jobs.remove(plugin_id);
+                Command::none()
+            }
+            QueueEvent::Closed => {
+                self.listening = false;
+                Command::none()
+            }
+        }
+    }
+
+    fn listen(&self) -> Command<QueueEvent> {
+        let receiver = self.receiver.clone();
+
+        Command::perform(
+            async move {
+                receiver
+                    .lock()
+                    .await
+                    .recv()
+                    .await
+                    .unwrap_or(QueueEvent::Closed)
+            },
+            identity,
+        )
+    }
+}
+
+async fn run(
+    slots: Arc<Semaphore>,
+    sender: mpsc::UnboundedSender<QueueEvent>,
+    plugin_id: i32,
+    cancelled: Arc<AtomicBool>,
+    job: Job,
+) -> QueueEvent {
+    let Ok(_slot) = slots.acquire().await else {
+        return QueueEvent::Finished {
+            plugin_id,
+            result: Err(JobFailure::Failed(
+                "The install queue was closed".to_string(),
+            )),
+        };
+    };
+    if cancelled.load(Ordering::Relaxed) {
+        return QueueEvent::Finished {
+            plugin_id,
+            result: Err(JobFailure::Cancelled),
+        };
+    }
+
+    let listener: ProgressListener = Arc::new(move |plugin, progress| {
+        let event = QueueEvent::Progress {
+            plugin_id,
+            plugin: plugin.to_string(),
+            progress: progress.clone(),
+        };
+        if sender.send(event).is_err() {
+            debug!("Nobody listens to the progress of {}", plugin);
+        }
+    });
+    let result = task::spawn_blocking(move || {
+        job(listener, cancelled).map_err(|error| {
+            debug!("Installation of {} failed: {}", plugin_id, error);
+            JobFailure::from_error(&error)
+        })
+    })
+    .await
+    .unwrap_or_else(|error| {
+        debug!("Installation of {} panicked: {}", plugin_id, error);
+        Err(JobFailure::Failed(
+            "The installation stopped unexpectedly".to_string(),
+        ))
+    });
+
+    QueueEvent::Finished { plugin_id, result }
+}
+
+fn job_status(plugin: &str, progress: &InstallProgress) -> JobStatus {
+    match progress {
+        InstallProgress::Downloading {
+            received,
+            total: Some(total),
+        } if *total > 0 => JobStatus {
+            step: format!("Downloading {}", plugin),
+            percent: f32::from(u8::try_from((received * 100 / total).min(100)).unwrap_or(100)),
+        },
+        InstallProgress::Downloading { received, .. } => JobStatus {
+            step: format!("Downloading {} ({} KB)", plugin, received / 1024),
+            percent: 0.0,
+        },
+        InstallProgress::Extracting => JobStatus {
+            step: format!("Extracting {}", plugin),
+            percent: 100.0,
+        },
+        InstallProgress::Moving => JobStatus {
+            step: format!("Installing {}", plugin),
+            percent: 100.0,
+        },
+    }
+}
+
+/// Progress bar of a job with a button that cancels it.
+pub fn job_row<'a, Message: Clone + 'a>(
+    status: &JobStatus,
+    cancel: Message,
+) -> Element<'a, Message> {
+    let progress = column()
+        .width(Length::Fill)
+        .spacing(2)
+        .push(text(&status.step).size(14))
+        .push(
+            progress_bar(0.0..=100.0, status.percent)
+                .height(Length::Units(6))
+                .style(style::ProgressBar),
+        );
+
+    row()
+        .width(Length::Fill)
+        .padding([0, 10])
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(progress)
+        .push(
+            button(text("Cancel"))
+                .on_press(cancel)
+                .padding(5)
+                .style(style::PrimaryButton::Enabled),
+        )
+        .into()
+}
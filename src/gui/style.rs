@@ -1,5 +1,5 @@
 use iced::pure::widget::scrollable;
-use iced::{button, container, Background, Color, Vector};
+use iced::{button, container, progress_bar, Background, Color, Vector};
 // 331C12
 pub const BORDER_COLOR: Color = Color::from_rgb(0.32, 0.41, 0.05);
 pub const BUTTON_COLOR_DEFAULT: Color = Color::from_rgb(0.39, 0.48, 0.1);
@@ -201,3 +201,14 @@ impl button::StyleSheet for InstallButton {
         }
     }
 }
+
+pub struct ProgressBar;
+impl progress_bar::StyleSheet for ProgressBar {
+    fn style(&self) -> progress_bar::Style {
+        progress_bar::Style {
+            background: Background::Color(NAVIGATION_COLOR),
+            bar: Background::Color(BUTTON_COLOR_DEFAULT),
+            border_radius: 2.0,
+        }
+    }
+}
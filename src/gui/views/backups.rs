@@ -0,0 +1,485 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::core::backup::{format_size, format_timestamp, BackupManager, BackupManifest};
+use crate::core::config::{
+    get_plugin_data_dir, get_plugins_backup_dir, get_plugins_dir, read_existing_settings_file,
+};
+use crate::core::error::Error;
+use crate::core::io::cache::Cache;
+use crate::core::plugin_data::{self, Character};
+use crate::gui::style;
+use iced::pure::{button, column, container, pick_list, row, scrollable, text, Element};
+use iced::{Alignment, Command, Length, Space};
+use log::debug;
+
+#[derive(Debug, Clone)]
+pub struct Backups {
+    cache: Arc<Cache>,
+    status: String,
+    /// Keys of the characters with plugin settings
+    characters: Vec<String>,
+    copy_from: Option<String>,
+    copy_to: Option<String>,
+    pub backups: Vec<BackupRow>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    LoadBackups,
+    CreatePressed,
+    CopyFromSelected(String),
+    CopyToSelected(String),
+    CopySettingsPressed,
+    Backup(usize, RowMessage),
+}
+
+impl Backups {
+    pub fn new(cache: Arc<Cache>) -> Self {
+        let mut backups = Self {
+            cache,
+            status: String::new(),
+            characters: Vec::new(),
+            copy_from: None,
+            copy_to: None,
+            backups: Vec::new(),
+        };
+        backups.reload();
+        backups
+    }
+
+    /// Returns the backup manager with the retention policy of the settings.
+    fn manager() -> Result<BackupManager, Error> {
+        let (keep_last, max_age) = read_existing_settings_file()?.backup_retention();
+
+        Ok(
+            BackupManager::new(&get_plugins_backup_dir(), &get_plugins_dir())
+                .with_plugin_data_dir(&get_plugin_data_dir())
+                .with_retention(keep_last, max_age),
+        )
+    }
+
+    fn reload(&mut self) {
+        self.backups = match Backups::manager() {
+            Ok(manager) => manager.backups().into_iter().map(BackupRow::new).collect(),
+            Err(error) => {
+                self.status = error.to_string();
+                Vec::new()
+            }
+        };
+        self.characters = plugin_data::characters(&get_plugin_data_dir())
+            .iter()
+            .map(Character::key)
+            .collect();
+    }
+
+    fn copy_settings(&self) -> Result<usize, Error> {
+        let (Some(from), Some(to)) = (&self.copy_from, &self.copy_to) else {
+            return Err(Error::config("Select both characters first"));
+        };
+        let plugin_data_dir = get_plugin_data_dir();
+        let from = plugin_data::find_character(&plugin_data_dir, from)?;
+        let to = plugin_data::find_character(&plugin_data_dir, to)?;
+
+        Ok(plugin_data::copy_character(&plugin_data_dir, &from, &to)?.len())
+    }
+
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::LoadBackups => {
+                self.status = String::new();
+                self.reload();
+                Command::none()
+            }
+            Message::CopyFromSelected(character) => {
+                self.copy_from = Some(character);
+                Command::none()
+            }
+            Message::CopyToSelected(character) => {
+                self.copy_to = Some(character);
+                Command::none()
+            }
+            Message::CopySettingsPressed => {
+                match self.copy_settings() {
+                    Ok(copied) => {
+                        self.status = format!("Copied {} settings files", copied);
+                        self.reload();
+                    }
+                    Err(error) => {
+                        debug!("Copying the settings failed: {}", error);
+                        self.status = error.to_string();
+                    }
+                }
+                Command::none()
+            }
+            Message::CreatePressed => {
+                match Backups::manager().and_then(|manager| manager.create()) {
+                    Ok(manifest) => {
+                        self.status = format!("Created {}", manifest.name);
+                    }
+                    Err(error) => {
+                        debug!("Backup failed: {}", error);
+                        self.status = format!("Backup failed: {}", error);
+                    }
+                }
+                self.reload();
+                Command::none()
+            }
+            Message::Backup(index, msg) => {
+                let event = self.backups[index].update(msg, &self.cache);
+                if let Event::Reload(status) = event {
+                    self.status = status;
+                    self.reload();
+                }
+                Command::none()
+            }
+        }
+    }
+
+    fn build_header(status: &str, backups_amount: usize) -> Element<'static, Message> {
+        let create_button = button(text("Create backup"))
+            .on_press(Message::CreatePressed)
+            .padding(5)
+            .style(style::PrimaryButton::Enabled);
+
+        row()
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(create_button)
+            .push(text(format!("{} backups", backups_amount)))
+            .push(Space::new(Length::Fill, Length::Shrink))
+            .push(text(status))
+            .into()
+    }
+
+    fn build_copy_row(&self) -> Element<'_, Message> {
+        let copy_button = button(text("Copy settings"))
+            .on_press(Message::CopySettingsPressed)
+            .padding(5)
+            .style(style::PrimaryButton::Enabled);
+
+        row()
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(text("Copy character settings from"))
+            .push(
+                pick_list(
+                    &self.characters[..],
+                    self.copy_from.clone(),
+                    Message::CopyFromSelected,
+                )
+                .padding(5),
+            )
+            .push(text("to"))
+            .push(
+                pick_list(
+                    &self.characters[..],
+                    self.copy_to.clone(),
+                    Message::CopyToSelected,
+                )
+                .padding(5),
+            )
+            .push(copy_button)
+            .into()
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let header = Backups::build_header(&self.status, self.backups.len());
+        let copy_row = self.build_copy_row();
+
+        let backup_panel = row()
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .padding([0, 13, 0, 0])
+            .push(text("Backup").width(Length::FillPortion(6)))
+            .push(text("Created").width(Length::FillPortion(3)))
+            .push(text("Size").width(Length::FillPortion(2)))
+            .push(text("Plugins").width(Length::FillPortion(2)));
+
+        let backups = self
+            .backups
+            .iter()
+            .enumerate()
+            .fold(column().padding([0, 13, 0, 0]).spacing(5), |col, (i, b)| {
+                col.push(b.view().map(move |msg| Message::Backup(i, msg)))
+            });
+
+        let backups_scrollable = scrollable(backups)
+            .scrollbar_width(10)
+            .style(style::Scrollable);
+
+        let content = column()
+            .width(Length::Fill)
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(header)
+            .push(copy_row)
+            .push(backup_panel)
+            .push(backups_scrollable);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(20)
+            .style(style::Content)
+            .into()
+    }
+}
+
+// Single backup that has a toggle effect to show its plugins
+#[derive(Debug, Clone)]
+pub struct BackupRow {
+    pub manifest: BackupManifest,
+    pub status: String,
+    opened: bool,
+    restore_confirmed: bool,
+    delete_confirmed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum RowMessage {
+    ToggleView,
+    RestorePressed,
+    RestorePluginPressed(String),
+    /// Restores the plugin settings of the character with the given key
+    RestoreCharacterPressed(String),
+    /// Restores the plugin settings file with the given name for every character
+    RestoreDataFilePressed(String),
+    DeletePressed,
+}
+
+pub enum Event {
+    Nothing,
+    /// The backups changed, contains the status for the header
+    Reload(String),
+}
+
+impl BackupRow {
+    pub fn new(manifest: BackupManifest) -> Self {
+        Self {
+            manifest,
+            status: String::new(),
+            opened: false,
+            restore_confirmed: false,
+            delete_confirmed: false,
+        }
+    }
+
+    pub fn update(&mut self, message: RowMessage, cache: &Cache) -> Event {
+        let name = self.manifest.name.clone();
+
+        match message {
+            RowMessage::ToggleView => {
+                self.opened = !self.opened;
+                self.restore_confirmed = false;
+                self.delete_confirmed = false;
+                Event::Nothing
+            }
+            // Restoring replaces every plugin, so it needs a second press
+            RowMessage::RestorePressed if !self.restore_confirmed => {
+                self.restore_confirmed = true;
+                self.status = "Every installed plugin is replaced".to_string();
+                Event::Nothing
+            }
+            RowMessage::RestorePressed => {
+                self.restore_confirmed = false;
+                match Backups::manager().and_then(|manager| manager.restore(cache, &name)) {
+                    Ok(()) => Event::Reload(format!("Restored {}", name)),
+                    Err(error) => {
+                        debug!("Restore of {} failed: {}", name, error);
+                        self.status = format!("Restore failed: {}", error);
+                        Event::Nothing
+                    }
+                }
+            }
+            RowMessage::RestorePluginPressed(plugin) => {
+                let result = Backups::manager()
+                    .and_then(|manager| manager.restore_plugin(cache, &name, &plugin));
+                match result {
+                    Ok(()) => {
+                        self.status = format!("Restored {}", plugin);
+                    }
+                    Err(error) => {
+                        debug!("Restore of {} failed: {}", plugin, error);
+                        self.status = format!("Restore of {} failed: {}", plugin, error);
+                    }
+                }
+                Event::Nothing
+            }
+            RowMessage::RestoreCharacterPressed(character) => {
+                let result = Backups::manager()
+                    .and_then(|manager| manager.restore_plugin_data(&name, Some(&character), None));
+                self.data_restored(&character, result);
+                Event::Nothing
+            }
+            RowMessage::RestoreDataFilePressed(file) => {
+                let result = Backups::manager()
+                    .and_then(|manager| manager.restore_plugin_data(&name, None, Some(&file)));
+                self.data_restored(&file, result);
+                Event::Nothing
+            }
+            RowMessage::DeletePressed if !self.delete_confirmed => {
+                self.delete_confirmed = true;
+                self.status = String::new();
+                Event::Nothing
+            }
+            RowMessage::DeletePressed => {
+                match Backups::manager().and_then(|manager| manager.delete(&name)) {
+                    Ok(()) => Event::Reload(format!("Deleted {}", name)),
+                    Err(error) => {
+                        debug!("Deletion of {} failed: {}", name, error);
+                        self.delete_confirmed = false;
+                        self.status = format!("Delete failed: {}", error);
+                        Event::Nothing
+                    }
+                }
+            }
+        }
+    }
+
+    fn data_restored(&mut self, target: &str, result: Result<Vec<String>, Error>) {
+        self.status = match result {
+            Ok(files) => format!("Restored {} settings files of {}", files.len(), target),
+            Err(error) => {
+                debug!("Restore of the settings of {} failed: {}", target, error);
+                format!("Restore of the settings of {} failed: {}", target, error)
+            }
+        };
+    }
+
+    /// Returns the names of the settings files of every character of the backup.
+    fn data_files(&self) -> BTreeSet<&String> {
+        self.manifest
+            .characters
+            .iter()
+            .flat_map(|character| &character.files)
+            .collect()
+    }
+
+    pub fn view(&self) -> Element<'_, RowMessage> {
+        let summary = button(
+            row()
+                .align_items(Alignment::Center)
+                .push(text(&self.manifest.name).width(Length::FillPortion(6)))
+                .push(
+                    text(format_timestamp(self.manifest.created_at)).width(Length::FillPortion(3)),
+                )
+                .push(text(format_size(self.manifest.size)).width(Length::FillPortion(2)))
+                .push(text(self.manifest.plugins.len().to_string()).width(Length::FillPortion(2))),
+        )
+        .on_press(RowMessage::ToggleView)
+        .style(style::PluginRow::Enabled);
+
+        if !self.opened {
+            return column().push(summary).into();
+        }
+
+        let plugins = self.manifest.plugins.iter().fold(
+            column().spacing(5).width(Length::Fill),
+            |col, plugin| {
+                col.push(
+                    row()
+                        .align_items(Alignment::Center)
+                        .spacing(10)
+                        .push(text(&plugin.name).width(Length::FillPortion(6)))
+                        .push(text(&plugin.version).width(Length::FillPortion(3)))
+                        .push(
+                            button(text("Restore"))
+                                .padding(5)
+                                .on_press(RowMessage::RestorePluginPressed(plugin.name.clone()))
+                                .style(style::PrimaryButton::Enabled),
+                        ),
+                )
+            },
+        );
+
+        let restore_btn = button(text(if self.restore_confirmed {
+            "Restore anyway"
+        } else {
+            "Restore all"
+        }))
+        .padding(5)
+        .on_press(RowMessage::RestorePressed)
+        .style(style::PrimaryButton::Enabled);
+
+        let delete_btn = button(text(if self.delete_confirmed {
+            "Delete anyway"
+        } else {
+            "Delete"
+        }))
+        .padding(5)
+        .on_press(RowMessage::DeletePressed)
+        .style(style::PrimaryButton::Enabled);
+
+        let button_row = row()
+            .push(text(&self.status))
+            .push(Space::new(Length::Fill, Length::Shrink))
+            .push(restore_btn)
+            .push(delete_btn)
+            .width(Length::Fill)
+            .spacing(10)
+            .align_items(Alignment::End);
+
+        let mut toggle_section = column().spacing(10).push(text("Plugins")).push(plugins);
+
+        if !self.manifest.characters.is_empty() {
+            let characters = self.manifest.characters.iter().fold(
+                column().spacing(5).width(Length::Fill),
+                |col, character| {
+                    col.push(
+                        row()
+                            .align_items(Alignment::Center)
+                            .spacing(10)
+                            .push(text(character.to_string()).width(Length::FillPortion(6)))
+                            .push(
+                                text(format!("{} files", character.files.len()))
+                                    .width(Length::FillPortion(3)),
+                            )
+                            .push(
+                                button(text("Restore settings"))
+                                    .padding(5)
+                                    .on_press(RowMessage::RestoreCharacterPressed(character.key()))
+                                    .style(style::PrimaryButton::Enabled),
+                            ),
+                    )
+                },
+            );
+            let data_files = self.data_files().into_iter().fold(
+                column().spacing(5).width(Length::Fill),
+                |col, file| {
+                    col.push(
+                        row()
+                            .align_items(Alignment::Center)
+                            .spacing(10)
+                            .push(text(file).width(Length::FillPortion(9)))
+                            .push(
+                                button(text("Restore settings"))
+                                    .padding(5)
+                                    .on_press(RowMessage::RestoreDataFilePressed(file.clone()))
+                                    .style(style::PrimaryButton::Enabled),
+                            ),
+                    )
+                },
+            );
+
+            toggle_section = toggle_section
+                .push(text("Character settings"))
+                .push(characters)
+                .push(text("Plugin settings of every character"))
+                .push(data_files);
+        }
+        let toggle_section = toggle_section.push(button_row);
+
+        column()
+            .push(summary)
+            .push(
+                container(toggle_section)
+                    .width(Length::Fill)
+                    .padding(15)
+                    .style(style::NavigationContainer),
+            )
+            .into()
+    }
+}
@@ -1,34 +1,86 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::core::config::get_plugins_dir;
+use crate::core::config::{get_storage_dir, read_existing_settings_file};
+use crate::core::dependencies::{DependencyReport, DependencyResolver};
+use crate::core::io::cache::Cache;
 use crate::core::io::cache::DatabaseHandler;
-use crate::core::Plugin;
-use crate::core::{config::get_tmp_dir, io::cache::Cache, Installer};
+use crate::core::io::feed_snapshot::format_age;
+use crate::core::io::FeedSnapshot;
+use crate::core::lotro_compendium::{refresh_feed, FeedDownloader, FeedRefresh, FeedSource};
+use crate::core::version::{offered_update, VersionOrdering};
+use crate::core::{Error, Installer, Plugin};
+use crate::gui::queue::{job_row, InstallQueue, Job, JobDirs, JobFailure, JobStatus, QueueEvent};
 use crate::gui::style;
+use chrono::Utc;
 use iced::pure::{button, column, container, row, scrollable, text, text_input, Element};
-use iced::{
-    alignment::{Horizontal, Vertical},
-    Alignment, Command, Length,
-};
+use iced::{alignment::Horizontal, Alignment, Command, Length};
 use log::debug;
 
 #[derive(Debug, Clone)]
 pub enum Catalog {
     Loaded(State),
+    /// The feed can't be downloaded, the plugins of the last downloaded feed are shown
     NoInternet(State),
 }
 
 impl Catalog {
-    pub fn new(cache: Arc<Cache>) -> Self {
+    pub fn new(cache: Arc<Cache>, queue: InstallQueue) -> Self {
         let state = State {
             cache,
             base_plugins: Vec::new(),
             plugins: Vec::new(),
             input_value: String::new(),
+            queue,
+            fetched_at: None,
+            feed_error: String::new(),
+            refreshing: false,
         };
 
         Self::Loaded(state)
     }
+
+    /// Downloads the feed and shows its plugins. The last downloaded feed is used if the download fails. A forced
+    /// refresh asks the server even if the feed was checked within the refresh interval.
+    pub fn refresh(&mut self, force: bool) -> Command<Message> {
+        let state = self.state_mut();
+        if state.refreshing {
+            return Command::none();
+        }
+        state.refreshing = true;
+
+        Command::perform(
+            Catalog::fetch_feed(state.cache.clone(), force),
+            Message::FeedRefreshed,
+        )
+    }
+
+    /// Whether a refresh is running, a new one is ignored until it finished.
+    pub fn is_refreshing(&self) -> bool {
+        match self {
+            Catalog::Loaded(state) | Catalog::NoInternet(state) => state.refreshing,
+        }
+    }
+
+    async fn fetch_feed(cache: Arc<Cache>, force: bool) -> Result<FeedRefresh, String> {
+        let settings = read_existing_settings_file().map_err(|error| error.to_string())?;
+        let min_interval = if force {
+            Duration::ZERO
+        } else {
+            settings.feed_refresh_interval()
+        };
+        let snapshot = FeedSnapshot::new(&get_storage_dir());
+
+        refresh_feed::<FeedDownloader>(&cache, &snapshot, &settings.feed_url, min_interval)
+            .await
+            .map_err(|error| error.to_string())
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        match self {
+            Catalog::Loaded(state) | Catalog::NoInternet(state) => state,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -37,13 +89,21 @@ pub struct State {
     pub input_value: String,
     pub base_plugins: Vec<PluginRow>,
     pub plugins: Vec<PluginRow>,
+    queue: InstallQueue,
+    /// Unix timestamp of the download of the shown feed
+    fetched_at: Option<i64>,
+    /// Why the last refresh couldn't download the feed
+    feed_error: String,
+    refreshing: bool,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     CatalogInputChanged(String),
     Catalog(usize, RowMessage),
+    Queue(QueueEvent),
     LoadPlugins,
+    FeedRefreshed(Result<FeedRefresh, String>),
     RetryPressed,
 }
 
@@ -58,6 +118,7 @@ impl Catalog {
                     &element.current_version,
                     &element.latest_version,
                 )
+                .with_update(element.offered_update())
             })
             .collect();
 
@@ -65,63 +126,183 @@ impl Catalog {
         rows
     }
 
-    pub fn update(&mut self, message: Message) -> Command<Message> {
+    /// Queues the installation of the plugin behind the row together with its missing dependencies.
+    fn queue_install(state: &mut State, row: &PluginRow) -> Command<Message> {
+        let cache = state.cache.clone();
+        let title = row.title.clone();
+        let dirs = match JobDirs::current() {
+            Ok(dirs) => dirs,
+            Err(error) => {
+                Catalog::finish_install(state, row.id, &Err(JobFailure::from_error(&error)));
+                return Command::none();
+            }
+        };
+        let job: Job = Box::new(move |listener, cancelled| {
+            let plugin = cache.get_plugin(&title)?.ok_or_else(|| {
+                Error::plugin(format!("Plugin {} doesn't exist in the cache", title))
+            })?;
+
+            DependencyResolver::new(
+                &cache,
+                &dirs.tmp_dir,
+                &dirs.plugins_dir,
+                Installer::download,
+            )
+            .with_archive_store(dirs.archive_store.clone())
+            .with_backup(dirs.backup)
+            .with_progress(listener)
+            .with_cancel_flag(cancelled)
+            .install(&plugin)
+        });
+
+        state.queue.push(row.id, job).map(Message::Queue)
+    }
+
+    fn finish_install(
+        state: &mut State,
+        plugin_id: i32,
+        result: &Result<DependencyReport, JobFailure>,
+    ) {
+        let rows = state
+            .base_plugins
+            .iter_mut()
+            .chain(state.plugins.iter_mut())
+            .filter(|row| row.id == plugin_id);
+
+        for row in rows {
+            row.status = match result {
+                Ok(report) if report.problems.is_empty() => "Installed".to_string(),
+                Ok(_) => "Installed with dependency problems".to_string(),
+                Err(JobFailure::Cancelled) => "Cancelled".to_string(),
+                Err(JobFailure::CorruptDownload) => "Corrupt download".to_string(),
+                Err(JobFailure::Failed(message)) => format!("Installation failed: {}", message),
+            };
+            if result.is_ok() {
+                row.current_version.clone_from(&row.latest_version);
+                row.update = VersionOrdering::Same;
+            }
+        }
+    }
+
+    /// Whether an installation of this view is queued or running.
+    pub fn is_busy(&self) -> bool {
         match self {
-            Catalog::Loaded(state) => match message {
-                Message::CatalogInputChanged(letter) => {
-                    let mut filerted_plugins = Vec::new();
-                    state.input_value = letter;
-
-                    for element in &state.base_plugins {
-                        if element
-                            .title
-                            .to_lowercase()
-                            .contains(&state.input_value.to_lowercase())
-                        {
-                            filerted_plugins.push(element.clone());
-                        }
+            Catalog::Loaded(state) | Catalog::NoInternet(state) => state.queue.is_busy(),
+        }
+    }
+
+    fn load_plugins(state: &mut State) {
+        let plugins = match state.cache.get_plugins() {
+            Ok(plugins) => plugins.into_values().collect::<Vec<Plugin>>(),
+            Err(error) => {
+                debug!("Can't read the catalog: {}", error);
+                return;
+            }
+        };
+        let rows = Catalog::map_plugins_to_rows(&plugins);
+        state.plugins.clone_from(&rows);
+        state.base_plugins = rows;
+    }
+
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        let state = self.state_mut();
+        match message {
+            Message::CatalogInputChanged(letter) => {
+                let mut filerted_plugins = Vec::new();
+                state.input_value = letter;
+
+                for element in &state.base_plugins {
+                    if element
+                        .title
+                        .to_lowercase()
+                        .contains(&state.input_value.to_lowercase())
+                    {
+                        filerted_plugins.push(element.clone());
                     }
-                    filerted_plugins
-                        .sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
-                    state.plugins = filerted_plugins;
-                    Command::none()
                 }
-                Message::Catalog(index, msg) => state.plugins[index]
-                    .update(msg, &*state.cache)
-                    .map(move |msg| Message::Catalog(index, msg)),
-                Message::LoadPlugins => {
-                    let plugins = state
-                        .cache
-                        .get_plugins()
-                        .values()
-                        .cloned()
-                        .collect::<Vec<Plugin>>();
-                    let rows = Catalog::map_plugins_to_rows(&plugins);
-                    state.plugins = rows.clone();
-                    state.base_plugins = rows;
-
-                    Command::none()
+                filerted_plugins
+                    .sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+                state.plugins = filerted_plugins;
+                Command::none()
+            }
+            // Installations run in the queue of the view, so they have to be handled here
+            Message::Catalog(_, RowMessage::InstallPressed(row)) => {
+                Catalog::queue_install(state, &row)
+            }
+            Message::Catalog(_, RowMessage::CancelPressed(plugin_id)) => {
+                state.queue.cancel(plugin_id);
+                Command::none()
+            }
+            Message::Catalog(index, msg) => state.plugins[index]
+                .update(msg)
+                .map(move |msg| Message::Catalog(index, msg)),
+            Message::Queue(event) => {
+                let command = state.queue.update(&event);
+                if let QueueEvent::Finished { plugin_id, result } = &event {
+                    Catalog::finish_install(state, *plugin_id, result);
                 }
-                Message::RetryPressed => Command::none(),
-            },
-            Catalog::NoInternet(_) => Command::none(),
+                command.map(Message::Queue)
+            }
+            Message::LoadPlugins => {
+                Catalog::load_plugins(state);
+                Command::none()
+            }
+            Message::FeedRefreshed(result) => {
+                state.refreshing = false;
+                let offline = match result {
+                    Ok(refresh) => {
+                        let offline = refresh.is_offline();
+                        state.fetched_at = Some(refresh.fetched_at);
+                        state.feed_error = match refresh.source {
+                            FeedSource::Downloaded
+                            | FeedSource::NotModified
+                            | FeedSource::Skipped => String::new(),
+                            FeedSource::Offline(error) => error,
+                        };
+                        offline
+                    }
+                    // Neither the feed nor a snapshot is available
+                    Err(error) => {
+                        debug!("Can't refresh the catalog: {}", error);
+                        state.feed_error = error;
+                        true
+                    }
+                };
+                Catalog::load_plugins(state);
+
+                let state = state.clone();
+                *self = if offline {
+                    Catalog::NoInternet(state)
+                } else {
+                    Catalog::Loaded(state)
+                };
+                Command::none()
+            }
+            Message::RetryPressed => self.refresh(true),
         }
     }
 
-    fn build_plugins_scrollable(plugins: &'_ [PluginRow]) -> Element<'_, Message> {
+    fn build_plugins_scrollable<'a>(
+        plugins: &'a [PluginRow],
+        queue: &'a InstallQueue,
+        placeholder: &str,
+    ) -> Element<'a, Message> {
         if plugins.is_empty() {
             row()
                 .height(Length::Fill)
-                .push(text("Loading plugins..."))
+                .push(text(placeholder))
                 .align_items(Alignment::Center)
                 .into()
         } else {
-            let plugins_column = plugins
-                .iter()
-                .enumerate()
-                .fold(column().padding([0, 13, 0, 0]).spacing(5), |col, (i, p)| {
-                    col.push(p.view().map(move |msg| Message::Catalog(i, msg)))
-                });
+            let plugins_column = plugins.iter().enumerate().fold(
+                column().padding([0, 13, 0, 0]).spacing(5),
+                |col, (i, p)| {
+                    col.push(
+                        p.view(queue.status(p.id))
+                            .map(move |msg| Message::Catalog(i, msg)),
+                    )
+                },
+            );
             scrollable(plugins_column)
                 .scrollbar_width(10)
                 .style(style::Scrollable)
@@ -129,91 +310,106 @@ impl Catalog {
         }
     }
 
-    pub fn view(&self) -> Element<Message> {
-        match self {
-            Catalog::Loaded(State {
-                cache: _,
-                plugins,
-                input_value,
-                base_plugins: _,
-            }) => {
-                let search_plugins = text_input(
-                    "Search plugins...",
-                    input_value,
-                    Message::CatalogInputChanged,
-                )
-                .padding(5);
+    /// Shows why the feed can't be downloaded and how old the shown catalog is.
+    fn build_offline_banner(state: &State) -> Element<Message> {
+        let description = match state.fetched_at {
+            Some(fetched_at) => format!(
+                "No Lembas server connection. Showing the catalog from {}",
+                format_age(fetched_at, Utc::now().timestamp())
+            ),
+            None => "No Lembas server connection".to_string(),
+        };
 
-                let plugin_amount = text(format!("{} plugins found", plugins.len()));
+        let mut retry_button = button(text(if state.refreshing {
+            "Retrying..."
+        } else {
+            "Retry"
+        }))
+        .padding(5)
+        .style(style::PrimaryButton::Enabled);
+        if !state.refreshing {
+            retry_button = retry_button.on_press(Message::RetryPressed);
+        }
 
-                let search_row = row()
+        let banner = row()
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(
+                column()
                     .width(Length::Fill)
-                    .align_items(Alignment::Center)
-                    .spacing(10)
-                    .push(search_plugins)
-                    .push(plugin_amount);
+                    .push(text(description))
+                    .push(text(&state.feed_error).size(14)),
+            )
+            .push(retry_button);
+
+        container(banner)
+            .width(Length::Fill)
+            .padding(10)
+            .style(style::NavigationContainer)
+            .into()
+    }
 
-                let plugin_name = text("Plugin").width(Length::FillPortion(6));
-                let current_version = text("Current Version").width(Length::FillPortion(3));
-                let latest_version = text("Latest version").width(Length::FillPortion(3));
-                let upgrade = text("").width(Length::FillPortion(2));
+    pub fn view(&self) -> Element<Message> {
+        let state = match self {
+            Catalog::Loaded(state) | Catalog::NoInternet(state) => state,
+        };
 
-                let plugin_panel = row()
-                    .width(Length::Fill)
-                    .align_items(Alignment::Center)
-                    .padding([0, 13, 0, 0])
-                    .push(plugin_name)
-                    .push(current_version)
-                    .push(latest_version)
-                    .push(upgrade);
+        let search_plugins = text_input(
+            "Search plugins...",
+            &state.input_value,
+            Message::CatalogInputChanged,
+        )
+        .padding(5);
 
-                let plugins_scrollable = Catalog::build_plugins_scrollable(plugins);
+        let plugin_amount = text(format!("{} plugins found", state.plugins.len()));
 
-                let content = column()
-                    .width(Length::Fill)
-                    .spacing(10)
-                    .align_items(Alignment::Center)
-                    .push(search_row)
-                    .push(plugin_panel)
-                    .push(plugins_scrollable);
-
-                container(content)
-                    .height(Length::Fill)
-                    .padding(20)
-                    .style(style::Content)
-                    .into()
-            }
-            Catalog::NoInternet(State {
-                cache: _,
-                plugins: _,
-                input_value: _,
-                base_plugins: _,
-            }) => {
-                let retry_button = button(text("Retry"))
-                    .on_press(Message::RetryPressed)
-                    .padding(5)
-                    .style(style::PrimaryButton::Enabled);
-
-                let content = column()
-                    .push(
-                        text("No Lembas server connection")
-                            .horizontal_alignment(Horizontal::Center)
-                            .vertical_alignment(Vertical::Center)
-                            .size(20),
-                    )
-                    .align_items(Alignment::Center)
-                    .spacing(10)
-                    .push(retry_button);
+        let search_row = row()
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(search_plugins)
+            .push(plugin_amount);
 
-                container(content)
-                    .width(Length::Fill)
-                    .height(Length::Fill)
-                    .center_y()
-                    .center_x()
-                    .style(style::Content)
-                    .into()
-            }
+        let plugin_name = text("Plugin").width(Length::FillPortion(6));
+        let current_version = text("Current Version").width(Length::FillPortion(3));
+        let latest_version = text("Latest version").width(Length::FillPortion(3));
+        let upgrade = text("").width(Length::FillPortion(2));
+
+        let plugin_panel = row()
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .padding([0, 13, 0, 0])
+            .push(plugin_name)
+            .push(current_version)
+            .push(latest_version)
+            .push(upgrade);
+
+        // Without a connection and a stored feed there is nothing to load
+        let placeholder = match self {
+            Catalog::NoInternet(_) if !state.refreshing => "No catalog is available offline",
+            _ => "Loading plugins...",
+        };
+        let plugins_scrollable =
+            Catalog::build_plugins_scrollable(&state.plugins, &state.queue, placeholder);
+
+        let mut content = column()
+            .width(Length::Fill)
+            .spacing(10)
+            .align_items(Alignment::Center);
+        if let Catalog::NoInternet(state) = self {
+            content = content.push(Catalog::build_offline_banner(state));
         }
+        let content = content
+            .push(search_row)
+            .push(plugin_panel)
+            .push(plugins_scrollable);
+
+        container(content)
+            .height(Length::Fill)
+            .padding(20)
+            .style(style::Content)
+            .into()
     }
 }
 #[derive(Clone, Debug)]
@@ -224,11 +420,13 @@ pub struct PluginRow {
     pub latest_version: String,
     pub status: String,
     pub download_url: String,
+    pub update: VersionOrdering,
 }
 
 #[derive(Clone, Debug)]
 pub enum RowMessage {
     InstallPressed(PluginRow),
+    CancelPressed(i32),
     WebsitePressed(PluginRow),
     NoEvent,
 }
@@ -244,101 +442,90 @@ impl PluginRow {
             latest_version: latest_version.to_string(),
             status: "Install".to_string(),
             download_url: format!("{}download{}", base_url, id),
+            update: offered_update(latest_version, current_version, false, ""),
         }
     }
 
-    pub fn update(&mut self, message: RowMessage, cache: &Cache) -> Command<RowMessage> {
-        match message {
-            RowMessage::InstallPressed(plugin) => {
-                let tmp_dir = get_tmp_dir();
-                let plugins_dir = get_plugins_dir();
-                let mut installer =
-                    Installer::new(&tmp_dir, &plugins_dir, plugin.id, &plugin.title);
-
-                match installer.download(&plugin.download_url) {
-                    Ok(content) => match installer.install(&content) {
-                        Ok(root_folder_name) => {
-                            installer.move_files(&root_folder_name);
-                            if cache
-                                .mark_as_installed(plugin.id, &plugin.latest_version)
-                                .is_ok()
-                            {
-                                installer.delete_cache_folder();
-                                self.status = "Installed".to_string();
-                                self.current_version = plugin.latest_version;
-                            } else {
-                                self.status = "Installation failed".to_string();
-                            }
-                        }
-                        Err(e) => {
-                            debug!("{:?}", e);
-                            self.status = "Installation failed".to_string();
-                        }
-                    },
-                    Err(error) => {
-                        debug!("{:?}", error);
-                        self.status = "Download failed".to_string();
-                    }
-                }
-                Command::none()
-            }
+    /// Sets how the latest version relates to the installed one, e.g. to respect a pin or an ignored version.
+    pub fn with_update(mut self, update: VersionOrdering) -> Self {
+        self.update = update;
+        self
+    }
 
+    pub fn update(&mut self, message: RowMessage) -> Command<RowMessage> {
+        match message {
             RowMessage::WebsitePressed(row) => {
-                webbrowser::open(&format!(
+                if let Err(error) = webbrowser::open(&format!(
                     "https://www.lotrointerface.com/downloads/info{}-{}.html",
                     row.id, row.title,
-                ))
-                .unwrap();
+                )) {
+                    debug!("Can't open the website of {}: {}", row.title, error);
+                }
+                Command::none()
+            }
+            RowMessage::InstallPressed(_) | RowMessage::CancelPressed(_) | RowMessage::NoEvent => {
                 Command::none()
             }
-            RowMessage::NoEvent => Command::none(),
         }
     }
 
-    pub fn view(&self) -> Element<RowMessage> {
+    /// The row of a plugin whose installation is queued shows the progress of the job.
+    pub fn view(&self, job: Option<&JobStatus>) -> Element<RowMessage> {
         let plugin = self.clone();
         let website_plugin = self.clone();
 
-        column()
-            .push(
-                button(
-                    row()
-                        .align_items(Alignment::Center)
-                        .push(text(&self.title).width(Length::FillPortion(6)))
-                        .push(text(&self.current_version).width(Length::FillPortion(3)))
-                        .push(text(&self.latest_version).width(Length::FillPortion(3)))
-                        .push(if plugin.current_version.is_empty() {
-                            button(
-                                text("Install")
-                                    .width(Length::Fill)
-                                    .horizontal_alignment(Horizontal::Center),
-                            )
-                            .on_press(RowMessage::InstallPressed(plugin))
-                            .style(style::PrimaryButton::Enabled)
-                            .width(Length::FillPortion(2))
-                        } else if plugin.current_version == plugin.latest_version {
-                            button(
-                                text("Installed")
-                                    .width(Length::Fill)
-                                    .horizontal_alignment(Horizontal::Center),
-                            )
-                            .on_press(RowMessage::NoEvent)
-                            .style(style::InstallButton::Enabled)
-                            .width(Length::FillPortion(2))
-                        } else {
-                            button(
-                                text("Update")
-                                    .width(Length::Fill)
-                                    .horizontal_alignment(Horizontal::Center),
-                            )
-                            .on_press(RowMessage::InstallPressed(plugin))
-                            .style(style::PrimaryButton::Enabled)
+        let content = column().push(
+            button(
+                row()
+                    .align_items(Alignment::Center)
+                    .push(text(&self.title).width(Length::FillPortion(6)))
+                    .push(text(&self.current_version).width(Length::FillPortion(3)))
+                    .push(text(&self.latest_version).width(Length::FillPortion(3)))
+                    .push(if job.is_some() {
+                        button(text(""))
+                            .style(style::TransparentButton::Enabled)
                             .width(Length::FillPortion(2))
-                        }),
-                )
-                .on_press(RowMessage::WebsitePressed(website_plugin))
-                .style(style::PluginRow::Enabled),
+                    } else if plugin.current_version.is_empty() {
+                        button(
+                            text(&self.status)
+                                .width(Length::Fill)
+                                .horizontal_alignment(Horizontal::Center),
+                        )
+                        .on_press(RowMessage::InstallPressed(plugin))
+                        .style(style::PrimaryButton::Enabled)
+                        .width(Length::FillPortion(2))
+                    } else if !matches!(
+                        plugin.update,
+                        VersionOrdering::Newer | VersionOrdering::Incomparable
+                    ) {
+                        button(
+                            text("Installed")
+                                .width(Length::Fill)
+                                .horizontal_alignment(Horizontal::Center),
+                        )
+                        .on_press(RowMessage::NoEvent)
+                        .style(style::InstallButton::Enabled)
+                        .width(Length::FillPortion(2))
+                    } else {
+                        button(
+                            text("Update")
+                                .width(Length::Fill)
+                                .horizontal_alignment(Horizontal::Center),
+                        )
+                        .on_press(RowMessage::InstallPressed(plugin))
+                        .style(style::PrimaryButton::Enabled)
+                        .width(Length::FillPortion(2))
+                    }),
             )
-            .into()
+            .on_press(RowMessage::WebsitePressed(website_plugin))
+            .style(style::PluginRow::Enabled),
+        );
+
+        match job {
+            Some(status) => content
+                .push(job_row(status, RowMessage::CancelPressed(self.id)))
+                .into(),
+            None => content.into(),
+        }
     }
 }
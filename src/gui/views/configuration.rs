@@ -1,20 +1,65 @@
-use crate::core::config::{read_existing_settings_file, save_settings_changes, SettingsFile};
+use crate::core::backup::format_size;
+use crate::core::config::{
+    get_active_profile, get_archives_dir, save_settings_changes, Profile, SettingsFile,
+    MAX_PARALLEL_INSTALLS,
+};
+use crate::core::error::{Error, PathContext};
+use crate::core::io::directories::{
+    check_directory, check_writable, move_contents, subdirectories, DirectoryKind,
+};
+use crate::core::io::ArchiveStore;
 use crate::gui::style;
-use iced::pure::{checkbox, column, container, row, text, text_input, Element};
+use iced::pure::{button, checkbox, column, container, row, scrollable, text, text_input, Element};
 use iced::{Alignment, Length};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DIRECTORY_KINDS: [DirectoryKind; 4] = [
+    DirectoryKind::LotroDocuments,
+    DirectoryKind::Plugins,
+    DirectoryKind::Backups,
+    DirectoryKind::Temp,
+];
 
 #[derive(Debug, Clone)]
 pub struct Configuration {
     description: String,
     settings: SettingsFile,
+    /// Readable message if the settings can't be saved
+    status: String,
+    archive_store: ArchiveStore,
+    /// Size of the archive store in bytes
+    archive_cache_size: u64,
+    archive_count: usize,
+    new_profile_name: String,
+    new_profile_documents_dir: String,
+    profile_status: String,
+    /// Directories of the active profile, resolving them may probe every installation
+    directories: Vec<(DirectoryKind, PathBuf)>,
+    picker: Option<DirectoryPicker>,
+    directory_status: String,
 }
 
-impl Default for Configuration {
-    fn default() -> Self {
-        Self {
+impl Configuration {
+    pub fn new(settings: &SettingsFile) -> Self {
+        let mut configuration = Self {
             description: "Enable Backup".to_string(),
-            settings: read_existing_settings_file(),
-        }
+            settings: settings.clone(),
+            status: String::new(),
+            archive_store: ArchiveStore::new(&get_archives_dir()),
+            archive_cache_size: 0,
+            archive_count: 0,
+            new_profile_name: String::new(),
+            new_profile_documents_dir: String::new(),
+            profile_status: String::new(),
+            directories: Vec::new(),
+            picker: None,
+            directory_status: String::new(),
+        };
+        configuration.refresh_archive_cache();
+        configuration.refresh_directories();
+        configuration
     }
 }
 
@@ -22,22 +67,336 @@ impl Default for Configuration {
 pub enum Message {
     BackupTriggered(bool),
     FeedUrlChanged(String),
+    FeedRefreshIntervalChanged(String),
+    UpdateCheckIntervalChanged(String),
+    BackupKeepLastChanged(String),
+    BackupMaxAgeChanged(String),
+    ArchiveCacheLimitChanged(String),
+    ArchiveCacheMaxAgeChanged(String),
+    ParallelInstallsChanged(String),
+    ClearArchiveCachePressed,
+    ProfileNameChanged(String),
+    ProfileDocumentsDirChanged(String),
+    AddProfilePressed,
+    RemoveProfilePressed(String),
+    ChangeDirectoryPressed(DirectoryKind),
+    PickerPathChanged(String),
+    PickerMoveToggled(bool),
+    PickerConfirmed,
+    PickerDefaultPressed,
+    PickerCancelled,
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Nothing,
+    /// A directory of the active profile changed, so the plugins have to be loaded again
+    DirectoriesChanged,
 }
 
 impl Configuration {
-    pub fn update(&mut self, msg: Message) {
+    pub fn update(&mut self, msg: Message) -> Event {
         match msg {
             Message::BackupTriggered(toggled) => {
                 self.settings.backup_enabled = toggled;
 
-                save_settings_changes(&self.settings);
+                self.save_settings();
             }
             Message::FeedUrlChanged(state) => {
                 self.settings.feed_url = state;
 
-                save_settings_changes(&self.settings);
+                self.save_settings();
+            }
+            Message::FeedRefreshIntervalChanged(state) => {
+                if let Some(minutes) = parse_limit(&state) {
+                    self.settings.feed_refresh_interval_minutes = minutes;
+
+                    self.save_settings();
+                }
+            }
+            Message::UpdateCheckIntervalChanged(state) => {
+                if let Some(hours) = parse_limit(&state) {
+                    self.settings.update_check_interval_hours = hours;
+
+                    self.save_settings();
+                }
+            }
+            Message::BackupKeepLastChanged(state) => {
+                if let Some(count) = parse_limit(&state) {
+                    self.settings.backup_keep_last = count;
+
+                    self.save_settings();
+                }
+            }
+            Message::BackupMaxAgeChanged(state) => {
+                if let Some(days) = parse_limit(&state) {
+                    self.settings.backup_max_age_days = days;
+
+                    self.save_settings();
+                }
+            }
+            Message::ArchiveCacheLimitChanged(state) => {
+                if let Some(limit) = parse_limit(&state) {
+                    self.settings.archive_cache_limit_mb = limit;
+
+                    self.save_settings();
+                }
+            }
+            Message::ArchiveCacheMaxAgeChanged(state) => {
+                if let Some(days) = parse_limit(&state) {
+                    self.settings.archive_cache_max_age_days = days;
+
+                    self.save_settings();
+                }
+            }
+            Message::ParallelInstallsChanged(state) => {
+                if let Some(count) = parse_limit(&state) {
+                    self.settings.parallel_installs = count;
+
+                    self.save_settings();
+                }
+            }
+            Message::ClearArchiveCachePressed => {
+                if let Err(error) = self.archive_store.clear() {
+                    debug!("Can't clear the archive cache: {}", error);
+                }
+                self.refresh_archive_cache();
+            }
+            Message::ProfileNameChanged(state) => {
+                self.new_profile_name = state;
+            }
+            Message::ProfileDocumentsDirChanged(state) => {
+                self.new_profile_documents_dir = state;
+            }
+            Message::AddProfilePressed => {
+                let mut profile = Profile::new(&self.new_profile_name);
+                if !self.new_profile_documents_dir.trim().is_empty() {
+                    profile = profile
+                        .with_lotro_documents_dir(Path::new(self.new_profile_documents_dir.trim()));
+                }
+                let name = profile.name.clone();
+
+                match self.settings.add_profile(profile) {
+                    Ok(()) => {
+                        self.save_settings();
+                        self.new_profile_name = String::new();
+                        self.new_profile_documents_dir = String::new();
+                        self.profile_status = format!("Added profile {}", name);
+                    }
+                    Err(error) => self.profile_status = error.to_string(),
+                }
+            }
+            Message::RemoveProfilePressed(name) => match self.settings.remove_profile(&name) {
+                Ok(profile) => {
+                    self.save_settings();
+                    self.profile_status = format!("Removed profile {}", profile.name);
+                }
+                Err(error) => self.profile_status = error.to_string(),
+            },
+            Message::ChangeDirectoryPressed(kind) => {
+                let directory = self
+                    .directories
+                    .iter()
+                    .find(|(directory_kind, _)| *directory_kind == kind)
+                    .map(|(_, directory)| directory.clone())
+                    .unwrap_or_default();
+                self.picker = Some(DirectoryPicker::new(kind, &directory));
+                self.directory_status = String::new();
+            }
+            Message::PickerPathChanged(path) => {
+                if let Some(picker) = &mut self.picker {
+                    picker.browse(path);
+                }
+            }
+            Message::PickerMoveToggled(toggled) => {
+                if let Some(picker) = &mut self.picker {
+                    picker.move_content = toggled;
+                }
+            }
+            Message::PickerConfirmed => return self.confirm_picker(false),
+            Message::PickerDefaultPressed => return self.confirm_picker(true),
+            Message::PickerCancelled => {
+                self.picker = None;
             }
         }
+
+        Event::Nothing
+    }
+
+    /// Keeps the picker open and shows why its directory can't be changed right now.
+    pub fn refuse_directory_change(&mut self, reason: &str) {
+        self.directory_status = reason.to_string();
+    }
+
+    /// Changes the directory of the picker to the browsed directory or to the default directory.
+    fn confirm_picker(&mut self, use_default: bool) -> Event {
+        let Some(picker) = self.picker.take() else {
+            return Event::Nothing;
+        };
+        let path = if use_default { "" } else { picker.path.trim() };
+
+        match self.change_directory(picker.kind, path, picker.move_content) {
+            Ok(directory) => {
+                self.directory_status =
+                    format!("{} changed to {}", picker.kind, directory.display());
+                self.refresh_directories();
+                Event::DirectoriesChanged
+            }
+            Err(error) => {
+                debug!("Can't change the {}: {}", picker.kind, error);
+                self.directory_status = error.to_string();
+                self.picker = Some(picker);
+                Event::Nothing
+            }
+        }
+    }
+
+    /// Stores the new directory of the active profile and moves the content of the old directory into it if lembas
+    /// owns the directory. The settings stay untouched if the directory isn't writable or the content can't be moved.
+    /// An empty path restores the default directory.
+    fn change_directory(
+        &mut self,
+        kind: DirectoryKind,
+        path: &str,
+        move_content: bool,
+    ) -> Result<PathBuf, Error> {
+        let profile = get_active_profile().name;
+        let old_directory = self.settings.directory(&profile, kind);
+
+        let mut settings = self.settings.clone();
+        settings.set_directory(&profile, kind, path);
+        let new_directory = settings.directory(&profile, kind);
+        check_directory(kind, &new_directory)?;
+        fs::create_dir_all(&new_directory).at(&new_directory)?;
+        check_writable(&new_directory)?;
+        if move_content && kind.is_owned() {
+            move_contents(&old_directory, &new_directory)?;
+        }
+
+        self.settings = settings;
+        save_settings_changes(&self.settings)?;
+
+        Ok(new_directory)
+    }
+
+    fn save_settings(&mut self) {
+        self.status = match save_settings_changes(&self.settings) {
+            Ok(()) => String::new(),
+            Err(error) => {
+                debug!("Can't save the settings: {}", error);
+                format!("The settings can't be saved: {}", error)
+            }
+        };
+    }
+
+    fn refresh_directories(&mut self) {
+        let profile = get_active_profile().name;
+
+        self.directories = DIRECTORY_KINDS
+            .iter()
+            .map(|kind| (*kind, self.settings.directory(&profile, *kind)))
+            .collect();
+    }
+
+    fn build_directories_section(&self) -> Element<Message> {
+        let directories = self.directories.iter().fold(
+            column().spacing(5).width(Length::Fill),
+            |col, (kind, directory)| {
+                col.push(
+                    row()
+                        .align_items(Alignment::Center)
+                        .spacing(10)
+                        .push(text(kind.to_string()).width(Length::FillPortion(2)))
+                        .push(text(directory.display().to_string()).width(Length::FillPortion(6)))
+                        .push(
+                            button(text("Change"))
+                                .on_press(Message::ChangeDirectoryPressed(*kind))
+                                .padding(5)
+                                .style(style::PrimaryButton::Enabled),
+                        ),
+                )
+            },
+        );
+
+        let mut section = column()
+            .spacing(10)
+            .push(text("Directories"))
+            .push(directories);
+        if let Some(picker) = &self.picker {
+            section = section.push(picker.view());
+        }
+
+        section.push(text(&self.directory_status)).into()
+    }
+
+    fn build_profiles_section(&self) -> Element<Message> {
+        let profiles = self.settings.profiles.iter().fold(
+            column().spacing(5).width(Length::Fill),
+            |col, profile| {
+                let mut remove_button = button(text("Remove")).padding(5);
+                if profile.name != self.settings.active_profile {
+                    remove_button = remove_button
+                        .on_press(Message::RemoveProfilePressed(profile.name.clone()))
+                        .style(style::PrimaryButton::Enabled);
+                }
+                let documents_dir = if profile.lotro_documents_dir.is_empty() {
+                    "First discovered installation".to_string()
+                } else {
+                    profile.lotro_documents_dir.clone()
+                };
+
+                col.push(
+                    row()
+                        .align_items(Alignment::Center)
+                        .spacing(10)
+                        .push(text(&profile.name).width(Length::FillPortion(2)))
+                        .push(text(documents_dir).width(Length::FillPortion(6)))
+                        .push(remove_button),
+                )
+            },
+        );
+
+        let add_row = row()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(
+                text_input(
+                    "Profile name",
+                    &self.new_profile_name,
+                    Message::ProfileNameChanged,
+                )
+                .padding(5)
+                .width(Length::FillPortion(2)),
+            )
+            .push(
+                text_input(
+                    "LOTRO documents directory, empty uses the first discovered installation",
+                    &self.new_profile_documents_dir,
+                    Message::ProfileDocumentsDirChanged,
+                )
+                .padding(5)
+                .width(Length::FillPortion(6)),
+            )
+            .push(
+                button(text("Add profile"))
+                    .on_press(Message::AddProfilePressed)
+                    .padding(5)
+                    .style(style::PrimaryButton::Enabled),
+            );
+
+        column()
+            .spacing(10)
+            .push(text("Profiles"))
+            .push(profiles)
+            .push(add_row)
+            .push(text(&self.profile_status))
+            .into()
+    }
+
+    fn refresh_archive_cache(&mut self) {
+        let archives = self.archive_store.archives();
+        self.archive_cache_size = archives.iter().map(|archive| archive.size).sum();
+        self.archive_count = archives.len();
     }
 
     pub fn view(&self) -> Element<Message> {
@@ -49,6 +408,31 @@ impl Configuration {
             Message::BackupTriggered,
         );
 
+        let retention_row = row()
+            .width(Length::Shrink)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(text("Keep the last backups"))
+            .push(
+                text_input(
+                    "0 keeps every backup",
+                    &limit_value(self.settings.backup_keep_last),
+                    Message::BackupKeepLastChanged,
+                )
+                .padding(5)
+                .width(Length::Units(150)),
+            )
+            .push(text("Maximum age in days"))
+            .push(
+                text_input(
+                    "0 keeps every backup",
+                    &limit_value(self.settings.backup_max_age_days),
+                    Message::BackupMaxAgeChanged,
+                )
+                .padding(5)
+                .width(Length::Units(150)),
+            );
+
         let feed_label = text("Feed URL");
 
         let feed_url =
@@ -61,12 +445,109 @@ impl Configuration {
             .push(feed_label)
             .push(feed_url);
 
+        let refresh_row = row()
+            .width(Length::Shrink)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(text("Check the feed at most every minutes"))
+            .push(
+                text_input(
+                    "0 checks on every refresh",
+                    &limit_value(self.settings.feed_refresh_interval_minutes),
+                    Message::FeedRefreshIntervalChanged,
+                )
+                .padding(5)
+                .width(Length::Units(150)),
+            )
+            .push(text("Check for updates every hours"))
+            .push(
+                text_input(
+                    "0 disables the check",
+                    &limit_value(self.settings.update_check_interval_hours),
+                    Message::UpdateCheckIntervalChanged,
+                )
+                .padding(5)
+                .width(Length::Units(150)),
+            );
+
+        // The queue reads the limit when it's created
+        let parallel_row = row()
+            .width(Length::Shrink)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(text("Parallel installations"))
+            .push(
+                text_input(
+                    &format!("1 to {}", MAX_PARALLEL_INSTALLS),
+                    &limit_value(self.settings.parallel_installs),
+                    Message::ParallelInstallsChanged,
+                )
+                .padding(5)
+                .width(Length::Units(150)),
+            )
+            .push(text("Applies after a restart"));
+
+        let cache_text = text("Archive cache");
+
+        let cache_size = text(format!(
+            "{} archives, {}",
+            self.archive_count,
+            format_size(self.archive_cache_size)
+        ));
+
+        let clear_button = button(text("Clear cache"))
+            .on_press(Message::ClearArchiveCachePressed)
+            .padding(5)
+            .style(style::PrimaryButton::Enabled);
+
+        let cache_size_row = row()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(cache_size)
+            .push(clear_button);
+
+        let limit_row = row()
+            .width(Length::Shrink)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(text("Maximum size in MB"))
+            .push(
+                text_input(
+                    "0 keeps every archive",
+                    &limit_value(self.settings.archive_cache_limit_mb),
+                    Message::ArchiveCacheLimitChanged,
+                )
+                .padding(5)
+                .width(Length::Units(150)),
+            )
+            .push(text("Maximum age in days"))
+            .push(
+                text_input(
+                    "0 keeps every archive",
+                    &limit_value(self.settings.archive_cache_max_age_days),
+                    Message::ArchiveCacheMaxAgeChanged,
+                )
+                .padding(5)
+                .width(Length::Units(150)),
+            );
+
         let content = column()
             .width(Length::Fill)
             .spacing(10)
             .push(about_text)
+            .push(text(&self.status))
             .push(checkbox)
-            .push(feed_row);
+            .push(retention_row)
+            .push(feed_row)
+            .push(refresh_row)
+            .push(parallel_row)
+            .push(cache_text)
+            .push(cache_size_row)
+            .push(limit_row)
+            .push(self.build_directories_section())
+            .push(self.build_profiles_section());
+
+        let content = scrollable(content);
 
         container(content)
             .padding(20)
@@ -76,3 +557,152 @@ impl Configuration {
             .into()
     }
 }
+
+/// Directory browser of the settings view. It checks the browsed directory on every change, whether it's writable is
+/// only checked when it's confirmed.
+#[derive(Debug, Clone)]
+struct DirectoryPicker {
+    kind: DirectoryKind,
+    path: String,
+    subdirectories: Vec<PathBuf>,
+    /// Warnings or the reason why the directory can't be used
+    check: Result<Vec<String>, String>,
+    move_content: bool,
+}
+
+impl DirectoryPicker {
+    /// Opens the picker in the directory or in its closest existing parent.
+    fn new(kind: DirectoryKind, directory: &Path) -> Self {
+        let start = directory
+            .ancestors()
+            .find(|ancestor| ancestor.is_dir())
+            .unwrap_or(directory);
+
+        let mut picker = Self {
+            kind,
+            path: String::new(),
+            subdirectories: Vec::new(),
+            check: Ok(Vec::new()),
+            move_content: false,
+        };
+        picker.browse(start.display().to_string());
+        picker
+    }
+
+    fn browse(&mut self, path: String) {
+        let directory = PathBuf::from(path.trim());
+
+        self.subdirectories = subdirectories(&directory);
+        self.check = check_directory(self.kind, &directory).map_err(|error| error.to_string());
+        self.path = path;
+    }
+
+    fn view(&self) -> Element<Message> {
+        let directory = PathBuf::from(self.path.trim());
+
+        let mut up_button = button(text("Up")).padding(5);
+        if let Some(parent) = directory.parent() {
+            up_button = up_button
+                .on_press(Message::PickerPathChanged(parent.display().to_string()))
+                .style(style::PrimaryButton::Enabled);
+        }
+        let path_row = row()
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(
+                text_input("Directory", &self.path, Message::PickerPathChanged)
+                    .padding(5)
+                    .width(Length::Fill),
+            )
+            .push(up_button);
+
+        let subdirectories = self.subdirectories.iter().fold(
+            column().spacing(2).width(Length::Fill),
+            |col, subdirectory| {
+                let name = subdirectory
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                col.push(
+                    button(text(name))
+                        .on_press(Message::PickerPathChanged(
+                            subdirectory.display().to_string(),
+                        ))
+                        .width(Length::Fill)
+                        .padding(2),
+                )
+            },
+        );
+
+        let messages = match &self.check {
+            Ok(warnings) => warnings
+                .iter()
+                .fold(column().spacing(2), |col, warning| col.push(text(warning))),
+            Err(error) => column().push(text(error)),
+        };
+
+        let mut use_button = button(text("Use this directory")).padding(5);
+        if self.check.is_ok() {
+            use_button = use_button
+                .on_press(Message::PickerConfirmed)
+                .style(style::PrimaryButton::Enabled);
+        }
+        let mut button_row = row().align_items(Alignment::Center).spacing(10);
+        if self.kind.is_owned() {
+            button_row = button_row.push(checkbox(
+                "Move the existing content",
+                self.move_content,
+                Message::PickerMoveToggled,
+            ));
+        }
+        let button_row = button_row
+            .push(use_button)
+            .push(
+                button(text("Use default"))
+                    .on_press(Message::PickerDefaultPressed)
+                    .padding(5)
+                    .style(style::PrimaryButton::Enabled),
+            )
+            .push(
+                button(text("Cancel"))
+                    .on_press(Message::PickerCancelled)
+                    .padding(5)
+                    .style(style::PrimaryButton::Enabled),
+            );
+
+        container(
+            column()
+                .spacing(10)
+                .push(text(format!("Choose the {}", self.kind)))
+                .push(path_row)
+                .push(
+                    container(scrollable(subdirectories))
+                        .height(Length::Units(150))
+                        .width(Length::Fill),
+                )
+                .push(messages)
+                .push(button_row),
+        )
+        .padding(10)
+        .style(style::Content)
+        .into()
+    }
+}
+
+/// Parses a limit of the archive cache or the backups. An empty input disables the limit.
+fn parse_limit(state: &str) -> Option<u64> {
+    let state = state.trim();
+    if state.is_empty() {
+        Some(0)
+    } else {
+        state.parse().ok()
+    }
+}
+
+fn limit_value(limit: u64) -> String {
+    if limit == 0 {
+        String::new()
+    } else {
+        limit.to_string()
+    }
+}
@@ -1,34 +1,46 @@
 pub mod about;
+pub mod backups;
 pub mod catalog;
 pub mod configuration;
 pub mod plugins;
 
+use std::collections::HashSet;
 use std::env;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use super::views::plugins::PluginMessage;
 use crate::core::config::{
-    get_database_file_path, initialize_directories, read_existing_settings_file,
+    adopt_discovered_installation, get_active_profile, get_database_file_path, get_plugins_dir,
+    initialize_directories, read_existing_settings_file, save_lotro_documents_dir,
+    save_settings_changes, set_profile_override, SettingsFile,
 };
+use crate::core::discovery::Installation;
+use crate::core::error::Error;
 use crate::core::io::cache::DatabaseHandler;
-use crate::core::io::Cache;
-use crate::core::lotro_compendium::{Downloader, FeedDownloader, FeedUrlParser};
+use crate::core::io::{Cache, Synchronizer};
+use crate::core::update::UpdatePlan;
+use crate::gui::queue::InstallQueue;
 use crate::gui::style;
 pub use about::About as AboutView;
+pub use backups::{Backups as BackupsView, Message as BackupMessage};
 pub use catalog::{Catalog as CatalogView, Message as CatalogMessage};
-pub use configuration::{Configuration as ConfigView, Message as ConfigMessage};
+pub use configuration::{
+    Configuration as ConfigView, Event as ConfigEvent, Message as ConfigMessage,
+};
 
-use iced::pure::{button, column, container, image, row, text, Application, Element};
+use iced::pure::{
+    button, column, container, image, pick_list, row, scrollable, text, Application, Element,
+};
 use iced::{
     alignment::{Horizontal, Vertical},
+    time,
     window::Settings as Window,
-    Alignment, Command, Length, Settings, Space,
+    Alignment, Command, Length, Settings, Space, Subscription,
 };
 use log::debug;
 pub use plugins::Plugins as PluginsView;
-use r2d2_sqlite::SqliteConnectionManager;
-use tokio::task;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -36,6 +48,7 @@ const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub enum View {
     Plugins,
     Catalog,
+    Backups,
     About,
     Configuration,
 }
@@ -48,42 +61,119 @@ impl Default for View {
 
 #[derive(Debug, Clone)]
 pub enum Lembas {
+    /// Several installations were found on the first start and the user has to pick one
+    SelectInstallation(Vec<Installation>),
     Loading,
     Loaded(Box<State>),
+    /// The directories, the settings or the cache database can't be opened
+    Failed(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct State {
+    cache: Arc<Cache>,
     view: View,
+    /// Names of every profile for the profile switcher
+    profiles: Vec<String>,
+    active_profile: String,
     plugins_view: PluginsView,
     catalog_view: CatalogView,
+    backups_view: BackupsView,
     about_view: AboutView,
     config_view: ConfigView,
+    /// Interval of the background update check, `None` if it's disabled
+    update_check: Option<Duration>,
+    /// The running feed refresh was started by the background update check
+    checking_updates: bool,
+    /// Plugin ids and versions that were already announced
+    announced_updates: HashSet<(i32, String)>,
+    /// Announces installed plugins with new versions or why the profile can't be switched until it's closed
+    update_notice: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    Loaded(State),
+    InstallationSelected(PathBuf),
+    Loaded(Result<Box<State>, String>),
+    RetryPressed,
+    ProfileSelected(String),
+    UpdateCheckTick,
+    UpdateNoticeClosed,
 
     // Navigation Panel
     PluginsPressed,
     CatalogPressed,
+    BackupsPressed,
     AboutPressed,
     SettingsPressed,
 
     CatalogAction(CatalogMessage),
     PluginAction(PluginMessage),
+    BackupAction(BackupMessage),
     ConfigAction(ConfigMessage),
 }
 
 impl State {
-    pub fn new(cache: &Arc<Cache>) -> Self {
-        Self {
+    pub fn new(cache: &Arc<Cache>, settings: &SettingsFile) -> Self {
+        // Both views install into the same plugins directory and share the limit of parallel installations
+        let queue = InstallQueue::new(settings.install_concurrency());
+
+        let mut state = Self {
+            cache: cache.clone(),
             view: View::default(),
-            plugins_view: PluginsView::new(cache.clone()),
-            catalog_view: CatalogView::new(cache.clone()),
+            profiles: Vec::new(),
+            active_profile: String::new(),
+            plugins_view: PluginsView::new(cache.clone(), queue.sibling()),
+            catalog_view: CatalogView::new(cache.clone(), queue),
+            backups_view: BackupsView::new(cache.clone()),
             about_view: AboutView::default(),
-            config_view: ConfigView::default(),
+            config_view: ConfigView::new(settings),
+            update_check: settings.update_check_interval(),
+            checking_updates: false,
+            announced_updates: HashSet::new(),
+            update_notice: None,
+        };
+        state.load_profiles(settings);
+        state
+    }
+
+    fn load_profiles(&mut self, settings: &SettingsFile) {
+        self.profiles = settings
+            .profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect();
+        self.active_profile = get_active_profile().name;
+    }
+
+    /// Shows a notice if installed plugins have new versions that weren't announced yet.
+    fn has_running_jobs(&self) -> bool {
+        self.plugins_view.is_busy() || self.catalog_view.is_busy()
+    }
+
+    fn announce_updates(&mut self) {
+        let installed_plugins = match self.cache.get_installed_plugins() {
+            Ok(plugins) => plugins,
+            Err(error) => {
+                debug!("Can't check the installed plugins for updates: {}", error);
+                return;
+            }
+        };
+
+        let updates = UpdatePlan::new(installed_plugins.into_values())
+            .plugins
+            .into_iter()
+            .filter(|plugin| {
+                self.announced_updates
+                    .insert((plugin.id, plugin.latest_version.clone()))
+            })
+            .map(|plugin| format!("{} {}", plugin.name, plugin.latest_version))
+            .collect::<Vec<String>>();
+        if !updates.is_empty() {
+            self.update_notice = Some(format!(
+                "New versions of installed plugins: {}",
+                updates.join(", ")
+            ));
         }
     }
 }
@@ -94,6 +184,14 @@ impl Application for Lembas {
     type Flags = ();
 
     fn new(_flags: ()) -> (Self, Command<Message>) {
+        match adopt_discovered_installation() {
+            Ok(installations) if !installations.is_empty() => {
+                return (Self::SelectInstallation(installations), Command::none());
+            }
+            Ok(_) => {}
+            Err(error) => return (Self::Failed(error.to_string()), Command::none()),
+        }
+
         (
             Self::Loading,
             Command::perform(Self::init_application(), Message::Loaded),
@@ -104,11 +202,49 @@ impl Application for Lembas {
         format!("Lembas {}", VERSION)
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        match self {
+            Lembas::Loaded(state) => match state.update_check {
+                Some(interval) => time::every(interval).map(|_| Message::UpdateCheckTick),
+                None => Subscription::none(),
+            },
+            _ => Subscription::none(),
+        }
+    }
+
     fn update(&mut self, message: Self::Message) -> Command<Message> {
         match self {
-            Lembas::Loading => {
-                if let Message::Loaded(state) = message {
-                    *self = Lembas::Loaded(Box::new(state));
+            Lembas::SelectInstallation(_) => {
+                if let Message::InstallationSelected(documents_dir) = message {
+                    if let Err(error) = save_lotro_documents_dir(&documents_dir) {
+                        *self = Lembas::Failed(error.to_string());
+                        return Command::none();
+                    }
+                    *self = Lembas::Loading;
+                    return Command::perform(Self::init_application(), Message::Loaded);
+                }
+                Command::none()
+            }
+            Lembas::Loading => match message {
+                // The application starts with the cached catalog, the feed is downloaded afterwards
+                Message::Loaded(Ok(mut state)) => {
+                    let command = state
+                        .catalog_view
+                        .refresh(false)
+                        .map(Message::CatalogAction);
+                    *self = Lembas::Loaded(state);
+                    command
+                }
+                Message::Loaded(Err(error)) => {
+                    *self = Lembas::Failed(error);
+                    Command::none()
+                }
+                _ => Command::none(),
+            },
+            Lembas::Failed(_) => {
+                if let Message::RetryPressed = message {
+                    *self = Lembas::Loading;
+                    return Command::perform(Self::init_application(), Message::Loaded);
                 }
                 Command::none()
             }
@@ -116,6 +252,7 @@ impl Application for Lembas {
                 Message::PluginsPressed => {
                     state.plugins_view.update(PluginMessage::LoadPlugins);
                     state.view = View::Plugins;
+                    state.update_notice = None;
                     Command::none()
                 }
                 Message::CatalogPressed => {
@@ -125,6 +262,13 @@ impl Application for Lembas {
                         .update(CatalogMessage::LoadPlugins)
                         .map(Message::CatalogAction)
                 }
+                Message::BackupsPressed => {
+                    state.view = View::Backups;
+                    state
+                        .backups_view
+                        .update(BackupMessage::LoadBackups)
+                        .map(Message::BackupAction)
+                }
                 Message::AboutPressed => {
                     state.view = View::About;
                     Command::none()
@@ -137,20 +281,90 @@ impl Application for Lembas {
                     state.plugins_view.update(msg).map(Message::PluginAction)
                 }
                 Message::CatalogAction(msg) => {
+                    // The versions of the installed plugins come from the feed
+                    if let CatalogMessage::FeedRefreshed(result) = &msg {
+                        state.plugins_view.update(PluginMessage::LoadPlugins);
+                        if state.checking_updates && result.is_ok() {
+                            state.announce_updates();
+                        }
+                        state.checking_updates = false;
+                    }
                     state.catalog_view.update(msg).map(Message::CatalogAction)
                 }
-                Message::Loaded(_) => Command::none(),
-                Message::ConfigAction(msg) => {
-                    state.config_view.update(msg);
+                // The timer follows the update check interval, the feed refresh interval must not skip the check
+                Message::UpdateCheckTick if !state.catalog_view.is_refreshing() => {
+                    state.checking_updates = true;
+                    state.catalog_view.refresh(true).map(Message::CatalogAction)
+                }
+                Message::UpdateNoticeClosed => {
+                    state.update_notice = None;
                     Command::none()
                 }
+                Message::BackupAction(msg) => {
+                    state.backups_view.update(msg).map(Message::BackupAction)
+                }
+                // Queued jobs hold the cache and the directories of the active profile
+                Message::ProfileSelected(name)
+                    if name != state.active_profile && state.has_running_jobs() =>
+                {
+                    state.update_notice = Some(format!(
+                        "Wait until the installations finished before switching to {}",
+                        name
+                    ));
+                    Command::none()
+                }
+                Message::ConfigAction(
+                    ConfigMessage::PickerConfirmed | ConfigMessage::PickerDefaultPressed,
+                ) if state.has_running_jobs() => {
+                    state.config_view.refuse_directory_change(
+                        "Wait until the installations finished before changing a directory",
+                    );
+                    Command::none()
+                }
+                Message::ProfileSelected(name) if name != state.active_profile => {
+                    let result = read_existing_settings_file().and_then(|mut settings| {
+                        settings.set_active_profile(&name)?;
+                        save_settings_changes(&settings)
+                    });
+                    if let Err(error) = result {
+                        debug!("Can't switch to profile {}: {}", name, error);
+                        return Command::none();
+                    }
+                    set_profile_override(None);
+
+                    *self = Lembas::Loading;
+                    Command::perform(Self::init_application(), Message::Loaded)
+                }
+                Message::Loaded(_)
+                | Message::UpdateCheckTick
+                | Message::RetryPressed
+                | Message::InstallationSelected(_)
+                | Message::ProfileSelected(_) => Command::none(),
+                Message::ConfigAction(msg) => match state.config_view.update(msg) {
+                    ConfigEvent::Nothing => {
+                        match read_existing_settings_file() {
+                            Ok(settings) => {
+                                state.load_profiles(&settings);
+                                state.update_check = settings.update_check_interval();
+                            }
+                            Err(error) => debug!("Can't reload the settings: {}", error),
+                        }
+                        Command::none()
+                    }
+                    ConfigEvent::DirectoriesChanged => {
+                        *self = Lembas::Loading;
+                        Command::perform(Self::init_application(), Message::Loaded)
+                    }
+                },
             },
         }
     }
 
     fn view(&self) -> Element<Message> {
         match self {
+            Lembas::SelectInstallation(installations) => select_installation(installations),
             Lembas::Loading => loading_data(),
+            Lembas::Failed(error) => loading_failed(error),
             Lembas::Loaded(state) => {
                 let plugins_btn =
                     button(text("My Plugins").horizontal_alignment(Horizontal::Center))
@@ -163,6 +377,11 @@ impl Application for Lembas {
                     .width(Length::Units(100))
                     .padding(5)
                     .style(style::PrimaryButton::Enabled);
+                let backups_btn = button(text("Backups").horizontal_alignment(Horizontal::Center))
+                    .on_press(Message::BackupsPressed)
+                    .width(Length::Units(100))
+                    .padding(5)
+                    .style(style::PrimaryButton::Enabled);
                 let about_btn = button(text("About").horizontal_alignment(Horizontal::Center))
                     .on_press(Message::AboutPressed)
                     .width(Length::Units(100))
@@ -178,6 +397,14 @@ impl Application for Lembas {
                 let mut image_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
                 image_path.push("resources/assets/bread_light.png");
 
+                let profile_picker = pick_list(
+                    &state.profiles[..],
+                    Some(state.active_profile.clone()),
+                    Message::ProfileSelected,
+                )
+                .width(Length::Units(100))
+                .padding(5);
+
                 let sidebar = column()
                     .spacing(20)
                     .align_items(Alignment::Center)
@@ -186,8 +413,10 @@ impl Application for Lembas {
                             .width(Length::Units(85))
                             .height(Length::Units(85)),
                     )
+                    .push(profile_picker)
                     .push(plugins_btn)
                     .push(catalog_btn)
+                    .push(backups_btn)
                     .push(Space::new(Length::Shrink, Length::Fill))
                     .push(about_btn)
                     .push(settings_btn);
@@ -198,23 +427,20 @@ impl Application for Lembas {
                     .padding(25)
                     .style(style::NavigationContainer);
 
-                match state.view {
-                    View::Plugins => {
-                        let main_container = state.plugins_view.view().map(Message::PluginAction);
-                        row().push(navigation_container).push(main_container).into()
-                    }
-                    View::Catalog => {
-                        let main_container = state.catalog_view.view().map(Message::CatalogAction);
-                        row().push(navigation_container).push(main_container).into()
-                    }
-                    View::About => {
-                        let main_container = state.about_view.view();
-                        row().push(navigation_container).push(main_container).into()
-                    }
-                    View::Configuration => {
-                        let main_container = state.config_view.view().map(Message::ConfigAction);
-                        row().push(navigation_container).push(main_container).into()
-                    }
+                let main_container = match state.view {
+                    View::Plugins => state.plugins_view.view().map(Message::PluginAction),
+                    View::Catalog => state.catalog_view.view().map(Message::CatalogAction),
+                    View::Backups => state.backups_view.view().map(Message::BackupAction),
+                    View::About => state.about_view.view(),
+                    View::Configuration => state.config_view.view().map(Message::ConfigAction),
+                };
+
+                match &state.update_notice {
+                    Some(notice) => row()
+                        .push(navigation_container)
+                        .push(column().push(update_notice(notice)).push(main_container))
+                        .into(),
+                    None => row().push(navigation_container).push(main_container).into(),
                 }
             }
         }
@@ -245,38 +471,118 @@ impl Lembas {
         Lembas::run(settings).unwrap_err();
     }
 
-    pub async fn init_application() -> State {
+    pub async fn init_application() -> Result<Box<State>, String> {
+        Self::load_state()
+            .map(Box::new)
+            .map_err(|error| error.to_string())
+    }
+
+    fn load_state() -> Result<State, Error> {
         let database_path = get_database_file_path();
-        initialize_directories();
-
-        let manager = SqliteConnectionManager::file(&database_path);
-        let pool = r2d2::Pool::new(manager).expect("Error while creating a database pool");
-
-        let cache = Cache::new(pool.clone());
-
-        cache.create_cache_db().expect("Unable to create cache db");
-        // Synchronizer::synchronize_application(&plugins_dir, &database_path, &settings.feed_url)
-        //     .await
-        //     .unwrap();
-
-        task::spawn(async {
-            debug!("Started fetching plugins from lotrocompendium");
-            let settings = read_existing_settings_file();
-            let cache = Cache::new(pool);
-            let result = FeedDownloader::fetch_feed_content(settings.feed_url).await;
-            if let Ok(content) = result {
-                let plugins = FeedUrlParser::parse_response_xml(&content);
-                if let Err(err) = cache.sync_plugins(&plugins) {
-                    log::debug!("Error while syncing the plugins during startup. {}", err);
-                }
-            }
-            debug!("Finished fetching plugins from lotrocompendium");
-        });
+        initialize_directories()?;
+        let settings = read_existing_settings_file()?;
 
-        State::new(&Arc::new(cache))
+        let cache = Cache::open(&database_path)?;
+
+        cache.create_cache_db()?;
+        if let Err(err) = Synchronizer::synchronize(&cache, &get_plugins_dir()) {
+            debug!("Error while synchronizing the plugins directory. {}", err);
+        }
+
+        Ok(State::new(&Arc::new(cache), &settings))
     }
 }
 
+fn select_installation(installations: &[Installation]) -> Element<Message> {
+    let installations = installations.iter().fold(
+        column().spacing(10).width(Length::Fill),
+        |col, installation| {
+            col.push(
+                row()
+                    .align_items(Alignment::Center)
+                    .spacing(10)
+                    .push(text(installation.source.to_string()).width(Length::FillPortion(2)))
+                    .push(
+                        text(installation.documents_dir.display().to_string())
+                            .width(Length::FillPortion(8)),
+                    )
+                    .push(
+                        button(text("Use"))
+                            .on_press(Message::InstallationSelected(
+                                installation.documents_dir.clone(),
+                            ))
+                            .padding(5)
+                            .style(style::PrimaryButton::Enabled),
+                    ),
+            )
+        },
+    );
+
+    let content = column()
+        .spacing(20)
+        .padding(25)
+        .push(text("Several LOTRO installations were found").size(20))
+        .push(text(
+            "Choose the installation whose plugins Lembas manages. You can change it later with lembas installations.",
+        ))
+        .push(scrollable(installations));
+
+    container(content)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(style::Content)
+        .into()
+}
+
+fn update_notice(notice: &str) -> Element<Message> {
+    let content = row()
+        .width(Length::Fill)
+        .align_items(Alignment::Center)
+        .spacing(10)
+        .push(text(notice).width(Length::Fill))
+        .push(
+            button(text("My Plugins"))
+                .on_press(Message::PluginsPressed)
+                .padding(5)
+                .style(style::PrimaryButton::Enabled),
+        )
+        .push(
+            button(text("Close"))
+                .on_press(Message::UpdateNoticeClosed)
+                .padding(5)
+                .style(style::PrimaryButton::Enabled),
+        );
+
+    container(content)
+        .width(Length::Fill)
+        .padding(10)
+        .style(style::NavigationContainer)
+        .into()
+}
+
+fn loading_failed(error: &str) -> Element<Message> {
+    let content = column()
+        .spacing(20)
+        .align_items(Alignment::Center)
+        .push(text("Lembas can't be started").size(20))
+        .push(text(error))
+        .push(
+            button(text("Retry"))
+                .on_press(Message::RetryPressed)
+                .padding(5)
+                .style(style::PrimaryButton::Enabled),
+        );
+
+    container(content)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(25)
+        .center_y()
+        .center_x()
+        .style(style::Content)
+        .into()
+}
+
 fn loading_data<'a>() -> Element<'a, Message> {
     container(
         text("Plugins loading...")
@@ -1,20 +1,36 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::{atomic::AtomicBool, Arc};
 
+use crate::core::backup::{BackupManager, BackupManifest};
 use crate::core::config::{
-    get_database_file_path, get_plugins_dir, get_tmp_dir, read_existing_settings_file,
+    get_archives_dir, get_database_file_path, get_plugins_dir, get_storage_dir, get_tmp_dir,
+    read_existing_settings_file,
 };
+use crate::core::dependencies::{dependents, DependencyReport, DependencyResolver};
+use crate::core::error::Error;
+use crate::core::installer::ProgressListener;
 use crate::core::io::cache::{self, DatabaseHandler};
-use crate::core::lotro_compendium::{Downloader, FeedDownloader, FeedUrlParser};
+use crate::core::io::feed_snapshot::format_age;
+use crate::core::io::{ArchiveStore, FeedSnapshot, Synchronizer};
+use crate::core::lotro_compendium::{refresh_feed, FeedDownloader, FeedRefresh, FeedSource};
+use crate::core::update::{UpdatePlan, UpdateSummary};
+use crate::core::version::{offered_update, VersionOrdering};
 use crate::core::{Installer, Plugin};
+use crate::gui::queue::{job_row, InstallQueue, Job, JobDirs, JobFailure, JobStatus, QueueEvent};
 use crate::gui::style;
 use cache::Cache;
-use iced::pure::{button, column, container, row, scrollable, text, text_input, Element};
+use chrono::Utc;
+use iced::pure::{button, checkbox, column, container, row, scrollable, text, text_input, Element};
 use iced::{alignment::Horizontal, Alignment, Command, Length, Space};
 use log::debug;
-use r2d2_sqlite::SqliteConnectionManager;
 use serde::{Deserialize, Serialize};
 use tokio::task;
 
+/// Queue id of a local installation. Local plugins have negative ids and plugins of the catalog positive ones.
+const LOCAL_INSTALL_JOB: i32 = 0;
+
 #[derive(Debug, Clone)]
 pub enum Plugins {
     Loaded(State),
@@ -24,7 +40,37 @@ pub enum Plugins {
 pub struct State {
     cache: Arc<Cache>,
     input_value: String,
+    local_path: String,
+    local_status: String,
+    /// Readable message of the last refresh or "Update all" that failed
+    status: String,
     pub plugins: Vec<PluginRow>,
+    queue: InstallQueue,
+    /// What the queued jobs of this view do, so their result is shown where it belongs
+    jobs: HashMap<i32, JobKind>,
+    update_run: Option<UpdateRun>,
+    /// Result of the last "Update all", shown until it's closed
+    summary: Option<UpdateSummary>,
+}
+
+#[derive(Debug, Clone)]
+enum JobKind {
+    Install,
+    LocalInstall,
+    /// Contains the version that is restored
+    Rollback(String),
+    Delete,
+}
+
+/// An "Update all" whose plugins are still being updated.
+#[derive(Debug, Clone)]
+struct UpdateRun {
+    plan: UpdatePlan,
+    /// Directories of the profile the run was started for
+    dirs: JobDirs,
+    /// Plugins whose update didn't finish yet
+    remaining: HashMap<i32, Plugin>,
+    summary: UpdateSummary,
 }
 
 #[derive(Debug, Clone)]
@@ -35,18 +81,30 @@ pub enum PluginMessage {
     PluginInputChanged(String),
     RefreshPressed,
     UpdateAllPressed,
-    DbRefreshed(Result<(), ApplicationError>),
+    UpdateBackupFinished(Result<Option<BackupManifest>, String>),
+    SummaryClosed,
+    DbRefreshed(Result<FeedRefresh, String>),
+    LocalPathChanged(String),
+    InstallLocalPressed,
 
     // Plugin View
     Plugin(usize, RowMessage),
+    Queue(QueueEvent),
 }
 
 impl Plugins {
-    pub fn new(cache: Arc<Cache>) -> Self {
+    pub fn new(cache: Arc<Cache>, queue: InstallQueue) -> Self {
         let mut state = State {
             cache,
             plugins: Vec::new(),
             input_value: String::new(),
+            local_path: String::new(),
+            local_status: String::new(),
+            status: String::new(),
+            queue,
+            jobs: HashMap::new(),
+            update_run: None,
+            summary: None,
         };
 
         state.plugins = Plugins::populate_plugin_rows(&state);
@@ -54,79 +112,165 @@ impl Plugins {
         Self::Loaded(state)
     }
 
-    async fn refresh_db() -> Result<(), ApplicationError> {
+    async fn refresh_db() -> Result<FeedRefresh, String> {
+        Self::synchronize().await.map_err(|error| error.to_string())
+    }
+
+    async fn synchronize() -> Result<FeedRefresh, Error> {
         let database_path = get_database_file_path();
-        let manager = SqliteConnectionManager::file(&database_path);
-        let pool = r2d2::Pool::new(manager).expect("Error while creating a database pool");
-
-        task::spawn(async {
-            debug!("Started fetching plugins from lotrocompendium");
-            let settings = read_existing_settings_file();
-            let cache = Cache::new(pool);
-            let result = FeedDownloader::fetch_feed_content(settings.feed_url).await;
-            if let Ok(content) = result {
-                let plugins = FeedUrlParser::parse_response_xml(&content);
-                if let Err(err) = cache.sync_plugins(&plugins) {
-                    log::debug!("Error while syncing the plugins during startup. {}", err);
-                }
-            }
-            debug!("Finished fetching plugins from lotrocompendium");
-        });
-        Ok(())
+        let cache = Cache::open(&database_path)?;
+        let settings = read_existing_settings_file()?;
+
+        let synchronized = Synchronizer::synchronize(&cache, &get_plugins_dir());
+
+        // The feed is fetched even if the plugins directory can't be read
+        let snapshot = FeedSnapshot::new(&get_storage_dir());
+        let refresh = refresh_feed::<FeedDownloader>(
+            &cache,
+            &snapshot,
+            &settings.feed_url,
+            settings.feed_refresh_interval(),
+        )
+        .await?;
+        synchronized.map(|_| refresh)
     }
 
     fn populate_plugin_rows(state: &State) -> Vec<PluginRow> {
         let mut plugins: Vec<PluginRow> = Vec::new();
 
-        let mut tmp_plugins: Vec<Plugin> = state
-            .cache
-            .get_installed_plugins()
-            .values()
-            .cloned()
-            .collect();
+        let mut tmp_plugins: Vec<Plugin> = match state.cache.get_installed_plugins() {
+            Ok(plugins) => plugins.into_values().collect(),
+            Err(error) => {
+                debug!("Can't read the installed plugins: {}", error);
+                Vec::new()
+            }
+        };
         tmp_plugins.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
         for plugin in tmp_plugins {
-            plugins.push(PluginRow::new(
-                plugin.id,
-                &plugin.name,
-                &plugin.author,
-                &plugin.description,
-                &plugin.current_version,
-                &plugin.latest_version,
-                &plugin.download_url,
-            ));
+            plugins.push(
+                PluginRow::new(
+                    plugin.id,
+                    &plugin.name,
+                    &plugin.author,
+                    &plugin.description,
+                    &plugin.current_version,
+                    &plugin.latest_version,
+                    &plugin.download_url,
+                )
+                .with_origin(ArchiveOrigin::of(&plugin))
+                .with_pinned(plugin.pinned == 1)
+                .with_ignore_updates_until(&plugin.ignore_updates_until)
+                .with_rollback_version(Plugins::rollback_version(state, &plugin)),
+            );
         }
         plugins
     }
 
+    /// Returns the version that was installed before the current version if its archive is still stored.
+    fn rollback_version(state: &State, plugin: &Plugin) -> Option<String> {
+        let installer = Installer::new(&get_tmp_dir(), &get_plugins_dir(), plugin.id, &plugin.name)
+            .with_archive_store(ArchiveStore::new(&get_archives_dir()));
+
+        match installer.rollback_candidates(&state.cache, plugin.id) {
+            Ok(candidates) => candidates.into_iter().next().map(|entry| entry.version),
+            Err(error) => {
+                debug!(
+                    "Can't read the version history of {}: {}",
+                    plugin.name, error
+                );
+                None
+            }
+        }
+    }
+
     pub fn update(&mut self, message: PluginMessage) -> Command<PluginMessage> {
         match self {
             Plugins::Loaded(state) => match message {
                 PluginMessage::Plugin(index, msg) => {
                     let update_event = state.plugins[index].update(msg, &*state.cache);
-                    if let Event::Synchronize = update_event.0 {
-                        state.plugins = Plugins::populate_plugin_rows(state);
+                    match update_event.0 {
+                        Event::Nothing => {}
+                        Event::Install(row) => {
+                            return Plugins::queue_install(state, *row);
+                        }
+                        Event::Rollback(row, version) => {
+                            return Plugins::queue_rollback(state, &row, version);
+                        }
+                        Event::Delete(row) => return Plugins::queue_delete(state, &row),
+                        Event::Cancel(plugin_id) => state.queue.cancel(plugin_id),
                     }
                     update_event
                         .1
                         .map(move |msg| PluginMessage::Plugin(index, msg))
                 }
+                PluginMessage::Queue(event) => {
+                    let command = state.queue.update(&event);
+                    if let QueueEvent::Finished { plugin_id, result } = &event {
+                        Plugins::finish_update_run(state, *plugin_id, result);
+                        match state.jobs.remove(plugin_id) {
+                            Some(JobKind::LocalInstall) => {
+                                Plugins::finish_local_install(state, result);
+                            }
+                            Some(JobKind::Rollback(version)) => {
+                                Plugins::finish_rollback(state, *plugin_id, &version, result);
+                            }
+                            Some(JobKind::Delete) => {
+                                Plugins::finish_delete(state, *plugin_id, result);
+                            }
+                            Some(JobKind::Install) | None => {
+                                Plugins::finish_install(state, *plugin_id, result);
+                            }
+                        }
+                    }
+                    command.map(PluginMessage::Queue)
+                }
 
                 PluginMessage::RefreshPressed => {
                     Command::perform(Self::refresh_db(), PluginMessage::DbRefreshed)
                 }
                 PluginMessage::UpdateAllPressed => {
-                    let tmp_dir = get_tmp_dir();
-                    let plugins_dir = get_plugins_dir();
-                    for element in &state.plugins {
-                        if element.current_version != element.latest_version {
-                            debug!("Update plugin: {}", element.title);
-                            let mut installer =
-                                Installer::new(&tmp_dir, &plugins_dir, element.id, &element.title);
-                            installer.run_installation(&state.cache, element);
-                            debug!("Update finished: {}", element.title);
-                        }
+                    if state.update_run.is_some() {
+                        return Command::none();
                     }
+                    // Only plugins with a version that is known to be newer are updated, pinned plugins and
+                    // ignored updates are listed as skipped
+                    let installed_plugins = match state.cache.get_installed_plugins() {
+                        Ok(plugins) => plugins,
+                        Err(error) => {
+                            state.status = error.to_string();
+                            return Command::none();
+                        }
+                    };
+                    let Some(dirs) = Plugins::job_dirs(state) else {
+                        return Command::none();
+                    };
+                    let plan = UpdatePlan::new(installed_plugins.into_values());
+                    state.summary = None;
+                    state.status = String::new();
+                    state.update_run = Some(UpdateRun {
+                        remaining: HashMap::new(),
+                        summary: UpdateSummary::new(&plan, None),
+                        plan: plan.clone(),
+                        dirs: dirs.clone(),
+                    });
+
+                    Command::perform(
+                        async move {
+                            task::spawn_blocking(move || {
+                                plan.backup(dirs.backup.as_ref())
+                                    .map_err(|error| error.to_string())
+                            })
+                            .await
+                            .unwrap_or_else(|error| Err(error.to_string()))
+                        },
+                        PluginMessage::UpdateBackupFinished,
+                    )
+                }
+                PluginMessage::UpdateBackupFinished(result) => {
+                    Plugins::start_update_run(state, result)
+                }
+                PluginMessage::SummaryClosed => {
+                    state.summary = None;
                     Command::none()
                 }
                 PluginMessage::LoadPlugins => {
@@ -152,16 +296,296 @@ impl Plugins {
                     Command::none()
                 }
                 PluginMessage::DbRefreshed(result) => {
-                    if result.is_ok() {
-                        state.plugins = Plugins::populate_plugin_rows(state);
+                    match result {
+                        Ok(refresh) => {
+                            state.status = match refresh.source {
+                                FeedSource::Downloaded
+                                | FeedSource::NotModified
+                                | FeedSource::Skipped => String::new(),
+                                FeedSource::Offline(_) => format!(
+                                    "Offline, using the catalog from {}",
+                                    format_age(refresh.fetched_at, Utc::now().timestamp())
+                                ),
+                            };
+                            state.plugins = Plugins::populate_plugin_rows(state);
+                        }
+                        Err(error) => {
+                            debug!("Refresh failed: {}", error);
+                            state.status = format!("Refresh failed: {}", error);
+                        }
                     }
                     Command::none()
                 }
+                PluginMessage::LocalPathChanged(path) => {
+                    state.local_path = path;
+                    state.local_status = String::new();
+                    Command::none()
+                }
+                PluginMessage::InstallLocalPressed => Plugins::queue_local_install(state),
             },
         }
     }
 
-    fn build_header(input_value: &str, plugins_amount: usize) -> Element<'static, PluginMessage> {
+    /// Queues the planned updates once the shared backup exists. Nothing is updated if the backup failed.
+    fn start_update_run(
+        state: &mut State,
+        backup: Result<Option<BackupManifest>, String>,
+    ) -> Command<PluginMessage> {
+        let Some(mut run) = state.update_run.take() else {
+            return Command::none();
+        };
+
+        let backup = match backup {
+            Ok(backup) => backup,
+            Err(error) => {
+                debug!("Backup before the update failed: {}", error);
+                for plugin in &run.plan.plugins {
+                    run.summary
+                        .add_failed(plugin, &format!("backup failed: {}", error));
+                }
+                state.summary = Some(run.summary);
+                return Command::none();
+            }
+        };
+        run.summary = UpdateSummary::new(&run.plan, backup.as_ref());
+        if run.plan.plugins.is_empty() {
+            state.summary = Some(run.summary);
+            return Command::none();
+        }
+
+        let mut commands = Vec::new();
+        for plugin in run.plan.plugins.clone() {
+            debug!("Update plugin: {}", plugin.name);
+            let plugin_id = plugin.id;
+            let cache = state.cache.clone();
+            let queued = plugin.clone();
+            let dirs = run.dirs.clone();
+            // The shared backup already covers every plugin of the run
+            let job: Job = Box::new(move |listener, cancelled| {
+                PluginRow::install_plugin(&cache, &queued, &dirs, None, listener, cancelled)
+            });
+            run.remaining.insert(plugin_id, plugin);
+            commands.push(state.queue.push(plugin_id, job).map(PluginMessage::Queue));
+        }
+        state.update_run = Some(run);
+
+        Command::batch(commands)
+    }
+
+    /// Adds a finished job to the running "Update all" and shows the summary after its last plugin.
+    fn finish_update_run(
+        state: &mut State,
+        plugin_id: i32,
+        result: &Result<DependencyReport, JobFailure>,
+    ) {
+        let Some(run) = state.update_run.as_mut() else {
+            return;
+        };
+        let Some(plugin) = run.remaining.remove(&plugin_id) else {
+            return;
+        };
+
+        match result {
+            Ok(report) => run.summary.add_updated(&plugin, report),
+            Err(failure) => run.summary.add_failed(&plugin, &failure.to_string()),
+        }
+        if run.remaining.is_empty() {
+            state.summary = state.update_run.take().map(|run| run.summary);
+        }
+    }
+
+    /// Whether a job of this view or an "Update all" is still running.
+    pub fn is_busy(&self) -> bool {
+        match self {
+            Plugins::Loaded(state) => state.queue.is_busy() || state.update_run.is_some(),
+        }
+    }
+
+    /// Resolves the directories of the active profile for a new job. Shows the error if the settings can't be read.
+    fn job_dirs(state: &mut State) -> Option<JobDirs> {
+        match JobDirs::current() {
+            Ok(dirs) => Some(dirs),
+            Err(error) => {
+                state.status = error.to_string();
+                None
+            }
+        }
+    }
+
+    /// Queues a job of this view. A plugin whose job is still queued isn't queued again.
+    fn queue_job(
+        state: &mut State,
+        plugin_id: i32,
+        kind: JobKind,
+        job: Job,
+    ) -> Command<PluginMessage> {
+        if state.queue.status(plugin_id).is_some() {
+            return Command::none();
+        }
+        state.jobs.insert(plugin_id, kind);
+
+        state.queue.push(plugin_id, job).map(PluginMessage::Queue)
+    }
+
+    /// Queues the update of the plugin behind the row. The window stays responsive while it runs.
+    fn queue_install(state: &mut State, row: PluginRow) -> Command<PluginMessage> {
+        debug!("Update plugin: {}", row.title);
+        let plugin_id = row.id;
+        let cache = state.cache.clone();
+        let Some(dirs) = Plugins::job_dirs(state) else {
+            return Command::none();
+        };
+        let job: Job = Box::new(move |listener, cancelled| {
+            PluginRow::install(&cache, &row, &dirs, listener, cancelled)
+        });
+
+        Plugins::queue_job(state, plugin_id, JobKind::Install, job)
+    }
+
+    /// Queues the installation of the local zip file or folder of the input.
+    fn queue_local_install(state: &mut State) -> Command<PluginMessage> {
+        let cache = state.cache.clone();
+        let path = PathBuf::from(state.local_path.trim());
+        let Some(dirs) = Plugins::job_dirs(state) else {
+            return Command::none();
+        };
+        let job: Job = Box::new(move |listener, cancelled| {
+            let plugin = dirs
+                .installer(LOCAL_INSTALL_JOB, "local_install")
+                .with_progress(listener)
+                .with_cancel_flag(cancelled)
+                .install_local(&cache, &path)?;
+
+            Ok(DependencyReport {
+                installed: vec![plugin.name],
+                problems: Vec::new(),
+            })
+        });
+        state.local_status = "Installing".to_string();
+
+        Plugins::queue_job(state, LOCAL_INSTALL_JOB, JobKind::LocalInstall, job)
+    }
+
+    /// Queues the reinstallation of the given version of the plugin behind the row.
+    fn queue_rollback(
+        state: &mut State,
+        row: &PluginRow,
+        version: String,
+    ) -> Command<PluginMessage> {
+        let cache = state.cache.clone();
+        let (plugin_id, title, restored) = (row.id, row.title.clone(), version.clone());
+        let Some(dirs) = Plugins::job_dirs(state) else {
+            return Command::none();
+        };
+        let job: Job = Box::new(move |listener, cancelled| {
+            dirs.installer(plugin_id, &title)
+                .with_progress(listener)
+                .with_cancel_flag(cancelled)
+                .roll_back_to(&cache, plugin_id, &restored)?;
+
+            Ok(DependencyReport::default())
+        });
+
+        Plugins::queue_job(state, plugin_id, JobKind::Rollback(version), job)
+    }
+
+    /// Queues the removal of the plugin behind the row.
+    fn queue_delete(state: &mut State, row: &PluginRow) -> Command<PluginMessage> {
+        let cache = state.cache.clone();
+        let (plugin_id, title) = (row.id, row.title.clone());
+        let Some(dirs) = Plugins::job_dirs(state) else {
+            return Command::none();
+        };
+        let job: Job = Box::new(move |_, _| {
+            dirs.installer(plugin_id, &title)
+                .uninstall(&cache, plugin_id)?;
+            cache.mark_as_uninstalled(&title)?;
+
+            Ok(DependencyReport::default())
+        });
+
+        Plugins::queue_job(state, plugin_id, JobKind::Delete, job)
+    }
+
+    fn finish_local_install(state: &mut State, result: &Result<DependencyReport, JobFailure>) {
+        match result {
+            Ok(report) => {
+                state.local_status = format!("Installed {}", report.installed.join(", "));
+                state.local_path = String::new();
+                state.plugins = Plugins::populate_plugin_rows(state);
+            }
+            Err(failure) => state.local_status = format!("Installation failed: {}", failure),
+        }
+    }
+
+    fn finish_rollback(
+        state: &mut State,
+        plugin_id: i32,
+        version: &str,
+        result: &Result<DependencyReport, JobFailure>,
+    ) {
+        let status = match result {
+            Ok(_) => {
+                state.plugins = Plugins::populate_plugin_rows(state);
+                format!("Rolled back to {}", version)
+            }
+            Err(JobFailure::Cancelled) => "Cancelled".to_string(),
+            Err(_) => "Rollback failed".to_string(),
+        };
+        if let Some(row) = state.plugins.iter_mut().find(|row| row.id == plugin_id) {
+            row.status = status;
+        }
+    }
+
+    fn finish_delete(
+        state: &mut State,
+        plugin_id: i32,
+        result: &Result<DependencyReport, JobFailure>,
+    ) {
+        if result.is_ok() {
+            state.plugins = Plugins::populate_plugin_rows(state);
+        } else if let Some(row) = state.plugins.iter_mut().find(|row| row.id == plugin_id) {
+            row.status = "Delete failed".to_string();
+        }
+    }
+
+    fn finish_install(
+        state: &mut State,
+        plugin_id: i32,
+        result: &Result<DependencyReport, JobFailure>,
+    ) {
+        let Some(row) = state.plugins.iter_mut().find(|row| row.id == plugin_id) else {
+            return;
+        };
+
+        match result {
+            Ok(report) => {
+                debug!("Update finished: {}", row.title);
+                let status = if report.problems.is_empty() {
+                    "Updated"
+                } else {
+                    "Updated with dependency problems"
+                };
+                state.plugins = Plugins::populate_plugin_rows(state);
+                if let Some(row) = state.plugins.iter_mut().find(|row| row.id == plugin_id) {
+                    row.status = status.to_string();
+                }
+            }
+            Err(failure) => {
+                row.status = match failure {
+                    JobFailure::Cancelled => "Cancelled".to_string(),
+                    JobFailure::CorruptDownload => "Corrupt download".to_string(),
+                    JobFailure::Failed(message) => format!("Update failed: {}", message),
+                };
+            }
+        }
+    }
+
+    fn build_header<'a>(
+        input_value: &str,
+        plugins_amount: usize,
+        status: &str,
+    ) -> Element<'a, PluginMessage> {
         let refresh_button = button(text("Refresh"))
             .on_press(PluginMessage::RefreshPressed)
             .padding(5)
@@ -186,6 +610,86 @@ impl Plugins {
             .push(update_all_button)
             .push(installed_plugins)
             .push(search_plugins)
+            .push(text(status))
+            .into()
+    }
+
+    fn build_local_install_row<'a>(
+        local_path: &str,
+        local_status: &str,
+    ) -> Element<'a, PluginMessage> {
+        let local_path = text_input(
+            "Path to a local zip file or plugin folder...",
+            local_path,
+            PluginMessage::LocalPathChanged,
+        )
+        .on_submit(PluginMessage::InstallLocalPressed)
+        .padding(5);
+        let install_button = button(text("Install local"))
+            .on_press(PluginMessage::InstallLocalPressed)
+            .padding(5)
+            .style(style::PrimaryButton::Enabled);
+
+        row()
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .push(local_path)
+            .push(install_button)
+            .push(text(local_status))
+            .into()
+    }
+
+    /// Lists what the last "Update all" updated, what failed and why and what was skipped.
+    fn build_summary(summary: &UpdateSummary) -> Element<'_, PluginMessage> {
+        let mut lines = column().width(Length::Fill).spacing(5);
+        if let Some(backup) = &summary.backup {
+            lines = lines.push(text(format!("Created backup {}", backup)));
+        }
+        for updated in &summary.updated {
+            let mut line = format!(
+                "Updated {} from {} to {}",
+                updated.name, updated.from, updated.to
+            );
+            if !updated.dependencies.is_empty() {
+                let _ = write!(line, " with {}", updated.dependencies.join(", "));
+            }
+            lines = lines.push(text(line));
+            for problem in &updated.problems {
+                lines = lines.push(text(format!("Warning: {}", problem)).size(16));
+            }
+        }
+        for failed in &summary.failed {
+            lines = lines.push(text(format!(
+                "Update of {} failed: {}",
+                failed.name, failed.error
+            )));
+        }
+        for skipped in &summary.skipped {
+            lines = lines.push(text(format!(
+                "Skipped {} {}: {}",
+                skipped.name, skipped.version, skipped.reason
+            )));
+        }
+        if summary.updated.is_empty() && summary.failed.is_empty() && summary.skipped.is_empty() {
+            lines = lines.push(text("Every plugin is up to date"));
+        }
+
+        let close_button = button(text("Close"))
+            .on_press(PluginMessage::SummaryClosed)
+            .padding(5)
+            .style(style::PrimaryButton::Enabled);
+        let content = row()
+            .width(Length::Fill)
+            .spacing(10)
+            .align_items(Alignment::Start)
+            .push(lines)
+            .push(close_button);
+
+        container(content)
+            .width(Length::Fill)
+            .padding(15)
+            .style(style::NavigationContainer)
             .into()
     }
 
@@ -194,9 +698,17 @@ impl Plugins {
             Plugins::Loaded(State {
                 cache: _,
                 input_value,
+                local_path,
+                local_status,
+                status,
                 plugins,
+                queue,
+                jobs: _,
+                update_run: _,
+                summary,
             }) => {
-                let header = Plugins::build_header(input_value, plugins.len());
+                let header = Plugins::build_header(input_value, plugins.len(), status);
+                let local_install_row = Plugins::build_local_install_row(local_path, local_status);
 
                 let plugin_name = text("Plugin").width(Length::FillPortion(6));
                 let current_version = text("Current Version").width(Length::FillPortion(3));
@@ -211,12 +723,15 @@ impl Plugins {
                     .push(latest_version)
                     .push(update);
 
-                let plugins = plugins
-                    .iter()
-                    .enumerate()
-                    .fold(column().padding([0, 13, 0, 0]).spacing(5), |col, (i, p)| {
-                        col.push(p.view().map(move |msg| PluginMessage::Plugin(i, msg)))
-                    });
+                let plugins = plugins.iter().enumerate().fold(
+                    column().padding([0, 13, 0, 0]).spacing(5),
+                    |col, (i, p)| {
+                        col.push(
+                            p.view(queue.status(p.id))
+                                .map(move |msg| PluginMessage::Plugin(i, msg)),
+                        )
+                    },
+                );
 
                 let plugins_scrollable = scrollable(plugins)
                     .scrollbar_width(10)
@@ -227,8 +742,13 @@ impl Plugins {
                     .spacing(10)
                     .align_items(Alignment::Center)
                     .push(header)
-                    .push(plugin_panel)
-                    .push(plugins_scrollable);
+                    .push(local_install_row);
+                let content = match summary {
+                    Some(summary) => content.push(Plugins::build_summary(summary)),
+                    None => content,
+                }
+                .push(plugin_panel)
+                .push(plugins_scrollable);
 
                 container(content)
                     .width(Length::Fill)
@@ -241,6 +761,30 @@ impl Plugins {
     }
 }
 
+/// Where the installed archive of a plugin came from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArchiveOrigin {
+    /// Downloaded from the feed without a checksum to verify it
+    #[default]
+    Unverified,
+    /// Downloaded from the feed and verified against its checksum
+    Verified,
+    /// Installed from a local zip file or folder
+    Local,
+}
+
+impl ArchiveOrigin {
+    fn of(plugin: &Plugin) -> Self {
+        if plugin.is_local() {
+            Self::Local
+        } else if plugin.verified == 1 {
+            Self::Verified
+        } else {
+            Self::Unverified
+        }
+    }
+}
+
 // Single row that has a toggle effect to show additional data
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PluginRow {
@@ -251,25 +795,55 @@ pub struct PluginRow {
     #[serde(default)]
     pub current_version: String,
     pub latest_version: String,
+    /// Outcome of the last job of the plugin, shown as plain text below the row
     pub status: String,
     pub download_url: String,
+    #[serde(default)]
+    pub origin: ArchiveOrigin,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Updates to older versions are ignored
+    #[serde(default)]
+    pub ignore_updates_until: String,
+    #[serde(skip)]
+    pub update: VersionOrdering,
+    /// Previous version that can be restored
+    #[serde(skip)]
+    pub rollback_version: Option<String>,
+    /// Version that is typed into the ignore updates input, it's saved on submit
+    #[serde(skip)]
+    ignore_updates_input: String,
 
     #[serde(skip)]
     opened: bool,
+    #[serde(skip)]
+    delete_confirmed: bool,
+    /// Why the delete has to be confirmed, shown next to the delete button
+    #[serde(skip)]
+    delete_notice: String,
 }
 
 #[derive(Clone, Debug)]
 pub enum RowMessage {
     ToggleView,
 
-    UpdatePressed(PluginRow),
-    DeletePressed(PluginRow),
+    UpdatePressed(Box<PluginRow>),
+    CancelPressed,
+    DeletePressed(Box<PluginRow>),
+    RollbackPressed(Box<PluginRow>),
+    PinToggled(bool),
+    IgnoreUpdatesUntilChanged(String),
+    IgnoreUpdatesUntilSubmitted,
     WebsitePressed(i32, String),
 }
 
 pub enum Event {
     Nothing,
-    Synchronize,
+    Install(Box<PluginRow>),
+    /// Contains the version that is restored
+    Rollback(Box<PluginRow>, String),
+    Delete(Box<PluginRow>),
+    Cancel(i32),
 }
 
 impl PluginRow {
@@ -282,103 +856,218 @@ impl PluginRow {
         latest_version: &str,
         download_url: &str,
     ) -> Self {
-        if current_version == latest_version {
-            Self {
-                id,
-                title: title.to_string(),
-                author: author.to_string(),
-                description: description.to_string(),
-                current_version: current_version.to_string(),
-                latest_version: latest_version.to_string(),
-                status: "".to_string(),
-                download_url: download_url.to_string(),
-                opened: false,
-            }
-        } else {
-            Self {
-                id,
-                title: title.to_string(),
-                author: author.to_string(),
-                description: description.to_string(),
-                current_version: current_version.to_string(),
-                latest_version: latest_version.to_string(),
-                status: "Update".to_string(),
-                download_url: download_url.to_string(),
-                opened: false,
-            }
+        let update = offered_update(latest_version, current_version, false, "");
+
+        Self {
+            id,
+            title: title.to_string(),
+            author: author.to_string(),
+            description: description.to_string(),
+            current_version: current_version.to_string(),
+            latest_version: latest_version.to_string(),
+            status: String::new(),
+            download_url: download_url.to_string(),
+            origin: ArchiveOrigin::default(),
+            pinned: false,
+            ignore_updates_until: String::new(),
+            update,
+            rollback_version: None,
+            ignore_updates_input: String::new(),
+            opened: false,
+            delete_confirmed: false,
+            delete_notice: String::new(),
+        }
+    }
+
+    /// Text of the update badge. Versions that can't be compared are offered as a manual update.
+    fn update_status(update: VersionOrdering) -> &'static str {
+        match update {
+            VersionOrdering::Newer => "Update",
+            VersionOrdering::Incomparable => "Update?",
+            VersionOrdering::Older | VersionOrdering::Same => "",
         }
     }
 
+    fn has_update(&self) -> bool {
+        matches!(
+            self.update,
+            VersionOrdering::Newer | VersionOrdering::Incomparable
+        )
+    }
+
+    pub fn with_origin(mut self, origin: ArchiveOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    fn is_local(&self) -> bool {
+        self.origin == ArchiveOrigin::Local
+    }
+
+    pub fn with_pinned(mut self, pinned: bool) -> Self {
+        self.pinned = pinned;
+        self.refresh_update();
+        self
+    }
+
+    pub fn with_ignore_updates_until(mut self, version: &str) -> Self {
+        self.ignore_updates_until = version.to_string();
+        self.ignore_updates_input = version.to_string();
+        self.refresh_update();
+        self
+    }
+
+    /// Recomputes the update badge after the pin or the ignored version changed.
+    fn refresh_update(&mut self) {
+        self.update = offered_update(
+            &self.latest_version,
+            &self.current_version,
+            self.pinned,
+            &self.ignore_updates_until,
+        );
+    }
+
+    pub fn with_rollback_version(mut self, version: Option<String>) -> Self {
+        self.rollback_version = version;
+        self
+    }
+
+    /// Installs the latest version of the plugin behind the given row together with its missing dependencies.
+    /// A pinned plugin is unpinned by the update.
+    fn install(
+        cache: &Cache,
+        row: &PluginRow,
+        dirs: &JobDirs,
+        listener: ProgressListener,
+        cancelled: Arc<AtomicBool>,
+    ) -> Result<DependencyReport, Error> {
+        let plugin = cache.get_plugin(&row.title)?.ok_or_else(|| {
+            Error::plugin(format!("Plugin {} doesn't exist in the cache", row.title))
+        })?;
+
+        PluginRow::install_plugin(
+            cache,
+            &plugin,
+            dirs,
+            dirs.backup.clone(),
+            listener,
+            cancelled,
+        )
+    }
+
+    /// Installs the latest version of a plugin. Without a backup manager the plugins directory isn't backed up
+    /// first.
+    fn install_plugin(
+        cache: &Cache,
+        plugin: &Plugin,
+        dirs: &JobDirs,
+        backup: Option<BackupManager>,
+        listener: ProgressListener,
+        cancelled: Arc<AtomicBool>,
+    ) -> Result<DependencyReport, Error> {
+        let report =
+            DependencyResolver::new(cache, &dirs.tmp_dir, &dirs.plugins_dir, Installer::download)
+                .with_archive_store(dirs.archive_store.clone())
+                .with_progress(listener)
+                .with_cancel_flag(cancelled)
+                .with_backup(backup)
+                .install(plugin)?;
+        cache.set_pinned(plugin.id, false)?;
+
+        Ok(report)
+    }
+
     pub fn update(&mut self, message: RowMessage, cache: &Cache) -> (Event, Command<RowMessage>) {
         match message {
             RowMessage::ToggleView => {
                 self.opened = !self.opened;
+                self.delete_confirmed = false;
+                self.delete_notice = String::new();
                 (Event::Nothing, Command::none())
             }
-            RowMessage::UpdatePressed(plugin) => {
-                let tmp_dir = get_tmp_dir();
+            RowMessage::UpdatePressed(plugin) => (Event::Install(plugin), Command::none()),
+            RowMessage::CancelPressed => (Event::Cancel(self.id), Command::none()),
+            RowMessage::DeletePressed(plugin) => {
                 let plugins_dir = get_plugins_dir();
-                let mut installer =
-                    Installer::new(&tmp_dir, &plugins_dir, plugin.id, &plugin.title);
-
-                match installer.run_installation(cache, &plugin) {
-                    (Event::Synchronize, status, latest_version) => {
-                        self.latest_version = latest_version;
-                        self.status = status;
-                        (Event::Synchronize, Command::none())
+
+                // Ask for a second press if other installed plugins still need this one
+                if !self.delete_confirmed {
+                    match dependents(cache, &plugins_dir, plugin.id) {
+                        Ok(dependents) if !dependents.is_empty() => {
+                            debug!("{} is needed by {}", plugin.title, dependents.join(", "));
+                            self.delete_notice = format!("Needed by {}", dependents.join(", "));
+                            self.delete_confirmed = true;
+                            return (Event::Nothing, Command::none());
+                        }
+                        Ok(_) => {}
+                        Err(error) => debug!("Can't check the dependents: {}", error),
                     }
-                    _ => (Event::Nothing, Command::none()),
                 }
+                self.delete_confirmed = false;
+                self.delete_notice = String::new();
+
+                (Event::Delete(plugin), Command::none())
             }
-            RowMessage::DeletePressed(plugin) => {
-                let tmp_dir = get_tmp_dir();
-                let plugins_dir = get_plugins_dir();
-                let mut installer =
-                    Installer::new(&tmp_dir, &plugins_dir, plugin.id, &plugin.title);
-
-                if let Ok(bytes) = installer.download(&plugin.download_url) {
-                    if installer.install(&bytes).is_ok() {
-                        if installer.delete().is_ok() {
-                            if cache.delete_plugin(&plugin.title).is_ok() {
-                                self.status = "Deleted".to_string();
-                                (Event::Synchronize, Command::none())
-                            } else {
-                                self.status = "Delete failed".to_string();
-                                (Event::Nothing, Command::none())
-                            }
-                        } else {
-                            self.status = "Delete failed".to_string();
-                            (Event::Nothing, Command::none())
-                        }
-                    } else {
-                        self.status = "Delete failed".to_string();
-                        (Event::Nothing, Command::none())
+            RowMessage::RollbackPressed(plugin) => {
+                let Some(version) = plugin.rollback_version.clone() else {
+                    return (Event::Nothing, Command::none());
+                };
+
+                (Event::Rollback(plugin, version), Command::none())
+            }
+            RowMessage::PinToggled(pinned) => {
+                match cache.set_pinned(self.id, pinned) {
+                    Ok(()) => {
+                        self.pinned = pinned;
+                        self.refresh_update();
                     }
-                } else {
-                    self.status = "Delete failed".to_string();
-                    (Event::Nothing, Command::none())
+                    Err(error) => debug!("Can't pin {}: {}", self.title, error),
                 }
+                (Event::Nothing, Command::none())
+            }
+            RowMessage::IgnoreUpdatesUntilChanged(version) => {
+                self.ignore_updates_input = version;
+                (Event::Nothing, Command::none())
+            }
+            RowMessage::IgnoreUpdatesUntilSubmitted => {
+                let version = self.ignore_updates_input.trim().to_string();
+                match cache.set_ignore_updates_until(self.id, &version) {
+                    Ok(()) => {
+                        self.ignore_updates_until = version;
+                        self.refresh_update();
+                    }
+                    Err(error) => debug!("Can't ignore the updates of {}: {}", self.title, error),
+                }
+                (Event::Nothing, Command::none())
             }
             RowMessage::WebsitePressed(id, title) => {
-                webbrowser::open(&format!(
+                if let Err(error) = webbrowser::open(&format!(
                     "https://www.lotrointerface.com/downloads/info{}-{}.html",
                     id, title,
-                ))
-                .unwrap();
+                )) {
+                    debug!("Can't open the website of {}: {}", title, error);
+                }
                 (Event::Nothing, Command::none())
             }
         }
     }
 
-    pub fn view(&self) -> Element<'_, RowMessage> {
+    /// The row of a plugin whose installation is queued shows the progress of the job.
+    pub fn view(&self, job: Option<&JobStatus>) -> Element<'_, RowMessage> {
         let plugin = self.clone();
         let bla = self.clone();
 
         let description_label = text("Description");
         let description = text(&self.description.to_string());
+        let verification = text(match self.origin {
+            ArchiveOrigin::Local => "Installed from a local archive",
+            ArchiveOrigin::Verified => "Archive verified against the feed checksum",
+            ArchiveOrigin::Unverified => "Archive unverified",
+        });
         let description_section = column()
             .push(description_label)
             .push(description)
+            .push(verification)
             .spacing(10)
             .width(Length::Fill);
 
@@ -387,27 +1076,76 @@ impl PluginRow {
             .on_press(RowMessage::WebsitePressed(self.id, self.title.clone()))
             .style(style::PrimaryButton::Enabled);
 
-        let delete_btn = button(text("Delete"))
-            .padding(5)
-            .on_press(RowMessage::DeletePressed(bla))
-            .style(style::PrimaryButton::Enabled);
+        let delete_btn = button(text(if self.delete_confirmed {
+            "Delete anyway"
+        } else {
+            "Delete"
+        }))
+        .padding(5)
+        .on_press(RowMessage::DeletePressed(Box::new(bla)))
+        .style(style::PrimaryButton::Enabled);
 
-        let button_row = row()
-            .push(Space::new(Length::Fill, Length::Shrink))
-            .push(website_btn)
+        let mut button_row = row();
+        if self.delete_confirmed {
+            button_row = button_row.push(text(&self.delete_notice));
+        }
+        button_row = button_row.push(Space::new(Length::Fill, Length::Shrink));
+        if let Some(version) = &self.rollback_version {
+            button_row = button_row.push(
+                button(text(format!("Roll back to {}", version)))
+                    .padding(5)
+                    .on_press(RowMessage::RollbackPressed(Box::new(self.clone())))
+                    .style(style::PrimaryButton::Enabled),
+            );
+        }
+        if !self.is_local() {
+            button_row = button_row.push(website_btn);
+        }
+        let button_row = button_row
             .push(delete_btn)
             .width(Length::Fill)
             .spacing(10)
             .align_items(Alignment::End);
 
-        let toggle_section = column().push(description_section).push(button_row);
+        let mut toggle_section = column().push(description_section).spacing(10);
+        // Local and unmanaged plugins have no updates to hold back
+        if !self.is_local() && !self.latest_version.is_empty() {
+            let update_row = row()
+                .width(Length::Fill)
+                .align_items(Alignment::Center)
+                .spacing(10)
+                .push(checkbox(
+                    "Pinned, skip every update",
+                    self.pinned,
+                    RowMessage::PinToggled,
+                ))
+                .push(text("Ignore updates until"))
+                .push(
+                    text_input(
+                        "Version",
+                        &self.ignore_updates_input,
+                        RowMessage::IgnoreUpdatesUntilChanged,
+                    )
+                    .on_submit(RowMessage::IgnoreUpdatesUntilSubmitted)
+                    .padding(5)
+                    .width(Length::Units(150)),
+                )
+                .push(
+                    button(text("Save"))
+                        .on_press(RowMessage::IgnoreUpdatesUntilSubmitted)
+                        .padding(5)
+                        .style(style::PrimaryButton::Enabled),
+                );
+            toggle_section = toggle_section.push(update_row);
+        }
+        let toggle_section = toggle_section.push(button_row);
 
         let container = container(toggle_section)
             .width(Length::Fill)
             .padding(15)
             .style(style::NavigationContainer);
 
-        if self.opened {
+        let content = if self.opened {
             column()
                 .push(
                     button(
@@ -416,18 +1154,24 @@ impl PluginRow {
                             .push(if self.latest_version.is_empty() {
                                 text(&format!("{} (unmanaged)", self.title))
                                     .width(Length::FillPortion(6))
+                            } else if self.is_local() {
+                                text(&format!("{} (local)", self.title))
+                                    .width(Length::FillPortion(6))
+                            } else if self.pinned {
+                                text(&format!("{} (pinned)", self.title))
+                                    .width(Length::FillPortion(6))
                             } else {
                                 text(&self.title).width(Length::FillPortion(6))
                             })
                             .push(text(&self.current_version).width(Length::FillPortion(3)))
                             .push(text(&self.latest_version).width(Length::FillPortion(3)))
-                            .push(if self.latest_version == self.current_version {
+                            .push(if !self.has_update() || job.is_some() {
                                 button(text("."))
                                     .style(style::TransparentButton::Enabled)
                                     .width(Length::FillPortion(2))
                             } else {
-                                button(text(&self.status))
-                                    .on_press(RowMessage::UpdatePressed(plugin))
+                                button(text(PluginRow::update_status(self.update)))
+                                    .on_press(RowMessage::UpdatePressed(Box::new(plugin)))
                                     .style(style::PrimaryButton::Enabled)
                                     .width(Length::FillPortion(2))
                             }),
@@ -436,53 +1180,55 @@ impl PluginRow {
                     .style(style::PluginRow::Enabled),
                 )
                 .push(container)
-                .into()
         } else {
-            column()
-                .push(
-                    button(
-                        row()
-                            .align_items(Alignment::Center)
-                            .push(if self.latest_version.is_empty() {
-                                text(&format!("{} (unmanaged)", self.title))
-                                    .width(Length::FillPortion(6))
-                            } else {
-                                text(&self.title).width(Length::FillPortion(6))
-                            })
-                            .push(text(&self.current_version).width(Length::FillPortion(3)))
-                            .push(text(&self.latest_version).width(Length::FillPortion(3)))
-                            .push(
-                                if self.latest_version == self.current_version
-                                    || self.latest_version.is_empty()
-                                {
-                                    button(
-                                        text(".")
-                                            .width(Length::Fill)
-                                            .horizontal_alignment(Horizontal::Center),
-                                    )
-                                    .style(style::TransparentButton::Enabled)
-                                    .width(Length::FillPortion(2))
-                                } else {
-                                    button(
-                                        text("Update")
-                                            .width(Length::Fill)
-                                            .horizontal_alignment(Horizontal::Center),
-                                    )
-                                    .style(style::PrimaryButton::Enabled)
-                                    .on_press(RowMessage::UpdatePressed(plugin))
-                                    .width(Length::FillPortion(2))
-                                },
-                            ),
-                    )
-                    .on_press(RowMessage::ToggleView)
-                    .style(style::PluginRow::Enabled),
+            column().push(
+                button(
+                    row()
+                        .align_items(Alignment::Center)
+                        .push(if self.latest_version.is_empty() {
+                            text(&format!("{} (unmanaged)", self.title))
+                                .width(Length::FillPortion(6))
+                        } else if self.is_local() {
+                            text(&format!("{} (local)", self.title)).width(Length::FillPortion(6))
+                        } else if self.pinned {
+                            text(&format!("{} (pinned)", self.title)).width(Length::FillPortion(6))
+                        } else {
+                            text(&self.title).width(Length::FillPortion(6))
+                        })
+                        .push(text(&self.current_version).width(Length::FillPortion(3)))
+                        .push(text(&self.latest_version).width(Length::FillPortion(3)))
+                        .push(if !self.has_update() || job.is_some() {
+                            button(
+                                text(".")
+                                    .width(Length::Fill)
+                                    .horizontal_alignment(Horizontal::Center),
+                            )
+                            .style(style::TransparentButton::Enabled)
+                            .width(Length::FillPortion(2))
+                        } else {
+                            button(
+                                text(PluginRow::update_status(self.update))
+                                    .width(Length::Fill)
+                                    .horizontal_alignment(Horizontal::Center),
+                            )
+                            .style(style::PrimaryButton::Enabled)
+                            .on_press(RowMessage::UpdatePressed(Box::new(plugin)))
+                            .width(Length::FillPortion(2))
+                        }),
                 )
-                .into()
+                .on_press(RowMessage::ToggleView)
+                .style(style::PluginRow::Enabled),
+            )
+        };
+
+        match job {
+            Some(status) => content
+                .push(job_row(status, RowMessage::CancelPressed))
+                .into(),
+            None if !self.status.is_empty() => content
+                .push(row().push(text(&self.status)).padding([5, 10]))
+                .into(),
+            None => content.into(),
         }
     }
 }
-
-#[derive(Debug, Clone)]
-pub enum ApplicationError {
-    Synchronize,
-}
@@ -9,5 +9,6 @@
     clippy::return_self_not_must_use
 )]
 
+pub mod cli;
 pub mod core;
 pub mod gui;
@@ -1,9 +1,32 @@
+use clap::Parser;
 use env_logger::Env;
+use lembas::cli::{self, Cli};
 use lembas::gui::views::Lembas;
+use std::process::ExitCode;
 
-fn main() {
-    let env = Env::default().filter_or("RUST_LOG", "lembas=debug");
-    env_logger::init_from_env(env);
+fn main() -> ExitCode {
+    let cli = Cli::parse();
 
-    Lembas::start();
+    // Commands print their own output, debug logs would mix with it
+    let filter = if cli.command.is_some() {
+        "lembas=warn"
+    } else {
+        "lembas=debug"
+    };
+    env_logger::init_from_env(Env::default().filter_or("RUST_LOG", filter));
+
+    if let Some(profile) = &cli.profile {
+        if let Err(error) = cli::use_profile(profile) {
+            eprintln!("Error: {}", error);
+            return ExitCode::from(cli::EXIT_FAILURE);
+        }
+    }
+
+    match cli.command {
+        Some(command) => cli::run(command, cli.json),
+        None => {
+            Lembas::start();
+            ExitCode::SUCCESS
+        }
+    }
 }
@@ -29,6 +29,48 @@ pub mod database_fixtures {
         (cache, test_dir)
     }
 
+    /// Creates a cache on top of a database that uses the initial schema of the `plugins` table.
+    pub fn setup_legacy() -> (Cache, PathBuf) {
+        let uuid = Uuid::new_v4().to_string();
+        let test_dir = env::temp_dir().join(format!("lembas_test_{}", &uuid[..7]));
+        let db_path = test_dir.join("db.sqlite3");
+
+        create_dir_all(&test_dir).unwrap();
+
+        let manager = SqliteConnectionManager::file(&db_path);
+        let pool = r2d2::Pool::new(manager).expect("Error while creating a database pool");
+        pool.get()
+            .unwrap()
+            .execute(
+                "CREATE TABLE plugins (
+                    id INTEGER PRIMARY KEY,
+                    name TEXT UNIQUE NOT NULL,
+                    author TEXT,
+                    current_version TEXT,
+                    plugin_id INTEGER,
+                    description TEXT,
+                    download_url TEXT,
+                    info_url TEXT,
+                    category TEXT,
+                    latest_version TEXT,
+                    downloads INT,
+                    archive_name TEXT,
+                    updated_at INT,
+                    hash TEXT,
+                    installed INT DEFAULT 0
+                );",
+                [],
+            )
+            .unwrap();
+
+        let cache = Cache::new(pool);
+        cache
+            .create_cache_db()
+            .expect("Failed to migrate the temporary db");
+
+        (cache, test_dir)
+    }
+
     pub fn setup_with_items() -> (Cache, PathBuf) {
         let uuid = Uuid::new_v4().to_string();
         let test_dir = env::temp_dir().join(format!("lembas_test_{}", &uuid[..7]));
@@ -78,11 +120,14 @@ pub mod installer_fixtures {
     use std::{
         env,
         fs::create_dir_all,
+        io::{Cursor, Write},
         path::{Path, PathBuf},
     };
 
-    use lembas::core::Installer;
+    use bytes::Bytes;
+    use lembas::core::{io::ArchiveStore, Installer};
     use uuid::Uuid;
+    use zip::{write::FileOptions, ZipWriter};
 
     pub fn setup_dirs() -> (PathBuf, PathBuf, PathBuf) {
         let uuid = Uuid::new_v4().to_string();
@@ -97,7 +142,35 @@ pub mod installer_fixtures {
         (test_dir, tmp_dir, plugins_dir)
     }
 
+    /// Returns an installer that keeps its archives in `archives` next to the tmp directory.
     pub fn installer_fixture(tmp_dir: &Path, plugins_dir: &Path) -> Installer {
+        let archives_dir = tmp_dir.parent().unwrap().join("archives");
         Installer::new(tmp_dir, plugins_dir, 1, "Hello World")
+            .with_archive_store(ArchiveStore::new(&archives_dir))
+    }
+
+    /// Builds a zip archive in memory. Entries that end with a `/` are added as directories.
+    pub fn build_archive(entries: &[(&str, &str)]) -> Bytes {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, content) in entries {
+            if name.ends_with('/') {
+                writer.add_directory(*name, FileOptions::default()).unwrap();
+            } else {
+                writer.start_file(*name, FileOptions::default()).unwrap();
+                writer.write_all(content.as_bytes()).unwrap();
+            }
+        }
+
+        Bytes::from(writer.finish().unwrap().into_inner())
+    }
+
+    pub fn munkey_archive() -> Bytes {
+        build_archive(&[
+            ("Munkey/", ""),
+            ("Munkey/Ah/", ""),
+            ("Munkey/Ah/Main.lua", "import \"Turbine\";"),
+            ("Munkey/Ah.plugin", "<Plugin></Plugin>"),
+        ])
     }
 }
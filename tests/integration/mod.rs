@@ -1,2 +1,16 @@
+pub mod test_archive;
+pub mod test_archive_store;
+pub mod test_backup;
 pub mod test_cache;
+pub mod test_cli;
+pub mod test_config;
+pub mod test_dependencies;
+pub mod test_directories;
+pub mod test_discovery;
+pub mod test_feed_snapshot;
 pub mod test_installer;
+pub mod test_layout;
+pub mod test_lockfile;
+pub mod test_plugin_data;
+pub mod test_synchronizer;
+pub mod test_update;
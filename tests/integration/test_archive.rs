@@ -0,0 +1,200 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Cursor, Write},
+    };
+
+    use bytes::Bytes;
+    use lembas::core::{
+        archive::{
+            extract_archive, validate_archive, ArchiveLimits, UnsafeArchiveEntry, UnsafeEntryReason,
+        },
+        error::ArchiveError,
+        Error,
+    };
+    use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+    use crate::fixtures::installer_fixtures::{build_archive, installer_fixture, setup_dirs};
+
+    fn refused_entry(bytes: &[u8], limits: &ArchiveLimits) -> UnsafeArchiveEntry {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        match validate_archive(&mut archive, limits).unwrap_err() {
+            Error::Archive(ArchiveError::UnsafeEntry(entry)) => entry,
+            error => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    /// Replaces the uncompressed size in the local and central header of the only entry of the archive.
+    fn declare_size(bytes: &[u8], size: u32) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        for (signature, offset) in [(b"PK\x03\x04", 22), (b"PK\x01\x02", 24)] {
+            let start = bytes
+                .windows(4)
+                .position(|window| window == signature)
+                .unwrap();
+            bytes[start + offset..start + offset + 4].copy_from_slice(&size.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn accept_regular_archive() {
+        let bytes = build_archive(&[("Munkey/", ""), ("Munkey/Ah.plugin", "<Plugin></Plugin>")]);
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        assert!(validate_archive(&mut archive, &ArchiveLimits::default()).is_ok());
+    }
+
+    #[test]
+    fn refuse_absolute_path() {
+        let bytes = build_archive(&[("Munkey/Ah.plugin", ""), ("/etc/cron.d/evil", "")]);
+
+        let result = refused_entry(&bytes, &ArchiveLimits::default());
+
+        assert_eq!(result.entry, "/etc/cron.d/evil");
+        assert_eq!(result.reason, UnsafeEntryReason::AbsolutePath);
+    }
+
+    #[test]
+    fn refuse_parent_directory() {
+        let bytes = build_archive(&[("Munkey/../../../evil.lua", "")]);
+
+        let result = refused_entry(&bytes, &ArchiveLimits::default());
+
+        assert_eq!(result.entry, "Munkey/../../../evil.lua");
+        assert_eq!(result.reason, UnsafeEntryReason::ParentDirectory);
+    }
+
+    #[test]
+    fn refuse_symlink() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_symlink("Munkey/link", "/home", FileOptions::default())
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let result = refused_entry(&bytes, &ArchiveLimits::default());
+
+        assert_eq!(result.entry, "Munkey/link");
+        assert_eq!(result.reason, UnsafeEntryReason::Symlink);
+    }
+
+    #[test]
+    fn refuse_device_file() {
+        let mut bytes = build_archive(&[("Munkey/device", "")]).to_vec();
+
+        // Patch the external attributes of the central directory entry to a character device
+        let header = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        let mode: u32 = 0o020_644 << 16;
+        bytes[header + 38..header + 42].copy_from_slice(&mode.to_le_bytes());
+
+        let result = refused_entry(&bytes, &ArchiveLimits::default());
+
+        assert_eq!(result.entry, "Munkey/device");
+        assert_eq!(result.reason, UnsafeEntryReason::SpecialFile(0o020_644));
+    }
+
+    #[test]
+    fn refuse_oversized_entry() {
+        let bytes = build_archive(&[("Munkey/Ah.plugin", "0123456789ABCDEF")]);
+        let limits = ArchiveLimits {
+            max_entry_size: 10,
+            ..ArchiveLimits::default()
+        };
+
+        let result = refused_entry(&bytes, &limits);
+
+        assert_eq!(result.entry, "Munkey/Ah.plugin");
+        assert_eq!(
+            result.reason,
+            UnsafeEntryReason::TooLarge {
+                size: 16,
+                limit: 10
+            }
+        );
+    }
+
+    #[test]
+    fn refuse_zip_bomb() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("Munkey/bomb.lua", FileOptions::default())
+            .unwrap();
+        writer.write_all(&vec![0; 4 * 1024 * 1024]).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let result = refused_entry(&bytes, &ArchiveLimits::default());
+
+        assert_eq!(result.entry, "Munkey/bomb.lua");
+        assert!(matches!(
+            result.reason,
+            UnsafeEntryReason::CompressionRatio { limit: 100, .. }
+        ));
+    }
+
+    #[test]
+    fn refuse_entry_larger_than_declared() {
+        let (test_dir, tmp_dir, _) = setup_dirs();
+        let target_dir = tmp_dir.join("extracted");
+        let content = "0123456789ABCDEF".repeat(64);
+        let bytes = declare_size(&build_archive(&[("Munkey/Ah.lua", &content)]), 16);
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let error = extract_archive(&mut archive, &target_dir, &ArchiveLimits::default());
+
+        match error.unwrap_err() {
+            Error::Archive(ArchiveError::UnsafeEntry(entry)) => {
+                assert_eq!(entry.entry, "Munkey/Ah.lua");
+                assert_eq!(
+                    entry.reason,
+                    UnsafeEntryReason::SizeMismatch { declared: 16 }
+                );
+            }
+            error => panic!("Unexpected error: {}", error),
+        }
+        assert!(
+            fs::metadata(target_dir.join("Munkey/Ah.lua"))
+                .unwrap()
+                .len()
+                <= 17
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+    }
+
+    #[test]
+    fn nothing_is_written_for_refused_archive() {
+        let (test_dir, tmp_dir, _) = setup_dirs();
+        let target_dir = tmp_dir.join("extracted");
+        let bytes = build_archive(&[("Munkey/Ah.plugin", ""), ("../evil.lua", "")]);
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let result = extract_archive(&mut archive, &target_dir, &ArchiveLimits::default());
+
+        assert!(result.is_err());
+        assert!(!target_dir.exists());
+        assert!(!tmp_dir.join("evil.lua").exists());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+    }
+
+    #[test]
+    fn installer_reports_refused_entry() {
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+        let bytes: Bytes = build_archive(&[("Munkey/Ah.plugin", ""), ("/evil.lua", "")]);
+
+        let error = installer.install(&bytes).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Refused archive entry /evil.lua: absolute paths are not allowed"
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+    }
+}
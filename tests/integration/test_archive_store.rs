@@ -0,0 +1,137 @@
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use lembas::core::io::{archive_store::StoredArchive, ArchiveStore};
+
+    use crate::fixtures::installer_fixtures::{munkey_archive, setup_dirs};
+
+    /// Moves the last use of an archive into the past.
+    fn age(root: &Path, archive: &StoredArchive, seconds: i64) {
+        let aged = StoredArchive {
+            used_at: archive.used_at - seconds,
+            ..archive.clone()
+        };
+        fs::write(
+            root.join(archive.plugin_id.to_string())
+                .join(format!("{}.json", archive.hash)),
+            serde_json::to_string(&aged).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn put_and_get() {
+        let (test_dir, _, _) = setup_dirs();
+        let store = ArchiveStore::new(&test_dir.join("archives"));
+        let bytes = munkey_archive();
+
+        let stored = store.put(1, "Munkey", "1.0", &bytes).unwrap();
+        assert_eq!(stored.hash, format!("{:x}", md5::compute(&bytes)));
+        assert_eq!(stored.size, bytes.len() as u64);
+
+        assert_eq!(store.get(1, "1.0", &stored.hash).unwrap(), bytes);
+        assert_eq!(store.get(1, "v1.0", "").unwrap(), bytes);
+        assert!(store.get(1, "1.1", "").is_none());
+        assert!(store.get(1, "1.0", "other").is_none());
+        assert!(store.get(2, "1.0", "").is_none());
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn same_content_is_stored_once() {
+        let (test_dir, _, _) = setup_dirs();
+        let store = ArchiveStore::new(&test_dir.join("archives"));
+
+        store.put(1, "Munkey", "1.0", &munkey_archive()).unwrap();
+        store.put(1, "Munkey", "1.0", &munkey_archive()).unwrap();
+        store.put(1, "Munkey", "1.1", b"other").unwrap();
+
+        assert_eq!(store.plugin_archives(1).len(), 2);
+        assert_eq!(store.size(), munkey_archive().len() as u64 + 5);
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_archive_is_removed() {
+        let (test_dir, _, _) = setup_dirs();
+        let root = test_dir.join("archives");
+        let store = ArchiveStore::new(&root);
+
+        let stored = store.put(1, "Munkey", "1.0", &munkey_archive()).unwrap();
+        fs::write(
+            root.join("1").join(format!("{}.zip", stored.hash)),
+            "broken",
+        )
+        .unwrap();
+
+        assert!(store.get(1, "1.0", &stored.hash).is_none());
+        assert!(store.archives().is_empty());
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn prune_by_size() {
+        let (test_dir, _, _) = setup_dirs();
+        let root = test_dir.join("archives");
+        let store = ArchiveStore::new(&root);
+
+        let old = store.put(1, "Munkey", "1.0", b"0123456789").unwrap();
+        let current = store.put(1, "Munkey", "1.1", b"ABCDEFGHIJ").unwrap();
+        let new = store.put(2, "Waypoint", "1.0", b"abcdefghij").unwrap();
+        age(&root, &old, 60);
+
+        assert!(store.prune(0, Duration::ZERO).unwrap().is_empty());
+
+        let removed = store.prune(15, Duration::ZERO).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].hash, old.hash);
+        assert_eq!(store.plugin_archives(1), vec![current]);
+        assert_eq!(store.plugin_archives(2), vec![new]);
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_the_latest_archive_of_every_plugin() {
+        let (test_dir, _, _) = setup_dirs();
+        let root = test_dir.join("archives");
+        let store = ArchiveStore::new(&root);
+
+        let old = store.put(1, "Munkey", "1.0", b"0123456789").unwrap();
+        age(&root, &old, 60);
+        let latest = store.put(2, "Waypoint", "1.0", b"abcdefghij").unwrap();
+
+        assert!(store.prune(5, Duration::ZERO).unwrap().is_empty());
+        assert_eq!(store.plugin_archives(1).len(), 1);
+        assert_eq!(store.plugin_archives(2), vec![latest]);
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn prune_by_age() {
+        let (test_dir, _, _) = setup_dirs();
+        let root = test_dir.join("archives");
+        let store = ArchiveStore::new(&root);
+
+        let old = store.put(1, "Munkey", "1.0", b"0123456789").unwrap();
+        store.put(2, "Waypoint", "1.0", b"abcdefghij").unwrap();
+        age(&root, &old, 3 * 24 * 60 * 60);
+
+        let removed = store
+            .prune(0, Duration::from_secs(2 * 24 * 60 * 60))
+            .unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].name, "Munkey");
+        assert_eq!(store.archives().len(), 1);
+
+        store.clear().unwrap();
+        assert_eq!(store.size(), 0);
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, read_dir, remove_dir_all},
+        path::PathBuf,
+        time::Duration,
+    };
+
+    use fs_extra::dir::{copy, CopyOptions};
+    use lembas::core::{
+        backup::BackupManager,
+        io::{cache::DatabaseHandler, Synchronizer},
+    };
+
+    use crate::database_fixtures;
+    use crate::fixtures::installer_fixtures::setup_dirs;
+
+    /// Copies the sample plugins into a fresh plugins directory. Returns the test, backup and plugins directory.
+    fn setup_plugins() -> (PathBuf, PathBuf, PathBuf) {
+        let (test_dir, _, plugins_dir) = setup_dirs();
+
+        for element in read_dir("tests/samples/plugin_folders").unwrap() {
+            copy(element.unwrap().path(), &plugins_dir, &CopyOptions::new())
+                .expect("Error while running setup method");
+        }
+
+        (test_dir.clone(), test_dir.join("backups"), plugins_dir)
+    }
+
+    #[test]
+    fn create_backup_with_manifest() {
+        let (test_dir, backup_dir, plugins_dir) = setup_plugins();
+        let manager = BackupManager::new(&backup_dir, &plugins_dir);
+
+        let manifest = manager.create().unwrap();
+
+        assert!(backup_dir.join(format!("{}.zip", manifest.name)).is_file());
+        assert!(manifest.size > 0);
+        let titan_bar = manifest.plugin("TitanBar").unwrap();
+        assert_eq!(titan_bar.id, 692);
+        assert!(titan_bar
+            .files
+            .contains(&"HabnaPlugins/TitanBar/Main.lua".to_string()));
+        assert_eq!(manager.backups(), vec![manifest]);
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn retention_keeps_the_last_backups() {
+        let (test_dir, backup_dir, plugins_dir) = setup_plugins();
+        let manager =
+            BackupManager::new(&backup_dir, &plugins_dir).with_retention(2, Duration::ZERO);
+
+        manager.create().unwrap();
+        let second = manager.create().unwrap();
+        let third = manager.create().unwrap();
+
+        let names = manager
+            .backups()
+            .into_iter()
+            .map(|backup| backup.name)
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec![third.name, second.name]);
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn restore_replaces_the_plugins_directory() {
+        let (test_dir, backup_dir, plugins_dir) = setup_plugins();
+        let (cache, cache_dir) = database_fixtures::setup();
+        let manager = BackupManager::new(&backup_dir, &plugins_dir);
+        let manifest = manager.create().unwrap();
+
+        remove_dir_all(plugins_dir.join("HabnaPlugins")).unwrap();
+        fs::write(plugins_dir.join("leftover.txt"), "leftover").unwrap();
+        manager.restore(&cache, &manifest.name).unwrap();
+
+        assert!(plugins_dir.join("HabnaPlugins/TitanBar/Main.lua").is_file());
+        assert!(!plugins_dir.join("leftover.txt").exists());
+        assert!(!test_dir.join(".plugins_restore").exists());
+        assert!(!test_dir.join(".plugins_previous").exists());
+        let titan_bar = cache.get_plugin_by_id(692).unwrap().unwrap();
+        assert_eq!(titan_bar.installed, 1);
+
+        database_fixtures::teardown(cache, cache_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn failed_restore_keeps_the_plugins_directory() {
+        let (test_dir, backup_dir, plugins_dir) = setup_plugins();
+        let (cache, cache_dir) = database_fixtures::setup();
+        let manager = BackupManager::new(&backup_dir, &plugins_dir).with_failure_after(2);
+        let manifest = manager.create().unwrap();
+
+        remove_dir_all(plugins_dir.join("HabnaPlugins")).unwrap();
+        fs::write(plugins_dir.join("leftover.txt"), "leftover").unwrap();
+        assert!(manager.restore(&cache, &manifest.name).is_err());
+
+        assert_eq!(
+            fs::read_to_string(plugins_dir.join("leftover.txt")).unwrap(),
+            "leftover"
+        );
+        assert!(plugins_dir.join("Atheisto").is_dir());
+        assert!(plugins_dir.join("Bunny").is_dir());
+        assert!(!plugins_dir.join("HabnaPlugins").exists());
+        assert_eq!(read_dir(&plugins_dir).unwrap().count(), 4);
+        assert!(!test_dir.join(".plugins_restore").exists());
+        assert!(!test_dir.join(".plugins_previous").exists());
+
+        database_fixtures::teardown(cache, cache_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn restore_single_plugin() {
+        let (test_dir, backup_dir, plugins_dir) = setup_plugins();
+        let (cache, cache_dir) = database_fixtures::setup();
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+        let manager = BackupManager::new(&backup_dir, &plugins_dir);
+        let manifest = manager.create().unwrap();
+
+        remove_dir_all(plugins_dir.join("HabnaPlugins/TitanBar")).unwrap();
+        remove_dir_all(plugins_dir.join("Atheisto")).unwrap();
+        manager
+            .restore_plugin(&cache, &manifest.name, "TitanBar")
+            .unwrap();
+
+        assert!(plugins_dir.join("HabnaPlugins/TitanBar/Main.lua").is_file());
+        // Other plugins stay untouched
+        assert!(!plugins_dir.join("Atheisto").exists());
+        assert!(plugins_dir.join("HabnaPlugins/HugeBag.plugin").is_file());
+        assert!(!cache.get_plugin_files(692).unwrap().is_empty());
+
+        database_fixtures::teardown(cache, cache_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn delete_backup() {
+        let (test_dir, backup_dir, plugins_dir) = setup_plugins();
+        let manager = BackupManager::new(&backup_dir, &plugins_dir);
+        let manifest = manager.create().unwrap();
+
+        manager.delete(&manifest.name).unwrap();
+
+        assert!(manager.backups().is_empty());
+        assert!(manager.backup(&manifest.name).is_err());
+
+        remove_dir_all(test_dir).unwrap();
+    }
+}
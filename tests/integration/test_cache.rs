@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use lembas::core::{io::cache::DatabaseHandler, Plugin};
+    use lembas::core::{io::cache::DatabaseHandler, plugin::LOCAL_SOURCE, Plugin};
 
     use crate::database_fixtures;
 
@@ -66,6 +66,35 @@ mod tests {
         database_fixtures::teardown(cache, test_dir);
     }
 
+    #[test]
+    fn mark_as_uninstalled_keeps_feed_plugins() {
+        let (cache, test_dir) = database_fixtures::setup();
+        let feed = Plugin::new("PetStable")
+            .with_id(1)
+            .with_current_version("1.0")
+            .with_remote_information("", "1.1", 0, "", 0, "")
+            .build();
+        let local = Plugin::new("Beta")
+            .with_id(-1)
+            .with_source(LOCAL_SOURCE)
+            .build();
+        cache.insert_plugin(&feed, 1).unwrap();
+        cache.insert_plugin(&local, 1).unwrap();
+        cache.set_pinned(feed.id, true).unwrap();
+
+        cache.mark_as_uninstalled("PetStable").unwrap();
+        cache.mark_as_uninstalled("Beta").unwrap();
+
+        let plugin = cache.get_plugin("PetStable").unwrap().unwrap();
+        assert_eq!(plugin.installed, 0);
+        assert_eq!(plugin.current_version, "");
+        assert_eq!(plugin.latest_version, "1.1");
+        assert_eq!(plugin.pinned, 0);
+        assert!(cache.get_plugin("Beta").unwrap().is_none());
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
     #[test]
     fn get_one_plugin() {
         let (cache, test_dir) = database_fixtures::setup_with_items();
@@ -81,7 +110,7 @@ mod tests {
     fn get_plugins() {
         let (cache, test_dir) = database_fixtures::setup_with_items();
 
-        let plugins = cache.get_plugins();
+        let plugins = cache.get_plugins().unwrap();
 
         assert_eq!(plugins.keys().len(), 2);
         assert!(plugins.contains_key("PetStable"));
@@ -89,4 +118,256 @@ mod tests {
 
         database_fixtures::teardown(cache, test_dir);
     }
+
+    #[test]
+    fn save_plugin_files() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        let files = vec!["Munkey/Ah.plugin".to_string(), "Munkey/Ah/".to_string()];
+        cache.save_plugin_files(2, &files).unwrap();
+        assert_eq!(cache.get_plugin_files(2).unwrap(), files);
+
+        let files = vec!["Munkey/Oh.plugin".to_string()];
+        cache.save_plugin_files(2, &files).unwrap();
+        assert_eq!(cache.get_plugin_files(2).unwrap(), files);
+
+        cache.delete_plugin_files(2).unwrap();
+        assert!(cache.get_plugin_files(2).unwrap().is_empty());
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn commit_installation() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        let files = vec!["Munkey/Ah.plugin".to_string()];
+        cache.commit_installation(1, "1.0", &files, true).unwrap();
+
+        let plugin = cache.get_plugin("Hello World").unwrap().unwrap();
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "1.0");
+        assert_eq!(plugin.verified, 1);
+        assert_eq!(cache.get_plugin_files(1).unwrap(), files);
+
+        assert!(cache.commit_installation(42, "1.0", &files, true).is_err());
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn migrate_existing_database() {
+        let (cache, test_dir) = database_fixtures::setup_legacy();
+
+        let plugin = Plugin::new("PetStable").with_id(1).with_size(1024).build();
+        cache.insert_plugin(&plugin, 0).unwrap();
+
+        assert_eq!(cache.get_plugin("PetStable").unwrap().unwrap().size, 1024);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn sync_skips_local_plugins() {
+        let (cache, test_dir) = database_fixtures::setup();
+
+        let local_plugin = Plugin::new("PetStable")
+            .with_id(-1)
+            .with_current_version("2.0b")
+            .with_source(LOCAL_SOURCE)
+            .build();
+        cache.insert_plugin(&local_plugin, 1).unwrap();
+
+        let feed_plugins = vec![
+            Plugin::new("PetStable")
+                .with_id(1)
+                .with_remote_information("", "1.1", 0, "", 0, "")
+                .build(),
+            Plugin::new("Hello World").with_id(2).build(),
+        ];
+        cache.sync_plugins(&feed_plugins).unwrap();
+
+        let plugin = cache.get_plugin("PetStable").unwrap().unwrap();
+        assert!(plugin.is_local());
+        assert_eq!(plugin.id, -1);
+        assert_eq!(plugin.current_version, "2.0b");
+        assert!(cache.get_plugin("Hello World").unwrap().is_some());
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    fn feed_plugin(id: i32, name: &str, latest_version: &str) -> Plugin {
+        Plugin::new(name)
+            .with_id(id)
+            .with_author("Marius")
+            .with_description("Feed description")
+            .with_remote_information("Bags", latest_version, 42, "archive.zip", 100, "abc")
+            .build()
+    }
+
+    #[test]
+    fn sync_inserts_new_plugins() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        let feed_plugins = vec![
+            feed_plugin(1, "Hello World", "0.1.0"),
+            feed_plugin(2, "PetStable", "1.1"),
+            feed_plugin(3, "HugeBag", "2.0"),
+        ];
+        cache.sync_plugins(&feed_plugins).unwrap();
+
+        let plugin = cache.get_plugin("HugeBag").unwrap().unwrap();
+        assert_eq!(plugin.id, 3);
+        assert_eq!(plugin.installed, 0);
+        assert_eq!(plugin.latest_version, "2.0");
+        assert_eq!(cache.get_plugins().unwrap().len(), 3);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn sync_updates_changed_plugins() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        cache
+            .sync_plugins(&[feed_plugin(2, "PetStable", "1.2")])
+            .unwrap();
+
+        let plugin = cache.get_plugin("PetStable").unwrap().unwrap();
+        assert_eq!(plugin.latest_version, "1.2");
+        assert_eq!(plugin.description, "Feed description");
+        assert_eq!(plugin.category, "Bags");
+        assert_eq!(plugin.downloads, 42);
+        assert_eq!(plugin.hash, "abc");
+        // The installation state belongs to the user
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "0.1.0");
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn sync_matches_renamed_plugins_by_id() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        cache
+            .sync_plugins(&[feed_plugin(2, "Pet Stable", "1.1")])
+            .unwrap();
+
+        assert!(cache.get_plugin("PetStable").unwrap().is_none());
+        let plugin = cache.get_plugin("Pet Stable").unwrap().unwrap();
+        assert_eq!(plugin.id, 2);
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "0.1.0");
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn sync_marks_missing_plugins_as_delisted() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+        let local_plugin = Plugin::new("Beta")
+            .with_id(-1)
+            .with_source(LOCAL_SOURCE)
+            .build();
+        cache.insert_plugin(&local_plugin, 1).unwrap();
+
+        cache
+            .sync_plugins(&[feed_plugin(2, "PetStable", "1.1")])
+            .unwrap();
+
+        assert_eq!(
+            cache.get_plugin("Hello World").unwrap().unwrap().delisted,
+            1
+        );
+        assert_eq!(cache.get_plugin("PetStable").unwrap().unwrap().delisted, 0);
+        assert_eq!(cache.get_plugin("Beta").unwrap().unwrap().delisted, 0);
+
+        // Plugins that return to the feed are listed again
+        cache
+            .sync_plugins(&[feed_plugin(1, "Hello World", "0.1.0")])
+            .unwrap();
+
+        assert_eq!(
+            cache.get_plugin("Hello World").unwrap().unwrap().delisted,
+            0
+        );
+        assert_eq!(cache.get_plugin("PetStable").unwrap().unwrap().delisted, 1);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn sync_skips_duplicate_names() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        let feed_plugins = vec![
+            feed_plugin(3, "HugeBag", "2.0"),
+            feed_plugin(4, "HugeBag", "2.1"),
+        ];
+        cache.sync_plugins(&feed_plugins).unwrap();
+
+        assert_eq!(cache.get_plugin("HugeBag").unwrap().unwrap().id, 3);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn sync_keeps_colliding_plugins_listed() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        // The feed entry of Hello World takes the name of PetStable, which is already used
+        cache
+            .sync_plugins(&[feed_plugin(1, "PetStable", "1.0")])
+            .unwrap();
+
+        let hello_world = cache.get_plugin("Hello World").unwrap().unwrap();
+        assert_eq!(hello_world.id, 1);
+        assert_eq!(hello_world.delisted, 0);
+        let pet_stable = cache.get_plugin("PetStable").unwrap().unwrap();
+        assert_eq!(pet_stable.id, 2);
+        assert_eq!(pet_stable.delisted, 0);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn next_local_plugin_id() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        assert_eq!(cache.next_local_plugin_id().unwrap(), -1);
+
+        let plugin = Plugin::new("Beta")
+            .with_id(-1)
+            .with_source(LOCAL_SOURCE)
+            .build();
+        cache.insert_plugin(&plugin, 1).unwrap();
+
+        assert_eq!(cache.next_local_plugin_id().unwrap(), -2);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn update_policy_survives_sync() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        cache.set_pinned(2, true).unwrap();
+        cache.set_ignore_updates_until(2, " 1.3 ").unwrap();
+        cache
+            .sync_plugins(&[feed_plugin(2, "PetStable", "1.2")])
+            .unwrap();
+
+        let plugin = cache.get_plugin("PetStable").unwrap().unwrap();
+        assert_eq!(plugin.pinned, 1);
+        assert_eq!(plugin.ignore_updates_until, "1.3");
+
+        cache.set_pinned(2, false).unwrap();
+        cache.set_ignore_updates_until(2, "").unwrap();
+        let plugin = cache.get_plugin("PetStable").unwrap().unwrap();
+        assert_eq!(plugin.pinned, 0);
+        assert!(plugin.ignore_updates_until.is_empty());
+
+        database_fixtures::teardown(cache, test_dir);
+    }
 }
@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use lembas::cli::{
+        exit_code, find_plugin, search_plugins, BackupCommand, Cli, Command, PluginNotFound,
+        PluginStatus, PluginSummary, ProfileCommand, EXIT_FAILURE, EXIT_NOT_FOUND,
+    };
+    use lembas::core::io::cache::DatabaseHandler;
+    use std::error::Error;
+
+    use crate::database_fixtures;
+
+    #[test]
+    fn parse_without_command() {
+        let cli = Cli::try_parse_from(["lembas"]).unwrap();
+
+        assert_eq!(cli.command, None);
+        assert!(!cli.json);
+    }
+
+    #[test]
+    fn parse_global_json_flag() {
+        let cli = Cli::try_parse_from(["lembas", "list", "--json"]).unwrap();
+
+        assert_eq!(cli.command, Some(Command::List));
+        assert!(cli.json);
+    }
+
+    #[test]
+    fn parse_update_requires_plugin_or_all() {
+        assert!(Cli::try_parse_from(["lembas", "update"]).is_err());
+        assert!(Cli::try_parse_from(["lembas", "update", "PetStable", "--all"]).is_err());
+
+        let cli = Cli::try_parse_from(["lembas", "update", "--all"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Update {
+                plugin: None,
+                all: true
+            })
+        );
+    }
+
+    #[test]
+    fn parse_install_from_path() {
+        assert!(Cli::try_parse_from(["lembas", "install"]).is_err());
+        assert!(
+            Cli::try_parse_from(["lembas", "install", "PetStable", "--path", "PetStable.zip"])
+                .is_err()
+        );
+
+        let cli = Cli::try_parse_from(["lembas", "install", "--path", "PetStable.zip"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Install {
+                plugin: None,
+                path: Some("PetStable.zip".into())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_profile_commands() {
+        let cli = Cli::try_parse_from(["lembas", "list", "--profile", "Bullroarer"]).unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("Bullroarer"));
+        assert_eq!(cli.command, Some(Command::List));
+
+        let cli = Cli::try_parse_from([
+            "lembas",
+            "profile",
+            "add",
+            "Bullroarer",
+            "--documents-dir",
+            "/games/bullroarer",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Profile {
+                action: Some(ProfileCommand::Add {
+                    name: "Bullroarer".to_string(),
+                    documents_dir: Some("/games/bullroarer".into())
+                })
+            })
+        );
+    }
+
+    #[test]
+    fn parse_installations_command() {
+        let cli = Cli::try_parse_from(["lembas", "installations"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Installations { select: None }));
+
+        let cli = Cli::try_parse_from(["lembas", "installations", "--select", "2"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Installations { select: Some(2) })
+        );
+    }
+
+    #[test]
+    fn parse_backup_commands() {
+        let cli = Cli::try_parse_from(["lembas", "backup"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Backup { action: None }));
+
+        let cli = Cli::try_parse_from([
+            "lembas", "backup", "restore", "backup_1", "--plugin", "TitanBar",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Backup {
+                action: Some(BackupCommand::Restore {
+                    backup: "backup_1".to_string(),
+                    plugin: Some("TitanBar".to_string())
+                })
+            })
+        );
+    }
+
+    #[test]
+    fn find_plugin_by_id_and_name() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        assert_eq!(find_plugin(&cache, "2").unwrap().name, "PetStable");
+        assert_eq!(find_plugin(&cache, "Hello World").unwrap().id, 1);
+        assert_eq!(find_plugin(&cache, "petstable").unwrap().id, 2);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn find_missing_plugin() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        let error = find_plugin(&cache, "Missing").unwrap_err();
+        assert!(error.is::<PluginNotFound>());
+        assert_eq!(exit_code(error.as_ref()), EXIT_NOT_FOUND);
+
+        let error: Box<dyn Error> = "Download failed".into();
+        assert_eq!(exit_code(error.as_ref()), EXIT_FAILURE);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn search_by_name_and_author() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        let names = search_plugins(&cache, "pet")
+            .unwrap()
+            .into_iter()
+            .map(|plugin| plugin.name)
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["PetStable"]);
+        assert_eq!(search_plugins(&cache, "marius").unwrap().len(), 2);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn summary_marks_available_updates() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        let plugin = find_plugin(&cache, "PetStable").unwrap();
+        let summary = PluginSummary::from(&plugin);
+        assert!(summary.installed);
+        assert_eq!(summary.status, PluginStatus::UpdateAvailable);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn summary_respects_ignored_updates() {
+        let (cache, test_dir) = database_fixtures::setup_with_items();
+
+        cache.set_ignore_updates_until(2, "1.2").unwrap();
+        let plugin = find_plugin(&cache, "PetStable").unwrap();
+        assert_eq!(
+            PluginSummary::from(&plugin).status,
+            PluginStatus::UpdateIgnored
+        );
+
+        cache.set_ignore_updates_until(2, "1.1").unwrap();
+        let plugin = find_plugin(&cache, "PetStable").unwrap();
+        assert_eq!(
+            PluginSummary::from(&plugin).status,
+            PluginStatus::UpdateAvailable
+        );
+
+        cache.set_pinned(2, true).unwrap();
+        let plugin = find_plugin(&cache, "PetStable").unwrap();
+        assert_eq!(PluginSummary::from(&plugin).status, PluginStatus::Pinned);
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+}
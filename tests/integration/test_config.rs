@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use lembas::core::config::{Profile, SettingsFile, DEFAULT_PROFILE, MAX_PARALLEL_INSTALLS};
+    use lembas::core::io::directories::DirectoryKind;
+
+    #[test]
+    fn default_settings_have_the_default_profile() {
+        let settings = SettingsFile::default();
+
+        assert_eq!(settings.active_profile, DEFAULT_PROFILE);
+        assert_eq!(
+            settings.profile("default").unwrap().database_file_name(),
+            "plugins.sqlite3"
+        );
+    }
+
+    #[test]
+    fn settings_without_profiles_use_the_default_profile() {
+        let settings: SettingsFile =
+            serde_json::from_str(r#"{"backup_enabled": false, "feed_url": "feed"}"#).unwrap();
+
+        assert_eq!(settings.profiles, vec![Profile::default()]);
+        assert_eq!(settings.active_profile, DEFAULT_PROFILE);
+    }
+
+    #[test]
+    fn install_concurrency_is_limited() {
+        let mut settings = SettingsFile::default();
+        assert_eq!(settings.install_concurrency(), 3);
+
+        settings.parallel_installs = 0;
+        assert_eq!(settings.install_concurrency(), 1);
+
+        settings.parallel_installs = 100;
+        assert_eq!(settings.install_concurrency(), MAX_PARALLEL_INSTALLS);
+    }
+
+    #[test]
+    fn refresh_intervals() {
+        let mut settings = SettingsFile::default();
+        assert_eq!(
+            settings.feed_refresh_interval(),
+            Duration::from_secs(15 * 60)
+        );
+        assert_eq!(settings.update_check_interval(), None);
+
+        settings.feed_refresh_interval_minutes = 0;
+        settings.update_check_interval_hours = 6;
+        assert_eq!(settings.feed_refresh_interval(), Duration::ZERO);
+        assert_eq!(
+            settings.update_check_interval(),
+            Some(Duration::from_secs(6 * 60 * 60))
+        );
+    }
+
+    #[test]
+    fn profile_paths() {
+        let profile = Profile::new("Legendary Server")
+            .with_lotro_documents_dir(Path::new("/games/lotro/Documents/LOTRO"));
+
+        assert_eq!(
+            profile.plugins_dir(),
+            Path::new("/games/lotro/Documents/LOTRO/Plugins")
+        );
+        assert_eq!(
+            profile.backup_dir(),
+            Path::new("/games/lotro/Documents/LOTRO/Plugins_Backup")
+        );
+        assert_eq!(
+            profile.database_file_name(),
+            "plugins_legendary_server.sqlite3"
+        );
+        assert!(profile.has_plugins_dir());
+        assert!(!Profile::new("Discovered").has_plugins_dir());
+        assert!(Profile {
+            plugins_dir_override: "/mnt/plugins".to_string(),
+            ..Profile::new("Moved")
+        }
+        .has_plugins_dir());
+    }
+
+    #[test]
+    fn override_profile_directories() {
+        let mut settings = SettingsFile::default();
+        settings
+            .add_profile(
+                Profile::new("Bullroarer").with_lotro_documents_dir(Path::new("/games/bullroarer")),
+            )
+            .unwrap();
+
+        settings.set_directory("bullroarer", DirectoryKind::Plugins, " /mnt/plugins ");
+        settings.set_directory("Bullroarer", DirectoryKind::Temp, "/mnt/tmp");
+
+        assert_eq!(
+            settings.directory("Bullroarer", DirectoryKind::Plugins),
+            Path::new("/mnt/plugins")
+        );
+        assert_eq!(
+            settings.directory("Bullroarer", DirectoryKind::Backups),
+            Path::new("/games/bullroarer/Plugins_Backup")
+        );
+        assert_eq!(
+            settings.directory(DEFAULT_PROFILE, DirectoryKind::Temp),
+            Path::new("/mnt/tmp")
+        );
+        // The plugin data stays in the documents directory
+        assert_eq!(
+            settings.profile("Bullroarer").unwrap().plugin_data_dir(),
+            Path::new("/games/bullroarer/PluginData")
+        );
+
+        settings.set_directory("Bullroarer", DirectoryKind::Plugins, "");
+        assert_eq!(
+            settings.directory("Bullroarer", DirectoryKind::Plugins),
+            Path::new("/games/bullroarer/Plugins")
+        );
+    }
+
+    #[test]
+    fn add_and_remove_profiles() {
+        let mut settings = SettingsFile::default();
+
+        settings.add_profile(Profile::new(" Bullroarer ")).unwrap();
+        assert!(settings.add_profile(Profile::new("bullroarer")).is_err());
+        assert!(settings.add_profile(Profile::new("")).is_err());
+        // Both would use the same cache database
+        settings.add_profile(Profile::new("Alt 1")).unwrap();
+        assert!(settings.add_profile(Profile::new("alt_1")).is_err());
+
+        settings.set_active_profile("BULLROARER").unwrap();
+        assert_eq!(settings.active_profile, "Bullroarer");
+        assert!(settings.remove_profile("Bullroarer").is_err());
+        assert!(settings.set_active_profile("Missing").is_err());
+
+        settings.set_active_profile(DEFAULT_PROFILE).unwrap();
+        settings.remove_profile("bullroarer").unwrap();
+        let names = settings
+            .profiles
+            .iter()
+            .map(|profile| profile.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec![DEFAULT_PROFILE, "Alt 1"]);
+    }
+}
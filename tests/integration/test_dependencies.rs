@@ -0,0 +1,290 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use bytes::Bytes;
+    use lembas::core::{
+        backup::BackupManager,
+        dependencies::{dependents, DependencyProblem, DependencyResolver},
+        io::{cache::DatabaseHandler, ArchiveStore, Cache},
+        Error, InstallProgress, Plugin,
+    };
+
+    use crate::database_fixtures;
+    use crate::fixtures::installer_fixtures::{build_archive, setup_dirs};
+
+    fn plugin_archive(name: &str, dependencies: &[i32]) -> Bytes {
+        let dependencies = dependencies
+            .iter()
+            .map(|id| format!("<dependency>{}</dependency>", id))
+            .collect::<String>();
+        let compendium = format!(
+            "<PluginConfig><Id>0</Id><Name>{0}</Name><Version>1.0</Version><Author>{0}</Author><Dependencies>{1}</Dependencies></PluginConfig>",
+            name, dependencies
+        );
+
+        build_archive(&[
+            (&format!("{0}/{0}.plugin", name), "<Plugin></Plugin>"),
+            (&format!("{0}/{0}/Main.lua", name), ""),
+            (&format!("{0}/{0}/{0}.plugincompendium", name), &compendium),
+        ])
+    }
+
+    fn insert_plugins(cache: &Cache, plugins: &[(i32, &str)]) {
+        for (id, name) in plugins {
+            let plugin = Plugin::new(name)
+                .with_id(*id)
+                .with_remote_information("", "1.0", 0, "", 0, "")
+                .build();
+            cache.insert_plugin(&plugin, 0).unwrap();
+        }
+    }
+
+    fn install(
+        cache: &Cache,
+        name: &str,
+        archives: &HashMap<i32, Bytes>,
+    ) -> (Vec<String>, Result<Vec<DependencyProblem>, String>) {
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut fetched = Vec::new();
+        let plugin = cache.get_plugin(name).unwrap().unwrap();
+
+        let result = DependencyResolver::new(cache, &tmp_dir, &plugins_dir, |_, plugin| {
+            fetched.push(plugin.name.clone());
+            Ok(archives[&plugin.id].clone())
+        })
+        .with_archive_store(ArchiveStore::new(&test_dir.join("archives")))
+        .install(&plugin)
+        .map(|report| {
+            assert_eq!(report.installed.last().unwrap(), name);
+            report.problems
+        })
+        .map_err(|error| error.to_string());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        (fetched, result)
+    }
+
+    #[test]
+    fn install_missing_dependency_first() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        insert_plugins(&cache, &[(1, "Compendium"), (2, "Waypoint")]);
+        let archives = HashMap::from([
+            (1, plugin_archive("Compendium", &[2])),
+            (2, plugin_archive("Waypoint", &[])),
+        ]);
+        let plugin = cache.get_plugin("Compendium").unwrap().unwrap();
+
+        let report = DependencyResolver::new(&cache, &tmp_dir, &plugins_dir, |_, plugin| {
+            Ok(archives[&plugin.id].clone())
+        })
+        .with_archive_store(ArchiveStore::new(&test_dir.join("archives")))
+        .install(&plugin)
+        .unwrap();
+
+        assert_eq!(report.installed, vec!["Waypoint", "Compendium"]);
+        assert!(report.problems.is_empty());
+        assert_eq!(cache.get_plugin("Waypoint").unwrap().unwrap().installed, 1);
+        assert_eq!(
+            cache.get_plugin("Compendium").unwrap().unwrap().installed,
+            1
+        );
+        assert_eq!(
+            dependents(&cache, &plugins_dir, 2).unwrap(),
+            vec!["Compendium"]
+        );
+        assert!(dependents(&cache, &plugins_dir, 1).unwrap().is_empty());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn back_up_once_before_dependencies() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let manager = BackupManager::new(&test_dir.join("backups"), &plugins_dir);
+        insert_plugins(&cache, &[(1, "Compendium"), (2, "Waypoint")]);
+        let archives = HashMap::from([
+            (1, plugin_archive("Compendium", &[2])),
+            (2, plugin_archive("Waypoint", &[])),
+        ]);
+        let plugin = cache.get_plugin("Compendium").unwrap().unwrap();
+
+        DependencyResolver::new(&cache, &tmp_dir, &plugins_dir, |_, plugin| {
+            Ok(archives[&plugin.id].clone())
+        })
+        .with_archive_store(ArchiveStore::new(&test_dir.join("archives")))
+        .with_backup(Some(manager.clone()))
+        .install(&plugin)
+        .unwrap();
+
+        let backups = manager.backups();
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].plugins.is_empty());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn skip_installed_dependency() {
+        let (cache, db_dir) = database_fixtures::setup();
+        insert_plugins(&cache, &[(1, "Compendium"), (2, "Waypoint")]);
+        cache.mark_as_installed(2, "1.0").unwrap();
+        let archives = HashMap::from([(1, plugin_archive("Compendium", &[2]))]);
+
+        let (fetched, result) = install(&cache, "Compendium", &archives);
+
+        assert_eq!(fetched, vec!["Compendium"]);
+        assert_eq!(result, Ok(Vec::new()));
+
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn report_unknown_dependency() {
+        let (cache, db_dir) = database_fixtures::setup();
+        insert_plugins(&cache, &[(1, "Compendium")]);
+        let archives = HashMap::from([(1, plugin_archive("Compendium", &[640]))]);
+
+        let (_, result) = install(&cache, "Compendium", &archives);
+
+        assert_eq!(
+            result,
+            Ok(vec![DependencyProblem::Unknown {
+                plugin: "Compendium".to_string(),
+                dependency_id: 640
+            }])
+        );
+        assert_eq!(
+            cache.get_plugin("Compendium").unwrap().unwrap().installed,
+            1
+        );
+
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn report_dependency_cycle() {
+        let (cache, db_dir) = database_fixtures::setup();
+        insert_plugins(&cache, &[(1, "Bags"), (2, "Vitals"), (3, "Party")]);
+        let archives = HashMap::from([
+            (1, plugin_archive("Bags", &[2])),
+            (2, plugin_archive("Vitals", &[3])),
+            (3, plugin_archive("Party", &[1])),
+        ]);
+
+        let (fetched, result) = install(&cache, "Bags", &archives);
+
+        assert_eq!(fetched, vec!["Bags", "Vitals", "Party"]);
+        assert_eq!(
+            result,
+            Ok(vec![DependencyProblem::Cycle(vec![
+                "Bags".to_string(),
+                "Vitals".to_string(),
+                "Party".to_string(),
+                "Bags".to_string()
+            ])])
+        );
+        assert_eq!(cache.get_installed_plugins().unwrap().len(), 3);
+
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn failed_dependency_stops_installation() {
+        let (cache, db_dir) = database_fixtures::setup();
+        insert_plugins(&cache, &[(1, "Compendium"), (2, "Waypoint")]);
+        let archives = HashMap::from([
+            (1, plugin_archive("Compendium", &[2])),
+            (2, Bytes::from_static(b"not a zip file")),
+        ]);
+
+        let (_, result) = install(&cache, "Compendium", &archives);
+
+        assert!(result.is_err());
+        assert!(cache.get_installed_plugins().unwrap().is_empty());
+
+        database_fixtures::teardown(cache, db_dir);
+    }
+    #[test]
+    fn report_progress_of_dependencies() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        insert_plugins(&cache, &[(1, "Compendium"), (2, "Waypoint")]);
+        let archives = HashMap::from([
+            (1, plugin_archive("Compendium", &[2])),
+            (2, plugin_archive("Waypoint", &[])),
+        ]);
+        let plugin = cache.get_plugin("Compendium").unwrap().unwrap();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let listener = reported.clone();
+
+        DependencyResolver::new(&cache, &tmp_dir, &plugins_dir, |_, plugin| {
+            Ok(archives[&plugin.id].clone())
+        })
+        .with_archive_store(ArchiveStore::new(&test_dir.join("archives")))
+        .with_progress(Arc::new(move |plugin, progress| {
+            listener
+                .lock()
+                .unwrap()
+                .push((plugin.to_string(), progress.clone()));
+        }))
+        .install(&plugin)
+        .unwrap();
+
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                ("Waypoint".to_string(), InstallProgress::Extracting),
+                ("Waypoint".to_string(), InstallProgress::Moving),
+                ("Compendium".to_string(), InstallProgress::Extracting),
+                ("Compendium".to_string(), InstallProgress::Moving),
+            ]
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn cancel_while_fetching_dependency() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        insert_plugins(&cache, &[(1, "Compendium"), (2, "Waypoint")]);
+        let archives = HashMap::from([
+            (1, plugin_archive("Compendium", &[2])),
+            (2, plugin_archive("Waypoint", &[])),
+        ]);
+        let plugin = cache.get_plugin("Compendium").unwrap().unwrap();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancel = cancelled.clone();
+
+        let error = DependencyResolver::new(&cache, &tmp_dir, &plugins_dir, |_, plugin| {
+            if plugin.id == 2 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            Ok(archives[&plugin.id].clone())
+        })
+        .with_archive_store(ArchiveStore::new(&test_dir.join("archives")))
+        .with_cancel_flag(cancelled)
+        .install(&plugin)
+        .unwrap_err();
+
+        assert!(matches!(error, Error::Cancelled));
+        assert!(cache.get_installed_plugins().unwrap().is_empty());
+        assert!(fs::read_dir(&plugins_dir).unwrap().next().is_none());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, create_dir_all, remove_dir_all};
+
+    use lembas::core::discovery::LOTRO_DOCUMENTS_DIR;
+    use lembas::core::io::directories::{
+        check_directory, looks_like_lotro_documents_dir, move_contents, DirectoryKind,
+    };
+
+    use crate::fixtures::installer_fixtures::setup_dirs;
+
+    #[test]
+    fn check_lotro_documents_directory() {
+        let (test_dir, _, plugins_dir) = setup_dirs();
+        let documents_dir = test_dir.join(LOTRO_DOCUMENTS_DIR);
+        create_dir_all(&documents_dir).unwrap();
+
+        assert!(looks_like_lotro_documents_dir(&documents_dir));
+        assert!(
+            check_directory(DirectoryKind::LotroDocuments, &documents_dir)
+                .unwrap()
+                .is_empty()
+        );
+        // The plugins directory isn't part of a LOTRO documents directory
+        assert_eq!(
+            check_directory(DirectoryKind::Plugins, &plugins_dir)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(check_directory(DirectoryKind::Temp, "relative/tmp".as_ref()).is_err());
+        // Only the directories of lembas are moved along
+        assert!(!DirectoryKind::LotroDocuments.is_owned());
+        assert!(DirectoryKind::Plugins.is_owned());
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn check_missing_directory() {
+        let (test_dir, _, _) = setup_dirs();
+        let file = test_dir.join("file.txt");
+        fs::write(&file, "content").unwrap();
+
+        let warnings =
+            check_directory(DirectoryKind::Backups, &test_dir.join("new/backups")).unwrap();
+
+        assert_eq!(warnings.len(), 1);
+        assert!(!test_dir.join("new").exists());
+        assert!(check_directory(DirectoryKind::Backups, &file).is_err());
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn move_directory_contents() {
+        let (test_dir, _, plugins_dir) = setup_dirs();
+        create_dir_all(plugins_dir.join("HabnaPlugins/TitanBar")).unwrap();
+        fs::write(plugins_dir.join("HabnaPlugins/TitanBar/Main.lua"), "lua").unwrap();
+        fs::write(plugins_dir.join("TitanBar.plugin"), "plugin").unwrap();
+        let target_dir = test_dir.join("moved/Plugins");
+
+        let mut moved = move_contents(&plugins_dir, &target_dir).unwrap();
+        moved.sort();
+
+        assert_eq!(moved, vec!["HabnaPlugins", "TitanBar.plugin"]);
+        assert!(target_dir.join("HabnaPlugins/TitanBar/Main.lua").is_file());
+        assert!(fs::read_dir(&plugins_dir).unwrap().next().is_none());
+        assert!(move_contents(&target_dir, &target_dir.join("nested")).is_err());
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn move_nothing_if_target_has_the_same_entry() {
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        fs::write(plugins_dir.join("TitanBar.plugin"), "new").unwrap();
+        fs::write(plugins_dir.join("HugeBag.plugin"), "new").unwrap();
+        fs::write(tmp_dir.join("TitanBar.plugin"), "old").unwrap();
+
+        assert!(move_contents(&plugins_dir, &tmp_dir).is_err());
+        assert!(plugins_dir.join("HugeBag.plugin").is_file());
+        assert_eq!(
+            fs::read_to_string(tmp_dir.join("TitanBar.plugin")).unwrap(),
+            "old"
+        );
+
+        remove_dir_all(test_dir).unwrap();
+    }
+}
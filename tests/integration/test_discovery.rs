@@ -0,0 +1,168 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, create_dir_all, remove_dir_all},
+        path::{Path, PathBuf},
+    };
+
+    use lembas::core::discovery::{
+        Discovery, Installation, InstallationSource, LOTRO_DOCUMENTS_DIR,
+    };
+
+    use crate::fixtures::installer_fixtures::setup_dirs;
+
+    /// Creates the LOTRO documents directory of a Wine prefix and returns it.
+    fn create_prefix(prefix: &Path, user: &str) -> PathBuf {
+        let documents_dir = prefix
+            .join("drive_c/users")
+            .join(user)
+            .join("Documents")
+            .join(LOTRO_DOCUMENTS_DIR);
+        create_dir_all(&documents_dir).unwrap();
+
+        documents_dir
+    }
+
+    fn sources(installations: &[Installation]) -> Vec<InstallationSource> {
+        installations
+            .iter()
+            .map(|installation| installation.source)
+            .collect()
+    }
+
+    #[test]
+    fn discover_nothing_in_empty_home() {
+        let (test_dir, _, _) = setup_dirs();
+
+        assert!(Discovery::new(&test_dir).installations().is_empty());
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn discover_native_installation() {
+        let (test_dir, _, _) = setup_dirs();
+        let documents_dir = test_dir.join("Documents").join(LOTRO_DOCUMENTS_DIR);
+        create_dir_all(&documents_dir).unwrap();
+        let redirected_dir = test_dir
+            .join("OneDrive/Documents")
+            .join(LOTRO_DOCUMENTS_DIR);
+        create_dir_all(&redirected_dir).unwrap();
+
+        let installations = Discovery::new(&test_dir).installations();
+
+        assert_eq!(
+            installations,
+            vec![
+                Installation {
+                    source: InstallationSource::Native,
+                    documents_dir,
+                },
+                Installation {
+                    source: InstallationSource::Native,
+                    documents_dir: redirected_dir,
+                },
+            ]
+        );
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn discover_steam_libraries() {
+        let (test_dir, _, _) = setup_dirs();
+        let steam_dir = test_dir.join(".steam/steam");
+        let default_library = create_prefix(
+            &steam_dir.join("steamapps/compatdata/212500/pfx"),
+            "steamuser",
+        );
+        let library = test_dir.join("SteamLibrary");
+        let external_library = create_prefix(
+            &library.join("steamapps/compatdata/212500/pfx"),
+            "steamuser",
+        );
+        fs::write(
+            steam_dir.join("steamapps/libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                library.display()
+            ),
+        )
+        .unwrap();
+
+        let installations = Discovery::new(&test_dir).installations();
+
+        let documents_dirs = installations
+            .iter()
+            .map(|installation| installation.documents_dir.clone())
+            .collect::<Vec<PathBuf>>();
+        assert_eq!(documents_dirs, vec![default_library, external_library]);
+        assert_eq!(
+            sources(&installations),
+            vec![InstallationSource::Steam, InstallationSource::Steam]
+        );
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn discover_wine_prefixes() {
+        let (test_dir, _, _) = setup_dirs();
+        let lutris_prefix = test_dir.join("Wine/lotro");
+        create_prefix(&lutris_prefix, "player");
+        create_dir_all(test_dir.join(".config/lutris/games")).unwrap();
+        fs::write(
+            test_dir.join(".config/lutris/games/lotro-1.yml"),
+            format!(
+                "game:\n  exe: drive_c/LOTRO/LotroLauncher.exe\n  prefix: {}\n",
+                lutris_prefix.display()
+            ),
+        )
+        .unwrap();
+        create_prefix(
+            &test_dir.join(".local/share/bottles/bottles/LOTRO"),
+            "player",
+        );
+        create_prefix(&test_dir.join(".wine"), "player");
+        create_prefix(&test_dir.join(".wine"), "Public");
+        let custom_prefix = test_dir.join("prefixes/lotro");
+        create_prefix(&custom_prefix, "player");
+
+        let installations = Discovery::new(&test_dir)
+            .with_wine_prefix(&custom_prefix)
+            .installations();
+
+        assert_eq!(
+            sources(&installations),
+            vec![
+                InstallationSource::Lutris,
+                InstallationSource::Bottles,
+                InstallationSource::Wine,
+                InstallationSource::Wine,
+            ]
+        );
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn discover_prefix_only_once() {
+        let (test_dir, _, _) = setup_dirs();
+        let prefix = test_dir.join("Games/lotro");
+        create_prefix(&prefix, "player");
+        create_dir_all(test_dir.join(".config/lutris/games")).unwrap();
+        fs::write(
+            test_dir.join(".config/lutris/games/lotro.yml"),
+            format!("game:\n  prefix: '{}'\n", prefix.display()),
+        )
+        .unwrap();
+
+        let installations = Discovery::new(&test_dir)
+            .with_wine_prefix(&prefix)
+            .installations();
+
+        assert_eq!(sources(&installations), vec![InstallationSource::Lutris]);
+
+        remove_dir_all(test_dir).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use std::{fs, time::Duration};
+
+    use lembas::core::io::{cache::DatabaseHandler, feed_snapshot::format_age, FeedSnapshot};
+    use lembas::core::lotro_compendium::{
+        refresh_feed, Downloader, FeedResponse, FeedSource, FeedValidators,
+    };
+    use lembas::core::Error;
+
+    use crate::database_fixtures::setup;
+
+    const FEED_URL: &str = "https://example.com/feed.xml";
+
+    struct SampleDownloader;
+
+    #[async_trait]
+    impl Downloader for SampleDownloader {
+        async fn fetch_feed_content(_url: String) -> Result<String, Error> {
+            Ok(fs::read_to_string("tests/samples/xml_files/feed_url.xml").unwrap())
+        }
+    }
+
+    struct OfflineDownloader;
+
+    #[async_trait]
+    impl Downloader for OfflineDownloader {
+        async fn fetch_feed_content(url: String) -> Result<String, Error> {
+            Err(Error::network(&url, "connection refused"))
+        }
+    }
+
+    /// Answers with the page of a proxy instead of the feed.
+    struct InvalidDownloader;
+
+    #[async_trait]
+    impl Downloader for InvalidDownloader {
+        async fn fetch_feed_content(_url: String) -> Result<String, Error> {
+            Ok("<html>Service unavailable</html>".to_string())
+        }
+    }
+
+    /// Answers conditional requests like lotrointerface, the feed never changes.
+    struct ConditionalDownloader;
+
+    #[async_trait]
+    impl Downloader for ConditionalDownloader {
+        async fn fetch_feed_content(url: String) -> Result<String, Error> {
+            SampleDownloader::fetch_feed_content(url).await
+        }
+
+        async fn fetch_feed_if_modified(
+            url: String,
+            validators: FeedValidators,
+        ) -> Result<FeedResponse, Error> {
+            if validators.etag == "\"v1\"" {
+                return Ok(FeedResponse::NotModified);
+            }
+
+            Ok(FeedResponse::Modified {
+                content: SampleDownloader::fetch_feed_content(url).await?,
+                validators: FeedValidators {
+                    etag: "\"v1\"".to_string(),
+                    last_modified: "Sat, 14 Aug 2021 16:51:51 GMT".to_string(),
+                },
+            })
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn download_saves_snapshot() {
+        let (cache, test_dir) = setup();
+        let snapshot = FeedSnapshot::new(&test_dir);
+
+        let refresh = block_on(refresh_feed::<SampleDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+
+        assert_eq!(refresh.source, FeedSource::Downloaded);
+        assert_eq!(refresh.plugins, 2);
+        assert!(cache.get_plugin("PrimePlugin").unwrap().is_some());
+
+        let metadata = snapshot.metadata().unwrap();
+        assert_eq!(metadata.url, FEED_URL);
+        assert_eq!(metadata.plugins, 2);
+        assert_eq!(metadata.fetched_at, refresh.fetched_at);
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn offline_uses_snapshot() {
+        let (cache, test_dir) = setup();
+        let snapshot = FeedSnapshot::new(&test_dir.join("storage"));
+        let downloaded = block_on(refresh_feed::<SampleDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+
+        // A new profile starts with an empty cache
+        let (offline_cache, offline_dir) = setup();
+        let refresh = block_on(refresh_feed::<OfflineDownloader>(
+            &offline_cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            refresh.source,
+            FeedSource::Offline(format!("Can't download {}: connection refused", FEED_URL))
+        );
+        assert_eq!(refresh.fetched_at, downloaded.fetched_at);
+        assert_eq!(refresh.plugins, 2);
+        assert!(offline_cache.get_plugin("PrimePlugin").unwrap().is_some());
+
+        fs::remove_dir_all(test_dir).unwrap();
+        fs::remove_dir_all(offline_dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_feed_keeps_snapshot() {
+        let (cache, test_dir) = setup();
+        let snapshot = FeedSnapshot::new(&test_dir);
+        block_on(refresh_feed::<SampleDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+
+        let refresh = block_on(refresh_feed::<InvalidDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+
+        assert!(refresh.is_offline());
+        assert_eq!(snapshot.metadata().unwrap().plugins, 2);
+        assert_eq!(snapshot.load().unwrap().unwrap().1.len(), 2);
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn offline_without_snapshot() {
+        let (cache, test_dir) = setup();
+        let snapshot = FeedSnapshot::new(&test_dir);
+
+        let result = block_on(refresh_feed::<OfflineDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ));
+
+        assert!(matches!(result, Err(Error::Network { .. })));
+        assert!(snapshot.metadata().is_none());
+        assert!(snapshot.load().unwrap().is_none());
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_age() {
+        assert_eq!(format_age(1000, 1030), "just now");
+        assert_eq!(format_age(1000, 1060), "1 minute ago");
+        assert_eq!(format_age(0, 5 * 60 + 59), "5 minutes ago");
+        assert_eq!(format_age(0, 2 * 3600), "2 hours ago");
+        assert_eq!(format_age(0, 3 * 86_400), "3 days ago");
+        assert_eq!(format_age(100, 0), "just now");
+    }
+
+    #[test]
+    fn not_modified_uses_snapshot() {
+        let (cache, test_dir) = setup();
+        let snapshot = FeedSnapshot::new(&test_dir.join("storage"));
+        let downloaded = block_on(refresh_feed::<ConditionalDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+        assert_eq!(downloaded.source, FeedSource::Downloaded);
+        let metadata = snapshot.metadata().unwrap();
+        assert_eq!(metadata.etag, "\"v1\"");
+        assert_eq!(metadata.last_modified, "Sat, 14 Aug 2021 16:51:51 GMT");
+        assert_eq!(
+            cache.get_feed_fetched_at().unwrap(),
+            Some(metadata.fetched_at)
+        );
+
+        let refresh = block_on(refresh_feed::<ConditionalDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+        assert_eq!(refresh.source, FeedSource::NotModified);
+        assert_eq!(refresh.fetched_at, downloaded.fetched_at);
+        assert_eq!(refresh.plugins, 2);
+
+        // The cache of another profile is filled from the snapshot
+        let (other_cache, other_dir) = setup();
+        let refresh = block_on(refresh_feed::<ConditionalDownloader>(
+            &other_cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+        assert_eq!(refresh.source, FeedSource::NotModified);
+        assert!(other_cache.get_plugin("PrimePlugin").unwrap().is_some());
+        assert_eq!(
+            other_cache.get_feed_fetched_at().unwrap(),
+            Some(downloaded.fetched_at)
+        );
+
+        fs::remove_dir_all(test_dir).unwrap();
+        fs::remove_dir_all(other_dir).unwrap();
+    }
+
+    #[test]
+    fn refresh_interval() {
+        let (cache, test_dir) = setup();
+        let snapshot = FeedSnapshot::new(&test_dir);
+        let downloaded = block_on(refresh_feed::<SampleDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+
+        let refresh = block_on(refresh_feed::<OfflineDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::from_secs(60 * 60),
+        ))
+        .unwrap();
+        assert_eq!(refresh.source, FeedSource::Skipped);
+        assert_eq!(refresh.fetched_at, downloaded.fetched_at);
+
+        // The interval only applies to the feed of the snapshot
+        let result = block_on(refresh_feed::<SampleDownloader>(
+            &cache,
+            &snapshot,
+            "https://example.com/other.xml",
+            Duration::from_secs(60 * 60),
+        ))
+        .unwrap();
+        assert_eq!(result.source, FeedSource::Downloaded);
+
+        let refresh = block_on(refresh_feed::<OfflineDownloader>(
+            &cache,
+            &snapshot,
+            FEED_URL,
+            Duration::ZERO,
+        ))
+        .unwrap();
+        assert!(refresh.is_offline());
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+}
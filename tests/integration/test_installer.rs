@@ -1,10 +1,23 @@
 #[cfg(test)]
 mod tests {
-    use std::{fs, path::Path};
+    use std::{
+        fs,
+        path::Path,
+        sync::{atomic::AtomicBool, Arc, Mutex},
+    };
 
     use bytes::Bytes;
+    use lembas::core::{
+        installer::verify_archive,
+        io::{cache::DatabaseHandler, ArchiveStore},
+        Error, InstallProgress,
+    };
+    use walkdir::WalkDir;
 
-    use crate::fixtures::installer_fixtures::{installer_fixture, setup_dirs};
+    use crate::database_fixtures;
+    use crate::fixtures::installer_fixtures::{
+        build_archive, installer_fixture, munkey_archive, setup_dirs,
+    };
 
     #[test]
     fn install_plugin() {
@@ -27,9 +40,9 @@ mod tests {
         let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
         let bytes = Bytes::from(&b"PK\x03\x04\n\0\0\0\0\0\xb2\x866>\0\0\0\0\0\0\0\0\0\0\0\0\x07\0\0\0Munkey/PK\x03\x04\n\0\0\0\0\0\xfc{\\>\0\0\0\0\0\0\0\0\0\0\0\0\n\0\0\0Munkey/Ah/PK\x03\x04\x14\0\0\0\x08\0\xfcjb>\x1fM\xcfn*\x04\0\0-\x0c\0\0\x12\0\0\0Munkey/Ah/Main.lua\xadV\xdbn\xdb8\x10}v\x80\xfc\xc3@]\xa3R*+\x96\x81\x02\xbb\xbe\x04H\xd2d\xfbPl\n8@\x1f\x02\xa7\xa0%\xda\x12*Q\x02I\xad\x13\x14\xed\xb7\xef\x0cu\xb1\xd4\xc8MZ\xec\x8bDQg\xce\x9c\x19\x0e\x87\x8c\xd3<\x93\x1a\xac\xdbB\xaec\xc1\xad\xe3\xa3\xe3\xa3\xf3\xe82\ra\x01\xd5\x9c\xb7\x8cx\x92\\fi\xcaDh;3\x82l\n\x11\xe88\x13`\xb0\xd3\xab\x07\x1e\x14\x9a\xdbA\x1a\xba\xc0\xe4V9\xc7G\x83\x7fY\xe2\xbb\x90\xe5\xd2\x05\x1cN\x90\x90\xfeLS\xa6\x83\xc8\xb6\xee\xed\xbb\xe1hh\x0f\xd5\xea\xe4n\x18\x0e=w\xe8\xac\xde\x0c\xd5\x89c\xdf\xdd\x0fwC\xe5\xb9+\xa7\x0f\xe2\x9d8\x7fX\xce\x0cF\xa3\xdb\x9bw7S\xd8\xc4\"\x04\x06k\xae5\x97\xb0c\x8f\xa03\x083\xd0Q\xacPD\xbc\x01\x9b\x84\xc0\xf7\x05\x888\x01\x0c\xa1T\xd3\xfaF\x89\xd5\xa7\x83f\\ 7\\F<\xf8\xa2\0\xcdc\xfdZ!\x0c\xf8C.\xb9R\x184\xd2\x0e\xca\xb8\xf1\xc9\x92\x92\xdf\xf3\x0c\x0f\xbe\x88\xde\x08\x84+\x1c\x16L\xf3\xae-O\x14'\n\xca\x06&E\xe5I\xacm\xfap\xc1\x8224X\xd2\xa4\"5\x94\xb4\"\xe5B\x83\xd22\x16[`\xdaL\xab\x9c\x05\x86\x86\"$\xeb;\x7fe\xa21\xe3\xc9\xea'\x11\x19\x8f\x90\xcb,,\x02\x1e\x82\xdee\xa4\xb9\xe0\x94\xaf\xc1\x80\xa0Y\x9a3\x14\\\xbbL\xb8\xd8\xea\xa8d\xcad\xc8eW\x19\xe2H\x1f\xe6\xbd.\x0bCD\xc2J\x02\x0f\tl\\\xf7\xc8\xdb$Y&k\xb9\x8e\x03\x8b\x05\xfc\x042AH\x19\x80!\xec\x84:\xaf\x03m\x03(\xa7~Uh\xf8k\xb6\x9f\xad\xcb\x0fM\xabYZ\x866\xe1\xd9\xe2YF\xbf\x97\xb1\xf1\xc3Eh\x065\xf3s\xc1\xcf\x7f%\xf6\xde\xc8\xfa\x02{\xa9\xf7\xb3\xdf\xf5\xee\xf7y\xaf%U9\xa8k\xbc\xd7\xb0\xc7\xae2#\xdd\xfa1\xe7\xb6\xceD\x91\xae\xb9Q\xe3\x97ub\x953\x16\x98*\x87\x92\xa4\xbf\xc6\x8dS\xe21P$5\xe0X\x11\xd8(\xa8vo\x11|\x94q\xe9bV\xab~\x91\x04\xa2}\x02\x9atA\xfd\xaa\xc8rblU\xbf\x12\xd3?'{=\x86 +\x92P\xbc\xd6\x90\xb2/\x9c6\x1bNg\x1b\xb3\x07c\x91\x17\xba\xc5\xf47\xd7\xefy\x92\xdbN+\xad\xe6i\x1eO\x1aw\xe5x]<f\x85va\x1d\x87\xd4\xb9\xcb\xcf\xcf\x01.R\xab0*\xd4\xe9\xd8\xfb\xeb\xadsb\xfb\xe3{\x8c\xf8\xb4|\xcf\xaaV\x8b\x04\x9dE!\x11\x9dc\xc4\xfb$c\xcd?\xe0\xb7\r\xd6\\n\xd7\x8bW\xe3\xf1\xf5\xf5x|\xb6\xe4\x1a.\x8c\x07\xec$\xf3S\xfcu\x06%\xe0\xfa\xda\0,j\xae\x8d4\x1c[\xaa\x03\xabx\xb0\rm\xb9\xee\xfc\xf9s\xfc#A\xdb\xdc\x02#\xbf.\xd9_\x90\xbb\xd4Ljj\x90\x17\x18\xf6\x13\xd1\x17\x8d\xcfN\x12\xe3\xb07\x83mE\x1dG\xa6d\xfe\xbf\xc4\xd0I\xb9\xe3\\\x1cN\x10\xc6r\x90\x03\xb5\xfcFf\x0f\xd7_S\xae\x08;\x9c\xf8\xe5\xa3\xd0\xeca\n\xa7,\x82y.\xe3\x80\xd7\xd4\x87m\xf0`\x0e\xa0c\xb8?\x84+k#\xe9\xa9\xa0e\x847\xa2F\x95\xe4\xba\x90\x02\xac\xf3\xf7\xf5\"\x10s\x910\x9dI\xeb\xe0\xb62\xf7\x02rH\x14\x1cOW\xdcJI\xc6\xc2\xb2\xe5\xdaV\xcdJ\xd920\xbb\xd9B\x06\xfd\xfd\x85{\xa8[\x93\r\x9dY\x84\xber\\\x1c\\=\xe3vofu\xf6DOo\xe9]\xd4\xf2.\x93/M\x94.\xe4\x1f\x19\xdd\xca\x04%!\xc9\x02tp\xcb\xd6\t\xc7\\|\xfdF}\xed\x9f\x9b\xdb\xab)\x14\xd8\xcd\xbe\n\x9c\x1c\x7f\xc3v\x06\x1f\n6z\xeb\x8d\x1b\x93M\x8e7\x1el\xab\xb67r\x8c\xd2\x9a\xb5A$L\xe9\xcf(\x05Q~3\x89\xb7)\xeeB\xc0r\x9c\xad\x04M\xe9\xaeh\x13\x9f\x0b>i\xdaE1\xaaQx]\xac\x0e\x1fE\x89\xf7\xf1\x8ec\x0cqlY\xfbCP\x93v/\xc6\xe6+\xb5m\x02q\x11\xe5\xb4\x8e\xaf\x96\x10\xfe\x86\xa4\x0c\x9eQQ\x1b8uBID\xc32_\xc0\xab\xca\xa8Q\xd1\xa5R\xc5\xdans\xf4i\x84Jd\xc9_\x15\x9e\xf9\xd5\xac_\xb7\xba\xce\xc3\xb0\xbe\xec\x83\xc5\"\xcb-\x8b\x1a\xb7\xcc\x7fPK\x03\x04\x14\0\0\0\x08\0\xd3\xa8d>H>X{|\0\0\0\xb7\0\0\0\x10\0\0\0Munkey/Ah.plugin\xb3\xb1\xaf\xc8\xcdQ(K-*\xce\xcc\xcf\xb3U2\xd43P\xb2\xb7\xe3\xe5\xb2\t\xc8)M\xcf\xcc\x03\xb28m<\xf3\xd2\xf2\x8br\x13K\x80\n@|N\x1b\xbf\xc4\xdcT;\xc7\x0c\x1b}0\x03,\xe4XZ\x92\x91_d\xe7[\x9a\x97\x9dZi\xa3\x0f\xe5\x82\xa5\xc2 f\xdb\x19\xe8\x99\xda\xe8\xc38 s\xf5\xd1\x0c\xb6\tHL\xceNLO\x85\x9a\xa2\xe7\x98\xa1\xe7\x9b\x98\x99g\xa3\x0f\x13\x07:K\x1f\xea.\0PK\x01\x02\x14\0\n\0\0\0\0\0\xb2\x866>\0\0\0\0\0\0\0\0\0\0\0\0\x07\0\0\0\0\0\0\0\0\0\x10\0\0\0\0\0\0\0Munkey/PK\x01\x02\x14\0\n\0\0\0\0\0\xfc{\\>\0\0\0\0\0\0\0\0\0\0\0\0\n\0\0\0\0\0\0\0\0\0\x10\0\0\0%\0\0\0Munkey/Ah/PK\x01\x02\x14\0\x14\0\0\0\x08\0\xfcjb>\x1fM\xcfn*\x04\0\0-\x0c\0\0\x12\0\0\0\0\0\0\0\x01\0 \0\0\0M\0\0\0Munkey/Ah/Main.luaPK\x01\x02\x14\0\x14\0\0\0\x08\0\xd3\xa8d>H>X{|\0\0\0\xb7\0\0\0\x10\0\0\0\0\0\0\0\x01\0 \0\0\0\xa7\x04\0\0Munkey/Ah.pluginPK\x05\x06\0\0\0\0\x04\0\x04\0\xeb\0\0\0Q\x05\0\0\0\0"[..]);
 
-        let folder_name = installer.install(&bytes).unwrap();
+        let layout = installer.install(&bytes).unwrap();
 
-        installer.move_files(&folder_name);
+        installer.move_files(&layout).unwrap();
 
         let result = get_files_from_directory(&installer.plugins_dir.join("Munkey"));
 
@@ -44,13 +57,13 @@ mod tests {
         let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
         let bytes = Bytes::from(&b"PK\x03\x04\n\0\0\0\0\0\xb2\x866>\0\0\0\0\0\0\0\0\0\0\0\0\x07\0\0\0Munkey/PK\x03\x04\n\0\0\0\0\0\xfc{\\>\0\0\0\0\0\0\0\0\0\0\0\0\n\0\0\0Munkey/Ah/PK\x03\x04\x14\0\0\0\x08\0\xfcjb>\x1fM\xcfn*\x04\0\0-\x0c\0\0\x12\0\0\0Munkey/Ah/Main.lua\xadV\xdbn\xdb8\x10}v\x80\xfc\xc3@]\xa3R*+\x96\x81\x02\xbb\xbe\x04H\xd2d\xfbPl\n8@\x1f\x02\xa7\xa0%\xda\x12*Q\x02I\xad\x13\x14\xed\xb7\xef\x0cu\xb1\xd4\xc8MZ\xec\x8bDQg\xce\x9c\x19\x0e\x87\x8c\xd3<\x93\x1a\xac\xdbB\xaec\xc1\xad\xe3\xa3\xe3\xa3\xf3\xe82\ra\x01\xd5\x9c\xb7\x8cx\x92\\fi\xcaDh;3\x82l\n\x11\xe88\x13`\xb0\xd3\xab\x07\x1e\x14\x9a\xdbA\x1a\xba\xc0\xe4V9\xc7G\x83\x7fY\xe2\xbb\x90\xe5\xd2\x05\x1cN\x90\x90\xfeLS\xa6\x83\xc8\xb6\xee\xed\xbb\xe1hh\x0f\xd5\xea\xe4n\x18\x0e=w\xe8\xac\xde\x0c\xd5\x89c\xdf\xdd\x0fwC\xe5\xb9+\xa7\x0f\xe2\x9d8\x7fX\xce\x0cF\xa3\xdb\x9bw7S\xd8\xc4\"\x04\x06k\xae5\x97\xb0c\x8f\xa03\x083\xd0Q\xacPD\xbc\x01\x9b\x84\xc0\xf7\x05\x888\x01\x0c\xa1T\xd3\xfaF\x89\xd5\xa7\x83f\\ 7\\F<\xf8\xa2\0\xcdc\xfdZ!\x0c\xf8C.\xb9R\x184\xd2\x0e\xca\xb8\xf1\xc9\x92\x92\xdf\xf3\x0c\x0f\xbe\x88\xde\x08\x84+\x1c\x16L\xf3\xae-O\x14'\n\xca\x06&E\xe5I\xacm\xfap\xc1\x8224X\xd2\xa4\"5\x94\xb4\"\xe5B\x83\xd22\x16[`\xdaL\xab\x9c\x05\x86\x86\"$\xeb;\x7fe\xa21\xe3\xc9\xea'\x11\x19\x8f\x90\xcb,,\x02\x1e\x82\xdee\xa4\xb9\xe0\x94\xaf\xc1\x80\xa0Y\x9a3\x14\\\xbbL\xb8\xd8\xea\xa8d\xcad\xc8eW\x19\xe2H\x1f\xe6\xbd.\x0bCD\xc2J\x02\x0f\tl\\\xf7\xc8\xdb$Y&k\xb9\x8e\x03\x8b\x05\xfc\x042AH\x19\x80!\xec\x84:\xaf\x03m\x03(\xa7~Uh\xf8k\xb6\x9f\xad\xcb\x0fM\xabYZ\x866\xe1\xd9\xe2YF\xbf\x97\xb1\xf1\xc3Eh\x065\xf3s\xc1\xcf\x7f%\xf6\xde\xc8\xfa\x02{\xa9\xf7\xb3\xdf\xf5\xee\xf7y\xaf%U9\xa8k\xbc\xd7\xb0\xc7\xae2#\xdd\xfa1\xe7\xb6\xceD\x91\xae\xb9Q\xe3\x97ub\x953\x16\x98*\x87\x92\xa4\xbf\xc6\x8dS\xe21P$5\xe0X\x11\xd8(\xa8vo\x11|\x94q\xe9bV\xab~\x91\x04\xa2}\x02\x9atA\xfd\xaa\xc8rblU\xbf\x12\xd3?'{=\x86 +\x92P\xbc\xd6\x90\xb2/\x9c6\x1bNg\x1b\xb3\x07c\x91\x17\xba\xc5\xf47\xd7\xefy\x92\xdbN+\xad\xe6i\x1eO\x1aw\xe5x]<f\x85va\x1d\x87\xd4\xb9\xcb\xcf\xcf\x01.R\xab0*\xd4\xe9\xd8\xfb\xeb\xadsb\xfb\xe3{\x8c\xf8\xb4|\xcf\xaaV\x8b\x04\x9dE!\x11\x9dc\xc4\xfb$c\xcd?\xe0\xb7\r\xd6\\n\xd7\x8bW\xe3\xf1\xf5\xf5x|\xb6\xe4\x1a.\x8c\x07\xec$\xf3S\xfcu\x06%\xe0\xfa\xda\0,j\xae\x8d4\x1c[\xaa\x03\xabx\xb0\rm\xb9\xee\xfc\xf9s\xfc#A\xdb\xdc\x02#\xbf.\xd9_\x90\xbb\xd4Ljj\x90\x17\x18\xf6\x13\xd1\x17\x8d\xcfN\x12\xe3\xb07\x83mE\x1dG\xa6d\xfe\xbf\xc4\xd0I\xb9\xe3\\\x1cN\x10\xc6r\x90\x03\xb5\xfcFf\x0f\xd7_S\xae\x08;\x9c\xf8\xe5\xa3\xd0\xeca\n\xa7,\x82y.\xe3\x80\xd7\xd4\x87m\xf0`\x0e\xa0c\xb8?\x84+k#\xe9\xa9\xa0e\x847\xa2F\x95\xe4\xba\x90\x02\xac\xf3\xf7\xf5\"\x10s\x910\x9dI\xeb\xe0\xb62\xf7\x02rH\x14\x1cOW\xdcJI\xc6\xc2\xb2\xe5\xdaV\xcdJ\xd920\xbb\xd9B\x06\xfd\xfd\x85{\xa8[\x93\r\x9dY\x84\xber\\\x1c\\=\xe3vofu\xf6DOo\xe9]\xd4\xf2.\x93/M\x94.\xe4\x1f\x19\xdd\xca\x04%!\xc9\x02tp\xcb\xd6\t\xc7\\|\xfdF}\xed\x9f\x9b\xdb\xab)\x14\xd8\xcd\xbe\n\x9c\x1c\x7f\xc3v\x06\x1f\n6z\xeb\x8d\x1b\x93M\x8e7\x1el\xab\xb67r\x8c\xd2\x9a\xb5A$L\xe9\xcf(\x05Q~3\x89\xb7)\xeeB\xc0r\x9c\xad\x04M\xe9\xaeh\x13\x9f\x0b>i\xdaE1\xaaQx]\xac\x0e\x1fE\x89\xf7\xf1\x8ec\x0cqlY\xfbCP\x93v/\xc6\xe6+\xb5m\x02q\x11\xe5\xb4\x8e\xaf\x96\x10\xfe\x86\xa4\x0c\x9eQQ\x1b8uBID\xc32_\xc0\xab\xca\xa8Q\xd1\xa5R\xc5\xdans\xf4i\x84Jd\xc9_\x15\x9e\xf9\xd5\xac_\xb7\xba\xce\xc3\xb0\xbe\xec\x83\xc5\"\xcb-\x8b\x1a\xb7\xcc\x7fPK\x03\x04\x14\0\0\0\x08\0\xd3\xa8d>H>X{|\0\0\0\xb7\0\0\0\x10\0\0\0Munkey/Ah.plugin\xb3\xb1\xaf\xc8\xcdQ(K-*\xce\xcc\xcf\xb3U2\xd43P\xb2\xb7\xe3\xe5\xb2\t\xc8)M\xcf\xcc\x03\xb28m<\xf3\xd2\xf2\x8br\x13K\x80\n@|N\x1b\xbf\xc4\xdcT;\xc7\x0c\x1b}0\x03,\xe4XZ\x92\x91_d\xe7[\x9a\x97\x9dZi\xa3\x0f\xe5\x82\xa5\xc2 f\xdb\x19\xe8\x99\xda\xe8\xc38 s\xf5\xd1\x0c\xb6\tHL\xceNLO\x85\x9a\xa2\xe7\x98\xa1\xe7\x9b\x98\x99g\xa3\x0f\x13\x07:K\x1f\xea.\0PK\x01\x02\x14\0\n\0\0\0\0\0\xb2\x866>\0\0\0\0\0\0\0\0\0\0\0\0\x07\0\0\0\0\0\0\0\0\0\x10\0\0\0\0\0\0\0Munkey/PK\x01\x02\x14\0\n\0\0\0\0\0\xfc{\\>\0\0\0\0\0\0\0\0\0\0\0\0\n\0\0\0\0\0\0\0\0\0\x10\0\0\0%\0\0\0Munkey/Ah/PK\x01\x02\x14\0\x14\0\0\0\x08\0\xfcjb>\x1fM\xcfn*\x04\0\0-\x0c\0\0\x12\0\0\0\0\0\0\0\x01\0 \0\0\0M\0\0\0Munkey/Ah/Main.luaPK\x01\x02\x14\0\x14\0\0\0\x08\0\xd3\xa8d>H>X{|\0\0\0\xb7\0\0\0\x10\0\0\0\0\0\0\0\x01\0 \0\0\0\xa7\x04\0\0Munkey/Ah.pluginPK\x05\x06\0\0\0\0\x04\0\x04\0\xeb\0\0\0Q\x05\0\0\0\0"[..]);
 
-        let folder_name = installer.install(&bytes).unwrap();
+        let layout = installer.install(&bytes).unwrap();
 
         let result = get_files_from_directory(&installer.tmp_file_path);
 
         assert_eq!(result, vec!["Munkey", "plugin.zip"]);
 
-        installer.move_files(&folder_name);
+        installer.move_files(&layout).unwrap();
 
         let result = get_files_from_directory(&installer.plugins_dir.join("Munkey"));
 
@@ -65,6 +78,594 @@ mod tests {
         fs::remove_dir_all(test_dir).expect("Error while running test teardown");
     }
 
+    #[test]
+    fn uninstall_plugin_from_manifest() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+
+        let layout = installer.install(&munkey_archive()).unwrap();
+        installer.move_files(&layout).unwrap();
+        cache.save_plugin_files(1, &installer.files).unwrap();
+
+        // Files of other plugins from the same author must survive the uninstall
+        fs::write(plugins_dir.join("Munkey").join("Other.plugin"), "").unwrap();
+
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+        installer.uninstall(&cache, 1).unwrap();
+
+        let result = get_files_from_directory(&plugins_dir.join("Munkey"));
+
+        assert_eq!(result, vec!["Other.plugin"]);
+        assert!(cache.get_plugin_files(1).unwrap().is_empty());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn uninstall_removes_empty_author_folder() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+
+        let layout = installer.install(&munkey_archive()).unwrap();
+        installer.move_files(&layout).unwrap();
+        cache.save_plugin_files(1, &installer.files).unwrap();
+
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+        installer.uninstall(&cache, 1).unwrap();
+
+        let result = get_files_from_directory(&plugins_dir);
+
+        assert_eq!(result, Vec::<String>::new());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn uninstall_without_manifest() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+
+        assert!(installer.uninstall(&cache, 1).is_err());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    fn first_version() -> Bytes {
+        build_archive(&[
+            ("Munkey/", ""),
+            ("Munkey/Ah/", ""),
+            ("Munkey/Ah/Main.lua", "v1"),
+            ("Munkey/Ah/Old.lua", "old"),
+            ("Munkey/Ah.plugin", "<Plugin>v1</Plugin>"),
+        ])
+    }
+
+    fn second_version() -> Bytes {
+        build_archive(&[
+            ("Munkey/", ""),
+            ("Munkey/Ah/", ""),
+            ("Munkey/Ah/Main.lua", "v2"),
+            ("Munkey/Ah/New.lua", "new"),
+            ("Munkey/Ah.plugin", "<Plugin>v2</Plugin>"),
+        ])
+    }
+
+    #[test]
+    fn install_archive_commits_plugin() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+
+        installer
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+
+        let plugin = cache.get_plugin("Hello World").unwrap().unwrap();
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "1.0");
+        assert_eq!(plugin.verified, 0);
+        assert_eq!(cache.get_plugin_files(1).unwrap().len(), 5);
+        assert_eq!(
+            get_file_tree(&plugins_dir),
+            vec![
+                (
+                    "Munkey/Ah.plugin".to_string(),
+                    "<Plugin>v1</Plugin>".to_string()
+                ),
+                ("Munkey/Ah/Main.lua".to_string(), "v1".to_string()),
+                ("Munkey/Ah/Old.lua".to_string(), "old".to_string()),
+            ]
+        );
+        assert!(!installer.tmp_file_path.exists());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn install_archive_with_multiple_roots() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let bytes = build_archive(&[
+            ("Plugins/Readme.txt", "readme"),
+            ("Plugins/Munkey/Ah/Main.lua", "ah"),
+            ("Plugins/Munkey/Ah.plugin", "<Plugin></Plugin>"),
+            ("Plugins/Turbine/Utils/Utils.lua", "utils"),
+            ("Plugins/Turbine/Utils.plugin", "<Plugin></Plugin>"),
+        ]);
+
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .install_archive(&cache, 1, "1.0", &bytes)
+            .unwrap();
+
+        assert_eq!(
+            get_file_tree(&plugins_dir),
+            vec![
+                (
+                    "Munkey/Ah.plugin".to_string(),
+                    "<Plugin></Plugin>".to_string()
+                ),
+                ("Munkey/Ah/Main.lua".to_string(), "ah".to_string()),
+                (
+                    "Turbine/Utils.plugin".to_string(),
+                    "<Plugin></Plugin>".to_string()
+                ),
+                ("Turbine/Utils/Utils.lua".to_string(), "utils".to_string()),
+            ]
+        );
+
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .uninstall(&cache, 1)
+            .unwrap();
+
+        assert_eq!(get_files_from_directory(&plugins_dir), Vec::<String>::new());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn install_flat_archive() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let bytes = build_archive(&[
+            ("Readme.txt", "readme"),
+            ("Ah/Main.lua", "ah"),
+            (
+                "Ah.plugin",
+                "<Plugin><Package>Munkey.Ah.Main</Package></Plugin>",
+            ),
+        ]);
+
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+        installer.install_archive(&cache, 1, "1.0", &bytes).unwrap();
+
+        assert_eq!(
+            cache.get_plugin_files(1).unwrap(),
+            vec!["Munkey/Ah.plugin", "Munkey/Ah/Main.lua"]
+        );
+        assert_eq!(get_files_from_directory(&plugins_dir), vec!["Munkey"]);
+
+        installer.uninstall(&cache, 1).unwrap();
+
+        assert_eq!(get_files_from_directory(&plugins_dir), Vec::<String>::new());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn update_archive_replaces_previous_files() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .install_archive(&cache, 1, "2.0", &second_version())
+            .unwrap();
+
+        let plugin = cache.get_plugin("Hello World").unwrap().unwrap();
+        assert_eq!(plugin.current_version, "2.0");
+        assert_eq!(
+            get_file_tree(&plugins_dir),
+            vec![
+                (
+                    "Munkey/Ah.plugin".to_string(),
+                    "<Plugin>v2</Plugin>".to_string()
+                ),
+                ("Munkey/Ah/Main.lua".to_string(), "v2".to_string()),
+                ("Munkey/Ah/New.lua".to_string(), "new".to_string()),
+            ]
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn corrupt_archive_keeps_installed_version() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+        let files_before = get_file_tree(&plugins_dir);
+
+        let result = installer_fixture(&tmp_dir, &plugins_dir).install_archive(
+            &cache,
+            1,
+            "2.0",
+            &Bytes::from_static(b"not a zip archive"),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(get_file_tree(&plugins_dir), files_before);
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn install_archive_reports_progress() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let listener = reported.clone();
+
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .with_progress(Arc::new(move |plugin, progress| {
+                listener
+                    .lock()
+                    .unwrap()
+                    .push((plugin.to_string(), progress.clone()));
+            }))
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                ("Hello World".to_string(), InstallProgress::Extracting),
+                ("Hello World".to_string(), InstallProgress::Moving),
+            ]
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn cancelled_update_keeps_installed_version() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+        let files_before = get_file_tree(&plugins_dir);
+
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir)
+            .with_cancel_flag(Arc::new(AtomicBool::new(true)));
+        let error = installer
+            .install_archive(&cache, 1, "2.0", &second_version())
+            .unwrap_err();
+
+        assert!(matches!(error, Error::Cancelled));
+        assert_eq!(get_file_tree(&plugins_dir), files_before);
+        assert_eq!(
+            cache
+                .get_plugin("Hello World")
+                .unwrap()
+                .unwrap()
+                .current_version,
+            "1.0"
+        );
+        assert!(!installer.tmp_file_path.exists());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    fn local_archive(version: &str) -> Bytes {
+        build_archive(&[
+            ("Readme.txt", "readme"),
+            ("Munkey/Ah/Main.lua", version),
+            (
+                "Munkey/Ah.plugin",
+                &format!(
+                    "<Plugin><Information><Name>Ah Beta</Name><Author>Munkey</Author><Version>{}</Version></Information><Package>Munkey.Ah.Main</Package></Plugin>",
+                    version
+                ),
+            ),
+        ])
+    }
+
+    #[test]
+    fn install_local_archive() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let archive_path = test_dir.join("AhBeta.zip");
+        fs::write(&archive_path, local_archive("2.0b")).unwrap();
+
+        let plugin = installer_fixture(&tmp_dir, &plugins_dir)
+            .install_local(&cache, &archive_path)
+            .unwrap();
+
+        assert_eq!(plugin.name, "Ah Beta");
+        assert_eq!(plugin.id, -1);
+
+        let cached_plugin = cache.get_plugin("Ah Beta").unwrap().unwrap();
+        assert!(cached_plugin.is_local());
+        assert_eq!(cached_plugin.installed, 1);
+        assert_eq!(cached_plugin.author, "Munkey");
+        assert_eq!(cached_plugin.current_version, "2.0b");
+        assert_eq!(cached_plugin.download_url, "");
+        assert_eq!(
+            cache.get_plugin_files(-1).unwrap(),
+            vec!["Munkey/Ah.plugin", "Munkey/Ah/Main.lua"]
+        );
+        assert_eq!(get_files_from_directory(&plugins_dir), vec!["Munkey"]);
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn install_local_folder() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let source_dir = test_dir.join("source").join("Munkey");
+        fs::create_dir_all(source_dir.join("Ah")).unwrap();
+        fs::write(source_dir.join("Ah").join("Main.lua"), "main").unwrap();
+        fs::write(
+            source_dir.join("Ah.plugin"),
+            "<Plugin><Information><Name>Ah</Name></Information></Plugin>",
+        )
+        .unwrap();
+
+        let plugin = installer_fixture(&tmp_dir, &plugins_dir)
+            .install_local(&cache, &source_dir)
+            .unwrap();
+
+        assert_eq!(plugin.name, "Ah");
+        assert_eq!(plugin.current_version, "local");
+        assert_eq!(
+            get_file_tree(&plugins_dir),
+            vec![
+                (
+                    "Munkey/Ah.plugin".to_string(),
+                    "<Plugin><Information><Name>Ah</Name></Information></Plugin>".to_string()
+                ),
+                ("Munkey/Ah/Main.lua".to_string(), "main".to_string()),
+            ]
+        );
+        assert!(source_dir.join("Ah").join("Main.lua").exists());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn reinstall_local_plugin_keeps_id() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let archive_path = test_dir.join("AhBeta.zip");
+
+        fs::write(&archive_path, local_archive("2.0b")).unwrap();
+        installer_fixture(&tmp_dir, &plugins_dir)
+            .install_local(&cache, &archive_path)
+            .unwrap();
+        fs::write(&archive_path, local_archive("2.1b")).unwrap();
+        let plugin = installer_fixture(&tmp_dir, &plugins_dir)
+            .install_local(&cache, &archive_path)
+            .unwrap();
+
+        assert_eq!(plugin.id, -1);
+        assert_eq!(cache.get_plugins().unwrap().len(), 1);
+        assert_eq!(
+            fs::read_to_string(plugins_dir.join("Munkey/Ah/Main.lua")).unwrap(),
+            "2.1b"
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn local_plugin_does_not_replace_catalog_plugin() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let source_dir = test_dir.join("source").join("Marius");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(
+            source_dir.join("PetStable.plugin"),
+            "<Plugin><Information><Name>PetStable</Name></Information></Plugin>",
+        )
+        .unwrap();
+
+        let result = installer_fixture(&tmp_dir, &plugins_dir).install_local(&cache, &source_dir);
+
+        assert!(result.is_err());
+        assert!(!cache.get_plugin("PetStable").unwrap().unwrap().is_local());
+        assert_eq!(get_files_from_directory(&plugins_dir), Vec::<String>::new());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn verify_archive_with_matching_checksum() {
+        let bytes = b"lembas";
+
+        let result = verify_archive(bytes, 6, "6E1C4D5EB6A26F6E4E7E5C0DD3A9E7D1");
+        assert!(result.is_err());
+
+        let result = verify_archive(bytes, 6, &format!("{:X}", md5::compute(bytes)));
+        assert_eq!(result, Ok(true));
+    }
+
+    #[test]
+    fn verify_archive_with_wrong_size() {
+        let result = verify_archive(b"lembas", 7, "").unwrap_err();
+
+        assert_eq!(result.expected_size, 7);
+        assert_eq!(result.actual_size, 6);
+        assert!(result.to_string().starts_with("Corrupt download"));
+    }
+
+    #[test]
+    fn verify_archive_without_feed_information() {
+        assert_eq!(verify_archive(b"lembas", 0, ""), Ok(false));
+    }
+
+    #[test]
+    fn install_archive_records_history() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+
+        installer
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+        installer
+            .install_archive(&cache, 1, "2.0", &second_version())
+            .unwrap();
+
+        let history = cache.get_plugin_history(1).unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|entry| entry.version.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["2.0", "1.0"]
+        );
+        assert_eq!(
+            history[1].hash,
+            format!("{:x}", md5::compute(first_version()))
+        );
+        let candidates = installer.rollback_candidates(&cache, 1).unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].version, "1.0");
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn roll_back_to_previous_version() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+        installer
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+        installer
+            .install_archive(&cache, 1, "2.0", &second_version())
+            .unwrap();
+
+        installer.roll_back_to(&cache, 1, "1.0").unwrap();
+
+        let plugin = cache.get_plugin("Hello World").unwrap().unwrap();
+        assert_eq!(plugin.current_version, "1.0");
+        assert_eq!(plugin.pinned, 1);
+        assert_eq!(
+            fs::read_to_string(plugins_dir.join("Munkey/Ah/Main.lua")).unwrap(),
+            "v1"
+        );
+        assert!(!plugins_dir.join("Munkey/Ah/New.lua").exists());
+        assert_eq!(
+            installer.rollback_candidates(&cache, 1).unwrap()[0].version,
+            "2.0"
+        );
+
+        cache.set_pinned(1, false).unwrap();
+        assert_eq!(cache.get_plugin("Hello World").unwrap().unwrap().pinned, 0);
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn roll_back_without_stored_archive() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir);
+        installer
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+        installer
+            .install_archive(&cache, 1, "2.0", &second_version())
+            .unwrap();
+        fs::remove_dir_all(test_dir.join("archives")).unwrap();
+
+        assert!(installer.rollback_candidates(&cache, 1).unwrap().is_empty());
+        assert!(installer.roll_back_to(&cache, 1, "1.0").is_err());
+        assert_eq!(
+            cache
+                .get_plugin("Hello World")
+                .unwrap()
+                .unwrap()
+                .current_version,
+            "2.0"
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn archive_store_override() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let archives_dir = test_dir.join("storage").join("archives");
+        let mut installer = installer_fixture(&tmp_dir, &plugins_dir)
+            .with_archive_store(ArchiveStore::new(&archives_dir));
+        installer
+            .install_archive(&cache, 1, "1.0", &first_version())
+            .unwrap();
+        installer
+            .install_archive(&cache, 1, "2.0", &second_version())
+            .unwrap();
+
+        assert_eq!(ArchiveStore::new(&archives_dir).plugin_archives(1).len(), 2);
+        assert!(!test_dir.join("archives").exists());
+        installer.roll_back_to(&cache, 1, "1.0").unwrap();
+        assert_eq!(
+            fs::read_to_string(plugins_dir.join("Munkey/Ah/Main.lua")).unwrap(),
+            "v1"
+        );
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    /// Returns all files below the given directory with their content, sorted by path.
+    fn get_file_tree(path: &Path) -> Vec<(String, String)> {
+        let mut files = WalkDir::new(path)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let relative_path = entry.path().strip_prefix(path).unwrap();
+                (
+                    relative_path.to_string_lossy().replace('\\', "/"),
+                    fs::read_to_string(entry.path()).unwrap(),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+        files.sort();
+        files
+    }
+
     fn get_files_from_directory(path: &Path) -> Vec<String> {
         fs::read_dir(path)
             .unwrap()
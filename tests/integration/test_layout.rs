@@ -0,0 +1,169 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bytes::Bytes;
+    use lembas::core::layout::ArchiveLayout;
+    use zip::ZipArchive;
+
+    use crate::fixtures::installer_fixtures::build_archive;
+
+    fn analyse(bytes: Bytes) -> ArchiveLayout {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        ArchiveLayout::analyse(&mut archive).unwrap()
+    }
+
+    const DESCRIPTOR: &str = "<Plugin><Package>Munkey.Ah.Main</Package></Plugin>";
+
+    #[test]
+    fn single_author_folder() {
+        let layout = analyse(build_archive(&[
+            ("Munkey/", ""),
+            ("Munkey/Ah/Main.lua", ""),
+            ("Munkey/Ah.plugin", DESCRIPTOR),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey"]);
+        assert_eq!(
+            layout.targets(),
+            vec!["Munkey/", "Munkey/Ah/Main.lua", "Munkey/Ah.plugin"]
+        );
+        assert!(layout.skipped.is_empty());
+    }
+
+    #[test]
+    fn multiple_author_folders() {
+        let layout = analyse(build_archive(&[
+            ("Munkey/Ah/Main.lua", ""),
+            ("Munkey/Ah.plugin", DESCRIPTOR),
+            ("Turbine/Utils/Utils.lua", ""),
+            ("Turbine/Utils.plugin", ""),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey", "Turbine"]);
+        assert_eq!(
+            layout.targets(),
+            vec![
+                "Munkey/Ah/Main.lua",
+                "Munkey/Ah.plugin",
+                "Turbine/Utils/Utils.lua",
+                "Turbine/Utils.plugin"
+            ]
+        );
+    }
+
+    #[test]
+    fn readme_next_to_author_folder() {
+        let layout = analyse(build_archive(&[
+            (
+                "Readme.txt",
+                "Copy the Munkey folder into your plugins folder",
+            ),
+            ("Munkey/Ah/Main.lua", ""),
+            ("Munkey/Ah.plugin", DESCRIPTOR),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey"]);
+        assert_eq!(
+            layout.targets(),
+            vec!["Munkey/Ah/Main.lua", "Munkey/Ah.plugin"]
+        );
+        assert_eq!(layout.skipped, vec!["Readme.txt"]);
+    }
+
+    #[test]
+    fn plugins_wrapper_folder() {
+        let layout = analyse(build_archive(&[
+            ("Plugins/", ""),
+            ("Plugins/Munkey/", ""),
+            ("Plugins/Munkey/Ah/Main.lua", ""),
+            ("Plugins/Munkey/Ah.plugin", DESCRIPTOR),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey"]);
+        assert_eq!(
+            layout.targets(),
+            vec!["Munkey/", "Munkey/Ah/Main.lua", "Munkey/Ah.plugin"]
+        );
+        assert_eq!(layout.entries[1].source, "Plugins/Munkey/Ah/Main.lua");
+    }
+
+    #[test]
+    fn nested_wrapper_folders() {
+        let layout = analyse(build_archive(&[
+            ("Ah v1.2/Readme.txt", ""),
+            ("Ah v1.2/Plugins/Munkey/Ah/Main.lua", ""),
+            ("Ah v1.2/Plugins/Munkey/Ah.plugin", DESCRIPTOR),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey"]);
+        assert_eq!(
+            layout.targets(),
+            vec!["Munkey/Ah/Main.lua", "Munkey/Ah.plugin"]
+        );
+        assert_eq!(layout.skipped, vec!["Ah v1.2/Readme.txt"]);
+    }
+
+    #[test]
+    fn descriptor_without_author_folder() {
+        let layout = analyse(build_archive(&[
+            ("Readme.txt", ""),
+            ("Ah/Main.lua", ""),
+            ("Ah.plugin", DESCRIPTOR),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey"]);
+        assert_eq!(
+            layout.targets(),
+            vec!["Munkey/Ah/Main.lua", "Munkey/Ah.plugin"]
+        );
+        assert_eq!(layout.skipped, vec!["Readme.txt"]);
+    }
+
+    #[test]
+    fn descriptor_inside_of_plugins_wrapper() {
+        let layout = analyse(build_archive(&[
+            ("Plugins/Ah/Main.lua", ""),
+            ("Plugins/Ah.plugin", DESCRIPTOR),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey"]);
+        assert_eq!(
+            layout.targets(),
+            vec!["Munkey/Ah/Main.lua", "Munkey/Ah.plugin"]
+        );
+    }
+
+    #[test]
+    fn nested_descriptor_belongs_to_outer_plugin() {
+        let layout = analyse(build_archive(&[
+            ("Munkey/Ah/Main.lua", ""),
+            ("Munkey/Ah/Modules/Extra.plugin", ""),
+            ("Munkey/Ah.plugin", DESCRIPTOR),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Munkey"]);
+        assert_eq!(layout.entries.len(), 3);
+    }
+
+    #[test]
+    fn archive_without_descriptor() {
+        let layout = analyse(build_archive(&[
+            ("Readme.txt", ""),
+            ("Turbine/UI/Lotro/Lotro.lua", ""),
+        ]));
+
+        assert_eq!(layout.roots, vec!["Turbine"]);
+        assert_eq!(layout.targets(), vec!["Turbine/UI/Lotro/Lotro.lua"]);
+        assert_eq!(layout.skipped, vec!["Readme.txt"]);
+    }
+
+    #[test]
+    fn archive_without_plugin() {
+        let bytes = build_archive(&[("Readme.txt", "")]);
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        assert!(ArchiveLayout::analyse(&mut archive).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use lembas::core::{
+        io::{cache::DatabaseHandler, ArchiveStore, Cache},
+        lockfile::{LockProblem, LockedPlugin, Lockfile, LOCKFILE_FORMAT},
+        plugin::LOCAL_SOURCE,
+        Error, Plugin,
+    };
+
+    use crate::database_fixtures;
+    use crate::fixtures::installer_fixtures::{build_archive, setup_dirs};
+
+    fn feed_plugin(id: i32, name: &str, current: &str, latest: &str) -> Plugin {
+        Plugin::new(name)
+            .with_id(id)
+            .with_current_version(current)
+            .with_remote_information("", latest, 0, "", 0, &format!("hash{}", latest))
+            .build()
+    }
+
+    fn locked(id: i32, name: &str, version: &str) -> LockedPlugin {
+        LockedPlugin {
+            id,
+            name: name.to_string(),
+            version: version.to_string(),
+            hash: String::new(),
+        }
+    }
+
+    fn lockfile(plugins: Vec<LockedPlugin>) -> Lockfile {
+        Lockfile {
+            format: LOCKFILE_FORMAT,
+            plugins,
+        }
+    }
+
+    fn names(plugins: &[Plugin]) -> Vec<&str> {
+        plugins.iter().map(|plugin| plugin.name.as_str()).collect()
+    }
+
+    fn insert(cache: &Cache, plugin: &Plugin, installed: i32) {
+        cache.insert_plugin(plugin, installed).unwrap();
+    }
+
+    #[test]
+    fn export_installed_plugins() {
+        let (cache, test_dir) = database_fixtures::setup();
+        insert(&cache, &feed_plugin(2, "Waypoint", "1.0", "1.0"), 1);
+        insert(&cache, &feed_plugin(1, "Compendium", "1.0", "1.2"), 1);
+        insert(&cache, &feed_plugin(3, "Bags", "", "2.0"), 0);
+        insert(
+            &cache,
+            &Plugin::new("Homebrew")
+                .with_id(-1)
+                .with_current_version("0.1")
+                .with_source(LOCAL_SOURCE),
+            1,
+        );
+
+        let lockfile = Lockfile::export(&cache).unwrap();
+
+        assert_eq!(lockfile.format, LOCKFILE_FORMAT);
+        assert_eq!(
+            lockfile.plugins,
+            vec![
+                locked(1, "Compendium", "1.0"),
+                LockedPlugin {
+                    hash: "hash1.0".to_string(),
+                    ..locked(2, "Waypoint", "1.0")
+                },
+            ]
+        );
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let (test_dir, _, _) = setup_dirs();
+        let path = test_dir.join("raid.json");
+        let lockfile = lockfile(vec![locked(1, "Compendium", "1.0")]);
+
+        lockfile.save(&path).unwrap();
+        assert_eq!(Lockfile::load(&path).unwrap(), lockfile);
+
+        fs::write(&path, r#"{"format": 99, "plugins": []}"#).unwrap();
+        assert!(Lockfile::load(&path).is_err());
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn load_errors() {
+        let (test_dir, _, _) = setup_dirs();
+        let path = test_dir.join("raid.json");
+
+        match Lockfile::load(&path).unwrap_err() {
+            Error::Filesystem {
+                path: error_path, ..
+            } => assert_eq!(error_path, path),
+            error => panic!("Unexpected error: {}", error),
+        }
+
+        fs::write(&path, "<html></html>").unwrap();
+        assert!(matches!(Lockfile::load(&path), Err(Error::Config(_))));
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn plan_install_update_and_remove() {
+        let (cache, test_dir) = database_fixtures::setup();
+        insert(&cache, &feed_plugin(1, "Compendium", "", "1.2"), 0);
+        insert(&cache, &feed_plugin(2, "Waypoint", "1.0", "1.1"), 1);
+        insert(&cache, &feed_plugin(3, "Bags", "2.0", "2.0"), 1);
+        insert(&cache, &feed_plugin(4, "Clock", "1.0", "1.0"), 1);
+
+        let plan = lockfile(vec![
+            locked(1, "Compendium", "1.2"),
+            locked(2, "Waypoint", "1.1"),
+            locked(3, "Bags", "v2.0"),
+        ])
+        .plan(&cache)
+        .unwrap();
+
+        assert_eq!(names(&plan.install), vec!["Compendium"]);
+        assert_eq!(names(&plan.update), vec!["Waypoint"]);
+        assert_eq!(names(&plan.remove), vec!["Clock"]);
+        assert!(plan.problems.is_empty());
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn plan_flags_unavailable_versions() {
+        let (cache, test_dir) = database_fixtures::setup();
+        insert(&cache, &feed_plugin(1, "Compendium", "", "1.3"), 0);
+        insert(&cache, &feed_plugin(2, "Waypoint", "1.0", "1.1"), 1);
+
+        let plan = lockfile(vec![
+            locked(1, "Compendium", "1.2"),
+            LockedPlugin {
+                hash: "other".to_string(),
+                ..locked(2, "Waypoint", "1.1")
+            },
+            locked(5, "Gone", "1.0"),
+        ])
+        .plan(&cache)
+        .unwrap();
+
+        assert!(plan.is_empty());
+        assert_eq!(
+            plan.problems,
+            vec![
+                LockProblem::VersionUnavailable {
+                    plugin: locked(1, "Compendium", "1.2"),
+                    offered: "1.3".to_string()
+                },
+                LockProblem::VersionUnavailable {
+                    plugin: LockedPlugin {
+                        hash: "other".to_string(),
+                        ..locked(2, "Waypoint", "1.1")
+                    },
+                    offered: "1.1".to_string()
+                },
+                LockProblem::NotInFeed(locked(5, "Gone", "1.0")),
+            ]
+        );
+
+        database_fixtures::teardown(cache, test_dir);
+    }
+
+    #[test]
+    fn apply_plan() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        insert(&cache, &feed_plugin(1, "Compendium", "", "1.2"), 0);
+        insert(&cache, &feed_plugin(2, "Waypoint", "", "1.0"), 0);
+        let archive = |name: &str| {
+            build_archive(&[
+                (&format!("{0}/{0}.plugin", name), "<Plugin></Plugin>"),
+                (&format!("{0}/{0}/Main.lua", name), ""),
+            ])
+        };
+
+        let store = ArchiveStore::new(&test_dir.join("archives"));
+
+        let plan = lockfile(vec![locked(2, "Waypoint", "1.0")])
+            .plan(&cache)
+            .unwrap();
+        plan.apply(&cache, &tmp_dir, &plugins_dir, &store, |_, plugin| {
+            Ok(archive(&plugin.name))
+        });
+        assert!(plugins_dir.join("Waypoint/Waypoint.plugin").exists());
+
+        let plan = lockfile(vec![
+            locked(1, "Compendium", "1.2"),
+            locked(3, "Missing", "1.0"),
+        ])
+        .plan(&cache)
+        .unwrap();
+        let report = plan.apply(&cache, &tmp_dir, &plugins_dir, &store, |_, plugin| {
+            Ok(archive(&plugin.name))
+        });
+
+        assert_eq!(report.installed, vec!["Compendium"]);
+        assert_eq!(report.removed, vec!["Waypoint"]);
+        assert!(report.failed.is_empty());
+        assert!(plugins_dir.join("Compendium/Compendium/Main.lua").exists());
+        assert!(!plugins_dir.join("Waypoint").exists());
+        let installed = cache.get_plugin("Compendium").unwrap().unwrap();
+        assert_eq!(installed.installed, 1);
+        assert_eq!(installed.current_version, "1.2");
+
+        fs::remove_dir_all(test_dir).unwrap();
+        database_fixtures::teardown(cache, db_dir);
+    }
+}
@@ -0,0 +1,137 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, create_dir_all, remove_dir_all},
+        path::{Path, PathBuf},
+    };
+
+    use lembas::core::{
+        backup::BackupManager,
+        plugin_data::{characters, copy_character, find_character, plugin_data_dir},
+    };
+
+    use crate::fixtures::installer_fixtures::setup_dirs;
+
+    /// Creates a plugin data directory with two characters of one account. Returns the test, plugins and plugin
+    /// data directory.
+    fn setup_plugin_data() -> (PathBuf, PathBuf, PathBuf) {
+        let (test_dir, _, plugins_dir) = setup_dirs();
+        let plugin_data_dir = plugin_data_dir(&plugins_dir);
+
+        write_data(
+            &plugin_data_dir,
+            "Account/Laurelin/Frodo/TitanBar.plugindata",
+            "frodo",
+        );
+        write_data(
+            &plugin_data_dir,
+            "Account/Laurelin/Frodo/HugeBag.plugindata",
+            "bag",
+        );
+        write_data(
+            &plugin_data_dir,
+            "Account/Laurelin/Frodo/notes.txt",
+            "notes",
+        );
+        create_dir_all(plugin_data_dir.join("Account/Laurelin/Sam")).unwrap();
+        write_data(
+            &plugin_data_dir,
+            "Account/AllServers/TitanBar.plugindata",
+            "account",
+        );
+
+        (test_dir, plugins_dir, plugin_data_dir)
+    }
+
+    fn write_data(plugin_data_dir: &Path, file: &str, content: &str) {
+        let path = plugin_data_dir.join(file);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn list_characters() {
+        let (test_dir, _, plugin_data_dir) = setup_plugin_data();
+
+        let characters = characters(&plugin_data_dir);
+
+        let keys = characters
+            .iter()
+            .map(|character| character.key())
+            .collect::<Vec<String>>();
+        assert_eq!(keys, vec!["Account/Laurelin/Frodo", "Account/Laurelin/Sam"]);
+        assert_eq!(
+            characters[0].files,
+            vec!["HugeBag.plugindata", "TitanBar.plugindata"]
+        );
+        assert!(characters[1].files.is_empty());
+        assert_eq!(
+            find_character(&plugin_data_dir, "account/laurelin/sam")
+                .unwrap()
+                .name,
+            "Sam"
+        );
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn copy_character_settings() {
+        let (test_dir, _, plugin_data_dir) = setup_plugin_data();
+        let frodo = find_character(&plugin_data_dir, "Account/Laurelin/Frodo").unwrap();
+        let sam = find_character(&plugin_data_dir, "Account/Laurelin/Sam").unwrap();
+
+        let copied = copy_character(&plugin_data_dir, &frodo, &sam).unwrap();
+
+        assert_eq!(copied.len(), 2);
+        let sam_dir = plugin_data_dir.join("Account/Laurelin/Sam");
+        assert_eq!(
+            fs::read_to_string(sam_dir.join("TitanBar.plugindata")).unwrap(),
+            "frodo"
+        );
+        assert!(!sam_dir.join("notes.txt").exists());
+        assert!(copy_character(&plugin_data_dir, &frodo, &frodo).is_err());
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn restore_plugin_data_per_character_and_file() {
+        let (test_dir, plugins_dir, plugin_data_dir) = setup_plugin_data();
+        let manager = BackupManager::new(&test_dir.join("backups"), &plugins_dir)
+            .with_plugin_data_dir(&plugin_data_dir);
+        let manifest = manager.create().unwrap();
+        assert_eq!(manifest.characters.len(), 2);
+
+        let frodo_dir = plugin_data_dir.join("Account/Laurelin/Frodo");
+        write_data(&frodo_dir, "TitanBar.plugindata", "changed");
+        write_data(&frodo_dir, "HugeBag.plugindata", "changed");
+        let restored = manager
+            .restore_plugin_data(&manifest.name, None, Some("titanbar"))
+            .unwrap();
+
+        assert_eq!(restored.len(), 2);
+        assert_eq!(
+            fs::read_to_string(frodo_dir.join("TitanBar.plugindata")).unwrap(),
+            "frodo"
+        );
+        assert_eq!(
+            fs::read_to_string(frodo_dir.join("HugeBag.plugindata")).unwrap(),
+            "changed"
+        );
+
+        manager
+            .restore_plugin_data(&manifest.name, Some("Account/Laurelin/Frodo"), None)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(frodo_dir.join("HugeBag.plugindata")).unwrap(),
+            "bag"
+        );
+        assert!(manager
+            .restore_plugin_data(&manifest.name, Some("Account/Laurelin/Sam"), None)
+            .is_err());
+
+        remove_dir_all(test_dir).unwrap();
+    }
+}
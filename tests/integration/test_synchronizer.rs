@@ -0,0 +1,353 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{read_dir, remove_dir_all, remove_file},
+        path::{Path, PathBuf},
+    };
+
+    use fs_extra::dir::{copy, CopyOptions};
+    use lembas::core::{
+        io::{cache::DatabaseHandler, Cache, Synchronizer},
+        Installer, Plugin,
+    };
+
+    use crate::database_fixtures;
+    use crate::fixtures::installer_fixtures::setup_dirs;
+
+    /// Copies the sample plugins into a fresh plugins directory.
+    fn setup_plugins() -> (PathBuf, PathBuf, PathBuf) {
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+
+        for element in read_dir("tests/samples/plugin_folders").unwrap() {
+            copy(element.unwrap().path(), &plugins_dir, &CopyOptions::new())
+                .expect("Error while running setup method");
+        }
+
+        (test_dir, tmp_dir, plugins_dir)
+    }
+
+    fn insert_catalog_plugin(cache: &Cache, id: i32, name: &str) {
+        let plugin = Plugin::new(name)
+            .with_id(id)
+            .with_remote_information("", "9.9", 0, "", 0, "")
+            .build();
+        cache.insert_plugin(&plugin, 0).unwrap();
+    }
+
+    fn sorted(mut names: Vec<String>) -> Vec<String> {
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn search_local_plugins() {
+        let (test_dir, _, plugins_dir) = setup_plugins();
+
+        let local_plugins = Synchronizer::search_local(&plugins_dir).unwrap();
+        let names = local_plugins
+            .iter()
+            .map(|local_plugin| local_plugin.plugin.name.clone())
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            sorted(names),
+            vec![
+                "Alt Wallet",
+                "Animalerie",
+                "BurglarHelper",
+                "CraftTimer",
+                "HugeBag",
+                "TitanBar",
+                "Voyage"
+            ]
+        );
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn search_local_collects_plugin_files() {
+        let (test_dir, _, plugins_dir) = setup_plugins();
+
+        let local_plugins = Synchronizer::search_local(&plugins_dir).unwrap();
+        let titan_bar = local_plugins
+            .iter()
+            .find(|local_plugin| local_plugin.plugin.name == "TitanBar")
+            .unwrap();
+
+        assert_eq!(titan_bar.plugin.id, 692);
+        assert!(titan_bar
+            .files
+            .contains(&"HabnaPlugins/TitanBar.plugincompendium".to_string()));
+        assert!(titan_bar
+            .files
+            .contains(&"HabnaPlugins/TitanBarReloader.plugin".to_string()));
+        assert!(titan_bar
+            .files
+            .contains(&"HabnaPlugins/TitanBar/".to_string()));
+        assert!(!titan_bar
+            .files
+            .iter()
+            .any(|file| file.starts_with("HabnaPlugins/HugeBag")));
+
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn search_local_missing_directory() {
+        let result = Synchronizer::search_local(Path::new("tests/samples/not_existing"));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn synchronize_inserts_unmanaged_plugins() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+
+        let report = Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        assert_eq!(report.added.len(), 7);
+        assert!(report.matched.is_empty());
+        assert!(report.removed.is_empty());
+
+        let installed = cache.get_installed_plugins().unwrap();
+        assert_eq!(installed.len(), 7);
+
+        let alt_wallet = &installed["Alt Wallet"];
+        assert_eq!(alt_wallet.id, 904);
+        assert_eq!(alt_wallet.current_version, "1.0.6");
+        assert!(alt_wallet.latest_version.is_empty());
+        assert!(alt_wallet.is_unmanaged());
+
+        // Plugins without a compendium file get a local id
+        let craft_timer = &installed["CraftTimer"];
+        assert!(craft_timer.id < 0);
+        assert!(cache
+            .get_plugin_files(craft_timer.id)
+            .unwrap()
+            .contains(&"Atheisto/CraftTimer.plugin".to_string()));
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn synchronize_is_idempotent() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+        let report = Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        assert!(report.added.is_empty());
+        assert_eq!(report.matched.len(), 7);
+        assert!(report.removed.is_empty());
+        assert_eq!(cache.get_installed_plugins().unwrap().len(), 7);
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn synchronize_matches_catalog_by_id() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+        insert_catalog_plugin(&cache, 904, "AltWallet");
+
+        let report = Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        assert_eq!(report.matched, vec!["AltWallet"]);
+        let plugin = cache.get_plugin("AltWallet").unwrap().unwrap();
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "1.0.6");
+        assert_eq!(plugin.latest_version, "9.9");
+        assert!(!plugin.is_unmanaged());
+        assert!(cache.get_plugin("Alt Wallet").unwrap().is_none());
+        assert!(!cache.get_plugin_files(904).unwrap().is_empty());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn synchronize_matches_catalog_by_name() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+        insert_catalog_plugin(&cache, 50, "CraftTimer");
+
+        let report = Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        assert_eq!(report.matched, vec!["CraftTimer"]);
+        let plugin = cache.get_plugin("CraftTimer").unwrap().unwrap();
+        assert_eq!(plugin.id, 50);
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "1.0");
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn synchronize_keeps_installed_version() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+        let plugin = Plugin::new("TitanBar")
+            .with_id(692)
+            .with_current_version("1.24.45")
+            .with_remote_information("", "1.24.45", 0, "", 0, "")
+            .build();
+        cache.insert_plugin(&plugin, 1).unwrap();
+
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        let plugin = cache.get_plugin("TitanBar").unwrap().unwrap();
+        assert_eq!(plugin.current_version, "1.24.45");
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn synchronize_removes_missing_plugins() {
+        let (cache, db_dir) = database_fixtures::setup_with_items();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+
+        let report = Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        // PetStable is marked as installed but doesn't exist in the plugins directory
+        assert_eq!(report.removed, vec!["PetStable"]);
+        assert!(cache.get_plugin("PetStable").unwrap().is_none());
+        assert!(cache.get_plugin("Hello World").unwrap().is_some());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn synchronize_removes_deleted_plugin_files() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        remove_file(plugins_dir.join("Homeopatix/Voyage.plugin")).unwrap();
+        remove_file(plugins_dir.join("Homeopatix/Voyage.plugincompendium")).unwrap();
+        remove_dir_all(plugins_dir.join("Homeopatix/Voyage")).unwrap();
+
+        let report = Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        assert_eq!(report.removed, vec!["Voyage"]);
+        assert!(cache.get_plugin("Voyage").unwrap().is_none());
+        assert!(cache.get_plugin_files(1125).unwrap().is_empty());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn synchronize_keeps_plugins_with_existing_files() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        // The compendium file is gone, but the plugin itself is still there
+        remove_file(plugins_dir.join("Homeopatix/Voyage.plugincompendium")).unwrap();
+
+        let report = Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        assert!(report.removed.is_empty());
+        assert!(cache.get_plugin("Voyage").unwrap().is_some());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn feed_adopts_unmanaged_plugin() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+        let unmanaged = cache.get_plugin("CraftTimer").unwrap().unwrap();
+        let files = cache.get_plugin_files(unmanaged.id).unwrap();
+
+        let feed_plugin = Plugin::new("CraftTimer")
+            .with_id(50)
+            .with_remote_information("", "1.1", 0, "", 0, "")
+            .build();
+        cache.sync_plugins(&[feed_plugin]).unwrap();
+
+        let plugin = cache.get_plugin("CraftTimer").unwrap().unwrap();
+        assert_eq!(plugin.id, 50);
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "1.0");
+        assert_eq!(plugin.latest_version, "1.1");
+        assert!(!plugin.is_unmanaged());
+        assert_eq!(cache.get_plugin_files(50).unwrap(), files);
+        assert!(cache.get_plugin_files(unmanaged.id).unwrap().is_empty());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn feed_adopts_unmanaged_plugin_by_id() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, _, plugins_dir) = setup_plugins();
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        let feed_plugin = Plugin::new("AltWallet")
+            .with_id(904)
+            .with_remote_information("", "1.0.7", 0, "", 0, "")
+            .build();
+        cache.sync_plugins(&[feed_plugin]).unwrap();
+
+        assert!(cache.get_plugin("Alt Wallet").unwrap().is_none());
+        let plugin = cache.get_plugin("AltWallet").unwrap().unwrap();
+        assert_eq!(plugin.installed, 1);
+        assert_eq!(plugin.current_version, "1.0.6");
+        assert!(!cache.get_plugin_files(904).unwrap().is_empty());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn uninstall_discovered_plugin() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_plugins();
+        Synchronizer::synchronize(&cache, &plugins_dir).unwrap();
+
+        let mut installer = Installer::new(&tmp_dir, &plugins_dir, 554, "HugeBag");
+        installer.uninstall(&cache, 554).unwrap();
+
+        assert!(!plugins_dir.join("HabnaPlugins/HugeBag").exists());
+        assert!(!plugins_dir
+            .join("HabnaPlugins/HugeBagUtility.plugin")
+            .exists());
+        assert!(plugins_dir.join("HabnaPlugins/TitanBar.plugin").exists());
+        assert!(plugins_dir.join("HabnaPlugins/TitanBar").is_dir());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn uninstall_plugin_without_manifest() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_plugins();
+        let plugin = Plugin::new("HugeBag").with_id(554).build();
+        cache.insert_plugin(&plugin, 1).unwrap();
+
+        let mut installer = Installer::new(&tmp_dir, &plugins_dir, 554, "HugeBag");
+        installer.uninstall(&cache, 554).unwrap();
+
+        assert!(!plugins_dir.join("HabnaPlugins/HugeBag").exists());
+        assert!(!plugins_dir
+            .join("HabnaPlugins/HugeBagUtility.plugin")
+            .exists());
+        assert!(plugins_dir.join("HabnaPlugins/TitanBar.plugin").exists());
+
+        database_fixtures::teardown(cache, db_dir);
+        remove_dir_all(test_dir).unwrap();
+    }
+}
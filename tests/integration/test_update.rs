@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use lembas::core::{
+        backup::BackupManager,
+        dependencies::{DependencyProblem, DependencyReport, DependencyResolver},
+        io::{cache::DatabaseHandler, ArchiveStore},
+        plugin::LOCAL_SOURCE,
+        update::{SkipReason, SkippedUpdate, UpdatePlan, UpdateSummary},
+        Plugin,
+    };
+
+    use crate::database_fixtures;
+    use crate::fixtures::installer_fixtures::{build_archive, setup_dirs};
+
+    fn installed(name: &str, current_version: &str, latest_version: &str) -> Plugin {
+        Plugin::new(name)
+            .with_id(1)
+            .with_current_version(current_version)
+            .with_remote_information("", latest_version, 0, "", 0, "")
+            .build()
+    }
+
+    #[test]
+    fn plan_skips_pinned_and_ignored_updates() {
+        let mut pinned = installed("Pinned", "1.0", "2.0");
+        pinned.pinned = 1;
+        let mut ignored = installed("Ignored", "1.0", "2.0");
+        ignored.ignore_updates_until = "3.0".to_string();
+
+        let plan = UpdatePlan::new([
+            installed("zeta", "1.0", "1.1"),
+            installed("Alpha", "1.0", "2.0"),
+            installed("Current", "2.0", "2.0"),
+            installed("Local", "1.0", "2.0").with_source(LOCAL_SOURCE),
+            pinned,
+            ignored,
+        ]);
+
+        let names: Vec<_> = plan.plugins.iter().map(|plugin| &plugin.name).collect();
+        assert_eq!(names, ["Alpha", "zeta"]);
+        assert_eq!(
+            plan.skipped,
+            [
+                SkippedUpdate {
+                    name: "Ignored".to_string(),
+                    version: "2.0".to_string(),
+                    reason: SkipReason::Ignored("3.0".to_string()),
+                },
+                SkippedUpdate {
+                    name: "Pinned".to_string(),
+                    version: "2.0".to_string(),
+                    reason: SkipReason::Pinned,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_backup_without_planned_updates() {
+        let (test_dir, _, plugins_dir) = setup_dirs();
+        let manager = BackupManager::new(&test_dir.join("backups"), &plugins_dir);
+
+        let plan = UpdatePlan::new([installed("Current", "2.0", "2.0")]);
+
+        assert!(plan.backup(Some(&manager)).unwrap().is_none());
+        assert!(manager.backups().is_empty());
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+    }
+
+    #[test]
+    fn update_all_creates_a_single_backup() {
+        let (cache, db_dir) = database_fixtures::setup();
+        let (test_dir, tmp_dir, plugins_dir) = setup_dirs();
+        let manager = BackupManager::new(&test_dir.join("backups"), &plugins_dir);
+        let plugins = [(1, "Alpha"), (2, "Beta")].map(|(id, name)| {
+            let mut plugin = installed(name, "1.0", "2.0");
+            plugin.id = id;
+            cache.insert_plugin(&plugin, 1).unwrap();
+            plugin
+        });
+
+        let plan = UpdatePlan::new(plugins);
+        assert!(plan.backup(Some(&manager)).unwrap().is_some());
+        for plugin in &plan.plugins {
+            DependencyResolver::new(&cache, &tmp_dir, &plugins_dir, |_, plugin| {
+                Ok(build_archive(&[(
+                    &format!("{0}/{0}.plugin", plugin.name),
+                    "<Plugin></Plugin>",
+                )]))
+            })
+            .with_archive_store(ArchiveStore::new(&test_dir.join("archives")))
+            .with_backup(None)
+            .install(plugin)
+            .unwrap();
+        }
+
+        assert_eq!(manager.backups().len(), 1);
+
+        fs::remove_dir_all(test_dir).expect("Error while running test teardown");
+        database_fixtures::teardown(cache, db_dir);
+    }
+
+    #[test]
+    fn summary_lists_updated_failed_and_skipped_plugins() {
+        let mut pinned = installed("Pinned", "1.0", "2.0");
+        pinned.pinned = 1;
+        let plan = UpdatePlan::new([
+            installed("Beta", "1.0", "1.1"),
+            installed("Alpha", "1.0", "2.0"),
+            pinned,
+        ]);
+        let mut summary = UpdateSummary::new(&plan, None);
+
+        summary.add_failed(&plan.plugins[1], "cancelled");
+        summary.add_updated(
+            &plan.plugins[0],
+            &DependencyReport {
+                installed: vec!["Turbine Utilities".to_string(), "Alpha".to_string()],
+                problems: vec![DependencyProblem::Cycle(vec![
+                    "Alpha".to_string(),
+                    "Turbine Utilities".to_string(),
+                ])],
+            },
+        );
+
+        assert!(!summary.is_success());
+        assert_eq!(summary.backup, None);
+        assert_eq!(summary.updated.len(), 1);
+        assert_eq!(summary.updated[0].name, "Alpha");
+        assert_eq!(summary.updated[0].from, "1.0");
+        assert_eq!(summary.updated[0].to, "2.0");
+        assert_eq!(summary.updated[0].dependencies, ["Turbine Utilities"]);
+        assert_eq!(summary.updated[0].problems.len(), 1);
+        assert_eq!(summary.failed[0].name, "Beta");
+        assert_eq!(summary.failed[0].error, "cancelled");
+        assert_eq!(summary.skipped[0].reason, SkipReason::Pinned);
+    }
+}